@@ -197,6 +197,19 @@ pub enum CardEffect {
     Revive,
     Truthtrance,
     WeatherControl,
+    Thumper,
+    MakerHook,
+}
+
+impl CardEffect {
+    /// Whether a card with this effect can be played in a battle plan, as a weapon, a defense or a cheap hero.
+    pub fn is_battle_card(&self) -> bool {
+        use CardEffect::*;
+        matches!(
+            self,
+            PoisonWeapon | ProjectileWeapon | Lasgun | PoisonDefense | ProjectileDefense | CheapHero | Worthless
+        )
+    }
 }
 
 #[derive(Copy, Clone, Serialize, Deserialize, PartialEq, Eq, Debug, Display, Hash)]
@@ -225,6 +238,8 @@ pub enum TreacheryCardKind {
     Kulon,
     LaLaLa,
     TripToGamont,
+    Thumper,
+    MakerHook,
 }
 
 #[derive(Copy, Clone, Serialize, Deserialize, PartialEq, Eq, Debug, Hash, Component)]
@@ -239,7 +254,7 @@ impl Display for TreacheryCard {
     }
 }
 
-#[derive(Copy, Clone, Serialize, Deserialize, PartialEq, Eq, Debug, Display, Hash, Component)]
+#[derive(Copy, Clone, Serialize, Deserialize, PartialEq, Eq, Debug, Display, Hash, Component, EnumIter)]
 pub enum SpiceCard {
     BrokenLand,
     CielagoNorth,
@@ -8,6 +8,7 @@ use bevy::{
     prelude::Component,
 };
 use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
 
 use crate::components::{CardEffect, Faction, Leader, Location, SpiceCard, Terrain, TreacheryCard, TreacheryCardKind};
 
@@ -23,13 +24,21 @@ pub struct Data {
     pub prediction_nodes: PredictionNodeData,
     pub traitor_nodes: Vec<Vec2>,
     pub token_nodes: TokenNodeData,
+    /// Every asset the game needs, which is loaded before it starts.
+    pub assets: Vec<String>,
 }
 
 impl Default for Data {
     fn default() -> Self {
         use ron::de::from_reader;
-        Data {
-            locations: from_reader(File::open("data/locations.ron").unwrap()).unwrap(),
+        let locations: HashMap<Location, LocationData> =
+            from_reader(File::open("data/locations.ron").unwrap()).unwrap();
+        // Every territory needs its nodes, including the center a worm surfaces in
+        for location in Location::iter() {
+            assert!(locations.contains_key(&location), "No location data for {}", location);
+        }
+        let data = Data {
+            locations,
             leaders: from_reader(File::open("data/leaders.ron").unwrap()).unwrap(),
             factions: from_reader(File::open("data/factions.ron").unwrap()).unwrap(),
             treachery_cards: from_reader(File::open("data/treachery_cards.ron").unwrap()).unwrap(),
@@ -39,7 +48,55 @@ impl Default for Data {
             prediction_nodes: from_reader(File::open("data/prediction_nodes.ron").unwrap()).unwrap(),
             traitor_nodes: from_reader(File::open("data/traitor_nodes.ron").unwrap()).unwrap(),
             token_nodes: from_reader(File::open("data/token_nodes.ron").unwrap()).unwrap(),
+            assets: from_reader(File::open("data/assets.ron").unwrap()).unwrap(),
+        };
+        // Anything left out of the manifest wouldn't be loaded, and would only be noticed once it failed to show up
+        for texture in data.textures() {
+            assert!(
+                data.assets.contains(&texture),
+                "{} is used but missing from data/assets.ron",
+                texture
+            );
         }
+        data
+    }
+}
+
+impl Data {
+    /// The paths of every texture named by the data, which the asset manifest must include.
+    pub fn textures(&self) -> Vec<String> {
+        let leaders = self.leaders.values().flat_map(|leader| {
+            [
+                format!("leaders/{}.png", leader.texture),
+                format!("traitor/traitor_{}.png", leader.texture),
+            ]
+        });
+        let treachery = self
+            .treachery_cards
+            .values()
+            .flat_map(|card| card.textures.iter())
+            .map(|texture| format!("treachery/treachery_{}.png", texture));
+        let spice = self
+            .spice_cards
+            .values()
+            .map(|card| format!("spice/spice_{}.png", card.texture));
+        let factions = self.factions.keys().flat_map(|faction| {
+            let code = faction.code();
+            [
+                format!("predictions/prediction_{}.png", code),
+                format!("shields/{}_shield_front.png", code),
+                format!("shields/{}_shield_back.png", code),
+                format!("tokens/{}_troop.png", code),
+                format!("tokens/{}_logo.png", code),
+            ]
+        });
+        leaders.chain(treachery).chain(spice).chain(factions).collect()
+    }
+
+    pub fn are_adjacent(&self, a: Location, b: Location) -> bool {
+        self.locations
+            .get(&a)
+            .map_or(false, |location| location.adjacent.contains(&b))
     }
 }
 
@@ -48,14 +105,21 @@ pub struct FactionStartingValues {
     pub units: u8,
     #[serde(default)]
     pub possible_locations: Option<HashSet<Location>>,
+    /// The most forces which may be placed in any one territory during setup.
+    #[serde(default)]
+    pub territory_limit: Option<u8>,
     pub spice: u8,
 }
 
 #[derive(Clone, PartialEq, Eq, Serialize, Deserialize, Debug)]
 pub struct FactionData {
     pub name: String,
+    pub description: String,
     pub starting_values: FactionStartingValues,
     pub special_forces: u8,
+    /// How many normal forces the faction revives for free each turn.
+    #[serde(default)]
+    pub free_revival: u8,
 }
 
 #[derive(Clone, PartialEq, Eq, Serialize, Deserialize, Debug)]
@@ -71,6 +135,10 @@ pub struct LocationData {
     pub name: String,
     pub terrain: Terrain,
     pub spice: Option<Vec3>,
+    /// The middle of the territory, where a worm surfaces.
+    pub center: Vec3,
+    /// Territories which share a border with this one.
+    pub adjacent: HashSet<Location>,
     pub sectors: HashMap<u8, LocationNodes>,
 }
 
@@ -81,6 +149,13 @@ pub struct LocationNodes {
     pub fighters: Vec<Vec3>,
 }
 
+impl LocationNodes {
+    /// The middle of the sector, in board space.
+    pub fn center(&self) -> Vec3 {
+        self.vertices.iter().fold(Vec3::ZERO, |sum, v| sum + *v) / self.vertices.len().max(1) as f32
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct CardEffectData {
     pub description: String,
@@ -142,3 +217,42 @@ pub struct TokenNodeData {
     pub fighters: Vec<Vec3>,
     pub factions: Vec<Vec3>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_territory_has_a_center_among_its_sectors() {
+        let data = Data::default();
+        for location in Location::iter() {
+            let location_data = &data.locations[&location];
+            let vertices = location_data
+                .sectors
+                .values()
+                .flat_map(|sector| sector.vertices.iter())
+                .copied()
+                .collect::<Vec<_>>();
+            let min = vertices.iter().copied().fold(Vec3::splat(f32::MAX), Vec3::min);
+            let max = vertices.iter().copied().fold(Vec3::splat(f32::MIN), Vec3::max);
+            let center = location_data.center.truncate();
+            assert!(
+                center.cmpge(min.truncate()).all() && center.cmple(max.truncate()).all(),
+                "the center of {} is outside it",
+                location
+            );
+        }
+    }
+
+    #[test]
+    fn a_territory_without_a_center_fails_to_load() {
+        let location = r#"(
+            name: "Nowhere",
+            terrain: Sand,
+            spice: None,
+            adjacent: [],
+            sectors: {},
+        )"#;
+        assert!(ron::from_str::<LocationData>(location).is_err());
+    }
+}
@@ -0,0 +1,337 @@
+use std::cell::Cell;
+
+use maplit::hashset;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use strum::{Display, EnumString};
+
+use super::{
+    driver::GameDriver,
+    phase::{setup::SetupPhase, Phase},
+    state::{EventReduce, ForceDial, GameEvent, GameState, PlayerId, Prompt},
+};
+use crate::{
+    components::{Location, LocationSector, Terrain, TreacheryCardKind},
+    data::Data,
+};
+
+/// A computer controlled player.
+pub trait Bot {
+    /// Pick the next event to send on behalf of `me`. This is only called when the bot has something to do.
+    fn choose_action(&self, driver: &GameDriver, me: PlayerId) -> GameEvent;
+}
+
+/// Whether a player is being waited on, either because they are active or because they have been prompted.
+pub fn needs_action(state: &GameState, player_id: PlayerId) -> bool {
+    state.active_player == Some(player_id) || state.prompts.contains_key(&player_id)
+}
+
+/// The events the given player could send right now. This covers every prompt and the common active player
+/// actions, which is enough for bots and keyboard shortcuts.
+pub fn legal_events(state: &GameState, data: &Data, me: PlayerId) -> Vec<GameEvent> {
+    use GameEvent::*;
+    let mut events = Vec::new();
+    let player = state.players.get(&me);
+    match state.prompts.get(&me) {
+        Some(Prompt::Faction { remaining }) => {
+            events.extend(
+                remaining
+                    .iter()
+                    .map(|&faction| ChooseFaction { player_id: me, faction }),
+            );
+        }
+        Some(Prompt::Traitor) => {
+            if let Some(player) = player {
+                events.extend(player.traitor_cards.iter().map(|card| ChooseTraitor {
+                    player_id: me,
+                    card_id: card.id,
+                }));
+            }
+        }
+        Some(Prompt::FactionPrediction) => {
+            events.extend(state.players.values().map(|player| MakeFactionPrediction {
+                player_id: me,
+                faction: player.faction,
+            }));
+        }
+        Some(Prompt::TurnPrediction) => {
+            events.extend((1..=10).map(|turn| MakeTurnPrediction { player_id: me, turn }));
+        }
+        Some(Prompt::Bid) => {
+            if let (Some(player), Some(bid_state)) = (player, state.bidding_cards.current()) {
+                let current_bid = bid_state.current_bid.as_ref().map_or(0, |bid| bid.spice);
+                events.extend((current_bid + 1..=player.spice).map(|spice| MakeBid { player_id: me, spice }));
+            }
+        }
+        Some(Prompt::RideWorm { location }) => {
+            events.push(RideTheWorm {
+                player_id: me,
+                from: *location,
+                to: None,
+            });
+            events.extend(stronghold_sectors(data).map(|to| RideTheWorm {
+                player_id: me,
+                from: *location,
+                to: Some(to),
+            }));
+        }
+        Some(Prompt::Karama { .. }) => {
+            if let Some(player) = player {
+                events.extend(
+                    player
+                        .treachery_cards
+                        .iter()
+                        .filter(|card| card.inner.kind == TreacheryCardKind::Karama)
+                        .map(|card| PlayCard {
+                            player_id: me,
+                            card_id: card.id,
+                        }),
+                );
+            }
+            events.push(Decline { player_id: me });
+        }
+        Some(Prompt::PlayCard { kind }) => {
+            if let Some(player) = player {
+                events.extend(
+                    player
+                        .treachery_cards
+                        .iter()
+                        .filter(|card| &card.inner.kind == kind)
+                        .map(|card| PlayCard {
+                            player_id: me,
+                            card_id: card.id,
+                        }),
+                );
+            }
+            events.push(Decline { player_id: me });
+        }
+        Some(Prompt::AdvisorOrFighter { location } | Prompt::AdvisorDecision { location }) => {
+            events.extend([true, false].map(|advisors| ChooseAdvisors {
+                player_id: me,
+                location: *location,
+                advisors,
+            }));
+        }
+        Some(Prompt::BattleOrder { locations }) => {
+            // Either way round is enough to choose from
+            events.push(ChooseBattleOrder {
+                player_id: me,
+                order: locations.clone(),
+            });
+            events.push(ChooseBattleOrder {
+                player_id: me,
+                order: locations.iter().rev().copied().collect(),
+            });
+        }
+        Some(Prompt::BattlePlan { battle }) => {
+            if let Some(player) = player {
+                let leaders = std::iter::once(None).chain(
+                    player
+                        .living_leaders
+                        .iter()
+                        .filter_map(|(leader, fought)| (!fought).then_some(Some(leader.id))),
+                );
+                let present = state.forces_present(me, battle.location);
+                for leader in leaders {
+                    for normal in 0..=present.normal {
+                        events.extend((0..=present.special).map(|special| SetBattlePlan {
+                            player_id: me,
+                            forces: ForceDial { normal, special },
+                            leader,
+                            treachery_cards: Vec::new(),
+                        }));
+                    }
+                }
+            }
+        }
+        // Bots don't need to stop and think
+        Some(Prompt::Continue) => events.push(Decline { player_id: me }),
+        // TODO: Revival can only be declined until reviving is run by the server
+        Some(Prompt::Revival) => events.push(Decline { player_id: me }),
+        // TODO: These decisions can't be made until battles and charity are run by the server
+        Some(
+            Prompt::GuildShip
+            | Prompt::Voice { .. }
+            | Prompt::Prescience { .. }
+            | Prompt::CallTraitor { .. }
+            | Prompt::Charity
+            | Prompt::Discard { .. },
+        )
+        | None => (),
+    }
+    if state.active_player == Some(me) {
+        if let (Phase::Setup(SetupPhase::PlaceForces), Some(player)) = (&state.phase, player) {
+            if let Some(force) = player.offworld_forces.iter().find(|force| !force.inner.is_special) {
+                let possible_locations = &data.factions[&player.faction].starting_values.possible_locations;
+                for (location, location_data) in data.locations.iter() {
+                    let allowed = match possible_locations {
+                        Some(possible_locations) => possible_locations.contains(location),
+                        None => location_data.terrain == Terrain::Stronghold,
+                    };
+                    if allowed {
+                        events.extend(location_data.sectors.keys().map(|&sector| ShipForces {
+                            player_id: me,
+                            to: location.with_sector(sector),
+                            forces: hashset!(force.id),
+                        }));
+                    }
+                }
+            }
+        }
+        events.push(Pass { player_id: me });
+    }
+    events.retain(|event| state.validate(data, event));
+    events
+}
+
+fn stronghold_sectors(data: &Data) -> impl Iterator<Item = LocationSector> + '_ {
+    data.locations
+        .iter()
+        .filter(|(_, location_data)| location_data.terrain == Terrain::Stronghold)
+        .flat_map(|(location, location_data)| location_data.sectors.keys().map(|&sector| location.with_sector(sector)))
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Display, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum Difficulty {
+    /// Picks any legal action.
+    Random,
+    /// Picks the action which looks best right now.
+    Greedy,
+    /// Tries each action and picks the one which leaves it in the best position.
+    Planner,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Display, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum Personality {
+    Balanced,
+    /// Values treachery cards highly and bids up to win them.
+    AggressiveBidder,
+    /// Holds onto spice and prefers to dig into strongholds.
+    Turtle,
+}
+
+/// A bot built from a difficulty and a personality. Its decisions only depend on the game and its seed.
+pub struct TieredBot {
+    pub difficulty: Difficulty,
+    pub personality: Personality,
+    seed: u64,
+    decisions: Cell<u64>,
+}
+
+impl TieredBot {
+    pub fn new(difficulty: Difficulty, personality: Personality, seed: u64) -> Self {
+        Self {
+            difficulty,
+            personality,
+            seed,
+            decisions: Default::default(),
+        }
+    }
+
+    fn rng(&self) -> StdRng {
+        let decisions = self.decisions.get();
+        self.decisions.set(decisions + 1);
+        StdRng::seed_from_u64(self.seed.wrapping_add(decisions))
+    }
+
+    /// How much the bot would pay for a treachery card.
+    fn card_value(&self) -> u8 {
+        match self.personality {
+            Personality::Balanced => 4,
+            Personality::AggressiveBidder => 8,
+            Personality::Turtle => 2,
+        }
+    }
+
+    /// A quick judgement of how good an event is on its own.
+    fn heuristic(&self, driver: &GameDriver, me: PlayerId, event: &GameEvent) -> f32 {
+        use GameEvent::*;
+        let is_stronghold = |location: &Location| driver.data.locations[location].terrain == Terrain::Stronghold;
+        match event {
+            ShipForces { to, .. } => {
+                let value = if is_stronghold(&to.location) { 5.0 } else { 1.0 };
+                if self.personality == Personality::Turtle {
+                    value * 1.5
+                } else {
+                    value
+                }
+            }
+            RideTheWorm { to: Some(to), .. } if is_stronghold(&to.location) => 3.0,
+            MakeBid { spice, .. } => {
+                let spice_left = driver.state.players[&me].spice.saturating_sub(*spice);
+                let reserve = if self.personality == Personality::Turtle { 5 } else { 0 };
+                if *spice <= self.card_value() && spice_left >= reserve {
+                    // Spend as little as possible to win
+                    1.0 + (self.card_value() - *spice) as f32 * 0.1
+                } else {
+                    -1.0
+                }
+            }
+            ChooseTraitor { card_id, .. } => driver.state.players[&me]
+                .traitor_cards
+                .get(card_id)
+                .map_or(0.0, |card| driver.data.leaders[&card.inner.leader].power as f32),
+            PlayCard { .. } => {
+                if self.personality == Personality::Turtle {
+                    -1.0
+                } else {
+                    2.0
+                }
+            }
+            _ => 0.0,
+        }
+    }
+
+    /// How good the game looks for the given player.
+    fn evaluate(state: &GameState, data: &Data, me: PlayerId) -> f32 {
+        let strongholds = state
+            .stronghold_control(data)
+            .values()
+            .filter(|player_id| **player_id == me)
+            .count() as f32;
+        let spice = state.players.get(&me).map_or(0, |player| player.spice) as f32;
+        let forces = state
+            .board
+            .values()
+            .flat_map(|location| location.sectors.values())
+            .filter_map(|sector| sector.forces.get(&me))
+            .map(|forces| forces.forces.len())
+            .sum::<usize>() as f32;
+        strongholds * 10.0 + spice + forces * 0.5
+    }
+}
+
+impl Bot for TieredBot {
+    fn choose_action(&self, driver: &GameDriver, me: PlayerId) -> GameEvent {
+        let mut rng = self.rng();
+        let mut events = legal_events(&driver.state, &driver.data, me);
+        // The game state is full of hash maps, so put the options in a stable order to keep the bot reproducible
+        events.sort_by_cached_key(|event| format!("{:?}", event));
+        if events.is_empty() {
+            return GameEvent::Pass { player_id: me };
+        }
+        let scores = events
+            .iter()
+            .map(|event| match self.difficulty {
+                Difficulty::Random => 0.0,
+                Difficulty::Greedy => self.heuristic(driver, me, event),
+                Difficulty::Planner => {
+                    let mut next = driver.clone();
+                    match next.apply(event.clone()) {
+                        Ok(_) => Self::evaluate(&next.state, &next.data, me) + 0.5 * self.heuristic(driver, me, event),
+                        Err(_) => f32::MIN,
+                    }
+                }
+            })
+            .collect::<Vec<_>>();
+        let best = scores.iter().copied().fold(f32::MIN, f32::max);
+        // Break ties randomly so bots don't all play the same way
+        let candidates = events
+            .into_iter()
+            .zip(scores)
+            .filter_map(|(event, score)| (score >= best).then_some(event))
+            .collect::<Vec<_>>();
+        candidates[rng.gen_range(0..candidates.len())].clone()
+    }
+}
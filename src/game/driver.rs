@@ -0,0 +1,1033 @@
+mod abilities;
+#[cfg(test)]
+pub mod tests;
+
+use std::collections::HashSet;
+
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use strum::IntoEnumIterator;
+
+use self::abilities::ABILITIES;
+use super::{
+    phase::{bidding::BiddingPhase, setup::SetupPhase, spice_blow::SpiceBlowPhase, storm::StormPhase, Phase},
+    state::{
+        DeckType, EndGameReason, EventReduce, GameEvent, GameState, KaramaEffect, PaymentReason, PlayerId, Prompt,
+        ResourceKind, ResponseWindow, RuleSet, Seating, SpawnType, StateError,
+    },
+    Object, ObjectIdGenerator,
+};
+use crate::{
+    components::{Faction, Leader, Location, SpiceCard, StormCard, TraitorCard, TreacheryCardKind, Troop},
+    data::{Data, SpiceLocationData},
+};
+
+/// How many traitors each player is dealt at the start of the game.
+const TRAITORS_DEALT: usize = 4;
+
+/// Runs the game rules against a [`GameState`] without any networking. Events are validated and consumed, and the
+/// follow-on events the server would generate are returned so the caller can decide how to deliver them.
+#[derive(Clone, Debug)]
+pub struct GameDriver {
+    pub state: GameState,
+    pub data: Data,
+    pub ids: ObjectIdGenerator,
+    players: Vec<PlayerId>,
+    generated: Vec<GameEvent>,
+    /// The seed all of the game's randomness was drawn from, so that it can be played again the same way.
+    seed: u64,
+    rng: StdRng,
+}
+
+impl GameDriver {
+    pub fn new(data: Data) -> Self {
+        let seed = rand::random();
+        Self {
+            state: Default::default(),
+            data,
+            ids: Default::default(),
+            players: Default::default(),
+            generated: Default::default(),
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Draw everything random from here on from the given seed. The same seed and the same player actions always
+    /// play out the same way.
+    pub fn reseed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    /// Forget the game which was being played, ready to start another.
+    pub fn reset(&mut self) {
+        self.state = Default::default();
+        self.ids = Default::default();
+        self.players.clear();
+        self.generated.clear();
+    }
+
+    /// Validate an event sent by a player and apply it. Returns every event that was consumed as a result, starting
+    /// with the event itself.
+    pub fn apply(&mut self, event: GameEvent) -> Result<Vec<GameEvent>, StateError> {
+        if self.state.validate(&self.data, &event) {
+            Ok(self.run(event))
+        } else {
+            Err(StateError::InvalidEvent(event))
+        }
+    }
+
+    /// Apply a trusted event without validation. Returns every event that was consumed as a result, starting with the
+    /// event itself.
+    pub fn run(&mut self, event: GameEvent) -> Vec<GameEvent> {
+        self.generate(event);
+        std::mem::take(&mut self.generated)
+    }
+
+    /// Rebuild the state of a game from its event log. No game logic is run, since the log already holds every event
+    /// which was generated.
+    pub fn replay<'a>(data: &Data, events: impl IntoIterator<Item = &'a GameEvent>) -> GameState {
+        let mut state = GameState::default();
+        for event in events {
+            state.consume(data, event.clone());
+        }
+        state
+    }
+
+    /// Seat the given players and start the game with the given optional rules.
+    pub fn start_game(
+        &mut self,
+        players: impl IntoIterator<Item = PlayerId>,
+        host: Option<PlayerId>,
+        rules: RuleSet,
+        seed: u64,
+    ) -> Vec<GameEvent> {
+        self.reseed(seed);
+        self.players = players.into_iter().collect();
+        self.generate(GameEvent::SetRules { rules });
+        if let Some(player_id) = host {
+            self.generate(GameEvent::SetHost { player_id });
+        }
+        self.run(GameEvent::AdvancePhase)
+    }
+
+    /// This is the server logic, which is run whenever the game state changes.
+    fn game_logic(&mut self, last_event: GameEvent) {
+        use GameEvent::*;
+        match last_event {
+            AdvancePhase => {
+                self.trigger_abilities();
+                self.enter_phase();
+            }
+            EndGame { .. } => {
+                if self.state.rules.hidden_spice {
+                    let players = &self.state.players;
+                    self.generate(RevealSpice {
+                        spice: players.iter().map(|(id, player)| (*id, player.spice)).collect(),
+                        bribe_spice: players.iter().map(|(id, player)| (*id, player.bribe_spice)).collect(),
+                    });
+                }
+                let traitors = self
+                    .state
+                    .players
+                    .iter()
+                    .map(|(player_id, player)| (*player_id, player.traitor_cards.iter().map(|card| card.id).collect()))
+                    .collect();
+                self.generate(RevealAll {
+                    bg_predictions: self.state.bg_predictions.clone(),
+                    traitors,
+                });
+            }
+            Concede { player_id } if self.state.play_order.len() < 2 => {
+                self.generate(EndGame {
+                    reason: EndGameReason::Conceded { player_id },
+                });
+            }
+            Concede { .. } if self.state.response_window.is_some() => {
+                self.close_window();
+            }
+            Concede { .. } if self.state.phase == Phase::Battle => {
+                // If the battle being fought was theirs, move on to the next one
+                if !self
+                    .state
+                    .prompts
+                    .values()
+                    .any(|prompt| matches!(prompt, Prompt::BattlePlan { .. } | Prompt::BattleOrder { .. }))
+                {
+                    self.next_battle();
+                }
+            }
+            Concede { .. } if self.state.phase == Phase::SpiceBlow(SpiceBlowPhase::ShaiHalud) => {
+                // Whoever else was offered a ride may still be deciding, but if it was only them the next worm surfaces
+                if !self.riders_deciding() {
+                    self.next_worm();
+                }
+            }
+            StartRound | Pass { .. } | Concede { .. } => match self.state.phase {
+                Phase::Setup(s) => match s {
+                    SetupPhase::ChooseFactions => {
+                        if let Some(player_id) = self.state.active_player {
+                            let mut remaining = Faction::iter().collect::<HashSet<_>>();
+                            for faction in self.state.factions.keys() {
+                                remaining.remove(faction);
+                            }
+                            self.generate(ShowPrompt {
+                                player_id,
+                                prompt: Prompt::Faction { remaining },
+                            });
+                        } else {
+                            self.generate(AdvancePhase);
+                        }
+                    }
+                    SetupPhase::DealTraitors => {
+                        if self.state.prompts.is_empty() {
+                            self.generate(AdvancePhase);
+                        }
+                    }
+                    SetupPhase::PlaceForces => {
+                        if let Some(player_id) = self.state.active_player {
+                            if self.data.factions[&self.state.players[&player_id].faction]
+                                .starting_values
+                                .units
+                                == 0
+                            {
+                                self.generate(Pass { player_id });
+                            }
+                        } else {
+                            self.generate(AdvancePhase);
+                        }
+                    }
+                    _ => (),
+                },
+                Phase::Bidding(s) => match s {
+                    BiddingPhase::Bidding => {
+                        // If there is a card to bid on
+                        if let Some(bid) = self.state.bidding_cards.current() {
+                            if let Some(player_id) = self.state.active_player.clone() {
+                                if let Some(current_bid) = bid.current_bid.as_ref() {
+                                    if current_bid.player_id == player_id {
+                                        if current_bid.spice > 0 {
+                                            let (winner, amount, card_id) =
+                                                (current_bid.player_id, current_bid.spice, bid.card.id);
+                                            // The Emperor is paid for every card they don't buy themselves
+                                            let emperor = self
+                                                .state
+                                                .factions
+                                                .get(&Faction::Emperor)
+                                                .copied()
+                                                .filter(|emperor| *emperor != winner);
+                                            self.generate(PaySpice {
+                                                from: winner,
+                                                to: emperor,
+                                                amount,
+                                                reason: PaymentReason::Bid,
+                                            });
+                                            self.generate(WinBid {
+                                                player_id: winner,
+                                                card_id: Some(card_id),
+                                            });
+                                            self.generate(StartRound);
+                                        } else {
+                                            // Nobody bid on the card, so it goes back in the deck
+                                            self.generate(ReturnBidCard {
+                                                card_id: Some(bid.card.id),
+                                            });
+                                            self.generate(StartRound);
+                                        }
+                                    } else {
+                                        if self.state.players[&player_id].spice > current_bid.spice {
+                                            self.generate(ShowPrompt {
+                                                player_id,
+                                                prompt: Prompt::Bid,
+                                            });
+                                        } else {
+                                            self.generate(Pass { player_id });
+                                        }
+                                    }
+                                } else {
+                                    self.generate(MakeBid { player_id, spice: 0 });
+                                    self.generate(ShowPrompt {
+                                        player_id,
+                                        prompt: Prompt::Bid,
+                                    });
+                                }
+                            } else {
+                                self.generate(StartRound);
+                            }
+                        } else {
+                            self.generate(AdvancePhase);
+                        }
+                    }
+                    _ => (),
+                },
+                Phase::Movement => {
+                    // Each player may ship and then move before passing
+                    match self.state.active_player {
+                        Some(player_id) => self.offer_shipment_block(player_id),
+                        None if !self.advisors_deciding() => self.generate(AdvancePhase),
+                        None => (),
+                    }
+                }
+                _ => (),
+            },
+            ChooseFaction { player_id, faction } => {
+                // Everything is spawned in a fixed order, so the same seed always deals out the same objects
+                for leader in Leader::iter()
+                    .filter(|leader| self.data.leaders[leader].faction == faction)
+                    .collect::<Vec<_>>()
+                {
+                    let leader = self.spawn(leader);
+                    self.generate(SpawnObject {
+                        spawn_type: SpawnType::Leader { player_id, leader },
+                    });
+                }
+                for unit in std::iter::repeat_with(|| Troop { is_special: false })
+                    .take(20 - self.data.factions[&faction].special_forces as usize)
+                    .chain(
+                        std::iter::repeat_with(|| Troop { is_special: true })
+                            .take(self.data.factions[&faction].special_forces as usize),
+                    )
+                {
+                    let unit = self.spawn(unit);
+                    self.generate(SpawnObject {
+                        spawn_type: SpawnType::Troop { player_id, unit },
+                    });
+                }
+                self.generate(Pass { player_id });
+            }
+            ChooseTraitor { player_id, card_id } => {
+                self.generate(TraitorChosen { player_id });
+                // Discard the cards that weren't picked
+                let mut discards = self.state.players[&player_id]
+                    .traitor_cards
+                    .iter()
+                    .filter_map(|card| (card.id != card_id).then_some(card.id))
+                    .collect::<Vec<_>>();
+                discards.sort();
+                for card_id in discards {
+                    self.generate(DiscardCard {
+                        player_id,
+                        card_id,
+                        to: DeckType::Traitor,
+                    });
+                }
+                self.generate(Pass { player_id });
+            }
+            MakeFactionPrediction { .. } => {
+                self.generate(ShowPrompt {
+                    player_id: self.state.active_player.unwrap(),
+                    prompt: Prompt::TurnPrediction,
+                });
+            }
+            MakeTurnPrediction { .. } => {
+                self.generate(AdvancePhase);
+            }
+            ShipForces { player_id, to, forces } => {
+                if matches!(self.state.phase, Phase::Setup(SetupPhase::PlaceForces)) {
+                    if let Some(player_id) = self.state.active_player {
+                        let player = &self.state.players[&player_id];
+                        let faction_data = &self.data.factions[&player.faction];
+                        if player.offworld_forces.len() == 20 - faction_data.starting_values.units as usize {
+                            self.generate(Pass { player_id });
+                        }
+                    }
+                } else if matches!(self.state.phase, Phase::Movement) {
+                    if self.state.board[&to.location].sectors[&to.sector].forces[&player_id].advisors {
+                        self.generate(ShowPrompt {
+                            player_id,
+                            prompt: Prompt::AdvisorOrFighter { location: to },
+                        });
+                    }
+                    // Advisors already there decide whether to fight for the sector before any battles are found
+                    if let Some(bg) = self
+                        .state
+                        .factions
+                        .get(&Faction::BeneGesserit)
+                        .copied()
+                        .filter(|bg| *bg != player_id)
+                    {
+                        let advising = self.state.board[&to.location].sectors[&to.sector]
+                            .forces
+                            .get(&bg)
+                            .map_or(false, |forces| forces.advisors && !forces.forces.is_empty());
+                        if advising {
+                            self.generate(ShowPrompt {
+                                player_id: bg,
+                                prompt: Prompt::AdvisorDecision { location: to },
+                            });
+                        }
+                    }
+                    let cost = self
+                        .state
+                        .shipment_cost(&self.data, player_id, to.location, forces.len());
+                    if cost > 0 {
+                        // The Guild is paid for shipments by everyone else
+                        let guild = self
+                            .state
+                            .factions
+                            .get(&Faction::SpacingGuild)
+                            .copied()
+                            .filter(|guild| *guild != player_id);
+                        self.generate(PaySpice {
+                            from: player_id,
+                            to: guild,
+                            amount: cost,
+                            reason: PaymentReason::Shipment,
+                        });
+                    }
+                }
+            }
+            ChooseAdvisors { .. }
+                if self.state.phase == Phase::Movement
+                    && self.state.active_player.is_none()
+                    && !self.advisors_deciding() =>
+            {
+                self.generate(AdvancePhase);
+            }
+            ChooseBattleOrder { .. } => {
+                self.next_battle();
+            }
+            SetBattlePlan { player_id, .. } => {
+                self.generate(BattlePlanSubmitted { player_id });
+            }
+            BattlePlanSubmitted { .. } => {
+                // Both sides reveal their plans at once
+                if self.state.committed_plans.len() == 2 {
+                    self.generate(ResolveBattle {
+                        plans: self.state.battle_plans.clone(),
+                    });
+                }
+            }
+            ResolveBattle { .. } => {
+                self.next_battle();
+            }
+            PeekSpiceBlow { player_id } => {
+                if let Some(card) = self.state.decks.spice.peek() {
+                    self.generate(ShowSpiceBlow {
+                        player_id,
+                        card_id: card.id,
+                    });
+                }
+            }
+            MoveForces { player_id, .. } => {
+                // Moving is the last thing a player does in their turn, unless a Hajr lets them move again
+                let player = &self.state.players[&player_id];
+                if !player.can_move() && !player.holds(TreacheryCardKind::Hajr) {
+                    self.generate(Pass { player_id });
+                }
+            }
+            PlayHajr { card_id, .. } => {
+                self.generate(RevealCard { card_id });
+            }
+            PlayCard { card_id, .. } => {
+                self.generate(RevealCard { card_id });
+                self.close_window();
+            }
+            Decline { .. } => {
+                self.close_window();
+            }
+            Revive {
+                player_id,
+                forces,
+                leader,
+            } => {
+                let player = &self.state.players[&player_id];
+                let revived = player
+                    .offworld_forces
+                    .iter()
+                    .filter(|force| forces.contains(&force.id))
+                    .map(|force| force.inner)
+                    .collect::<Vec<_>>();
+                let leader = leader.and_then(|leader| {
+                    player
+                        .living_leaders
+                        .keys()
+                        .find(|living| living.id == leader)
+                        .map(|living| living.inner)
+                });
+                let cost = self.state.revival_cost(&self.data, player_id, &revived, leader);
+                if cost > 0 {
+                    self.generate(PaySpice {
+                        from: player_id,
+                        to: None,
+                        amount: cost,
+                        reason: PaymentReason::Revival,
+                    });
+                }
+                self.close_window();
+            }
+            Devour { location } => {
+                // Anyone left standing after the worm devours, the Fremen or those with a Maker Hook, may ride it
+                // elsewhere
+                let mut riders = self.state.board.get(&location).map_or_else(Vec::new, |location_state| {
+                    location_state
+                        .sectors
+                        .values()
+                        .flat_map(|sector| sector.forces.keys().copied())
+                        .collect::<HashSet<_>>()
+                        .into_iter()
+                        .collect()
+                });
+                riders.sort();
+                if riders.is_empty() {
+                    self.next_worm();
+                }
+                for player_id in riders {
+                    self.generate(ShowPrompt {
+                        player_id,
+                        prompt: Prompt::RideWorm { location },
+                    });
+                }
+            }
+            RideTheWorm { .. } => {
+                if !self.riders_deciding() {
+                    self.next_worm();
+                }
+            }
+            PlayThumper { card_id, .. } => {
+                self.generate(RevealCard { card_id });
+                // While worms are already surfacing, this one waits its turn
+                if self.state.phase == Phase::Movement {
+                    self.next_worm();
+                }
+            }
+            Bribe {
+                player_id,
+                other_player_id,
+                spice,
+            } => {
+                self.generate(PaySpice {
+                    from: player_id,
+                    to: Some(other_player_id),
+                    amount: spice,
+                    reason: PaymentReason::Bribe,
+                });
+            }
+            MakeBid { player_id, .. } => {
+                self.generate(Pass { player_id });
+            }
+            _ => (),
+        }
+    }
+
+    /// Run the logic for the start of the phase which was just entered.
+    fn enter_phase(&mut self) {
+        use GameEvent::*;
+        if self.state.phase == self.state.sequence.turn_start() && self.state.spice_blows == 0 {
+            self.return_unsold_cards();
+            self.generate(AdvanceTurn);
+        }
+        if self.state.phase.skippable() && !self.state.has_decisions() {
+            self.generate(AdvancePhase);
+            return;
+        }
+        match &self.state.phase {
+            Phase::Setup(s) => match s {
+                SetupPhase::ChooseFactions => {
+                    // Players are given in the order they joined, which is kept if the rules ask for it
+                    let mut play_order = std::mem::take(&mut self.players);
+                    if self.state.rules.seating == Seating::Random {
+                        play_order.shuffle(&mut self.rng);
+                    }
+                    self.generate(SetPlayOrder { play_order });
+                    self.generate(StartRound);
+                }
+                SetupPhase::Prediction => {
+                    // Wait for the Bene Gesserit, if they are playing
+                    if self.state.prompts.is_empty() {
+                        self.generate(AdvancePhase);
+                    }
+                }
+                SetupPhase::AtStart => {
+                    for card in self.data.treachery_deck.clone() {
+                        let card = self.spawn(card);
+                        self.generate(SpawnObject {
+                            spawn_type: SpawnType::TreacheryCard(card),
+                        });
+                    }
+                    for leader in Leader::iter() {
+                        let faction = self.data.leaders[&leader].faction;
+                        if self.state.factions.contains_key(&faction) {
+                            let card = self.spawn(TraitorCard { leader });
+                            self.generate(SpawnObject {
+                                spawn_type: SpawnType::TraitorCard(card),
+                            });
+                        }
+                    }
+                    for card in SpiceCard::iter()
+                        .filter(|card| self.data.spice_cards.contains_key(card))
+                        .collect::<Vec<_>>()
+                    {
+                        let card = self.spawn(card);
+                        self.generate(SpawnObject {
+                            spawn_type: SpawnType::SpiceCard(card),
+                        });
+                    }
+                    for card in (1..=6).map(|val| StormCard { val }) {
+                        let card = self.spawn(card);
+                        self.generate(SpawnObject {
+                            spawn_type: SpawnType::StormCard(card),
+                        });
+                    }
+
+                    let mut deck_order = self.state.decks.traitor.card_ids();
+                    deck_order.shuffle(&mut self.rng);
+                    self.generate(SetDeckOrder {
+                        deck_order: Some(deck_order),
+                        deck_type: DeckType::Traitor,
+                    });
+
+                    let mut deck_order = self.state.decks.treachery.card_ids();
+                    deck_order.shuffle(&mut self.rng);
+                    self.generate(SetDeckOrder {
+                        deck_order: Some(deck_order),
+                        deck_type: DeckType::Treachery,
+                    });
+
+                    let mut deck_order = self.state.decks.spice.card_ids();
+                    deck_order.shuffle(&mut self.rng);
+                    self.generate(SetDeckOrder {
+                        deck_order: Some(deck_order),
+                        deck_type: DeckType::Spice,
+                    });
+
+                    let mut deck_order = self.state.decks.storm.card_ids();
+                    deck_order.shuffle(&mut self.rng);
+                    self.generate(SetDeckOrder {
+                        deck_order: Some(deck_order),
+                        deck_type: DeckType::Storm,
+                    });
+
+                    self.generate(AdvancePhase);
+                }
+                SetupPhase::DealTraitors => {
+                    // Anyone who wasn't dealt their traitors by an ability keeps one of those they are dealt now
+                    let choosing = self
+                        .state
+                        .play_order
+                        .iter()
+                        .filter(|player_id| self.state.players[player_id].traitor_cards.is_empty())
+                        .copied()
+                        .collect::<Vec<_>>();
+                    for player_id in std::iter::repeat(choosing.clone()).take(TRAITORS_DEALT).flatten() {
+                        self.deal_traitor(player_id);
+                    }
+                    for player_id in choosing {
+                        self.generate(ShowPrompt {
+                            player_id,
+                            prompt: Prompt::Traitor,
+                        });
+                    }
+                }
+                SetupPhase::PlaceForces => {
+                    self.generate(StartRound);
+                }
+                SetupPhase::DealTreachery => {
+                    for player_id in self.state.play_order.clone() {
+                        self.deal_card(player_id);
+                    }
+                    // Whoever is last to act starts with the tech token, to make up for it
+                    if let Some(player_id) = self
+                        .state
+                        .play_order
+                        .last()
+                        .copied()
+                        .filter(|_| self.state.rules.tech_tokens)
+                    {
+                        self.generate(GrantResource {
+                            player_id,
+                            kind: ResourceKind::SpiceProduction,
+                            amount: 1,
+                        });
+                    }
+                    self.generate(AdvancePhase);
+                }
+            },
+            Phase::Storm(p) => match p {
+                StormPhase::Reveal => {
+                    // Last turn's bribes can be spent now
+                    if self.state.players.values().any(|player| player.bribe_spice > 0) {
+                        self.generate(CollectBribes);
+                    }
+                    if self.state.storm_placed {
+                        if self.state.decks.storm.is_empty() {
+                            let mut deck_order = self.state.decks.storm.discard_order.clone();
+                            deck_order.shuffle(&mut self.rng);
+                            self.generate(SetDeckOrder {
+                                deck_order: Some(deck_order),
+                                deck_type: DeckType::Storm,
+                            });
+                        }
+                        if let Some(card_id) = self.state.decks.storm.peek().map(|card| card.id) {
+                            self.generate(RevealStorm { card_id });
+                        }
+                    }
+                    self.generate(AdvancePhase);
+                }
+                StormPhase::WeatherControl => {
+                    if self.state.storm_placed {
+                        self.offer_card(TreacheryCardKind::WeatherControl);
+                    } else {
+                        self.generate(AdvancePhase);
+                    }
+                }
+                StormPhase::FamilyAtomics => {
+                    if self.state.storm_placed && !self.state.shield_wall_destroyed {
+                        self.offer_card(TreacheryCardKind::FamilyAtomics);
+                    } else {
+                        self.generate(AdvancePhase);
+                    }
+                }
+                StormPhase::MoveStorm => {
+                    // The storm starts somewhere random, and is only moved by storm cards after that
+                    if !self.state.storm_placed {
+                        let sectors = self.rng.gen_range(0..18);
+                        self.generate(MoveStorm { sectors });
+                    } else if let Some(storm_card) = self.state.storm_card.as_ref() {
+                        self.generate(MoveStorm {
+                            sectors: storm_card.inner.val,
+                        });
+                    }
+                    self.generate(AdvancePhase);
+                }
+            },
+            Phase::SpiceBlow(s) => match s {
+                SpiceBlowPhase::Reveal => {
+                    loop {
+                        let card_id = self.state.decks.spice.peek().unwrap().id;
+                        self.generate(RevealSpiceBlow { card_id });
+                        match self.state.spice_card.as_ref().unwrap().inner {
+                            SpiceCard::ShaiHalud => (),
+                            _ => {
+                                break;
+                            }
+                        }
+                    }
+                    self.generate(AdvancePhase);
+                }
+                SpiceBlowPhase::ShaiHalud => {
+                    self.next_worm();
+                }
+                SpiceBlowPhase::PlaceSpice => {
+                    if let Some(spice_card) = self.state.spice_card.as_ref() {
+                        let SpiceLocationData {
+                            location,
+                            sector,
+                            spice,
+                        } = self.data.spice_cards[&spice_card.inner].location_data.unwrap();
+                        self.generate(PlaceSpice {
+                            location: location.with_sector(sector),
+                            spice,
+                        });
+                    }
+                    self.generate(AdvancePhase);
+                }
+            },
+            Phase::Revival => {
+                let eligible = self
+                    .state
+                    .play_order
+                    .iter()
+                    .filter(|player_id| !self.state.players[player_id].tanks.is_empty())
+                    .copied()
+                    .collect();
+                self.open_window(Prompt::Revival, eligible, AdvancePhase);
+            }
+            Phase::Movement => {
+                self.generate(StartRound);
+            }
+            Phase::Battle => {
+                let battles = self.state.find_battles();
+                self.generate(SetBattles { battles });
+                self.next_battle();
+            }
+            Phase::Nexus => {
+                if self.state.nexus.is_some() {
+                    // TODO: hold the nexus
+                }
+                self.generate(AdvancePhase);
+            }
+            Phase::Collection => {
+                let mut collections = Vec::new();
+                for (location, location_state) in
+                    Location::iter().filter_map(|location| Some((location, self.state.board.get(&location)?)))
+                {
+                    let mut sectors = location_state.sectors.iter().collect::<Vec<_>>();
+                    sectors.sort_by_key(|(&sector, _)| sector);
+                    for (&sector, sector_state) in sectors {
+                        if sector_state.spice == 0 {
+                            continue;
+                        }
+                        // Each sector pays out separately, and only to a player who has it to themselves
+                        if let Some((player_id, forces)) = sector_state.sole_occupant() {
+                            // Worked out wide, since a big enough stack of forces could collect more than a u8 holds
+                            let rate = self.state.collection_rate(&self.data, player_id) as usize;
+                            let spice = (rate * forces.forces.len()).min(sector_state.spice as usize) as u8;
+                            collections.push(CollectSpice {
+                                player_id,
+                                spice,
+                                from: Some(location.with_sector(sector)),
+                            });
+                        }
+                    }
+                }
+                // Tech tokens yield spice without any forces to collect it
+                if let Some(holders) = self.state.resources.get(&ResourceKind::SpiceProduction) {
+                    let mut holders = holders.iter().collect::<Vec<_>>();
+                    holders.sort();
+                    collections.extend(holders.into_iter().map(|(&player_id, &spice)| CollectSpice {
+                        player_id,
+                        spice,
+                        from: None,
+                    }));
+                }
+                for event in collections {
+                    self.generate(event);
+                }
+                self.regenerate_spice();
+                self.generate(AdvancePhase);
+            }
+            Phase::Control => match self.state.check_victory(&self.data) {
+                Some(reason) => self.generate(EndGame { reason }),
+                None if self.state.rules.mentat_pause => {
+                    let eligible = self.state.play_order.iter().copied().collect();
+                    self.open_window(Prompt::Continue, eligible, AdvancePhase);
+                }
+                None => self.generate(AdvancePhase),
+            },
+            Phase::Bidding(s) => match s {
+                BiddingPhase::DealCards => {
+                    self.generate(StartBidding);
+                    self.generate(AdvancePhase);
+                }
+                BiddingPhase::Bidding => {
+                    self.generate(StartRound);
+                }
+            },
+            _ => (),
+        }
+    }
+
+    /// Deal the top card of the traitor deck to a player.
+    fn deal_traitor(&mut self, player_id: PlayerId) {
+        let card_id = self.state.decks.traitor.peek().map(|card| card.id);
+        self.generate(GameEvent::DealTraitor { player_id, card_id });
+    }
+
+    /// Deal the top card of the treachery deck to a player.
+    fn deal_card(&mut self, player_id: PlayerId) {
+        let card_id = self.state.decks.treachery.peek().map(|card| card.id);
+        self.generate(GameEvent::DealCard {
+            player_id,
+            from: DeckType::Treachery,
+            card_id,
+        });
+    }
+
+    /// Trigger the faction abilities for the phase which was just entered.
+    fn trigger_abilities(&mut self) {
+        let phase = self.state.phase;
+        for ability in ABILITIES.iter().filter(|ability| ability.phase == phase) {
+            if let Some(player_id) = self.state.factions.get(&ability.faction).copied() {
+                (ability.trigger)(self, player_id);
+            }
+        }
+    }
+
+    /// Give every player holding a treachery card of the given kind the chance to play it. The phase advances once
+    /// the card is played or everyone has declined.
+    fn offer_card(&mut self, kind: TreacheryCardKind) {
+        let holders = self
+            .state
+            .play_order
+            .iter()
+            .filter(|player_id| {
+                self.state.players[player_id]
+                    .treachery_cards
+                    .iter()
+                    .any(|card| card.inner.kind == kind)
+                    && self.may_play(kind, **player_id)
+            })
+            .copied()
+            .collect();
+        self.open_window(Prompt::PlayCard { kind }, holders, GameEvent::AdvancePhase);
+    }
+
+    /// Whether a player is in a position to play a card of the given kind. Family Atomics may only be set off by
+    /// someone with forces on or next to the Shield Wall.
+    fn may_play(&self, kind: TreacheryCardKind, player_id: PlayerId) -> bool {
+        match kind {
+            TreacheryCardKind::FamilyAtomics => self.state.board.iter().any(|(&location, location_state)| {
+                (location == Location::ShieldWall || self.data.are_adjacent(Location::ShieldWall, location))
+                    && location_state
+                        .sectors
+                        .values()
+                        .any(|sector| sector.forces.get(&player_id).map_or(false, |f| !f.forces.is_empty()))
+            }),
+            _ => true,
+        }
+    }
+
+    /// Put any cards still up for bid back under the treachery deck, so an auction which ended early doesn't carry over
+    /// into the next turn.
+    fn return_unsold_cards(&mut self) {
+        while let Some(card_id) = self.state.bidding_cards.current().map(|bid_state| bid_state.card.id) {
+            self.generate(GameEvent::ReturnBidCard { card_id: Some(card_id) });
+        }
+    }
+
+    /// Regrow spice in the sectors the rules say it regrows in, wherever it has all been collected. Spice which
+    /// regrows in the storm is lost, like any other.
+    fn regenerate_spice(&mut self) {
+        for regrowth in self.state.rules.spice_regrowth.clone() {
+            let barren = self
+                .state
+                .board
+                .get(&regrowth.location.location)
+                .and_then(|location_state| location_state.sectors.get(&regrowth.location.sector))
+                .map_or(true, |sector_state| sector_state.spice == 0);
+            if barren {
+                self.generate(GameEvent::PlaceSpice {
+                    location: regrowth.location,
+                    spice: regrowth.spice,
+                });
+            }
+        }
+    }
+
+    /// Whether the Bene Gesserit are still deciding whether their advisors will fight someone who shipped in.
+    fn advisors_deciding(&self) -> bool {
+        self.state
+            .prompts
+            .values()
+            .any(|prompt| matches!(prompt, Prompt::AdvisorDecision { .. }))
+    }
+
+    /// Whether anyone left standing after a worm devoured is still deciding whether to ride it.
+    fn riders_deciding(&self) -> bool {
+        self.state
+            .prompts
+            .values()
+            .any(|prompt| matches!(prompt, Prompt::RideWorm { .. }))
+    }
+
+    /// Give the Guild the chance to use a Karama to stop a player from shipping this turn, before they get to.
+    fn offer_shipment_block(&mut self, target: PlayerId) {
+        let guild = match self.state.factions.get(&Faction::SpacingGuild) {
+            Some(&guild) if guild != target => guild,
+            _ => return,
+        };
+        let target_player = &self.state.players[&target];
+        if target_player.shipped
+            || target_player.offworld_forces.is_empty()
+            || self.state.shipment_blocked.contains(&target)
+            || !self.state.players[&guild]
+                .treachery_cards
+                .iter()
+                .any(|card| card.inner.kind == TreacheryCardKind::Karama)
+        {
+            return;
+        }
+        self.open_window(
+            Prompt::Karama {
+                effect: KaramaEffect::BlockShipment { target },
+            },
+            HashSet::from([guild]),
+            GameEvent::SetActive { player_id: target },
+        );
+    }
+
+    /// Prompt each eligible player and wait for all of them to respond before running `on_complete`. If nobody is
+    /// eligible, `on_complete` runs right away.
+    fn open_window(&mut self, prompt: Prompt, eligible: HashSet<PlayerId>, on_complete: GameEvent) {
+        if eligible.is_empty() {
+            self.generate(on_complete);
+        } else {
+            self.generate(GameEvent::OpenWindow {
+                window: ResponseWindow::new(prompt, eligible, on_complete),
+            });
+        }
+    }
+
+    /// Close the open response window and continue, if everyone has responded.
+    fn close_window(&mut self) {
+        if let Some(window) = self.state.response_window.as_ref() {
+            if window.is_complete() {
+                let on_complete = *window.on_complete.clone();
+                self.generate(GameEvent::CloseWindow);
+                self.generate(on_complete);
+            }
+        }
+    }
+
+    /// Let the next worm which surfaced this turn devour, or move on once they all have. Anyone with forces in the
+    /// worm's territory gets the chance to use a Maker Hook first.
+    fn next_worm(&mut self) {
+        if let Some(location) = self.state.worms.first().copied() {
+            let fremen = self.state.factions.get(&Faction::Fremen).copied();
+            let holders = self
+                .state
+                .board
+                .get(&location)
+                .into_iter()
+                .flat_map(|location_state| location_state.sectors.values())
+                .flat_map(|sector| sector.forces.keys())
+                .filter(|player_id| Some(**player_id) != fremen)
+                .filter(|player_id| {
+                    self.state.players[player_id]
+                        .treachery_cards
+                        .iter()
+                        .any(|card| card.inner.kind == TreacheryCardKind::MakerHook)
+                })
+                .copied()
+                .collect();
+            self.open_window(
+                Prompt::PlayCard {
+                    kind: TreacheryCardKind::MakerHook,
+                },
+                holders,
+                GameEvent::Devour { location },
+            );
+        } else if self.state.phase == Phase::SpiceBlow(SpiceBlowPhase::ShaiHalud) {
+            self.generate(GameEvent::AdvancePhase);
+        }
+    }
+
+    /// Fight the next battle, once its aggressor has chosen which of their battles comes first. Move on once every
+    /// battle has been fought.
+    fn next_battle(&mut self) {
+        if let Some(battle) = self.state.battles.front().copied() {
+            let locations = self.state.aggressor_battle_locations();
+            if !self.state.battle_order_chosen && locations.len() > 1 {
+                self.generate(GameEvent::ShowPrompt {
+                    player_id: battle.aggressor,
+                    prompt: Prompt::BattleOrder { locations },
+                });
+            } else {
+                for player_id in [battle.aggressor, battle.defender] {
+                    self.generate(GameEvent::ShowPrompt {
+                        player_id,
+                        prompt: Prompt::BattlePlan { battle },
+                    });
+                }
+            }
+        } else {
+            self.generate(GameEvent::AdvancePhase);
+        }
+    }
+
+    /// Consume an event and run the game logic for it.
+    fn generate(&mut self, event: GameEvent) {
+        self.state.consume(&self.data, event.clone());
+        self.generated.push(event.clone());
+        self.game_logic(event);
+    }
+
+    fn spawn<T>(&mut self, t: T) -> Object<T> {
+        self.ids.spawn(t)
+    }
+}
@@ -0,0 +1,58 @@
+use super::{GameDriver, TRAITORS_DEALT};
+use crate::{
+    components::Faction,
+    game::{
+        phase::{setup::SetupPhase, Phase},
+        state::{GameEvent, PlayerId, Prompt},
+    },
+};
+
+/// A faction power which the server triggers for that faction's player as a phase begins, before the rest of the
+/// phase's logic runs.
+pub struct FactionAbility {
+    pub faction: Faction,
+    pub phase: Phase,
+    pub trigger: fn(&mut GameDriver, PlayerId),
+}
+
+/// Every faction power which is triggered by a phase beginning. Powers which instead change how a rule applies, like
+/// the Fremen moving further and surviving worms, the Guild's shipping rates or the Sardaukar's strength in battle,
+/// are checked where that rule is.
+pub const ABILITIES: &[FactionAbility] = &[
+    FactionAbility {
+        faction: Faction::BeneGesserit,
+        phase: Phase::Setup(SetupPhase::Prediction),
+        trigger: prediction,
+    },
+    FactionAbility {
+        faction: Faction::Harkonnen,
+        phase: Phase::Setup(SetupPhase::DealTraitors),
+        trigger: keep_traitors,
+    },
+    FactionAbility {
+        faction: Faction::Harkonnen,
+        phase: Phase::Setup(SetupPhase::DealTreachery),
+        trigger: extra_treachery_card,
+    },
+];
+
+/// The Bene Gesserit secretly predict which faction will win, and on which turn.
+fn prediction(driver: &mut GameDriver, player_id: PlayerId) {
+    driver.generate(GameEvent::SetActive { player_id });
+    driver.generate(GameEvent::ShowPrompt {
+        player_id,
+        prompt: Prompt::FactionPrediction,
+    });
+}
+
+/// The Harkonnen keep every traitor they are dealt, rather than choosing one.
+fn keep_traitors(driver: &mut GameDriver, player_id: PlayerId) {
+    for _ in 0..TRAITORS_DEALT {
+        driver.deal_traitor(player_id);
+    }
+}
+
+/// The Harkonnen start with an extra treachery card.
+fn extra_treachery_card(driver: &mut GameDriver, player_id: PlayerId) {
+    driver.deal_card(player_id);
+}
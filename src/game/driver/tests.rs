@@ -0,0 +1,2520 @@
+//! Games played out through the driver, without a server or any networking.
+
+use std::collections::HashSet;
+
+use strum::IntoEnumIterator;
+
+use super::GameDriver;
+use crate::{
+    components::{Faction, Leader, Location, LocationSector, SpiceCard, Terrain, TreacheryCard, TreacheryCardKind},
+    data::Data,
+    game::{
+        phase::{
+            bidding::BiddingPhase, setup::SetupPhase, spice_blow::SpiceBlowPhase, storm::StormPhase, Phase,
+            PhaseSequence,
+        },
+        state::{
+            audit_events, Adjustment, Battle, DeckType, EndGameReason, EventReduce, ForceDial, GameEvent, GameState,
+            KaramaEffect, PaymentReason, PlayerId, Prompt, RuleSet, Seating, SpiceRegrowth, StateError,
+        },
+        Object, ObjectId,
+    },
+};
+
+/// The seed every test game is played with, unless a test asks for another.
+pub const SEED: u64 = 1;
+
+/// The turn the Bene Gesserit predict, later than any test plays to.
+pub const PREDICTED_TURN: u8 = 10;
+
+/// Players numbered from 1, one for each of the given factions.
+pub fn players(factions: &[Faction]) -> Vec<PlayerId> {
+    (1..=factions.len() as u64).map(PlayerId).collect()
+}
+
+/// The least any player can do to let the game carry on, or none if nobody can do anything. Player `n` picks the
+/// `n`th of the given factions, traitors and predictions are the first on offer, starting forces all go to the
+/// faction's first possible territory, and everything else is declined or passed.
+pub fn next_action(driver: &GameDriver, factions: &[Faction]) -> Option<GameEvent> {
+    let state = &driver.state;
+    let mut waiting = state
+        .play_order
+        .iter()
+        .chain(state.prompts.keys())
+        .copied()
+        .collect::<Vec<_>>();
+    waiting.sort();
+    waiting.dedup();
+    waiting
+        .into_iter()
+        .filter_map(|player_id| action_for(driver, factions, player_id))
+        .find(|event| state.validate(&driver.data, event))
+}
+
+fn action_for(driver: &GameDriver, factions: &[Faction], player_id: PlayerId) -> Option<GameEvent> {
+    use GameEvent::*;
+    let state = &driver.state;
+    if state
+        .response_window
+        .as_ref()
+        .map_or(false, |window| window.can_respond(&player_id))
+    {
+        return Some(Decline { player_id });
+    }
+    let event = match state.prompts.get(&player_id) {
+        Some(Prompt::Faction { .. }) => ChooseFaction {
+            player_id,
+            faction: factions[player_id.0 as usize - 1],
+        },
+        Some(Prompt::Traitor) => ChooseTraitor {
+            player_id,
+            card_id: state.players[&player_id]
+                .traitor_cards
+                .iter()
+                .map(|card| card.id)
+                .min()?,
+        },
+        Some(Prompt::FactionPrediction) => MakeFactionPrediction {
+            player_id,
+            faction: factions[0],
+        },
+        Some(Prompt::TurnPrediction) => MakeTurnPrediction {
+            player_id,
+            turn: PREDICTED_TURN,
+        },
+        Some(Prompt::RideWorm { location }) => RideTheWorm {
+            player_id,
+            from: *location,
+            to: None,
+        },
+        Some(Prompt::AdvisorOrFighter { location } | Prompt::AdvisorDecision { location }) => ChooseAdvisors {
+            player_id,
+            location: *location,
+            advisors: true,
+        },
+        Some(Prompt::BattleOrder { locations }) => ChooseBattleOrder {
+            player_id,
+            order: locations.clone(),
+        },
+        Some(Prompt::BattlePlan { .. }) => SetBattlePlan {
+            player_id,
+            forces: Default::default(),
+            leader: None,
+            treachery_cards: Vec::new(),
+        },
+        _ if state.active_player == Some(player_id) => match state.phase {
+            Phase::Setup(SetupPhase::PlaceForces) => starting_forces(driver, player_id)?,
+            _ => Pass { player_id },
+        },
+        _ => return None,
+    };
+    Some(event)
+}
+
+/// Ship all of a player's starting forces to the first territory they may start in.
+fn starting_forces(driver: &GameDriver, player_id: PlayerId) -> Option<GameEvent> {
+    let player = &driver.state.players[&player_id];
+    let starting_values = &driver.data.factions[&player.faction].starting_values;
+    let location = Location::iter()
+        .find(|location| {
+            starting_values
+                .possible_locations
+                .as_ref()
+                .map_or(*location == Location::PolarSink, |possible| possible.contains(location))
+        })
+        .unwrap();
+    let sector = *driver.data.locations[&location].sectors.keys().min()?;
+    let mut forces = player
+        .offworld_forces
+        .iter()
+        .filter(|force| !force.inner.is_special)
+        .map(|force| force.id)
+        .collect::<Vec<_>>();
+    forces.sort();
+    forces.truncate(starting_values.units as usize);
+    Some(GameEvent::ShipForces {
+        player_id,
+        to: location.with_sector(sector),
+        forces: forces.into_iter().collect(),
+    })
+}
+
+/// A game run by the driver alone, keeping every event just as the server would.
+#[derive(Clone)]
+pub struct TestGame {
+    pub driver: GameDriver,
+    pub log: Vec<GameEvent>,
+    factions: Vec<Faction>,
+}
+
+impl TestGame {
+    /// Start a game between the given factions, seated in the order given.
+    pub fn start(factions: &[Faction], rules: RuleSet) -> Self {
+        Self::start_with_seed(factions, rules, SEED)
+    }
+
+    pub fn start_with_seed(factions: &[Faction], rules: RuleSet, seed: u64) -> Self {
+        let mut driver = GameDriver::new(Data::default());
+        let rules = RuleSet {
+            seating: Seating::JoinOrder,
+            ..rules
+        };
+        let log = driver.start_game(players(factions), Some(PlayerId(1)), rules, seed);
+        Self {
+            driver,
+            log,
+            factions: factions.to_vec(),
+        }
+    }
+
+    /// Start a game between the given factions and play through setup, stopping at the first decision of the first
+    /// turn.
+    pub fn setup(factions: &[Faction], rules: RuleSet) -> Self {
+        let mut game = Self::start(factions, rules);
+        game.play_until(|game| !matches!(game.driver.state.phase, Phase::Setup(_)));
+        game
+    }
+
+    /// The player playing a faction.
+    pub fn player(&self, faction: Faction) -> PlayerId {
+        self.driver.state.factions[&faction]
+    }
+
+    /// Validate and apply an event as if a player sent it.
+    pub fn apply(&mut self, event: GameEvent) -> Result<Vec<GameEvent>, StateError> {
+        let events = self.driver.apply(event)?;
+        self.log.extend(events.iter().cloned());
+        Ok(events)
+    }
+
+    /// Apply an event without validating it, as the server would.
+    pub fn run(&mut self, event: GameEvent) -> Vec<GameEvent> {
+        let events = self.driver.run(event);
+        self.log.extend(events.iter().cloned());
+        events
+    }
+
+    /// Have whoever the game is waiting on do the least they can. Returns whether anyone did anything.
+    pub fn step(&mut self) -> bool {
+        match next_action(&self.driver, &self.factions) {
+            Some(event) => {
+                self.apply(event).unwrap();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Step until the game is in the given condition, checking before every step.
+    pub fn play_until(&mut self, done: impl Fn(&Self) -> bool) {
+        for _ in 0..10_000 {
+            if done(self) {
+                return;
+            }
+            assert!(
+                self.step(),
+                "nobody can act in {:?}, with prompts {:?}",
+                self.driver.state.phase,
+                self.driver.state.prompts
+            );
+        }
+        panic!("the game never got where it was going");
+    }
+
+    /// Step until the game enters the given phase.
+    pub fn play_until_phase(&mut self, phase: Phase) {
+        self.play_until(|game| game.driver.state.phase == phase);
+    }
+
+    /// Put some of a player's reserves straight onto the board, without shipping them. Returns the forces put there.
+    pub fn put_forces(&mut self, player_id: PlayerId, to: LocationSector, count: usize) -> HashSet<ObjectId> {
+        let state = &mut self.driver.state;
+        let player = state.players.get_mut(&player_id).unwrap();
+        let mut forces = player.offworld_forces.iter().cloned().collect::<Vec<_>>();
+        forces.sort_by_key(|force| (force.inner.is_special, force.id));
+        forces.truncate(count);
+        assert_eq!(forces.len(), count, "not enough forces in reserve");
+        let ids = forces.iter().map(|force| force.id).collect();
+        let sector = state
+            .board
+            .entry(to.location)
+            .or_default()
+            .sectors
+            .entry(to.sector)
+            .or_default()
+            .forces
+            .entry(player_id)
+            .or_default();
+        for force in forces {
+            player.offworld_forces.remove(&force);
+            sector.forces.insert(force);
+        }
+        ids
+    }
+
+    /// Hand a player a treachery card of the given kind, wherever it was. Returns the card given.
+    pub fn give_card(&mut self, player_id: PlayerId, kind: TreacheryCardKind) -> ObjectId {
+        let state = &mut self.driver.state;
+        let deck = &mut state.decks.treachery;
+        let is_kind = |card: &&Object<TreacheryCard>| card.inner.kind == kind;
+        let card = if let Some(id) = deck.cards.iter().find(is_kind).map(|card| card.id) {
+            deck.take(id)
+        } else if let Some(id) = deck.discards.iter().find(is_kind).map(|card| card.id) {
+            deck.discard_order.retain(|card_id| *card_id != id);
+            deck.discards.take(&id)
+        } else {
+            state.players.values_mut().find_map(|player| {
+                let id = player.treachery_cards.iter().find(is_kind)?.id;
+                player.treachery_cards.take(&id)
+            })
+        };
+        // Cards which aren't in the deck, like those from the expansion, are made for the occasion
+        let card = card.unwrap_or_else(|| self.driver.spawn(TreacheryCard { kind, variant: 0 }));
+        let id = card.id;
+        self.driver
+            .state
+            .players
+            .get_mut(&player_id)
+            .unwrap()
+            .treachery_cards
+            .insert(card);
+        id
+    }
+
+    /// How many of a player's forces are in a sector.
+    pub fn forces_at(&self, player_id: PlayerId, at: LocationSector) -> usize {
+        self.driver
+            .state
+            .board
+            .get(&at.location)
+            .and_then(|location_state| location_state.sectors.get(&at.sector))
+            .and_then(|sector| sector.forces.get(&player_id))
+            .map_or(0, |forces| forces.forces.len())
+    }
+
+    /// How much spice is lying in a sector.
+    pub fn spice_at(&self, at: LocationSector) -> u8 {
+        self.driver
+            .state
+            .board
+            .get(&at.location)
+            .and_then(|location_state| location_state.sectors.get(&at.sector))
+            .map_or(0, |sector| sector.spice)
+    }
+
+    /// Leave spice lying in a sector, on top of whatever is there already.
+    pub fn put_spice(&mut self, at: LocationSector, spice: u8) {
+        self.driver
+            .state
+            .board
+            .entry(at.location)
+            .or_default()
+            .sectors
+            .entry(at.sector)
+            .or_default()
+            .spice += spice;
+    }
+}
+
+/// Every phase the game enters as the given events are consumed, starting from the given state.
+pub fn phases_entered(data: &Data, mut state: GameState, events: &[GameEvent]) -> Vec<Phase> {
+    let mut phases = Vec::new();
+    for event in events {
+        let advance = *event == GameEvent::AdvancePhase;
+        state.consume(data, event.clone());
+        if advance {
+            phases.push(state.phase);
+        }
+    }
+    phases
+}
+
+#[test]
+fn setup_waits_for_each_decision() {
+    let mut game = TestGame::start(&[Faction::Atreides, Faction::Harkonnen], RuleSet::default());
+    let phase = |game: &TestGame| game.driver.state.phase;
+    assert_eq!(phase(&game), Phase::Setup(SetupPhase::ChooseFactions));
+    assert!(game.step());
+    assert_eq!(phase(&game), Phase::Setup(SetupPhase::ChooseFactions));
+    // Without the Bene Gesserit nobody predicts, and only the Atreides choose a traitor
+    assert!(game.step());
+    assert_eq!(phase(&game), Phase::Setup(SetupPhase::DealTraitors));
+    assert!(game.step());
+    assert_eq!(phase(&game), Phase::Setup(SetupPhase::PlaceForces));
+    assert_eq!(game.driver.state.active_player, Some(game.player(Faction::Atreides)));
+    assert!(game.step());
+    assert_eq!(phase(&game), Phase::Setup(SetupPhase::PlaceForces));
+    assert_eq!(game.driver.state.active_player, Some(game.player(Faction::Harkonnen)));
+    // The first turn runs without anyone until the first card is up for bid
+    assert!(game.step());
+    assert_eq!(phase(&game), Phase::Bidding(BiddingPhase::Bidding));
+    assert_eq!(game.driver.state.game_turn, 1);
+}
+
+#[test]
+fn phases_follow_the_sequence() {
+    let mut game = TestGame::start(
+        &[Faction::Atreides, Faction::BeneGesserit, Faction::Fremen],
+        RuleSet::default(),
+    );
+    let before = GameState::default();
+    game.play_until(|game| game.driver.state.game_turn == 2 && game.driver.state.phase == Phase::Movement);
+    let sequence = PhaseSequence::default();
+    // Setup and every phase of the first turn, then the second turn up until movement
+    let expected = sequence
+        .setup
+        .iter()
+        .chain(sequence.turn.iter())
+        .chain(sequence.turn.iter().take_while(|phase| **phase != Phase::Movement))
+        .chain([Phase::Movement].iter())
+        .copied()
+        .collect::<Vec<_>>();
+    assert_eq!(phases_entered(&game.driver.data, before, &game.log), expected);
+}
+
+#[test]
+fn each_step_moves_on_through_the_turn() {
+    let mut game = TestGame::setup(&[Faction::Atreides, Faction::Harkonnen], RuleSet::default());
+    assert_eq!(game.driver.state.phase, Phase::Bidding(BiddingPhase::Bidding));
+    let turn = game.driver.state.game_turn;
+    let sequence = PhaseSequence::default();
+    let bidding = sequence
+        .turn
+        .iter()
+        .position(|phase| *phase == Phase::Bidding(BiddingPhase::Bidding))
+        .unwrap();
+    let mut expected = sequence.turn.iter().cycle().skip(bidding + 1);
+    while game.driver.state.game_turn == turn || game.driver.state.phase != Phase::Bidding(BiddingPhase::Bidding) {
+        let before = game.driver.state.clone();
+        let from = game.log.len();
+        assert!(game.step(), "stalled in {:?}", game.driver.state.phase);
+        let entered = phases_entered(&game.driver.data, before, &game.log[from..]);
+        for phase in entered.iter() {
+            assert_eq!(Some(phase), expected.next());
+        }
+        // Each step leaves the game in the last phase it entered, waiting on someone
+        if let Some(phase) = entered.last() {
+            assert_eq!(game.driver.state.phase, *phase);
+        }
+    }
+    assert_eq!(game.driver.state.game_turn, turn + 1);
+}
+
+/// The spice collected by each player from each sector in the given events.
+fn collected(events: &[GameEvent]) -> Vec<(PlayerId, u8, Option<LocationSector>)> {
+    events
+        .iter()
+        .filter_map(|event| match event {
+            GameEvent::CollectSpice { player_id, spice, from } => Some((*player_id, *spice, *from)),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn collection_pays_sole_occupants_at_their_rate() {
+    let mut game = TestGame::setup(
+        &[Faction::Atreides, Faction::Harkonnen, Faction::Fremen],
+        RuleSet::default(),
+    );
+    game.play_until_phase(Phase::Movement);
+    let (atreides, harkonnen, fremen) = (
+        game.player(Faction::Atreides),
+        game.player(Faction::Harkonnen),
+        game.player(Faction::Fremen),
+    );
+    // The Atreides hold Arrakeen from the start, so they collect 3 spice a force
+    let old_gap = Location::OldGap.with_sector(9);
+    game.put_forces(atreides, old_gap, 2);
+    game.driver
+        .state
+        .board
+        .get_mut(&Location::OldGap)
+        .unwrap()
+        .sectors
+        .get_mut(&9)
+        .unwrap()
+        .spice = 10;
+    // The Fremen hold no city, so they collect 2 a force, but no more than is there
+    let broken_land = Location::BrokenLand.with_sector(11);
+    game.put_forces(fremen, broken_land, 3);
+    game.driver
+        .state
+        .board
+        .get_mut(&Location::BrokenLand)
+        .unwrap()
+        .sectors
+        .get_mut(&11)
+        .unwrap()
+        .spice = 4;
+    // Nobody collects from a sector they share
+    let tsimpo = Location::Tsimpo.with_sector(11);
+    game.put_forces(atreides, tsimpo, 1);
+    game.put_forces(harkonnen, tsimpo, 1);
+    game.driver
+        .state
+        .board
+        .get_mut(&Location::Tsimpo)
+        .unwrap()
+        .sectors
+        .get_mut(&11)
+        .unwrap()
+        .spice = 5;
+
+    // Straight on to collection, since the battle would settle who holds Tsimpo
+    game.driver.state.phase = Phase::Battle;
+    let events = game.run(GameEvent::AdvancePhase);
+    let mut collections = collected(&events);
+    collections.sort_by_key(|(player_id, ..)| *player_id);
+    let mut expected = vec![(atreides, 6, Some(old_gap)), (fremen, 4, Some(broken_land))];
+    expected.sort_by_key(|(player_id, ..)| *player_id);
+    assert_eq!(collections, expected);
+}
+
+#[test]
+fn collection_rate_depends_on_the_cities() {
+    let mut game = TestGame::setup(&[Faction::Atreides, Faction::Fremen], RuleSet::default());
+    let (atreides, fremen) = (game.player(Faction::Atreides), game.player(Faction::Fremen));
+    let data = game.driver.data.clone();
+    assert_eq!(game.driver.state.collection_rate(&data, atreides), 3);
+    assert_eq!(game.driver.state.collection_rate(&data, fremen), 2);
+    // Sharing Arrakeen means nobody controls it
+    game.put_forces(fremen, Location::Arrakeen.with_sector(9), 1);
+    assert_eq!(game.driver.state.collection_rate(&data, atreides), 2);
+    game.put_forces(fremen, Location::Carthag.with_sector(10), 1);
+    assert_eq!(game.driver.state.collection_rate(&data, fremen), 3);
+}
+
+#[test]
+fn spice_piles_up_no_further_than_it_can_be_counted() {
+    let mut game = TestGame::setup(&[Faction::Atreides, Faction::Harkonnen], RuleSet::default());
+    let (atreides, harkonnen) = (game.player(Faction::Atreides), game.player(Faction::Harkonnen));
+    game.driver.state.players.get_mut(&atreides).unwrap().spice = u8::MAX - 1;
+    game.driver.state.players.get_mut(&harkonnen).unwrap().spice = 10;
+    let old_gap = Location::OldGap.with_sector(9);
+    game.put_spice(old_gap, 6);
+    let data = game.driver.data.clone();
+    let state = &mut game.driver.state;
+    state.consume(
+        &data,
+        GameEvent::CollectSpice {
+            player_id: atreides,
+            spice: 6,
+            from: Some(old_gap),
+        },
+    );
+    assert_eq!(state.players[&atreides].spice, u8::MAX);
+    // Whatever is paid is still taken from the payer, even if the rest can't be counted
+    state.players.get_mut(&atreides).unwrap().bribe_spice = u8::MAX - 1;
+    for reason in [PaymentReason::Bribe, PaymentReason::Bid] {
+        state.consume(
+            &data,
+            GameEvent::PaySpice {
+                from: harkonnen,
+                to: Some(atreides),
+                amount: 5,
+                reason,
+            },
+        );
+    }
+    assert_eq!(state.players[&atreides].bribe_spice, u8::MAX);
+    state.consume(&data, GameEvent::CollectBribes);
+    assert_eq!(state.players[&atreides].spice, u8::MAX);
+    assert_eq!(state.players[&atreides].bribe_spice, 0);
+    assert_eq!(state.players[&harkonnen].spice, 0);
+}
+
+#[test]
+fn conceding_withdraws_a_bid() {
+    let mut game = TestGame::setup(
+        &[Faction::Atreides, Faction::Harkonnen, Faction::Fremen],
+        RuleSet::default(),
+    );
+    let (atreides, harkonnen, fremen) = (
+        game.player(Faction::Atreides),
+        game.player(Faction::Harkonnen),
+        game.player(Faction::Fremen),
+    );
+    // The Atreides open the bidding, and the Harkonnen raise it before leaving
+    assert_eq!(game.driver.state.active_player, Some(harkonnen));
+    game.apply(GameEvent::MakeBid {
+        player_id: harkonnen,
+        spice: 3,
+    })
+    .unwrap();
+    assert_eq!(game.driver.state.active_player, Some(fremen));
+    let card = game.driver.state.bidding_cards.current().unwrap().card.id;
+    game.apply(GameEvent::Concede { player_id: harkonnen }).unwrap();
+
+    let state = &game.driver.state;
+    let bid_state = state.bidding_cards.current().unwrap();
+    assert_eq!(bid_state.card.id, card);
+    assert_ne!(bid_state.current_bid.as_ref().map(|bid| bid.player_id), Some(harkonnen));
+    assert_eq!(state.play_order, vec![atreides, fremen]);
+    // Their forces went to the tanks rather than vanishing
+    let player = &state.players[&harkonnen];
+    assert_eq!(player.tanks.forces.len(), 10);
+    assert_eq!(game.forces_at(harkonnen, Location::Carthag.with_sector(10)), 0);
+    assert!(player.treachery_cards.is_empty());
+
+    // The auction carries on without them, and nobody pays for their bid
+    let spice = state.players[&fremen].spice;
+    game.play_until_phase(Phase::Movement);
+    assert!(game.log.iter().all(|event| !matches!(
+        event,
+        GameEvent::WinBid { player_id, .. } if *player_id == harkonnen
+    )));
+    assert_eq!(game.driver.state.players[&fremen].spice, spice);
+}
+
+/// Have worms surface in the given territories, as if they had just been revealed, and let them devour.
+fn surface_worms(game: &mut TestGame, worms: &[Location]) {
+    game.driver.state.worms = worms.to_vec();
+    game.driver.state.phase = Phase::SpiceBlow(SpiceBlowPhase::Reveal);
+    game.run(GameEvent::AdvancePhase);
+}
+
+#[test]
+fn fremen_survive_the_worm_and_ride_it() {
+    let mut game = TestGame::setup(&[Faction::Atreides, Faction::Fremen], RuleSet::default());
+    let (atreides, fremen) = (game.player(Faction::Atreides), game.player(Faction::Fremen));
+    let (hagga_basin, hagga_basin_east) = (
+        Location::HaggaBasin.with_sector(11),
+        Location::HaggaBasin.with_sector(12),
+    );
+    game.put_forces(fremen, hagga_basin, 3);
+    game.put_forces(atreides, hagga_basin_east, 2);
+    game.driver
+        .state
+        .board
+        .get_mut(&Location::HaggaBasin)
+        .unwrap()
+        .sectors
+        .get_mut(&11)
+        .unwrap()
+        .spice = 6;
+    let tanked = game.driver.state.players[&atreides].tanks.forces.len();
+
+    surface_worms(&mut game, &[Location::HaggaBasin]);
+    game.play_until(|game| game.driver.state.prompts.contains_key(&fremen));
+    assert_eq!(
+        game.driver.state.prompts[&fremen],
+        Prompt::RideWorm {
+            location: Location::HaggaBasin
+        }
+    );
+    assert_eq!(game.forces_at(fremen, hagga_basin), 3);
+    assert_eq!(game.forces_at(atreides, hagga_basin_east), 0);
+    assert_eq!(game.driver.state.players[&atreides].tanks.forces.len(), tanked + 2);
+    assert_eq!(game.spice_at(hagga_basin), 0);
+
+    // The worm can't carry them into the storm, wherever it is
+    let storm = game.driver.state.storm_sector;
+    let data = game.driver.data.clone();
+    let into_storm = Location::iter()
+        .filter(|location| *location != Location::PolarSink)
+        .find(|location| data.locations[location].sectors.contains_key(&storm))
+        .unwrap()
+        .with_sector(storm);
+    assert!(game
+        .apply(GameEvent::RideTheWorm {
+            player_id: fremen,
+            from: Location::HaggaBasin,
+            to: Some(into_storm),
+        })
+        .is_err());
+    let to = Location::iter()
+        .flat_map(|location| {
+            data.locations[&location]
+                .sectors
+                .keys()
+                .map(move |&sector| location.with_sector(sector))
+        })
+        .find(|to| to.location != Location::PolarSink && !game.driver.state.in_storm(to))
+        .unwrap();
+    game.apply(GameEvent::RideTheWorm {
+        player_id: fremen,
+        from: Location::HaggaBasin,
+        to: Some(to),
+    })
+    .unwrap();
+    assert_eq!(game.forces_at(fremen, to), 3);
+    assert_eq!(game.forces_at(fremen, hagga_basin), 0);
+    assert_ne!(game.driver.state.phase, Phase::SpiceBlow(SpiceBlowPhase::ShaiHalud));
+}
+
+#[test]
+fn a_rider_who_leaves_lets_the_next_worm_surface() {
+    let mut game = empty_board(&[Faction::Atreides, Faction::Harkonnen, Faction::Fremen]);
+    let fremen = game.player(Faction::Fremen);
+    let hagga_basin = Location::HaggaBasin.with_sector(11);
+    game.put_forces(fremen, hagga_basin, 3);
+    game.driver.state.storm_sector = 0;
+
+    surface_worms(&mut game, &[Location::HaggaBasin, Location::HaggaBasin]);
+    game.play_until(|game| game.driver.state.prompts.contains_key(&fremen));
+    game.apply(GameEvent::Concede { player_id: fremen }).unwrap();
+    // Only the second worm is left, and with nobody there to devour it is done at once
+    assert!(game.driver.state.worms.is_empty());
+    assert_ne!(game.driver.state.phase, Phase::SpiceBlow(SpiceBlowPhase::ShaiHalud));
+}
+
+#[test]
+fn every_worm_devours_in_turn() {
+    let mut game = TestGame::setup(&[Faction::Atreides, Faction::Fremen], RuleSet::default());
+    let (atreides, fremen) = (game.player(Faction::Atreides), game.player(Faction::Fremen));
+    let (hagga_basin, broken_land) = (
+        Location::HaggaBasin.with_sector(11),
+        Location::BrokenLand.with_sector(11),
+    );
+    game.put_forces(fremen, hagga_basin, 2);
+    game.put_forces(atreides, hagga_basin, 1);
+    game.put_forces(atreides, broken_land, 2);
+    let tanked = game.driver.state.players[&atreides].tanks.forces.len();
+
+    surface_worms(&mut game, &[Location::HaggaBasin, Location::BrokenLand]);
+    // The second worm waits until the Fremen have decided whether to ride the first
+    game.play_until(|game| game.driver.state.prompts.contains_key(&fremen));
+    assert_eq!(game.driver.state.worms, vec![Location::BrokenLand]);
+    assert_eq!(game.forces_at(atreides, broken_land), 2);
+    game.apply(GameEvent::RideTheWorm {
+        player_id: fremen,
+        from: Location::HaggaBasin,
+        to: None,
+    })
+    .unwrap();
+    game.play_until(|game| game.driver.state.phase != Phase::SpiceBlow(SpiceBlowPhase::ShaiHalud));
+    assert!(game.driver.state.worms.is_empty());
+    assert_eq!(game.forces_at(atreides, broken_land), 0);
+    assert_eq!(game.forces_at(fremen, hagga_basin), 2);
+    assert_eq!(game.driver.state.players[&atreides].tanks.forces.len(), tanked + 3);
+    let devoured = game
+        .log
+        .iter()
+        .filter_map(|event| match event {
+            GameEvent::Devour { location } => Some(*location),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(devoured, vec![Location::HaggaBasin, Location::BrokenLand]);
+}
+
+/// Whether a card has been played from a player's hand onto the discard pile.
+fn discarded(game: &TestGame, player_id: PlayerId, card_id: ObjectId) -> bool {
+    !game.driver.state.players[&player_id].treachery_cards.contains(&card_id)
+        && game.driver.state.decks.treachery.discards.contains(&card_id)
+}
+
+#[test]
+fn a_thumper_calls_a_worm_on_the_players_turn_to_move() {
+    let mut game = empty_board(&[Faction::Atreides, Faction::Harkonnen]);
+    let (atreides, harkonnen) = (game.player(Faction::Atreides), game.player(Faction::Harkonnen));
+    let thumper = game.give_card(atreides, TreacheryCardKind::Thumper);
+    let hagga_basin = Location::HaggaBasin.with_sector(11);
+    game.put_forces(harkonnen, hagga_basin, 3);
+    let tanked = game.driver.state.players[&harkonnen].tanks.forces.len();
+    let play = |location: Location| GameEvent::PlayThumper {
+        player_id: atreides,
+        card_id: thumper,
+        location,
+    };
+
+    // Not outside of the spice blow or movement
+    assert!(game.apply(play(Location::HaggaBasin)).is_err());
+    movement_until(&mut game, harkonnen);
+    // Nor on someone else's turn
+    assert!(game.apply(play(Location::HaggaBasin)).is_err());
+    game.apply(GameEvent::Pass { player_id: harkonnen }).unwrap();
+    movement_until(&mut game, atreides);
+    // And only out in the sand
+    assert!(game.apply(play(Location::Arrakeen)).is_err());
+
+    game.apply(play(Location::HaggaBasin)).unwrap();
+    assert!(discarded(&game, atreides, thumper));
+    assert_eq!(game.forces_at(harkonnen, hagga_basin), 0);
+    assert_eq!(game.driver.state.players[&harkonnen].tanks.forces.len(), tanked + 3);
+    assert!(game.driver.state.worms.is_empty());
+    // Their turn carries on once the worm is gone
+    assert_eq!(game.driver.state.phase, Phase::Movement);
+    assert_eq!(game.driver.state.active_player, Some(atreides));
+}
+
+#[test]
+fn a_maker_hook_rides_out_the_worm_while_a_thumper_calls_another() {
+    let mut game = TestGame::setup(&[Faction::Atreides, Faction::Harkonnen], RuleSet::default());
+    let (atreides, harkonnen) = (game.player(Faction::Atreides), game.player(Faction::Harkonnen));
+    let hook = game.give_card(atreides, TreacheryCardKind::MakerHook);
+    let thumper = game.give_card(harkonnen, TreacheryCardKind::Thumper);
+    let (hagga_basin, broken_land) = (
+        Location::HaggaBasin.with_sector(11),
+        Location::BrokenLand.with_sector(11),
+    );
+    game.put_forces(atreides, hagga_basin, 2);
+    game.put_forces(harkonnen, hagga_basin, 1);
+    game.put_forces(atreides, broken_land, 2);
+    let tanked = |game: &TestGame, player_id: PlayerId| game.driver.state.players[&player_id].tanks.forces.len();
+    let (atreides_tanked, harkonnen_tanked) = (tanked(&game, atreides), tanked(&game, harkonnen));
+
+    // While the Atreides decide whether to hold on with their hook, the Harkonnen call a second worm
+    surface_worms(&mut game, &[Location::HaggaBasin]);
+    let window = game.driver.state.response_window.as_ref().unwrap();
+    assert_eq!(
+        window.prompt,
+        Prompt::PlayCard {
+            kind: TreacheryCardKind::MakerHook
+        }
+    );
+    assert!(window.can_respond(&atreides));
+    game.apply(GameEvent::PlayThumper {
+        player_id: harkonnen,
+        card_id: thumper,
+        location: Location::BrokenLand,
+    })
+    .unwrap();
+    assert!(discarded(&game, harkonnen, thumper));
+    assert_eq!(
+        game.driver.state.worms,
+        vec![Location::HaggaBasin, Location::BrokenLand]
+    );
+
+    // The hook keeps the Atreides above the worm, and lets them ride it
+    game.apply(GameEvent::PlayCard {
+        player_id: atreides,
+        card_id: hook,
+    })
+    .unwrap();
+    assert!(discarded(&game, atreides, hook));
+    assert_eq!(game.forces_at(atreides, hagga_basin), 2);
+    assert_eq!(game.forces_at(harkonnen, hagga_basin), 0);
+    assert_eq!(tanked(&game, harkonnen), harkonnen_tanked + 1);
+    assert_eq!(
+        game.driver.state.prompts.get(&atreides),
+        Some(&Prompt::RideWorm {
+            location: Location::HaggaBasin
+        })
+    );
+    // The thumper's worm waits its turn
+    assert_eq!(game.forces_at(atreides, broken_land), 2);
+    game.apply(GameEvent::RideTheWorm {
+        player_id: atreides,
+        from: Location::HaggaBasin,
+        to: None,
+    })
+    .unwrap();
+
+    // Having used their hook, the Atreides get no help from it against the next worm
+    game.play_until(|game| game.driver.state.phase != Phase::SpiceBlow(SpiceBlowPhase::ShaiHalud));
+    assert!(game.driver.state.worms.is_empty());
+    assert_eq!(game.forces_at(atreides, broken_land), 0);
+    assert_eq!(game.forces_at(atreides, hagga_basin), 2);
+    assert_eq!(tanked(&game, atreides), atreides_tanked + 2);
+}
+
+#[test]
+fn family_atomics_are_offered_only_next_to_the_shield_wall() {
+    let mut game = TestGame::setup(&[Faction::Atreides, Faction::Harkonnen], RuleSet::default());
+    let (atreides, harkonnen) = (game.player(Faction::Atreides), game.player(Faction::Harkonnen));
+    let data = game.driver.data.clone();
+    let sector_of = |location: Location| location.with_sector(*data.locations[&location].sectors.keys().min().unwrap());
+    let far_away = Location::iter()
+        .find(|&location| {
+            location != Location::ShieldWall
+                && location != Location::PolarSink
+                && !data.are_adjacent(Location::ShieldWall, location)
+        })
+        .unwrap();
+    let next_door = Location::iter()
+        .find(|&location| location != Location::PolarSink && data.are_adjacent(Location::ShieldWall, location))
+        .unwrap();
+    game.give_card(atreides, TreacheryCardKind::FamilyAtomics);
+    game.put_forces(atreides, sector_of(far_away), 1);
+    game.put_forces(harkonnen, sector_of(Location::ShieldWall), 1);
+    // Leave the auction behind, without anyone still waiting to bid
+    let storm = |game: &mut TestGame| {
+        game.driver.state.prompts.clear();
+        game.driver.state.phase = Phase::Storm(StormPhase::WeatherControl);
+        game.run(GameEvent::AdvancePhase);
+    };
+
+    // Too far away to set them off, so the phase goes straight on
+    storm(&mut game);
+    assert!(game.driver.state.response_window.is_none());
+    assert_ne!(game.driver.state.phase, Phase::Storm(StormPhase::FamilyAtomics));
+
+    // Next to the wall they are offered, only to the player holding them
+    game.put_forces(atreides, sector_of(next_door), 1);
+    storm(&mut game);
+    assert_eq!(game.driver.state.phase, Phase::Storm(StormPhase::FamilyAtomics));
+    assert_eq!(
+        game.driver.state.prompts.get(&atreides),
+        Some(&Prompt::PlayCard {
+            kind: TreacheryCardKind::FamilyAtomics
+        })
+    );
+    assert!(!game.driver.state.prompts.contains_key(&harkonnen));
+    assert!(game.apply(GameEvent::Decline { player_id: harkonnen }).is_err());
+
+    game.apply(GameEvent::Decline { player_id: atreides }).unwrap();
+    assert_ne!(game.driver.state.phase, Phase::Storm(StormPhase::FamilyAtomics));
+    assert!(!game.driver.state.shield_wall_destroyed);
+    assert_eq!(game.forces_at(harkonnen, sector_of(Location::ShieldWall)), 1);
+}
+
+#[test]
+fn nobody_bids_more_than_they_have() {
+    let mut game = TestGame::setup(&[Faction::Atreides, Faction::Harkonnen], RuleSet::default());
+    let harkonnen = game.player(Faction::Harkonnen);
+    assert_eq!(game.driver.state.active_player, Some(harkonnen));
+    let spice = game.driver.state.players[&harkonnen].spice;
+    assert!(matches!(
+        game.apply(GameEvent::MakeBid {
+            player_id: harkonnen,
+            spice: spice + 1,
+        }),
+        Err(StateError::InvalidEvent(_))
+    ));
+    assert_eq!(game.driver.state.active_player, Some(harkonnen));
+    game.apply(GameEvent::MakeBid {
+        player_id: harkonnen,
+        spice,
+    })
+    .unwrap();
+}
+
+#[test]
+fn nobody_pays_more_spice_than_they_have() {
+    let mut game = TestGame::setup(&[Faction::Atreides, Faction::Harkonnen], RuleSet::default());
+    let (atreides, harkonnen) = (game.player(Faction::Atreides), game.player(Faction::Harkonnen));
+    let spice = |game: &TestGame, player_id| game.driver.state.players[&player_id].spice;
+    let (atreides_spice, harkonnen_spice) = (spice(&game, atreides), spice(&game, harkonnen));
+
+    game.run(GameEvent::PaySpice {
+        from: harkonnen,
+        to: Some(atreides),
+        amount: harkonnen_spice + 5,
+        reason: PaymentReason::Bid,
+    });
+    assert_eq!(spice(&game, harkonnen), 0);
+    assert_eq!(spice(&game, atreides), atreides_spice + harkonnen_spice);
+
+    // Someone who isn't playing has nothing to pay with
+    game.run(GameEvent::PaySpice {
+        from: PlayerId(99),
+        to: Some(atreides),
+        amount: 5,
+        reason: PaymentReason::Bid,
+    });
+    assert_eq!(spice(&game, atreides), atreides_spice + harkonnen_spice);
+}
+
+#[test]
+fn the_same_seed_plays_the_same_game() {
+    let factions = [Faction::Atreides, Faction::Harkonnen, Faction::Fremen, Faction::Emperor];
+    // Each game reads its own data, so nothing can depend on the order a particular map happens to iterate in
+    let play = |seed| {
+        let mut game = TestGame::start_with_seed(&factions, RuleSet::default(), seed);
+        game.play_until(|game| game.driver.state.game_turn >= 3);
+        game
+    };
+    let (first, second) = (play(SEED), play(SEED));
+    assert_eq!(first.log, second.log);
+    assert_eq!(first.driver.state, second.driver.state);
+
+    let other = play(SEED + 1);
+    assert_ne!(first.log, other.log);
+}
+
+/// A game between the given factions with the board cleared, so only the forces a test puts there control anything.
+fn empty_board(factions: &[Faction]) -> TestGame {
+    let mut game = TestGame::setup(factions, RuleSet::default());
+    game.driver.state.board.clear();
+    game
+}
+
+/// Put one of a player's forces in each of the given strongholds.
+fn hold(game: &mut TestGame, player_id: PlayerId, strongholds: &[Location]) {
+    for &location in strongholds {
+        let sector = *game.driver.data.locations[&location].sectors.keys().min().unwrap();
+        game.put_forces(player_id, location.with_sector(sector), 1);
+    }
+}
+
+#[test]
+fn victory_depends_on_the_number_of_players() {
+    let mut game = empty_board(&[Faction::Atreides, Faction::Harkonnen, Faction::Fremen, Faction::Emperor]);
+    let emperor = game.player(Faction::Emperor);
+    hold(&mut game, emperor, &[Location::Arrakeen, Location::Carthag]);
+    assert_eq!(game.driver.state.check_victory(&game.driver.data), None);
+    hold(&mut game, emperor, &[Location::SietchTabr]);
+    assert_eq!(
+        game.driver.state.check_victory(&game.driver.data),
+        Some(EndGameReason::Victory { winners: vec![emperor] })
+    );
+
+    // With only three players, three strongholds isn't enough
+    let mut game = empty_board(&[Faction::Atreides, Faction::Harkonnen, Faction::Emperor]);
+    let emperor = game.player(Faction::Emperor);
+    hold(
+        &mut game,
+        emperor,
+        &[Location::Arrakeen, Location::Carthag, Location::SietchTabr],
+    );
+    assert_eq!(game.driver.state.check_victory(&game.driver.data), None);
+    hold(&mut game, emperor, &[Location::TueksSietch]);
+    assert_eq!(
+        game.driver.state.check_victory(&game.driver.data),
+        Some(EndGameReason::Victory { winners: vec![emperor] })
+    );
+}
+
+#[test]
+fn allies_win_together_with_one_more_stronghold_each() {
+    let mut game = empty_board(&[Faction::Atreides, Faction::Harkonnen, Faction::Fremen, Faction::Emperor]);
+    let (atreides, fremen, emperor) = (
+        game.player(Faction::Atreides),
+        game.player(Faction::Fremen),
+        game.player(Faction::Emperor),
+    );
+    game.driver.state.alliances = vec![[fremen, emperor].into_iter().collect()];
+    hold(
+        &mut game,
+        emperor,
+        &[Location::Arrakeen, Location::Carthag, Location::SietchTabr],
+    );
+    assert_eq!(game.driver.state.check_victory(&game.driver.data), None);
+    hold(&mut game, fremen, &[Location::TueksSietch]);
+    assert_eq!(
+        game.driver.state.check_victory(&game.driver.data),
+        Some(EndGameReason::Victory {
+            winners: vec![fremen, emperor]
+        })
+    );
+
+    // A bigger alliance needs more again
+    game.driver.state.alliances = vec![[atreides, fremen, emperor].into_iter().collect()];
+    assert_eq!(game.driver.state.check_victory(&game.driver.data), None);
+    hold(&mut game, atreides, &[Location::HabbanyaSietch]);
+    assert_eq!(
+        game.driver.state.check_victory(&game.driver.data),
+        Some(EndGameReason::Victory {
+            winners: vec![atreides, fremen, emperor]
+        })
+    );
+}
+
+#[test]
+fn the_storm_only_sweeps_the_sectors_it_passes_over() {
+    let mut game = TestGame::setup(&[Faction::Atreides, Faction::Harkonnen], RuleSet::default());
+    let (atreides, harkonnen) = (game.player(Faction::Atreides), game.player(Faction::Harkonnen));
+    let (west, east) = (
+        Location::HaggaBasin.with_sector(11),
+        Location::HaggaBasin.with_sector(12),
+    );
+    game.put_forces(atreides, west, 2);
+    game.put_forces(harkonnen, east, 3);
+    let tanked = game.driver.state.players[&atreides].tanks.forces.len();
+    let state = &mut game.driver.state;
+    state.storm_sector = 10;
+    state.storm_card = state.decks.storm.iter().find(|card| card.inner.val == 1).cloned();
+    // What the board shows will be swept is exactly what is
+    assert_eq!(state.storm_path(), vec![11]);
+
+    let data = game.driver.data.clone();
+    game.driver.state.consume(&data, GameEvent::MoveStorm { sectors: 1 });
+    assert_eq!(game.driver.state.storm_sector, 11);
+    assert_eq!(game.forces_at(atreides, west), 0);
+    assert_eq!(game.driver.state.players[&atreides].tanks.forces.len(), tanked + 2);
+    assert_eq!(game.forces_at(harkonnen, east), 3);
+
+    // The sector the storm starts from isn't swept again
+    game.put_forces(atreides, west, 1);
+    game.driver.state.consume(&data, GameEvent::MoveStorm { sectors: 1 });
+    assert_eq!(game.forces_at(atreides, west), 1);
+    assert_eq!(game.forces_at(harkonnen, east), 0);
+}
+
+#[test]
+fn dealing_moves_cards_from_the_deck_to_the_hand_one_at_a_time() {
+    let mut game = TestGame::setup(&[Faction::Atreides, Faction::Harkonnen], RuleSet::default());
+    let atreides = game.player(Faction::Atreides);
+    let deck = game.driver.state.decks.treachery.card_order.clone();
+    let hand = game.driver.state.players[&atreides].treachery_cards.len();
+
+    for dealt in 1..=3 {
+        let card_id = game.driver.state.decks.treachery.peek().map(|card| card.id);
+        game.run(GameEvent::DealCard {
+            player_id: atreides,
+            from: DeckType::Treachery,
+            card_id,
+        });
+        // Cards come off the top of the deck
+        let top = deck[deck.len() - dealt];
+        let state = &game.driver.state;
+        assert_eq!(state.decks.treachery.card_order, deck[..deck.len() - dealt]);
+        assert_eq!(state.players[&atreides].treachery_cards.len(), hand + dealt);
+        assert!(state.players[&atreides].treachery_cards.contains(&top));
+    }
+}
+
+#[test]
+fn each_player_is_dealt_four_traitors_only_they_are_shown() {
+    let factions = [Faction::Atreides, Faction::Harkonnen, Faction::Emperor];
+    let mut game = TestGame::start(&factions, RuleSet::default());
+    game.play_until_phase(Phase::Setup(SetupPhase::DealTraitors));
+    let state = &game.driver.state;
+    let mut dealt = HashSet::new();
+    for player_id in players(&factions) {
+        let hand = state.players[&player_id]
+            .traitor_cards
+            .iter()
+            .map(|card| card.id)
+            .collect::<HashSet<_>>();
+        assert_eq!(hand.len(), 4);
+        assert!(hand.is_disjoint(&dealt));
+        dealt.extend(hand);
+    }
+    // The Harkonnen keep all of theirs, so only everyone else chooses one
+    for (player_id, faction) in players(&factions).into_iter().zip(factions) {
+        let choosing = state.prompts.get(&player_id) == Some(&Prompt::Traitor);
+        assert_eq!(choosing, faction != Faction::Harkonnen, "{}", faction);
+    }
+
+    // Nobody is told the order of the traitor deck, and a player only told about their own traitors still sees four
+    // dealt to everyone
+    let me = PlayerId(1);
+    assert!(!game.log.iter().any(|event| {
+        let traitor_order = matches!(
+            event,
+            GameEvent::SetDeckOrder {
+                deck_type: DeckType::Traitor,
+                ..
+            }
+        );
+        traitor_order && event.visible_to(me, &state.rules)
+    }));
+    let mut told = GameState::default();
+    for event in game.log.iter().filter(|event| event.visible_to(me, &state.rules)) {
+        let event = event.redacted_for(me);
+        if let GameEvent::DealTraitor { player_id, card_id } = &event {
+            assert_eq!(card_id.is_some(), *player_id == me);
+        }
+        told.consume(&game.driver.data, event);
+    }
+    for player_id in players(&factions) {
+        assert_eq!(told.players[&player_id].traitor_cards.len(), 4);
+    }
+    assert_eq!(told.players[&me].traitor_cards, state.players[&me].traitor_cards);
+}
+
+/// Two neighbouring sand territories, each with a sector out of the storm, to ship into and move between.
+fn open_sand(game: &TestGame) -> (LocationSector, LocationSector) {
+    let data = &game.driver.data;
+    let state = &game.driver.state;
+    let open = |location: Location| {
+        let mut sectors = data.locations[&location].sectors.keys().copied().collect::<Vec<_>>();
+        sectors.sort();
+        sectors
+            .into_iter()
+            .map(|sector| location.with_sector(sector))
+            .find(|sector| !state.in_storm(sector))
+    };
+    let sand = |location: &Location| data.locations[location].terrain == Terrain::Sand;
+    Location::iter()
+        .filter(sand)
+        .find_map(|from| {
+            let to = Location::iter().find(|to| sand(to) && data.are_adjacent(from, *to) && open(*to).is_some())?;
+            Some((open(from)?, open(to)?))
+        })
+        .unwrap()
+}
+
+/// Some of a player's reserves, normal forces first.
+fn reserves(game: &TestGame, player_id: PlayerId, count: usize) -> HashSet<ObjectId> {
+    let mut forces = game.driver.state.players[&player_id]
+        .offworld_forces
+        .iter()
+        .map(|force| (force.inner.is_special, force.id))
+        .collect::<Vec<_>>();
+    forces.sort();
+    forces.into_iter().take(count).map(|(_, id)| id).collect()
+}
+
+#[test]
+fn each_player_ships_once_then_moves_once() {
+    let mut game = TestGame::setup(&[Faction::Atreides, Faction::Harkonnen], RuleSet::default());
+    game.play_until_phase(Phase::Movement);
+    let player_id = game.driver.state.active_player.unwrap();
+    let (from, to) = open_sand(&game);
+    let forces = reserves(&game, player_id, 2);
+
+    game.apply(GameEvent::ShipForces {
+        player_id,
+        to: from,
+        forces: forces.clone(),
+    })
+    .unwrap();
+    assert_eq!(game.forces_at(player_id, from), 2);
+    assert!(game
+        .apply(GameEvent::ShipForces {
+            player_id,
+            to: from,
+            forces: reserves(&game, player_id, 1),
+        })
+        .is_err());
+
+    game.apply(GameEvent::MoveForces {
+        player_id,
+        path: vec![from, to],
+        forces: forces.clone(),
+    })
+    .unwrap();
+    assert_eq!(game.forces_at(player_id, to), 2);
+    assert!(game
+        .apply(GameEvent::MoveForces {
+            player_id,
+            path: vec![to, from],
+            forces,
+        })
+        .is_err());
+    assert_eq!(game.forces_at(player_id, to), 2);
+}
+
+#[test]
+fn bene_gesserit_ship_in_beside_an_enemy_as_advisors() {
+    let mut game = TestGame::setup(&[Faction::BeneGesserit, Faction::Harkonnen], RuleSet::default());
+    game.play_until_phase(Phase::Movement);
+    let bg = game.player(Faction::BeneGesserit);
+    let harkonnen = game.player(Faction::Harkonnen);
+    let (to, _) = open_sand(&game);
+    game.put_forces(harkonnen, to, 3);
+    game.driver.state.active_player = Some(bg);
+    game.driver.state.players.get_mut(&bg).unwrap().spice = 10;
+
+    game.apply(GameEvent::ShipForces {
+        player_id: bg,
+        to,
+        forces: reserves(&game, bg, 1),
+    })
+    .unwrap();
+    let state = &game.driver.state;
+    assert!(state.board[&to.location].sectors[&to.sector].forces[&bg].advisors);
+    assert_eq!(state.prompts.get(&bg), Some(&Prompt::AdvisorOrFighter { location: to }));
+    assert!(!matches!(
+        state.prompts.get(&harkonnen),
+        Some(Prompt::AdvisorOrFighter { .. } | Prompt::AdvisorDecision { .. })
+    ));
+    assert!(game
+        .apply(GameEvent::ChooseAdvisors {
+            player_id: harkonnen,
+            location: to,
+            advisors: false,
+        })
+        .is_err());
+
+    game.apply(GameEvent::ChooseAdvisors {
+        player_id: bg,
+        location: to,
+        advisors: true,
+    })
+    .unwrap();
+    assert!(game.driver.state.find_battles().is_empty());
+}
+
+#[test]
+fn advisors_decide_whether_to_fight_someone_who_ships_in() {
+    let mut game = TestGame::setup(&[Faction::BeneGesserit, Faction::Harkonnen], RuleSet::default());
+    game.play_until_phase(Phase::Movement);
+    let bg = game.player(Faction::BeneGesserit);
+    let harkonnen = game.player(Faction::Harkonnen);
+    let (to, _) = open_sand(&game);
+    game.put_forces(bg, to, 2);
+    game.driver
+        .state
+        .board
+        .get_mut(&to.location)
+        .unwrap()
+        .sectors
+        .get_mut(&to.sector)
+        .unwrap()
+        .forces
+        .get_mut(&bg)
+        .unwrap()
+        .advisors = true;
+    game.driver.state.prompts.clear();
+    game.driver.state.active_player = Some(harkonnen);
+    game.driver.state.players.get_mut(&harkonnen).unwrap().spice = 10;
+
+    game.apply(GameEvent::ShipForces {
+        player_id: harkonnen,
+        to,
+        forces: reserves(&game, harkonnen, 3),
+    })
+    .unwrap();
+    assert_eq!(
+        game.driver.state.prompts.get(&bg),
+        Some(&Prompt::AdvisorDecision { location: to })
+    );
+    assert!(game
+        .apply(GameEvent::ChooseAdvisors {
+            player_id: harkonnen,
+            location: to,
+            advisors: false,
+        })
+        .is_err());
+
+    // Staying as advisors leaves nothing to fight over
+    let mut stay = game.clone();
+    stay.apply(GameEvent::ChooseAdvisors {
+        player_id: bg,
+        location: to,
+        advisors: true,
+    })
+    .unwrap();
+    assert!(stay.driver.state.prompts.get(&bg).is_none());
+    assert!(stay.driver.state.find_battles().is_empty());
+
+    // Flipping to fighters means a battle for the territory
+    game.apply(GameEvent::ChooseAdvisors {
+        player_id: bg,
+        location: to,
+        advisors: false,
+    })
+    .unwrap();
+    let battles = game.driver.state.find_battles();
+    assert_eq!(battles.len(), 1);
+    assert_eq!(battles[0].location, to.location);
+}
+
+#[test]
+fn the_storm_deck_is_reshuffled_once_every_card_is_drawn() {
+    let mut game = TestGame::setup(&[Faction::Atreides, Faction::Harkonnen], RuleSet::default());
+    let mut moves = Vec::new();
+    for turn in 0..7 {
+        game.driver.state.prompts.clear();
+        game.driver.state.phase = Phase::Control;
+        let events = game.run(GameEvent::AdvancePhase);
+        let storm = &game.driver.state.decks.storm;
+        assert_eq!(storm.cards.len() + storm.discards.len(), 6);
+        let reshuffled = events.iter().any(|event| {
+            matches!(
+                event,
+                GameEvent::SetDeckOrder {
+                    deck_type: DeckType::Storm,
+                    ..
+                }
+            )
+        });
+        // Only once the last of the six cards has been drawn
+        assert_eq!(reshuffled, turn == 6);
+        moves.extend(events.iter().filter_map(|event| match event {
+            GameEvent::MoveStorm { sectors } => Some(*sectors),
+            _ => None,
+        }));
+    }
+    let mut first_pass = moves[..6].to_vec();
+    first_pass.sort();
+    assert_eq!(first_pass, vec![1, 2, 3, 4, 5, 6]);
+    assert_eq!(moves.len(), 7);
+    let storm = &game.driver.state.decks.storm;
+    assert_eq!((storm.cards.len(), storm.discards.len()), (5, 1));
+}
+
+#[test]
+fn the_storm_is_placed_once_then_moved_by_storm_cards() {
+    let mut game = TestGame::setup(&[Faction::Atreides, Faction::Harkonnen], RuleSet::default());
+    let storm_events = |events: &[GameEvent]| {
+        events
+            .iter()
+            .filter(|event| matches!(event, GameEvent::RevealStorm { .. } | GameEvent::MoveStorm { .. }))
+            .cloned()
+            .collect::<Vec<_>>()
+    };
+    // The first turn sets the storm down somewhere, without drawing a card
+    let placed = storm_events(&game.log);
+    assert!(matches!(placed[..], [GameEvent::MoveStorm { sectors }] if sectors < 18));
+    assert!(game.driver.state.storm_placed);
+
+    // Coming back to the storm without a card in hand doesn't place it again
+    game.driver.state.prompts.clear();
+    game.driver.state.phase = Phase::Storm(StormPhase::FamilyAtomics);
+    let events = game.run(GameEvent::AdvancePhase);
+    assert!(storm_events(&events).is_empty());
+
+    // Every turn after that it moves as far as the card drawn says
+    let start = game.driver.state.storm_sector;
+    game.driver.state.prompts.clear();
+    game.driver.state.phase = Phase::Control;
+    let events = game.run(GameEvent::AdvancePhase);
+    let storm = &game.driver.state.decks.storm;
+    let card_id = *storm.discard_order.last().unwrap();
+    let drawn = storm.get(card_id).unwrap().inner.val;
+    assert_eq!(
+        storm_events(&events),
+        vec![
+            GameEvent::RevealStorm { card_id },
+            GameEvent::MoveStorm { sectors: drawn }
+        ]
+    );
+    assert_eq!(game.driver.state.storm_sector, (start + drawn) % 18);
+}
+
+/// Leave movement behind and find the battles, without anyone still waiting on something earlier.
+fn start_battles(game: &mut TestGame) -> usize {
+    game.driver.state.prompts.clear();
+    game.driver.state.active_player = None;
+    game.driver.state.phase = Phase::Movement;
+    let start = game.log.len();
+    game.run(GameEvent::AdvancePhase);
+    start
+}
+
+/// The battles fought since the given point in the log, in the order their plans were asked for.
+fn battles_fought(game: &TestGame, since: usize) -> Vec<Battle> {
+    game.log[since..]
+        .iter()
+        .filter_map(|event| match event {
+            GameEvent::ShowPrompt {
+                player_id,
+                prompt: Prompt::BattlePlan { battle },
+            } if *player_id == battle.aggressor => Some(*battle),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The first sector of each territory, other than the Polar Sink where nobody fights.
+fn contested(game: &TestGame, count: usize) -> Vec<LocationSector> {
+    Location::iter()
+        .filter(|location| *location != Location::PolarSink)
+        .take(count)
+        .map(|location| location.with_sector(*game.driver.data.locations[&location].sectors.keys().min().unwrap()))
+        .collect()
+}
+
+#[test]
+fn the_aggressor_chooses_the_order_of_their_battles() {
+    let mut game = empty_board(&[Faction::Atreides, Faction::Harkonnen]);
+    let aggressor = game.driver.state.play_order[0];
+    let defender = game.driver.state.play_order[1];
+    let territories = contested(&game, 3);
+    for &at in territories.iter() {
+        game.put_forces(aggressor, at, 1);
+        game.put_forces(defender, at, 1);
+    }
+    let since = start_battles(&mut game);
+    let mut locations = territories.iter().map(|at| at.location).collect::<Vec<_>>();
+    match game.driver.state.prompts.get(&aggressor) {
+        Some(Prompt::BattleOrder { locations: offered }) => {
+            let mut offered = offered.clone();
+            offered.sort_by_key(|offered| locations.iter().position(|location| location == offered));
+            assert_eq!(offered, locations);
+        }
+        prompt => panic!("the aggressor was prompted with {:?}", prompt),
+    }
+    assert!(!game.driver.state.prompts.contains_key(&defender));
+
+    // Only the aggressor may choose, and only an order of the battles they have
+    locations.reverse();
+    assert!(game
+        .apply(GameEvent::ChooseBattleOrder {
+            player_id: defender,
+            order: locations.clone(),
+        })
+        .is_err());
+    assert!(game
+        .apply(GameEvent::ChooseBattleOrder {
+            player_id: aggressor,
+            order: locations[..2].to_vec(),
+        })
+        .is_err());
+    game.apply(GameEvent::ChooseBattleOrder {
+        player_id: aggressor,
+        order: locations.clone(),
+    })
+    .unwrap();
+    game.play_until(|game| game.driver.state.phase != Phase::Battle);
+    let fought = battles_fought(&game, since)
+        .into_iter()
+        .map(|battle| battle.location)
+        .collect::<Vec<_>>();
+    assert_eq!(fought, locations);
+}
+
+#[test]
+fn leaving_mid_battle_moves_on_to_the_next() {
+    let factions = [Faction::Atreides, Faction::Harkonnen, Faction::Fremen, Faction::Emperor];
+    let mut game = empty_board(&factions);
+    let players = game.driver.state.play_order.clone();
+    let territories = contested(&game, 2);
+    game.put_forces(players[0], territories[0], 1);
+    game.put_forces(players[1], territories[0], 1);
+    game.put_forces(players[2], territories[1], 1);
+    game.put_forces(players[3], territories[1], 1);
+    let since = start_battles(&mut game);
+    let (first, second) = (game.driver.state.battles[0], game.driver.state.battles[1]);
+
+    // One side has already made their plan when the other leaves
+    game.apply(GameEvent::SetBattlePlan {
+        player_id: first.aggressor,
+        forces: Default::default(),
+        leader: None,
+        treachery_cards: Vec::new(),
+    })
+    .unwrap();
+    game.apply(GameEvent::Concede {
+        player_id: first.defender,
+    })
+    .unwrap();
+    let state = &game.driver.state;
+    assert_eq!(state.battles, vec![second]);
+    assert!(state.committed_plans.is_empty());
+    assert!(state.battle_plans.is_empty());
+    for player_id in [second.aggressor, second.defender] {
+        assert_eq!(state.prompts[&player_id], Prompt::BattlePlan { battle: second });
+    }
+    assert!(!state.prompts.contains_key(&first.aggressor));
+
+    game.play_until(|game| game.driver.state.phase != Phase::Battle);
+    assert_eq!(battles_fought(&game, since), vec![first, second]);
+    let resolved = game.log[since..]
+        .iter()
+        .filter(|event| matches!(event, GameEvent::ResolveBattle { .. }))
+        .count();
+    assert_eq!(resolved, 1);
+}
+
+#[test]
+fn leaving_drops_battles_still_to_come() {
+    let factions = [Faction::Atreides, Faction::Harkonnen, Faction::Fremen];
+    let mut game = empty_board(&factions);
+    let players = game.driver.state.play_order.clone();
+    let territories = contested(&game, 2);
+    game.put_forces(players[0], territories[0], 1);
+    game.put_forces(players[1], territories[0], 1);
+    game.put_forces(players[1], territories[1], 1);
+    game.put_forces(players[2], territories[1], 1);
+    let since = start_battles(&mut game);
+    let first = game.driver.state.battles[0];
+    let leaving = *players.iter().find(|player_id| !first.involves(**player_id)).unwrap();
+
+    // The battle being fought carries on without them
+    game.apply(GameEvent::Concede { player_id: leaving }).unwrap();
+    assert_eq!(game.driver.state.battles, vec![first]);
+    game.play_until(|game| game.driver.state.phase != Phase::Battle);
+    assert_eq!(battles_fought(&game, since), vec![first]);
+}
+
+#[test]
+fn the_first_in_storm_order_attacks_and_a_third_faction_waits_its_turn() {
+    let mut game = empty_board(&[Faction::Atreides, Faction::Harkonnen, Faction::Fremen]);
+    let order = game.driver.state.play_order.clone();
+    let at = contested(&game, 1)[0];
+    // Arriving last in storm order first doesn't make anyone the aggressor
+    for &player_id in order.iter().rev() {
+        game.put_forces(player_id, at, 1);
+    }
+    assert_eq!(game.driver.state.combatants(at.location), Some((order[0], order[1])));
+
+    let since = start_battles(&mut game);
+    assert_eq!(
+        game.driver.state.battles,
+        vec![Battle {
+            location: at.location,
+            aggressor: order[0],
+            defender: order[1],
+        }]
+    );
+    game.play_until(|game| game.driver.state.phase != Phase::Battle);
+    assert_eq!(
+        battles_fought(&game, since),
+        vec![
+            Battle {
+                location: at.location,
+                aggressor: order[0],
+                defender: order[1],
+            },
+            Battle {
+                location: at.location,
+                aggressor: order[0],
+                defender: order[2],
+            },
+        ]
+    );
+}
+
+/// Fight a battle in which the aggressor overwhelms the defender, who sends a leader to die. Returns the leader.
+fn overwhelm(game: &mut TestGame) -> Object<Leader> {
+    let aggressor = game.driver.state.play_order[0];
+    let defender = game.driver.state.play_order[1];
+    let at = contested(game, 1)[0];
+    game.put_forces(aggressor, at, 10);
+    game.put_forces(defender, at, 1);
+    start_battles(game);
+    let mut leaders = game.driver.state.players[&defender]
+        .living_leaders
+        .keys()
+        .cloned()
+        .collect::<Vec<_>>();
+    leaders.sort_by_key(|leader| leader.id);
+    let leader = leaders.remove(0);
+    for (player_id, forces, leader) in [
+        (aggressor, ForceDial { normal: 10, special: 0 }, None),
+        (defender, ForceDial::default(), Some(leader.id)),
+    ] {
+        game.apply(GameEvent::SetBattlePlan {
+            player_id,
+            forces,
+            leader,
+            treachery_cards: Vec::new(),
+        })
+        .unwrap();
+    }
+    assert!(game.driver.state.players[&defender].tanks.leaders.contains(&leader));
+    leader
+}
+
+#[test]
+fn kanly_credits_the_winner_with_the_leaders_they_kill() {
+    let rules = RuleSet {
+        kanly: true,
+        ..Default::default()
+    };
+    let mut game = empty_board(&[Faction::Atreides, Faction::Harkonnen]);
+    game.driver.state.rules = rules;
+    let aggressor = game.driver.state.play_order[0];
+    let leader = overwhelm(&mut game);
+    assert_eq!(game.driver.state.kanly.get(&aggressor), Some(&vec![leader.inner]));
+    assert_eq!(game.driver.state.kanly.len(), 1);
+
+    // Without the rule, nobody keeps score
+    let mut game = empty_board(&[Faction::Atreides, Faction::Harkonnen]);
+    overwhelm(&mut game);
+    assert!(game.driver.state.kanly.is_empty());
+}
+
+#[test]
+fn a_new_turn_resets_what_players_did_and_starts_after_the_storm() {
+    let mut game = TestGame::setup(&[Faction::Atreides, Faction::Harkonnen], RuleSet::default());
+    let seats = game.driver.state.seats.clone();
+    let turn = game.driver.state.game_turn;
+    {
+        let state = &mut game.driver.state;
+        state.shipment_blocked.insert(seats[0]);
+        for player in state.players.values_mut() {
+            player.shipped = true;
+            player.moved = true;
+            player.extra_moves = 1;
+            player.living_leaders.values_mut().for_each(|fought| *fought = true);
+        }
+    }
+    assert!(game.apply(GameEvent::AdvanceTurn).is_err());
+
+    // The turn only advances once, as the next one starts
+    game.driver.state.prompts.clear();
+    game.driver.state.phase = Phase::Control;
+    let events = game.run(GameEvent::AdvancePhase);
+    assert_eq!(
+        events.iter().filter(|event| **event == GameEvent::AdvanceTurn).count(),
+        1
+    );
+    let state = &game.driver.state;
+    assert_eq!(state.game_turn, turn + 1);
+    assert!(state.shipment_blocked.is_empty());
+    for player in state.players.values() {
+        assert!(!player.shipped && !player.moved);
+        assert_eq!(player.extra_moves, 0);
+        assert!(player.living_leaders.values().all(|fought| !fought));
+    }
+
+    // Play starts with whoever the storm reaches next
+    let state = &mut game.driver.state;
+    state.storm_sector = state.seat_sector(1) - 1;
+    game.run(GameEvent::AdvanceTurn);
+    assert_eq!(game.driver.state.play_order, vec![seats[1], seats[0]]);
+    let state = &mut game.driver.state;
+    state.storm_sector = state.seat_sector(1);
+    game.run(GameEvent::AdvanceTurn);
+    assert_eq!(game.driver.state.play_order, seats);
+}
+
+/// The phases a turn steps through under the given rules, from its start until the auction is under way.
+fn turn_phases(rules: RuleSet) -> Vec<Phase> {
+    let mut game = TestGame::setup(&[Faction::Atreides, Faction::Harkonnen], rules);
+    game.driver.state.prompts.clear();
+    game.driver.state.phase = Phase::Control;
+    let before = game.driver.state.clone();
+    let events = game.run(GameEvent::AdvancePhase);
+    phases_entered(&game.driver.data, before, &events)
+}
+
+#[test]
+fn the_rules_decide_whether_the_spice_blows_first_and_how_often() {
+    let storm = [
+        Phase::Storm(StormPhase::Reveal),
+        Phase::Storm(StormPhase::WeatherControl),
+        Phase::Storm(StormPhase::FamilyAtomics),
+        Phase::Storm(StormPhase::MoveStorm),
+    ];
+    let blow = [
+        Phase::SpiceBlow(SpiceBlowPhase::Reveal),
+        Phase::SpiceBlow(SpiceBlowPhase::ShaiHalud),
+        Phase::SpiceBlow(SpiceBlowPhase::PlaceSpice),
+    ];
+    let auction = [
+        Phase::Nexus,
+        Phase::Bidding(BiddingPhase::DealCards),
+        Phase::Bidding(BiddingPhase::Bidding),
+    ];
+    let variants = [
+        (RuleSet::default(), [&storm[..], &blow, &auction].concat()),
+        (
+            RuleSet {
+                spice_blow_first: true,
+                ..Default::default()
+            },
+            [&blow[..], &storm, &auction].concat(),
+        ),
+        (
+            RuleSet {
+                spice_blows: 2,
+                ..Default::default()
+            },
+            [&storm[..], &blow, &blow, &auction].concat(),
+        ),
+        (
+            RuleSet {
+                spice_blow_first: true,
+                spice_blows: 2,
+                ..Default::default()
+            },
+            [&blow[..], &blow, &storm, &auction].concat(),
+        ),
+    ];
+    for (rules, phases) in variants {
+        assert_eq!(turn_phases(rules.clone()), phases, "with {:?}", rules);
+    }
+}
+
+#[test]
+fn passing_hands_play_on_in_order_until_everyone_has_gone() {
+    let mut game = empty_board(&[Faction::Atreides, Faction::Harkonnen, Faction::Fremen]);
+    let order = game.driver.state.play_order.clone();
+    game.driver.state.prompts.clear();
+    game.driver.state.phase = Phase::Revival;
+    game.run(GameEvent::AdvancePhase);
+    assert_eq!(game.driver.state.phase, Phase::Movement);
+    assert_eq!(game.driver.state.active_player, Some(order[0]));
+
+    for (i, &player_id) in order.iter().enumerate() {
+        // Nobody may pass for whoever's turn it is
+        for &other in order.iter().filter(|other| **other != player_id) {
+            assert!(game.apply(GameEvent::Pass { player_id: other }).is_err());
+        }
+        let events = game.apply(GameEvent::Pass { player_id }).unwrap();
+        match order.get(i + 1) {
+            Some(&next) => {
+                assert_eq!(game.driver.state.active_player, Some(next));
+                assert_eq!(game.driver.state.phase, Phase::Movement);
+            }
+            // Once the last player passes, the round is over rather than starting again with the first
+            None => assert_eq!(events[..2], [GameEvent::Pass { player_id }, GameEvent::AdvancePhase]),
+        }
+    }
+}
+
+#[test]
+fn revival_is_skipped_when_nobody_has_anything_to_revive() {
+    let mut game = TestGame::setup(&[Faction::Atreides, Faction::Harkonnen], RuleSet::default());
+    let revival = |game: &mut TestGame| {
+        game.driver.state.prompts.clear();
+        game.driver.state.bidding_cards.clear();
+        game.driver.state.phase = Phase::Bidding(BiddingPhase::Bidding);
+        game.run(GameEvent::AdvancePhase)
+    };
+    assert!(game.driver.state.players.values().all(|player| player.tanks.is_empty()));
+    let events = revival(&mut game);
+    assert!(!events.iter().any(|event| matches!(event, GameEvent::OpenWindow { .. })));
+    assert_eq!(game.driver.state.phase, Phase::Movement);
+
+    // Anyone with forces in the tanks may revive them, so the phase waits for them
+    let harkonnen = game.player(Faction::Harkonnen);
+    let player = game.driver.state.players.get_mut(&harkonnen).unwrap();
+    let force = player.offworld_forces.iter().next().cloned().unwrap();
+    player.offworld_forces.remove(&force);
+    player.tanks.forces.insert(force);
+    revival(&mut game);
+    assert_eq!(game.driver.state.phase, Phase::Revival);
+    let window = game.driver.state.response_window.as_ref().unwrap();
+    assert_eq!(window.prompt, Prompt::Revival);
+    assert_eq!(window.eligible, HashSet::from([harkonnen]));
+}
+
+/// Stack the spice deck so its next blows are the given cards, in order.
+fn stack_spice_deck(game: &mut TestGame, cards: &[SpiceCard]) {
+    let deck = &mut game.driver.state.decks.spice;
+    for card in cards.iter().rev() {
+        let id = deck.cards.iter().find(|object| object.inner == *card).unwrap().id;
+        deck.card_order.retain(|other| *other != id);
+        deck.card_order.push(id);
+    }
+}
+
+/// Blow the spice afresh, from the end of the storm.
+fn blow_spice(game: &mut TestGame) {
+    game.driver.state.prompts.clear();
+    game.driver.state.spice_blows = 0;
+    game.driver.state.phase = Phase::Storm(StormPhase::MoveStorm);
+    game.run(GameEvent::AdvancePhase);
+}
+
+/// Where a spice card blows, and how much.
+fn spice_blow(game: &TestGame, card: SpiceCard) -> (LocationSector, u8) {
+    let blow = game.driver.data.spice_cards[&card].location_data.unwrap();
+    (blow.location.with_sector(blow.sector), blow.spice)
+}
+
+/// Two spice cards which blow in different territories, and a sector the storm can sit in without covering either.
+fn two_blows(game: &TestGame) -> ([SpiceCard; 2], u8) {
+    let cards = SpiceCard::iter()
+        .filter(|card| {
+            game.driver
+                .data
+                .spice_cards
+                .get(card)
+                .map_or(false, |data| data.location_data.is_some())
+        })
+        .take(2)
+        .collect::<Vec<_>>();
+    let (first, second) = (spice_blow(game, cards[0]).0, spice_blow(game, cards[1]).0);
+    let calm = (0..18)
+        .find(|sector| *sector != first.sector && *sector != second.sector)
+        .unwrap();
+    ([cards[0], cards[1]], calm)
+}
+
+#[test]
+fn spice_blown_onto_spice_adds_to_it() {
+    let rules = RuleSet {
+        spice_blows: 2,
+        ..Default::default()
+    };
+    let mut game = empty_board(&[Faction::Atreides, Faction::Harkonnen]);
+    game.driver.state.rules = rules;
+    let (cards, calm) = two_blows(&game);
+    let (first, first_spice) = spice_blow(&game, cards[0]);
+    let (second, second_spice) = spice_blow(&game, cards[1]);
+    game.driver.state.storm_sector = calm;
+    game.put_spice(first, 4);
+    stack_spice_deck(&mut game, &cards);
+
+    // Both blows of the turn land, neither replacing what was there
+    blow_spice(&mut game);
+    assert_eq!(game.spice_at(first), 4 + first_spice);
+    assert_eq!(game.spice_at(second), second_spice);
+
+    // And the same territory blowing again piles more on, once its card is back on top of the deck
+    game.driver.state.rules.spice_blows = 1;
+    let deck = &mut game.driver.state.decks.spice;
+    let card = deck
+        .discards
+        .iter()
+        .find(|object| object.inner == cards[0])
+        .cloned()
+        .unwrap();
+    deck.discards.remove(&card);
+    deck.discard_order.retain(|id| *id != card.id);
+    deck.add(card);
+    blow_spice(&mut game);
+    assert_eq!(game.spice_at(first), 4 + 2 * first_spice);
+}
+
+#[test]
+fn spice_blown_into_the_storm_is_lost() {
+    let mut game = empty_board(&[Faction::Atreides, Faction::Harkonnen]);
+    let ([card, _], _) = two_blows(&game);
+    let (at, _) = spice_blow(&game, card);
+    game.driver.state.storm_sector = at.sector;
+    stack_spice_deck(&mut game, &[card]);
+    blow_spice(&mut game);
+    assert_eq!(game.spice_at(at), 0);
+    assert!(game
+        .driver
+        .state
+        .decks
+        .spice
+        .discards
+        .iter()
+        .any(|object| object.inner == card));
+}
+
+/// Collect spice, from the end of the battles, and return what everyone collected from where. The game pauses before
+/// the next turn, so its spice blow stays out of the way.
+fn collect_spice(game: &mut TestGame) -> Vec<(PlayerId, Option<LocationSector>, u8)> {
+    game.driver.state.rules.mentat_pause = true;
+    game.driver.state.response_window = None;
+    game.driver.state.prompts.clear();
+    game.driver.state.phase = Phase::Battle;
+    game.run(GameEvent::AdvancePhase)
+        .into_iter()
+        .filter_map(|event| match event {
+            GameEvent::CollectSpice { player_id, spice, from } => Some((player_id, from, spice)),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn spice_is_only_collected_from_a_sector_held_alone() {
+    let mut game = empty_board(&[Faction::Atreides, Faction::Harkonnen]);
+    let (atreides, harkonnen) = (game.player(Faction::Atreides), game.player(Faction::Harkonnen));
+    let location = Location::iter()
+        .find(|location| {
+            let location_data = &game.driver.data.locations[location];
+            location_data.terrain == Terrain::Sand && location_data.sectors.len() > 1
+        })
+        .unwrap();
+    let mut sectors = game.driver.data.locations[&location]
+        .sectors
+        .keys()
+        .copied()
+        .collect::<Vec<_>>();
+    sectors.sort();
+    let (west, east) = (location.with_sector(sectors[0]), location.with_sector(sectors[1]));
+    game.put_spice(west, 6);
+    game.put_spice(east, 6);
+
+    // Sharing a sector, neither collects any of it
+    game.put_forces(atreides, west, 1);
+    game.put_forces(harkonnen, west, 1);
+    assert!(collect_spice(&mut game).is_empty());
+    assert_eq!(game.spice_at(west), 6);
+
+    // Apart in the same territory, each collects from their own sector
+    game.driver.state.board.clear();
+    game.put_spice(west, 6);
+    game.put_spice(east, 6);
+    game.put_forces(atreides, west, 1);
+    game.put_forces(harkonnen, east, 1);
+    let mut collected = collect_spice(&mut game);
+    collected.sort_by_key(|(player_id, ..)| *player_id);
+    let rate = |player_id| game.driver.state.collection_rate(&game.driver.data, player_id);
+    let mut expected = vec![
+        (atreides, Some(west), rate(atreides)),
+        (harkonnen, Some(east), rate(harkonnen)),
+    ];
+    expected.sort_by_key(|(player_id, ..)| *player_id);
+    assert_eq!(collected, expected);
+}
+
+/// Start the Movement phase, and pass for anyone ahead of the given player until it is their turn to ship.
+fn movement_until(game: &mut TestGame, player_id: PlayerId) {
+    game.driver.state.prompts.clear();
+    game.driver.state.phase = Phase::Revival;
+    game.run(GameEvent::AdvancePhase);
+    while game.driver.state.active_player != Some(player_id) && game.driver.state.response_window.is_none() {
+        let active = game.driver.state.active_player.unwrap();
+        game.apply(GameEvent::Pass { player_id: active }).unwrap();
+    }
+}
+
+#[test]
+fn the_guild_can_use_a_karama_to_block_a_shipment() {
+    let mut game = TestGame::setup(&[Faction::SpacingGuild, Faction::Atreides], RuleSet::default());
+    let (guild, atreides) = (game.player(Faction::SpacingGuild), game.player(Faction::Atreides));
+    let karama = game.give_card(guild, TreacheryCardKind::Karama);
+    let (to, _) = open_sand(&game);
+    movement_until(&mut game, atreides);
+    let window = game.driver.state.response_window.as_ref().unwrap();
+    assert_eq!(
+        window.prompt,
+        Prompt::Karama {
+            effect: KaramaEffect::BlockShipment { target: atreides }
+        }
+    );
+    assert_eq!(window.eligible, HashSet::from([guild]));
+    let ship = |game: &mut TestGame| {
+        game.apply(GameEvent::ShipForces {
+            player_id: atreides,
+            to,
+            forces: reserves(game, atreides, 1),
+        })
+    };
+    // Nobody ships while the Guild is deciding
+    assert!(ship(&mut game).is_err());
+
+    game.apply(GameEvent::PlayCard {
+        player_id: guild,
+        card_id: karama,
+    })
+    .unwrap();
+    assert_eq!(game.driver.state.active_player, Some(atreides));
+    assert!(game.driver.state.shipment_blocked.contains(&atreides));
+    assert!(game.driver.state.decks.treachery.discards.contains(&karama));
+    assert!(!game.driver.state.players[&guild].treachery_cards.contains(&karama));
+    assert!(ship(&mut game).is_err());
+    assert_eq!(game.forces_at(atreides, to), 0);
+}
+
+#[test]
+fn a_shipment_the_guild_lets_pass_goes_ahead() {
+    let mut game = TestGame::setup(&[Faction::SpacingGuild, Faction::Atreides], RuleSet::default());
+    let (guild, atreides) = (game.player(Faction::SpacingGuild), game.player(Faction::Atreides));
+    game.give_card(guild, TreacheryCardKind::Karama);
+    let (to, _) = open_sand(&game);
+    movement_until(&mut game, atreides);
+    game.apply(GameEvent::Decline { player_id: guild }).unwrap();
+    assert_eq!(game.driver.state.active_player, Some(atreides));
+    game.apply(GameEvent::ShipForces {
+        player_id: atreides,
+        to,
+        forces: reserves(&game, atreides, 1),
+    })
+    .unwrap();
+    assert_eq!(game.forces_at(atreides, to), 1);
+}
+
+#[test]
+fn spice_only_regrows_under_the_rule_and_never_into_the_storm() {
+    let mut game = empty_board(&[Faction::Atreides, Faction::Harkonnen]);
+    let (at, _) = open_sand(&game);
+    collect_spice(&mut game);
+    assert!(game.driver.state.board.is_empty());
+
+    game.driver.state.rules.spice_regrowth = vec![SpiceRegrowth { location: at, spice: 3 }];
+    collect_spice(&mut game);
+    assert_eq!(game.spice_at(at), 3);
+    // Only barren sectors regrow
+    collect_spice(&mut game);
+    assert_eq!(game.spice_at(at), 3);
+
+    game.driver.state.board.clear();
+    game.driver.state.storm_sector = at.sector;
+    collect_spice(&mut game);
+    assert_eq!(game.spice_at(at), 0);
+}
+
+#[test]
+fn a_hajr_grants_exactly_one_more_move() {
+    let mut game = empty_board(&[Faction::Atreides, Faction::Harkonnen]);
+    let atreides = game.player(Faction::Atreides);
+    let hajr = game.give_card(atreides, TreacheryCardKind::Hajr);
+    let (from, to) = open_sand(&game);
+    let forces = game.put_forces(atreides, from, 2);
+    movement_until(&mut game, atreides);
+    let move_to = |game: &mut TestGame, path: Vec<LocationSector>| {
+        game.apply(GameEvent::MoveForces {
+            player_id: atreides,
+            path,
+            forces: forces.clone(),
+        })
+    };
+
+    move_to(&mut game, vec![from, to]).unwrap();
+    // Still holding the Hajr, they stay in play to use it
+    assert_eq!(game.driver.state.active_player, Some(atreides));
+    assert!(move_to(&mut game, vec![to, from]).is_err());
+
+    game.apply(GameEvent::PlayHajr {
+        player_id: atreides,
+        card_id: hajr,
+    })
+    .unwrap();
+    assert!(game.driver.state.decks.treachery.discards.contains(&hajr));
+    move_to(&mut game, vec![to, from]).unwrap();
+    assert_eq!(game.forces_at(atreides, from), 2);
+    assert!(move_to(&mut game, vec![from, to]).is_err());
+    assert_eq!(game.forces_at(atreides, from), 2);
+}
+
+/// Blow a Shai-Hulud and then some spice on the given turn. Returns whether a Nexus was waiting as the Nexus phase
+/// began.
+fn worm_on_turn(turn: u8) -> (TestGame, bool) {
+    let mut game = empty_board(&[Faction::Atreides, Faction::Harkonnen]);
+    let ([spice, _], calm) = two_blows(&game);
+    game.driver.state.game_turn = turn;
+    game.driver.state.storm_sector = calm;
+    stack_spice_deck(&mut game, &[SpiceCard::ShaiHalud, spice]);
+    game.driver.state.prompts.clear();
+    game.driver.state.spice_blows = 0;
+    game.driver.state.phase = Phase::Storm(StormPhase::MoveStorm);
+    let mut state = game.driver.state.clone();
+    let mut nexus = None;
+    for event in game.run(GameEvent::AdvancePhase) {
+        state.consume(&game.driver.data, event);
+        if state.phase == Phase::Nexus && nexus.is_none() {
+            nexus = Some(state.nexus.is_some());
+        }
+    }
+    (game, nexus.expect("the Nexus phase was never reached"))
+}
+
+#[test]
+fn a_worm_calls_a_nexus_which_is_over_once_the_phase_ends() {
+    let (game, nexus) = worm_on_turn(2);
+    assert!(nexus);
+    assert!(game.driver.state.nexus.is_none());
+    assert_ne!(game.driver.state.phase, Phase::Nexus);
+
+    // Not on the first turn
+    let (game, nexus) = worm_on_turn(1);
+    assert!(!nexus);
+    assert!(game.driver.state.nexus.is_none());
+}
+
+#[test]
+fn a_worm_devours_where_its_own_blow_last_placed_spice_before_the_other_blow_lands() {
+    let mut game = empty_board(&[Faction::Atreides, Faction::Harkonnen]);
+    game.driver.state.rules.spice_blows = 2;
+    game.driver.state.game_turn = 2;
+    let atreides = game.player(Faction::Atreides);
+    let ([first, second], calm) = two_blows(&game);
+    let (first_at, _) = spice_blow(&game, first);
+    let (second_at, second_spice) = spice_blow(&game, second);
+    game.driver.state.storm_sector = calm;
+    // The first blow last placed its spice where Atreides are now collecting it
+    let pile = game
+        .driver
+        .state
+        .decks
+        .spice
+        .cards
+        .iter()
+        .find(|card| card.inner == first)
+        .cloned()
+        .unwrap();
+    game.driver.state.spice_piles = vec![pile];
+    game.put_forces(atreides, first_at, 2);
+    game.put_spice(first_at, 5);
+    stack_spice_deck(&mut game, &[SpiceCard::ShaiHalud, second]);
+
+    let mut state = game.driver.state.clone();
+    state.prompts.clear();
+    state.spice_blows = 0;
+    state.phase = Phase::Storm(StormPhase::MoveStorm);
+    let start = game.log.len();
+    blow_spice(&mut game);
+    let events = &game.log[start..];
+    let position = |predicate: &dyn Fn(&GameEvent) -> bool| events.iter().position(predicate);
+    let devoured = position(&|event| matches!(event, GameEvent::Devour { .. })).unwrap();
+    let placed = position(&|event| matches!(event, GameEvent::PlaceSpice { location, .. } if *location == second_at));
+    let mut nexus = None;
+    for (idx, event) in events.iter().enumerate() {
+        state.consume(&game.driver.data, event.clone());
+        if state.phase == Phase::Nexus {
+            nexus = Some(idx);
+            break;
+        }
+    }
+
+    // The worm surfaces in the first blow's territory, not the second's, and devours before the second blow lands
+    assert_eq!(
+        events[devoured],
+        GameEvent::Devour {
+            location: first_at.location
+        }
+    );
+    assert!(devoured < placed.unwrap());
+    assert!(placed < nexus);
+    assert_eq!(game.forces_at(atreides, first_at), 0);
+    assert_eq!(game.spice_at(first_at), 0);
+    assert_eq!(game.spice_at(second_at), second_spice);
+}
+
+#[test]
+fn setup_placement_stops_at_the_territory_limit() {
+    let mut game = TestGame::start(&[Faction::Atreides, Faction::BeneGesserit], RuleSet::default());
+    let starting_values = &mut game
+        .driver
+        .data
+        .factions
+        .get_mut(&Faction::BeneGesserit)
+        .unwrap()
+        .starting_values;
+    starting_values.units = 3;
+    starting_values.territory_limit = Some(2);
+    game.play_until(|game| {
+        game.driver.state.phase == Phase::Setup(SetupPhase::PlaceForces)
+            && game.driver.state.active_player == game.driver.state.factions.get(&Faction::BeneGesserit).copied()
+    });
+    let bg = game.player(Faction::BeneGesserit);
+    let (here, there) = open_sand(&game);
+    let place = |game: &mut TestGame, to: LocationSector, count: usize| {
+        game.apply(GameEvent::ShipForces {
+            player_id: bg,
+            to,
+            forces: reserves(game, bg, count),
+        })
+    };
+
+    assert!(place(&mut game, here, 3).is_err());
+    place(&mut game, here, 2).unwrap();
+    assert!(place(&mut game, here, 1).is_err());
+    place(&mut game, there, 1).unwrap();
+    assert_eq!(game.forces_at(bg, here), 2);
+    assert_eq!(game.forces_at(bg, there), 1);
+}
+
+#[test]
+fn a_card_nobody_bids_on_goes_under_the_deck_and_the_next_is_auctioned() {
+    let mut game = TestGame::setup(&[Faction::Atreides, Faction::Harkonnen], RuleSet::default());
+    let current = |game: &TestGame| game.driver.state.bidding_cards.current().map(|bid| bid.card.id);
+    let unsold = current(&game).unwrap();
+    let left = game.driver.state.bidding_cards.len();
+    assert!(left > 1);
+    let since = game.log.len();
+    game.play_until(|game| current(game) != Some(unsold));
+
+    let deck = &game.driver.state.decks.treachery;
+    assert_eq!(deck.card_order.first(), Some(&unsold));
+    assert!(deck.cards.contains(&unsold));
+    assert_eq!(game.driver.state.bidding_cards.len(), left - 1);
+    assert!(current(&game).is_some());
+    assert!(!game.log[since..]
+        .iter()
+        .any(|event| matches!(event, GameEvent::WinBid { .. })));
+    assert!(game
+        .driver
+        .state
+        .players
+        .values()
+        .all(|player| !player.treachery_cards.contains(&unsold)));
+}
+
+#[test]
+fn cards_left_up_for_bid_go_under_the_deck_in_order_before_the_next_turn() {
+    let mut game = TestGame::setup(&[Faction::Atreides, Faction::Harkonnen], RuleSet::default());
+    // The card up for bid is the last on the table
+    let unsold = game
+        .driver
+        .state
+        .bidding_cards
+        .iter()
+        .rev()
+        .map(|bid| bid.card.id)
+        .collect::<Vec<_>>();
+    assert!(unsold.len() > 1);
+    let deck = game.driver.state.decks.treachery.card_order.clone();
+
+    // The auction is cut short, and the turn ends with the cards still on the table
+    game.driver.state.prompts.clear();
+    game.driver.state.phase = Phase::Control;
+    let mut state = game.driver.state.clone();
+    let events = game.run(GameEvent::AdvancePhase);
+    let returned = events
+        .iter()
+        .take_while(|event| **event != GameEvent::AdvanceTurn)
+        .filter_map(|event| match event {
+            GameEvent::ReturnBidCard { card_id } => *card_id,
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(returned, unsold);
+    for event in events {
+        let next_turn = event == GameEvent::AdvanceTurn;
+        state.consume(&game.driver.data, event);
+        if next_turn {
+            break;
+        }
+    }
+    assert!(state.bidding_cards.current().is_none());
+    // Each goes beneath the last, so they come back up in the order they were to be auctioned
+    let order = &state.decks.treachery.card_order;
+    let mut bottom = order[..unsold.len()].to_vec();
+    bottom.reverse();
+    assert_eq!(bottom, unsold);
+    assert_eq!(order[unsold.len()..], deck[..]);
+}
+
+/// Play on until it's a faction's turn to place their starting forces.
+fn placing(factions: &[Faction], faction: Faction) -> TestGame {
+    let mut game = TestGame::start(factions, RuleSet::default());
+    game.play_until(|game| {
+        game.driver.state.phase == Phase::Setup(SetupPhase::PlaceForces)
+            && game.driver.state.active_player == game.driver.state.factions.get(&faction).copied()
+    });
+    game
+}
+
+/// The first sector of a territory.
+fn first_sector(game: &TestGame, location: Location) -> LocationSector {
+    location.with_sector(*game.driver.data.locations[&location].sectors.keys().min().unwrap())
+}
+
+#[test]
+fn fremen_may_only_start_in_their_own_territories() {
+    let mut game = placing(&[Faction::Atreides, Faction::Fremen], Faction::Fremen);
+    let fremen = game.player(Faction::Fremen);
+    let forces = reserves(&game, fremen, 10);
+    let ship = |game: &mut TestGame, location: Location, forces: HashSet<ObjectId>| {
+        let to = first_sector(game, location);
+        game.apply(GameEvent::ShipForces {
+            player_id: fremen,
+            to,
+            forces,
+        })
+    };
+    assert!(ship(&mut game, Location::Arrakeen, forces.clone()).is_err());
+    // Only forces still in reserve can be placed
+    let theirs = reserves(&game, game.player(Faction::Atreides), 1);
+    assert!(ship(&mut game, Location::SietchTabr, theirs).is_err());
+    ship(&mut game, Location::SietchTabr, forces).unwrap();
+    assert_eq!(game.forces_at(fremen, first_sector(&game, Location::SietchTabr)), 10);
+}
+
+#[test]
+fn factions_without_starting_territories_may_start_anywhere() {
+    let mut game = placing(&[Faction::Atreides, Faction::BeneGesserit], Faction::BeneGesserit);
+    let bg = game.player(Faction::BeneGesserit);
+    let to = first_sector(&game, Location::Carthag);
+    game.apply(GameEvent::ShipForces {
+        player_id: bg,
+        to,
+        forces: reserves(&game, bg, 1),
+    })
+    .unwrap();
+    assert_eq!(game.forces_at(bg, to), 1);
+}
+
+#[test]
+fn only_the_host_may_adjust_the_game_and_only_under_the_rule() {
+    let rules = RuleSet {
+        admin_adjustments: true,
+        ..Default::default()
+    };
+    let mut game = TestGame::setup(&[Faction::Atreides, Faction::Harkonnen], rules);
+    let host = game.driver.state.host.unwrap();
+    let other = *game
+        .driver
+        .state
+        .play_order
+        .iter()
+        .find(|player_id| **player_id != host)
+        .unwrap();
+    let adjust = |player_id, adjustment| GameEvent::AdminAdjust {
+        player_id,
+        target: other,
+        adjustment,
+    };
+    let spice = game.driver.state.players[&other].spice;
+
+    assert!(game.apply(adjust(other, Adjustment::Spice(5))).is_err());
+    game.apply(adjust(host, Adjustment::Spice(5))).unwrap();
+    game.apply(adjust(host, Adjustment::KillForces(2))).unwrap();
+    let state = &game.driver.state;
+    assert_eq!(state.players[&other].spice, spice + 5);
+    assert_eq!(state.players[&other].tanks.forces.len(), 2);
+    assert_eq!(
+        state.adjustments,
+        vec![(other, Adjustment::Spice(5)), (other, Adjustment::KillForces(2))]
+    );
+    assert!(game.log.contains(&adjust(host, Adjustment::Spice(5))));
+    // Nothing can be taken which isn't there
+    assert!(game
+        .apply(adjust(host, Adjustment::Spice(-(spice as i16) - 6)))
+        .is_err());
+
+    // Without the rule, not even the host may
+    let mut game = TestGame::setup(&[Faction::Atreides, Faction::Harkonnen], RuleSet::default());
+    assert!(game.apply(adjust(host, Adjustment::Spice(5))).is_err());
+    assert!(game.driver.state.adjustments.is_empty());
+}
+
+#[test]
+fn only_one_special_force_is_revived_each_turn() {
+    let mut game = TestGame::setup(&[Faction::Emperor, Faction::Atreides], RuleSet::default());
+    let emperor = game.player(Faction::Emperor);
+    let player = game.driver.state.players.get_mut(&emperor).unwrap();
+    player.spice = 20;
+    let sardaukar = player
+        .offworld_forces
+        .iter()
+        .filter(|force| force.inner.is_special)
+        .take(2)
+        .cloned()
+        .collect::<Vec<_>>();
+    let normal = player
+        .offworld_forces
+        .iter()
+        .filter(|force| !force.inner.is_special)
+        .take(2)
+        .cloned()
+        .collect::<Vec<_>>();
+    assert_eq!((sardaukar.len(), normal.len()), (2, 2));
+    for force in sardaukar.iter().chain(normal.iter()) {
+        player.offworld_forces.remove(force);
+        player.tanks.forces.insert(force.clone());
+    }
+    game.driver.state.prompts.clear();
+    game.driver.state.bidding_cards.clear();
+    game.driver.state.phase = Phase::Bidding(BiddingPhase::Bidding);
+    game.run(GameEvent::AdvancePhase);
+    assert_eq!(game.driver.state.phase, Phase::Revival);
+
+    let revive = |forces: &[ObjectId]| GameEvent::Revive {
+        player_id: emperor,
+        forces: forces.iter().copied().collect(),
+        leader: None,
+    };
+    assert!(game.apply(revive(&[sardaukar[0].id, sardaukar[1].id])).is_err());
+    game.apply(revive(&[sardaukar[0].id, normal[0].id, normal[1].id]))
+        .unwrap();
+    let tanks = &game.driver.state.players[&emperor].tanks.forces;
+    assert!(tanks.contains(&sardaukar[1]));
+    assert!(!tanks.contains(&sardaukar[0]) && !tanks.contains(&normal[0]) && !tanks.contains(&normal[1]));
+}
+
+/// The game as a player sees it, from only what they were told of it.
+fn told_to(game: &TestGame, player_id: PlayerId) -> GameState {
+    let rules = &game.driver.state.rules;
+    let mut told = GameState::default();
+    for event in game.log.iter().filter(|event| event.visible_to(player_id, rules)) {
+        told.consume(&game.driver.data, event.redacted_for(player_id));
+    }
+    told
+}
+
+#[test]
+fn nobody_is_told_anything_they_shouldnt_be_over_a_whole_game() {
+    let factions = [
+        Faction::Atreides,
+        Faction::Harkonnen,
+        Faction::BeneGesserit,
+        Faction::Fremen,
+        Faction::SpacingGuild,
+        Faction::Emperor,
+    ];
+    for rules in [
+        RuleSet::default(),
+        RuleSet {
+            hidden_spice: true,
+            advanced: true,
+            ..Default::default()
+        },
+    ] {
+        let mut game = TestGame::setup(&factions, rules);
+        game.play_until(|game| game.driver.state.game_turn == 3);
+        let rules = &game.driver.state.rules;
+        let leaks = audit_events(&game.log, &players(&factions), rules);
+        assert!(leaks.is_empty(), "{}", leaks[0]);
+
+        let state = &game.driver.state;
+        for player_id in players(&factions) {
+            // What players aren't told doesn't leave them waiting on anyone who has already decided
+            let told = told_to(&game, player_id);
+            assert_eq!(told.prompts, state.prompts);
+            // They know their own hand, but not the order of the decks it was dealt from
+            assert_eq!(
+                told.players[&player_id].treachery_cards,
+                state.players[&player_id].treachery_cards
+            );
+            assert_ne!(told.decks.treachery.card_order, state.decks.treachery.card_order);
+            assert_ne!(told.decks.spice.card_order, state.decks.spice.card_order);
+        }
+    }
+}
+
+#[test]
+fn a_card_dealt_to_someone_else_is_stood_in_for_until_it_is_seen() {
+    let mut game = TestGame::setup(&[Faction::Atreides, Faction::Fremen], RuleSet::default());
+    let (atreides, fremen) = (game.player(Faction::Atreides), game.player(Faction::Fremen));
+    let card_id = game.driver.state.players[&atreides]
+        .treachery_cards
+        .iter()
+        .next()
+        .unwrap()
+        .id;
+
+    // The Fremen know the Atreides were dealt a card, but not which
+    let told = told_to(&game, fremen);
+    assert_eq!(told.players[&atreides].treachery_cards.len(), 1);
+    let stand_in = told.players[&atreides].treachery_cards.iter().next().unwrap().id;
+    assert_ne!(stand_in, card_id);
+
+    // Once it is discarded they see it, and the card which stood in for it goes back in the deck
+    game.run(GameEvent::DiscardCard {
+        player_id: atreides,
+        card_id,
+        to: DeckType::Treachery,
+    });
+    let told = told_to(&game, fremen);
+    assert!(told.decks.treachery.is_discarded(card_id));
+    assert!(told.players[&atreides].treachery_cards.is_empty());
+    assert!(told.decks.treachery.cards.contains(&stand_in));
+    assert_eq!(
+        told.decks.treachery.cards.len(),
+        game.driver.state.decks.treachery.cards.len()
+    );
+}
+
+#[test]
+fn the_fremen_peek_at_a_spice_blow_alone_before_everyone_sees_it() {
+    let mut game = empty_board(&[Faction::Atreides, Faction::Fremen]);
+    game.driver.state.rules.advanced = true;
+    game.driver.state.rules.spice_blows = 2;
+    game.driver.state.game_turn = 2;
+    let (atreides, fremen) = (game.player(Faction::Atreides), game.player(Faction::Fremen));
+    let ([first, second], calm) = two_blows(&game);
+    let (first_at, _) = spice_blow(&game, first);
+    game.driver.state.storm_sector = calm;
+    let pile = game
+        .driver
+        .state
+        .decks
+        .spice
+        .cards
+        .iter()
+        .find(|card| card.inner == first)
+        .cloned()
+        .unwrap();
+    game.driver.state.spice_piles = vec![pile];
+    // A worm surfacing under the Fremen gives them a moment in the middle of the blows
+    game.put_forces(fremen, first_at, 2);
+    stack_spice_deck(&mut game, &[SpiceCard::ShaiHalud, second]);
+    let start = game.log.len();
+    blow_spice(&mut game);
+    assert_eq!(game.driver.state.phase, Phase::SpiceBlow(SpiceBlowPhase::ShaiHalud));
+    let next_blow = game.driver.state.decks.spice.peek().unwrap().id;
+    // Players aren't told the order of the deck, so all they have is a guess at it
+    let mut guess = game.driver.state.clone();
+    guess.decks.spice.card_order.rotate_right(1);
+
+    assert!(game.apply(GameEvent::PeekSpiceBlow { player_id: atreides }).is_err());
+    let events = game.apply(GameEvent::PeekSpiceBlow { player_id: fremen }).unwrap();
+    let shown = GameEvent::ShowSpiceBlow {
+        player_id: fremen,
+        card_id: next_blow,
+    };
+    assert!(events.contains(&shown));
+    let rules = &game.driver.state.rules;
+    assert!(shown.visible_to(fremen, rules) && !shown.visible_to(atreides, rules));
+    // So the Fremen now know which card is on top, and nobody else does until it is revealed
+    let top = |player_id: PlayerId| {
+        let mut told = guess.clone();
+        for event in events.iter().filter(|event| event.visible_to(player_id, rules)) {
+            told.consume(&game.driver.data, event.redacted_for(player_id));
+        }
+        told.decks.spice.peek().map(|card| card.id)
+    };
+    assert_eq!(top(fremen), Some(next_blow));
+    assert_ne!(top(atreides), Some(next_blow));
+    // The same card isn't shown twice
+    assert!(game.apply(GameEvent::PeekSpiceBlow { player_id: fremen }).is_err());
+
+    game.play_until(|game| !matches!(game.driver.state.phase, Phase::SpiceBlow(_)));
+    let events = &game.log[start..];
+    let count = |events: &[GameEvent], wanted: &GameEvent| events.iter().filter(|event| *event == wanted).count();
+    assert_eq!(count(events, &shown), 1);
+    // The card peeked at was still on top of the deck, so it is the next one revealed to everyone
+    let peeked = events.iter().position(|event| *event == shown).unwrap();
+    let revealed = GameEvent::RevealSpiceBlow { card_id: next_blow };
+    assert_eq!(count(&events[peeked..], &revealed), 1);
+    assert!(game.driver.state.peeked_spice_blow.is_none());
+    assert!(game
+        .driver
+        .state
+        .decks
+        .spice
+        .discards
+        .iter()
+        .any(|card| card.id == next_blow));
+}
@@ -0,0 +1,214 @@
+use bevy::{math::vec3, prelude::*};
+use iyes_loopless::prelude::ConditionSet;
+
+use crate::{
+    components::Terrain, data::Data, game::state::GameState, lerper::LerpUICamera, palette::faction_color,
+    settings::Settings, Screen,
+};
+
+pub struct LegendPlugin;
+
+impl Plugin for LegendPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            ConditionSet::new()
+                .run_in_state(Screen::Game)
+                .with_system(toggle_legend)
+                .with_system(toggle_colorblind)
+                .with_system(legend)
+                .with_system(force_symbols)
+                .with_system(position_labels)
+                .into(),
+        );
+    }
+}
+
+/// Part of the legend overlay, which is despawned when it is hidden.
+#[derive(Component)]
+struct Legend;
+
+/// A label marking whose forces are in a stack, shown with the colorblind palette.
+#[derive(Component)]
+struct ForceSymbol;
+
+/// A label which follows a point on the board.
+#[derive(Component)]
+struct BoardLabel {
+    pos: Vec3,
+}
+
+fn toggle_legend(keyboard_input: Res<Input<KeyCode>>, mut settings: ResMut<Settings>) {
+    if keyboard_input.just_pressed(settings.key_bindings.toggle_legend) {
+        settings.show_legend = !settings.show_legend;
+    }
+}
+
+fn toggle_colorblind(keyboard_input: Res<Input<KeyCode>>, mut settings: ResMut<Settings>) {
+    if keyboard_input.just_pressed(settings.key_bindings.toggle_colorblind) {
+        settings.colorblind = !settings.colorblind;
+    }
+}
+
+fn legend(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    game_state: Res<GameState>,
+    data: Res<Data>,
+    asset_server: Res<AssetServer>,
+    legend: Query<Entity, With<Legend>>,
+    mut shown_factions: Local<Option<(usize, bool)>>,
+) {
+    let factions = settings
+        .show_legend
+        .then_some((game_state.factions.len(), settings.colorblind));
+    if *shown_factions == factions {
+        return;
+    }
+    *shown_factions = factions;
+    for entity in legend.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    if !settings.show_legend {
+        return;
+    }
+
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+
+    // Label the strongholds
+    for location_data in data.locations.values() {
+        if location_data.terrain != Terrain::Stronghold {
+            continue;
+        }
+        let vertices = location_data
+            .sectors
+            .values()
+            .flat_map(|nodes| nodes.vertices.iter())
+            .collect::<Vec<_>>();
+        let center = vertices.iter().fold(Vec3::ZERO, |sum, v| sum + **v) / vertices.len().max(1) as f32;
+        commands
+            .spawn_bundle(TextBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    ..default()
+                },
+                text: Text::from_section(
+                    location_data.name.clone(),
+                    TextStyle {
+                        font: font.clone(),
+                        font_size: 16.0,
+                        color: Color::WHITE,
+                    },
+                ),
+                ..default()
+            })
+            .insert(BoardLabel {
+                pos: vec3(center.x, 0.01, -center.y),
+            })
+            .insert(Legend);
+    }
+
+    // List the abilities of the factions in play
+    let mut factions = game_state.factions.keys().collect::<Vec<_>>();
+    factions.sort_by_key(|faction| faction.to_string());
+    let sections = factions
+        .into_iter()
+        .map(|faction| {
+            let faction_data = &data.factions[faction];
+            TextSection::new(
+                format!("{}: {}\n", faction_data.name, faction_data.description),
+                TextStyle {
+                    font: font.clone(),
+                    font_size: 16.0,
+                    color: faction_color(*faction, settings.colorblind),
+                },
+            )
+        })
+        .collect::<Vec<_>>();
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    top: Val::Px(60.0),
+                    left: Val::Px(5.0),
+                    ..default()
+                },
+                max_size: Size::new(Val::Px(400.0), Val::Undefined),
+                ..default()
+            },
+            text: Text::from_sections(sections),
+            ..default()
+        })
+        .insert(Legend);
+}
+
+/// Mark each stack of forces on the board with the faction it belongs to, so players don't have to tell the tokens
+/// apart by color alone.
+fn force_symbols(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    game_state: Res<GameState>,
+    data: Res<Data>,
+    asset_server: Res<AssetServer>,
+    symbols: Query<Entity, With<ForceSymbol>>,
+) {
+    if !game_state.is_changed() && !settings.is_changed() {
+        return;
+    }
+    for entity in symbols.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    if !settings.colorblind {
+        return;
+    }
+
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    for (location, location_state) in game_state.board.iter() {
+        for (sector, sector_state) in location_state.sectors.iter() {
+            for (i, (player_id, forces)) in sector_state.forces.iter().enumerate() {
+                if forces.forces.is_empty() {
+                    continue;
+                }
+                let faction = game_state.players[player_id].faction;
+                let node = data.locations[location].sectors[sector].fighters[i];
+                commands
+                    .spawn_bundle(TextBundle {
+                        style: Style {
+                            position_type: PositionType::Absolute,
+                            ..default()
+                        },
+                        text: Text::from_section(
+                            faction.code().to_uppercase(),
+                            TextStyle {
+                                font: font.clone(),
+                                font_size: 14.0,
+                                color: faction_color(faction, true),
+                            },
+                        ),
+                        ..default()
+                    })
+                    .insert(BoardLabel {
+                        pos: vec3(node.x, node.z, -node.y),
+                    })
+                    .insert(ForceSymbol);
+            }
+        }
+    }
+}
+
+fn position_labels(
+    camera: Query<(&Camera, &GlobalTransform), With<LerpUICamera>>,
+    mut labels: Query<(&BoardLabel, &mut Style, &mut Visibility)>,
+) {
+    if let Ok((camera, camera_transform)) = camera.get_single() {
+        for (label, mut style, mut visibility) in labels.iter_mut() {
+            if let Some(screen_pos) = camera.world_to_viewport(camera_transform, label.pos) {
+                style.position.left = Val::Px(screen_pos.x);
+                style.position.bottom = Val::Px(screen_pos.y);
+                visibility.is_visible = true;
+            } else {
+                visibility.is_visible = false;
+            }
+        }
+    }
+}
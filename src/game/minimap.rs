@@ -0,0 +1,188 @@
+use bevy::{math::vec3, prelude::*};
+use iyes_loopless::prelude::ConditionSet;
+
+use super::state::GameState;
+use crate::{
+    components::{LocationSector, Terrain},
+    data::{CameraNode, Data},
+    lerper::{Lerp, Lerper},
+    palette::{faction_color, storm_preview_color},
+    settings::Settings,
+    Screen,
+};
+
+pub struct MinimapPlugin;
+
+impl Plugin for MinimapPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            ConditionSet::new()
+                .run_in_state(Screen::Game)
+                .with_system(toggle_minimap)
+                .with_system(minimap)
+                .with_system(minimap_focus)
+                .into(),
+        );
+    }
+}
+
+/// How far the board extends from its center, in board space.
+const BOARD_RADIUS: f32 = 0.85;
+/// The width and height of the minimap, in pixels.
+const MINIMAP_SIZE: f32 = 200.0;
+const SECTOR_SIZE: f32 = 7.0;
+const MARKER_SIZE: f32 = 5.0;
+
+/// The minimap panel, which is despawned when it is hidden.
+#[derive(Component)]
+struct Minimap;
+
+/// A sector drawn on the minimap, which focuses the camera on it when clicked.
+#[derive(Component)]
+struct MinimapSector(LocationSector);
+
+fn toggle_minimap(keyboard_input: Res<Input<KeyCode>>, mut settings: ResMut<Settings>) {
+    if keyboard_input.just_pressed(settings.key_bindings.toggle_minimap) {
+        settings.show_minimap = !settings.show_minimap;
+    }
+}
+
+/// Where a point on the board falls on the minimap, measured from its bottom left corner.
+fn minimap_pos(pos: Vec3, size: f32) -> UiRect<Val> {
+    let scale = |v: f32| ((v / BOARD_RADIUS + 1.0) * 0.5 * MINIMAP_SIZE - size * 0.5).clamp(0.0, MINIMAP_SIZE - size);
+    UiRect {
+        left: Val::Px(scale(pos.x)),
+        bottom: Val::Px(scale(pos.y)),
+        ..default()
+    }
+}
+
+fn marker(pos: Vec3, size: f32, color: Color) -> NodeBundle {
+    NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            position: minimap_pos(pos, size),
+            size: Size::new(Val::Px(size), Val::Px(size)),
+            ..default()
+        },
+        color: color.into(),
+        ..default()
+    }
+}
+
+fn minimap(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    game_state: Res<GameState>,
+    data: Res<Data>,
+    minimap: Query<Entity, With<Minimap>>,
+) {
+    if !game_state.is_changed() && !settings.is_changed() {
+        return;
+    }
+    for entity in minimap.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    if !settings.show_minimap {
+        return;
+    }
+
+    let mut storm_color = storm_preview_color(settings.colorblind);
+    storm_color.set_a(1.0);
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    bottom: Val::Px(10.0),
+                    left: Val::Px(10.0),
+                    ..default()
+                },
+                size: Size::new(Val::Px(MINIMAP_SIZE), Val::Px(MINIMAP_SIZE)),
+                ..default()
+            },
+            color: Color::rgba(0.0, 0.0, 0.0, 0.7).into(),
+            ..default()
+        })
+        .insert(Minimap)
+        .with_children(|parent| {
+            for (location, location_data) in data.locations.iter() {
+                for (&sector, nodes) in location_data.sectors.iter() {
+                    let location_sector = location.with_sector(sector);
+                    let center = nodes.center();
+                    let color = if game_state.in_storm(&location_sector) {
+                        storm_color
+                    } else {
+                        match location_data.terrain {
+                            Terrain::Sand => Color::rgb(0.75, 0.6, 0.35),
+                            Terrain::Rock => Color::rgb(0.45, 0.35, 0.25),
+                            Terrain::Stronghold => Color::rgb(0.9, 0.9, 0.9),
+                            Terrain::PolarSink => Color::rgb(0.5, 0.6, 0.7),
+                        }
+                    };
+                    parent
+                        .spawn_bundle(ButtonBundle {
+                            style: Style {
+                                position_type: PositionType::Absolute,
+                                position: minimap_pos(center, SECTOR_SIZE),
+                                size: Size::new(Val::Px(SECTOR_SIZE), Val::Px(SECTOR_SIZE)),
+                                ..default()
+                            },
+                            color: color.into(),
+                            ..default()
+                        })
+                        .insert(MinimapSector(location_sector));
+
+                    let sector_state = game_state
+                        .board
+                        .get(location)
+                        .and_then(|location_state| location_state.sectors.get(&sector));
+                    if let Some(sector_state) = sector_state {
+                        if sector_state.spice > 0 {
+                            parent.spawn_bundle(marker(center, MARKER_SIZE * 0.6, Color::ORANGE));
+                        }
+                        for (i, (player_id, forces)) in sector_state.forces.iter().enumerate() {
+                            if forces.forces.is_empty() {
+                                continue;
+                            }
+                            // Stacks are placed the same way they are on the board
+                            let node = nodes.fighters.get(i).copied().unwrap_or(center);
+                            parent.spawn_bundle(marker(
+                                node,
+                                MARKER_SIZE,
+                                faction_color(game_state.players[player_id].faction, settings.colorblind),
+                            ));
+                        }
+                    }
+                }
+            }
+        });
+}
+
+/// Look down on a sector which was clicked on the minimap.
+fn minimap_focus(
+    data: Res<Data>,
+    interactions: Query<(&Interaction, &MinimapSector), Changed<Interaction>>,
+    mut camera: Query<&mut Lerper, With<Camera>>,
+) {
+    for (interaction, MinimapSector(sector)) in interactions.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+        if let (Some(mut lerper), Some(nodes)) = (
+            camera.iter_mut().next(),
+            data.locations[&sector.location].sectors.get(&sector.sector),
+        ) {
+            let center = nodes.center();
+            let at = vec3(center.x, 0.0, -center.y);
+            lerper.replace(Lerp::move_camera(
+                CameraNode {
+                    pos: at + 0.8 * Vec3::Y,
+                    at,
+                    up: -Vec3::Z,
+                },
+                1.0,
+            ));
+        }
+    }
+}
@@ -1,28 +1,56 @@
+pub mod bot;
+pub mod driver;
+mod legend;
+mod minimap;
 mod object;
 pub mod phase;
+pub mod prompt;
+mod rebuild;
+mod replay;
+mod results;
+pub mod save;
+mod sector_info;
+mod spectator;
+pub mod spice;
 pub mod state;
+pub mod stats;
 
 use std::{f32::consts::PI, time::Duration};
 
 use bevy::{ecs::schedule::ShouldRun, math::vec3, prelude::*};
-use bevy_mod_picking::{PickableBundle, PickingEvent};
-use iyes_loopless::prelude::{AppLooplessStateExt, ConditionSet};
+use bevy_mod_picking::{HoverEvent, PickableBundle, PickingCamera, PickingEvent};
+use iyes_loopless::prelude::{AppLooplessStateExt, ConditionSet, NextState};
 use maplit::hashset;
 use renet::RenetClient;
 
 pub use self::object::*;
 use self::{
-    phase::PhasePlugin,
-    state::{DeckType, EventReduce, GameEvent, GameState, PlayerId, SpawnType},
+    legend::LegendPlugin,
+    minimap::MinimapPlugin,
+    phase::{
+        movement::{self, MovePlan},
+        setup::SetupPhase,
+        Phase, PhasePlugin,
+    },
+    prompt::PromptPlugin,
+    rebuild::RebuildPlugin,
+    replay::{Replay, ReplayPlugin},
+    results::ResultsPlugin,
+    sector_info::SectorInfoPlugin,
+    spectator::SpectatorPlugin,
+    spice::SpicePlugin,
+    state::{DeckType, EventReduce, GameEvent, GameState, PlayerId, Prompt, SpawnType},
 };
 use crate::{
     components::{
-        FactionChoiceCard, FactionPredictionCard, LocationSector, TraitorCard, TreacheryCard, Troop, TurnPredictionCard,
+        Faction, FactionChoiceCard, FactionPredictionCard, LocationSector, SpiceCard, TraitorCard, TreacheryCard,
+        Troop, TurnPredictionCard,
     },
     data::Data,
     lerper::{Lerp, Lerper, UITransform},
-    network::{GameEvents, SendEvent},
-    util::hand_positions,
+    network::{EventLog, GameClient, GameEvents},
+    settings::Settings,
+    util::{hand_positions, opponent_hand_positions},
     Screen,
 };
 
@@ -41,7 +69,8 @@ impl Plugin for GamePlugin {
             .add_event::<PickedEvent<TurnPredictionCard>>()
             .add_event::<PickedEvent<TraitorCard>>()
             .add_event::<PickedEvent<TreacheryCard>>()
-            .add_event::<PickedEvent<LocationSector>>();
+            .add_event::<PickedEvent<LocationSector>>()
+            .add_event::<PickedEvent<PlayerShield>>();
 
         app.add_system_set(
             ConditionSet::new()
@@ -51,8 +80,12 @@ impl Plugin for GamePlugin {
                 .with_system(hiararchy_picker::<TurnPredictionCard>)
                 .with_system(hiararchy_picker::<TraitorCard>)
                 .with_system(hiararchy_picker::<TreacheryCard>)
-                .with_system(hiararchy_picker::<LocationSector>)
+                .with_system(sector_picker)
+                .with_system(hiararchy_picker::<PlayerShield>)
                 .with_system(ship_troop_input)
+                .with_system(play_card_input)
+                .with_system(inspect_shield)
+                .with_system(hover_hand)
                 .with_system(game_event_pauser)
                 .into(),
         );
@@ -66,11 +99,25 @@ impl Plugin for GamePlugin {
                 .with_system(pull_events.exclusive_system().at_end())
                 .with_system(spawn_object)
                 .with_system(ship_forces)
+                .with_system(flip_advisors)
                 .with_system(discard_card)
-                .with_system(hand),
+                .with_system(reveal_card)
+                .with_system(hand)
+                .with_system(shuffle_traitors)
+                .with_system(card_faces)
+                .with_system(leave_game),
         );
 
-        app.add_plugin(PhasePlugin);
+        app.add_plugin(PhasePlugin)
+            .add_plugin(LegendPlugin)
+            .add_plugin(MinimapPlugin)
+            .add_plugin(SpicePlugin)
+            .add_plugin(PromptPlugin)
+            .add_plugin(RebuildPlugin)
+            .add_plugin(ReplayPlugin)
+            .add_plugin(ResultsPlugin)
+            .add_plugin(SectorInfoPlugin)
+            .add_plugin(SpectatorPlugin);
 
         app.add_exit_system(Screen::Game, reset);
     }
@@ -100,7 +147,17 @@ impl GameEventPauser {
     }
 }
 
-fn game_event_pauser(mut pause: ResMut<GameEventPauser>, time: Res<Time>) {
+fn game_event_pauser(
+    mut pause: ResMut<GameEventPauser>,
+    time: Res<Time>,
+    settings: Res<Settings>,
+    keyboard_input: Res<Input<KeyCode>>,
+) {
+    if pause.paused && keyboard_input.just_pressed(settings.key_bindings.skip_pause) {
+        if let Some(duration) = &mut pause.duration {
+            *duration = Duration::ZERO;
+        }
+    }
     if pause.paused {
         if let Some(duration) = &mut pause.duration {
             *duration = duration.saturating_sub(time.delta());
@@ -124,8 +181,36 @@ fn check_for_event(game_events: Res<GameEvents>, pause: Res<GameEventPauser>) ->
 #[derive(Component)]
 pub struct PlayerFactionText;
 
-fn reset() {
-    todo!()
+/// Marks the shield belonging to a player. Only the owner may look behind it.
+#[derive(Copy, Clone, Component)]
+pub struct PlayerShield {
+    pub player_id: PlayerId,
+}
+
+#[derive(Component)]
+struct ShieldPanel;
+
+fn reset(mut commands: Commands) {
+    commands.insert_resource(GameState::default());
+    commands.insert_resource(GameEvents::default());
+    commands.insert_resource(EventLog::default());
+    commands.insert_resource(ObjectEntityMap::default());
+    commands.insert_resource(GameEventPauser::default());
+}
+
+fn leave_game(
+    mut commands: Commands,
+    game_events: Res<GameEvents>,
+    mut client: ResMut<RenetClient>,
+    my_id: Res<PlayerId>,
+) {
+    if let Some(GameEvent::Concede { player_id }) = game_events.peek() {
+        if *my_id == *player_id {
+            client.disconnect();
+            commands.remove_resource::<RenetClient>();
+            commands.insert_resource(NextState(Screen::MainMenu));
+        }
+    }
 }
 
 pub struct PickedEvent<T> {
@@ -133,6 +218,23 @@ pub struct PickedEvent<T> {
     pub inner: T,
 }
 
+/// Find the nearest entity up the hierarchy which has a component.
+fn find_in_hierarchy<T: Component + Clone>(
+    mut entity: Entity,
+    pickables: &Query<&T>,
+    parents: &Query<&Parent>,
+) -> Option<PickedEvent<T>> {
+    loop {
+        if let Ok(inner) = pickables.get(entity) {
+            return Some(PickedEvent {
+                picked: entity,
+                inner: inner.clone(),
+            });
+        }
+        entity = parents.get(entity).ok()?.get();
+    }
+}
+
 // Converts PickingEvents to typed PickedEvents by looking up the hierarchy if needed
 fn hiararchy_picker<T: Component + Clone>(
     pickables: Query<&T>,
@@ -143,27 +245,39 @@ fn hiararchy_picker<T: Component + Clone>(
     if !pickables.is_empty() {
         for event in picking_events.iter() {
             if let PickingEvent::Clicked(clicked) = event {
-                let mut clicked = *clicked;
-                loop {
-                    if let Ok(inner) = pickables.get(clicked) {
-                        picked_events.send(PickedEvent {
-                            picked: clicked,
-                            inner: inner.clone(),
-                        });
-                        return;
-                    } else {
-                        if let Ok(parent) = parents.get(clicked).map(|p| p.get()) {
-                            clicked = parent;
-                        } else {
-                            break;
-                        }
-                    }
+                if let Some(picked) = find_in_hierarchy(*clicked, &pickables, &parents) {
+                    picked_events.send(picked);
+                    return;
                 }
             }
         }
     }
 }
 
+/// Sectors are picked from everything under the cursor, nearest first, rather than only whatever is on top. That way
+/// forces and spice standing in a sector don't stop it from being clicked.
+fn sector_picker(
+    sectors: Query<&LocationSector>,
+    parents: Query<&Parent>,
+    cameras: Query<&PickingCamera>,
+    mut picking_events: EventReader<PickingEvent>,
+    mut picked_events: EventWriter<PickedEvent<LocationSector>>,
+) {
+    if picking_events
+        .iter()
+        .any(|event| matches!(event, PickingEvent::Clicked(_)))
+    {
+        if let Some(picked) = cameras
+            .iter()
+            .filter_map(|camera| camera.intersect_list())
+            .flatten()
+            .find_map(|(entity, _)| find_in_hierarchy(*entity, &sectors, &parents))
+        {
+            picked_events.send(picked);
+        }
+    }
+}
+
 fn spawn_object(
     game_events: Res<GameEvents>,
     mut commands: Commands,
@@ -175,284 +289,497 @@ fn spawn_object(
     my_id: Res<PlayerId>,
 ) {
     if let Some(GameEvent::SpawnObject { spawn_type }) = game_events.peek() {
-        match spawn_type {
-            SpawnType::Leader {
-                player_id,
-                leader: Object {
-                    id: object_id,
-                    inner: leader,
-                },
-            } => {
-                if *my_id == *player_id {
-                    let big_token = asset_server.get_handle("big_token.gltf#Mesh0/Primitive0");
-                    let texture =
-                        asset_server.get_handle(format!("leaders/{}.png", data.leaders[&leader].texture).as_str());
-                    let entity = commands
-                        .spawn_bundle(SpatialBundle::from_transform(Transform::from_translation(
-                            // TODO: Stack them somehow
-                            data.token_nodes.leaders[0],
-                        )))
-                        .insert_bundle(PickableBundle::default())
-                        .insert_bundle((*leader, *object_id))
-                        .insert_bundle(PbrBundle {
-                            mesh: big_token.clone(),
-                            material: materials.add(StandardMaterial::from(texture)),
-                            ..Default::default()
-                        })
-                        .insert(Lerper::default())
-                        .id();
-                    object_entity.world.insert(*object_id, entity);
-                } else {
-                    // TODO: represent other player objects
-                }
-            }
-            SpawnType::Troop {
-                player_id,
-                unit: Object {
-                    id: object_id,
-                    inner: unit,
-                },
-            } => {
-                if *my_id == *player_id {
-                    let faction = game_state.players[&player_id].faction;
-                    let little_token = asset_server.get_handle("little_token.gltf#Mesh0/Primitive0");
-                    let troop_texture =
-                        asset_server.get_handle(format!("tokens/{}_troop.png", faction.code()).as_str());
-                    let entity = commands
-                        .spawn_bundle(SpatialBundle::from_transform(Transform::from_translation(
-                            // TODO: Stack them somehow
-                            data.token_nodes.fighters[0], // + (i as f32 * 0.0036 * Vec3::Y)
-                        )))
-                        .insert_bundle(PickableBundle::default())
-                        .insert_bundle((*unit, *object_id))
-                        .insert_bundle(PbrBundle {
-                            mesh: little_token.clone(),
-                            material: materials.add(StandardMaterial::from(troop_texture)),
-                            ..Default::default()
-                        })
-                        .insert(Lerper::default())
-                        .id();
-                    object_entity.world.insert(*object_id, entity);
-                } else {
-                    // TODO: represent other player objects
-                }
-            }
-            SpawnType::TraitorCard(Object {
-                id: object_id,
-                inner: card,
-            }) => {
-                let card_face = asset_server.get_handle("card.gltf#Mesh0/Primitive0");
-                let card_back = asset_server.get_handle("card.gltf#Mesh0/Primitive1");
-
-                let traitor_front_texture = asset_server.get_handle(
-                    format!("traitor/traitor_{}.png", data.leaders[&card.leader].texture.as_str()).as_str(),
-                );
+        if let Some((object_id, entity)) = spawn_visual(
+            &mut commands,
+            &asset_server,
+            &mut materials,
+            &data,
+            &game_state,
+            *my_id,
+            spawn_type,
+        ) {
+            object_entity.world.insert(object_id, entity);
+        }
+    }
+}
 
-                let traitor_back_texture = asset_server.get_handle("traitor/traitor_back.png");
+/// Spawn whatever represents an object in the world, if the local player can see it.
+/// Sardaukar and Fedaykin are tinted gold to set them apart from ordinary forces.
+const SPECIAL_FORCE_TINT: Color = Color::rgb(1.0, 0.8, 0.35);
 
+fn spawn_visual(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    materials: &mut Assets<StandardMaterial>,
+    data: &Data,
+    game_state: &GameState,
+    my_id: PlayerId,
+    spawn_type: &SpawnType,
+) -> Option<(ObjectId, Entity)> {
+    match spawn_type {
+        SpawnType::Leader {
+            player_id,
+            leader: Object {
+                id: object_id,
+                inner: leader,
+            },
+        } => {
+            if my_id == *player_id {
+                let big_token = asset_server.get_handle("big_token.gltf#Mesh0/Primitive0");
+                let texture =
+                    asset_server.get_handle(format!("leaders/{}.png", data.leaders[&leader].texture).as_str());
                 let entity = commands
-                    .spawn_bundle((*card, *object_id))
-                    .insert_bundle(SpatialBundle::from_transform(
-                        // TODO: stack them
-                        Transform::from_translation(vec3(1.23, 0.0049, -0.3))
-                            * Transform::from_rotation(Quat::from_rotation_z(PI)),
-                    ))
-                    .insert(Lerper::default())
-                    .with_children(|parent| {
-                        parent
-                            .spawn_bundle(PbrBundle {
-                                mesh: card_face.clone(),
-                                material: materials.add(StandardMaterial::from(traitor_front_texture)),
-                                ..default()
-                            })
-                            .insert_bundle(PickableBundle::default());
-                        parent
-                            .spawn_bundle(PbrBundle {
-                                mesh: card_back.clone(),
-                                material: materials.add(StandardMaterial::from(traitor_back_texture)),
-                                ..default()
-                            })
-                            .insert_bundle(PickableBundle::default());
+                    .spawn_bundle(SpatialBundle::from_transform(Transform::from_translation(
+                        // TODO: Stack them somehow
+                        data.token_nodes.leaders[0],
+                    )))
+                    .insert_bundle(PickableBundle::default())
+                    .insert_bundle((*leader, *object_id))
+                    .insert_bundle(PbrBundle {
+                        mesh: big_token.clone(),
+                        material: materials.add(StandardMaterial::from(texture)),
+                        ..Default::default()
                     })
+                    .insert(Lerper::default())
                     .id();
-                object_entity.world.insert(*object_id, entity);
+                Some((*object_id, entity))
+            } else {
+                // TODO: represent other player objects
+                None
             }
-            SpawnType::TreacheryCard(Object {
+        }
+        SpawnType::Troop {
+            player_id,
+            unit: Object {
                 id: object_id,
-                inner: card,
-            }) => {
-                let card_face = asset_server.get_handle("card.gltf#Mesh0/Primitive0");
-                let card_back = asset_server.get_handle("card.gltf#Mesh0/Primitive1");
-
-                let treachery_front_texture = asset_server.get_handle(
+                inner: unit,
+            },
+        } => {
+            let faction = game_state.players[player_id].faction;
+            let little_token = asset_server.get_handle("little_token.gltf#Mesh0/Primitive0");
+            let troop_texture = asset_server.get_handle(format!("tokens/{}_troop.png", faction.code()).as_str());
+            // Elite forces stand out in a stack, whoever they belong to
+            let material = StandardMaterial {
+                base_color: if unit.is_special {
+                    SPECIAL_FORCE_TINT
+                } else {
+                    Color::WHITE
+                },
+                ..StandardMaterial::from(troop_texture)
+            };
+            // Other players' reserves are out of sight until they are shipped to the board
+            let on_board = game_state
+                .board
+                .values()
+                .flat_map(|location| location.sectors.values())
+                .flat_map(|sector| sector.forces.values())
+                .any(|forces| forces.forces.iter().any(|force| force.id == *object_id));
+            let entity = commands
+                .spawn_bundle(SpatialBundle::from_transform(Transform::from_translation(
+                    // TODO: Stack them somehow
+                    data.token_nodes.fighters[0], // + (i as f32 * 0.0036 * Vec3::Y)
+                )))
+                .insert_bundle(PickableBundle::default())
+                .insert_bundle((*unit, *object_id))
+                .insert_bundle(PbrBundle {
+                    mesh: little_token.clone(),
+                    material: materials.add(material),
+                    ..Default::default()
+                })
+                .insert(Visibility {
+                    is_visible: my_id == *player_id || on_board,
+                })
+                .insert(Lerper::default())
+                .id();
+            Some((*object_id, entity))
+        }
+        SpawnType::TraitorCard(Object {
+            id: object_id,
+            inner: card,
+        }) => {
+            let entity = spawn_card(
+                commands,
+                asset_server,
+                materials,
+                (*card, *object_id),
+                // TODO: stack them
+                Transform::from_translation(vec3(1.23, 0.0049, -0.3))
+                    * Transform::from_rotation(Quat::from_rotation_z(PI)),
+                asset_server.get_handle(
+                    format!("traitor/traitor_{}.png", data.leaders[&card.leader].texture.as_str()).as_str(),
+                ),
+                asset_server.get_handle("traitor/traitor_back.png"),
+            );
+            Some((*object_id, entity))
+        }
+        SpawnType::TreacheryCard(Object {
+            id: object_id,
+            inner: card,
+        }) => {
+            let entity = spawn_card(
+                commands,
+                asset_server,
+                materials,
+                (*card, *object_id),
+                // TODO: stack them
+                Transform::from_translation(vec3(1.23, 0.0049, -0.87))
+                    * Transform::from_rotation(Quat::from_rotation_z(PI)),
+                asset_server.get_handle(
                     format!(
                         "treachery/treachery_{}.png",
                         data.treachery_cards[&card.kind].textures[card.variant]
                     )
                     .as_str(),
-                );
+                ),
+                asset_server.get_handle("treachery/treachery_back.png"),
+            );
+            Some((*object_id, entity))
+        }
+        SpawnType::SpiceCard(Object {
+            id: object_id,
+            inner: card,
+        }) => {
+            let entity = spawn_card(
+                commands,
+                asset_server,
+                materials,
+                (*card, *object_id),
+                Transform::from_translation(vec3(1.23, 0.0049, 0.3))
+                    * Transform::from_rotation(Quat::from_rotation_z(PI)),
+                asset_server.get_handle(format!("spice/spice_{}.png", data.spice_cards[&card].texture).as_str()),
+                asset_server.get_handle("spice/spice_back.png"),
+            );
+            Some((*object_id, entity))
+        }
+        SpawnType::StormCard(Object {
+            id: object_id,
+            inner: card,
+        }) => {
+            let entity = spawn_card(
+                commands,
+                asset_server,
+                materials,
+                (*card, *object_id),
+                Transform::from_translation(vec3(1.23, 0.0049, 0.87))
+                    * Transform::from_rotation(Quat::from_rotation_z(PI)),
+                asset_server.get_handle(format!("storm/storm_{}.png", card.val).as_str()),
+                asset_server.get_handle("storm/storm_back.png"),
+            );
+            Some((*object_id, entity))
+        }
+        SpawnType::Worm { location, id } => {
+            let big_token = asset_server.get_handle("big_token.gltf#Mesh0/Primitive0");
+            let texture = asset_server
+                .get_handle(format!("spice/spice_{}.png", data.spice_cards[&SpiceCard::ShaiHalud].texture).as_str());
+            let center = data.locations[location].center;
+            let entity = commands
+                .spawn_bundle(PbrBundle {
+                    mesh: big_token,
+                    material: materials.add(StandardMaterial::from(texture)),
+                    transform: Transform::from_translation(vec3(center.x, center.z, -center.y)),
+                    ..default()
+                })
+                .insert(*id)
+                .insert(Lerper::default())
+                .id();
+            Some((*id, entity))
+        }
+    }
+}
 
-                let treachery_back_texture = asset_server.get_handle("treachery/treachery_back.png");
+/// Marks a card whose face is not known to the local player. Its face is rendered with the card back.
+#[derive(Component)]
+pub struct Hidden;
 
-                let entity = commands
-                    .spawn_bundle((*card, *object_id))
-                    .insert_bundle(SpatialBundle::from_transform(
-                        // TODO: stack them
-                        Transform::from_translation(vec3(1.23, 0.0049, -0.87))
-                            * Transform::from_rotation(Quat::from_rotation_z(PI)),
-                    ))
-                    .insert(Lerper::default())
-                    .with_children(|parent| {
-                        parent
-                            .spawn_bundle(PbrBundle {
-                                mesh: card_face.clone(),
-                                material: materials.add(StandardMaterial::from(treachery_front_texture)),
-                                ..default()
-                            })
-                            .insert_bundle(PickableBundle::default());
-                        parent
-                            .spawn_bundle(PbrBundle {
-                                mesh: card_back.clone(),
-                                material: materials.add(StandardMaterial::from(treachery_back_texture)),
-                                ..default()
-                            })
-                            .insert_bundle(PickableBundle::default());
-                    })
-                    .id();
-                object_entity.world.insert(*object_id, entity);
-            }
-            SpawnType::SpiceCard(Object {
-                id: object_id,
-                inner: card,
-            }) => {
-                let card_face = asset_server.get_handle("card.gltf#Mesh0/Primitive0");
-                let card_back = asset_server.get_handle("card.gltf#Mesh0/Primitive1");
+#[derive(Component)]
+pub struct CardFace {
+    pub front: Handle<Image>,
+    pub back: Handle<Image>,
+}
 
-                let spice_front_texture =
-                    asset_server.get_handle(format!("spice/spice_{}.png", data.spice_cards[&card].texture).as_str());
-                let spice_back_texture = asset_server.get_handle("spice/spice_back.png");
+/// Spawns a card face down. Whether the face is shown is decided by [`card_faces`].
+fn spawn_card(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    materials: &mut Assets<StandardMaterial>,
+    bundle: impl Bundle,
+    transform: Transform,
+    front: Handle<Image>,
+    back: Handle<Image>,
+) -> Entity {
+    let card_face = asset_server.get_handle("card.gltf#Mesh0/Primitive0");
+    let card_back = asset_server.get_handle("card.gltf#Mesh0/Primitive1");
 
-                let entity = commands
-                    .spawn_bundle((*card, *object_id))
-                    .insert_bundle(SpatialBundle {
-                        transform: Transform::from_translation(vec3(1.23, 0.0049, 0.3))
-                            * Transform::from_rotation(Quat::from_rotation_z(PI)),
-                        ..default()
-                    })
-                    .insert(Lerper::default())
-                    .with_children(|parent| {
-                        parent
-                            .spawn_bundle(PbrBundle {
-                                mesh: card_face.clone(),
-                                material: materials.add(StandardMaterial::from(spice_front_texture)),
-                                ..default()
-                            })
-                            .insert_bundle(PickableBundle::default());
-                        parent
-                            .spawn_bundle(PbrBundle {
-                                mesh: card_back.clone(),
-                                material: materials.add(StandardMaterial::from(spice_back_texture)),
-                                ..default()
-                            })
-                            .insert_bundle(PickableBundle::default());
-                    })
-                    .id();
-                object_entity.world.insert(*object_id, entity);
-            }
-            SpawnType::StormCard(Object {
-                id: object_id,
-                inner: card,
-            }) => {
-                let card_face = asset_server.get_handle("card.gltf#Mesh0/Primitive0");
-                let card_back = asset_server.get_handle("card.gltf#Mesh0/Primitive1");
+    commands
+        .spawn_bundle(bundle)
+        .insert_bundle(SpatialBundle::from_transform(transform))
+        .insert(Lerper::default())
+        .insert(Hidden)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(PbrBundle {
+                    mesh: card_face,
+                    material: materials.add(StandardMaterial::from(back.clone())),
+                    ..default()
+                })
+                .insert(CardFace {
+                    front,
+                    back: back.clone(),
+                })
+                .insert_bundle(PickableBundle::default());
+            parent
+                .spawn_bundle(PbrBundle {
+                    mesh: card_back,
+                    material: materials.add(StandardMaterial::from(back)),
+                    ..default()
+                })
+                .insert_bundle(PickableBundle::default());
+        })
+        .id()
+}
 
-                let storm_front_texture = asset_server.get_handle(format!("storm/storm_{}.png", card.val).as_str());
-                let storm_back_texture = asset_server.get_handle("storm/storm_back.png");
+/// Whether the local player is allowed to see the face of a secret object. This is the single place deciding what
+/// gets rendered face up, so spectators and opponents never see more than the public information.
+pub fn is_revealed(game_state: &GameState, my_id: PlayerId, id: ObjectId) -> bool {
+    if let Some(player) = game_state.players.get(&my_id) {
+        if player.treachery_cards.contains(&id) || player.traitor_cards.contains(&id) {
+            return true;
+        }
+    }
+    game_state.decks.treachery.is_discarded(id)
+        || game_state.decks.spice.is_discarded(id)
+        || game_state.decks.storm.is_discarded(id)
+        || game_state.spice_card.as_ref().map_or(false, |card| card.id == id)
+        || game_state.storm_card.as_ref().map_or(false, |card| card.id == id)
+        || game_state.peeked_spice_blow == Some(id)
+}
 
-                let entity = commands
-                    .spawn_bundle((*card, *object_id))
-                    .insert_bundle(SpatialBundle {
-                        transform: Transform::from_translation(vec3(1.23, 0.0049, 0.87))
-                            * Transform::from_rotation(Quat::from_rotation_z(PI)),
-                        ..default()
-                    })
-                    .insert(Lerper::default())
-                    .with_children(|parent| {
-                        parent
-                            .spawn_bundle(PbrBundle {
-                                mesh: card_face.clone(),
-                                material: materials.add(StandardMaterial::from(storm_front_texture)),
-                                ..default()
-                            })
-                            .insert_bundle(PickableBundle::default());
-                        parent
-                            .spawn_bundle(PbrBundle {
-                                mesh: card_back.clone(),
-                                material: materials.add(StandardMaterial::from(storm_back_texture)),
-                                ..default()
-                            })
-                            .insert_bundle(PickableBundle::default());
-                    })
-                    .id();
-                object_entity.world.insert(*object_id, entity);
+fn card_faces(
+    mut commands: Commands,
+    game_state: Res<GameState>,
+    replay: Option<Res<Replay>>,
+    my_id: Res<PlayerId>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    cards: Query<(Entity, &ObjectId, &Children, Option<&Hidden>)>,
+    faces: Query<(&CardFace, &Handle<StandardMaterial>)>,
+) {
+    for (entity, id, children, hidden) in cards.iter() {
+        // Nothing is secret once the game is over
+        let revealed = is_revealed(&game_state, *my_id, *id)
+            || ((replay.is_some() || game_state.phase == Phase::EndGame)
+                && game_state
+                    .players
+                    .values()
+                    .any(|player| player.treachery_cards.contains(id) || player.traitor_cards.contains(id)));
+        if revealed != hidden.is_none() {
+            for (face, material) in children.iter().filter_map(|child| faces.get(*child).ok()) {
+                if let Some(material) = materials.get_mut(material) {
+                    material.base_color_texture = Some(if revealed {
+                        face.front.clone()
+                    } else {
+                        face.back.clone()
+                    });
+                }
+            }
+            if revealed {
+                commands.entity(entity).remove::<Hidden>();
+            } else {
+                commands.entity(entity).insert(Hidden);
             }
-            SpawnType::Worm { location, id } => todo!(),
         }
     }
 }
 
 fn hand(
+    mut commands: Commands,
     game_events: Res<GameEvents>,
     game_state: Res<GameState>,
     mut hand_cards: Query<&mut Lerper>,
     object_entity: Res<ObjectEntityMap>,
     my_id: Res<PlayerId>,
+    mut pause: ResMut<GameEventPauser>,
 ) {
     if let Some(
-        GameEvent::DealCard { player_id, .. }
-        | GameEvent::DiscardCard { player_id, .. }
-        | GameEvent::WinBid { player_id, .. },
+        event @ (GameEvent::DealCard { .. }
+        | GameEvent::DealTraitor { .. }
+        | GameEvent::DiscardCard { .. }
+        | GameEvent::WinBid { .. }
+        | GameEvent::PlayCard { .. }
+        | GameEvent::PlayThumper { .. }
+        | GameEvent::PlayHajr { .. }
+        | GameEvent::ResolveBattle { .. }),
     ) = game_events.peek()
     {
-        if *my_id == *player_id {
-            if let Some(player) = game_state.players.get(&my_id) {
-                let hand = player
-                    .traitor_cards
-                    .iter()
-                    .map(|o| o.id)
-                    .chain(player.treachery_cards.iter().map(|o| o.id))
-                    .collect::<Vec<_>>();
-                let hand_positions = hand_positions(hand.len());
-                for (id, pos) in hand.into_iter().zip(hand_positions.into_iter()) {
-                    if let Some(entity) = object_entity.world.get(&id) {
-                        if let Some(mut lerper) = hand_cards.get_mut(*entity).ok() {
-                            lerper.replace(Lerp::ui_to(
-                                UITransform::from(pos).with_rotation(Quat::from_rotation_x(PI / 2.0)),
-                                0.1,
-                                0.0,
-                            ));
-                        }
+        if let GameEvent::DiscardCard { card_id, .. } = event {
+            if let Some(entity) = object_entity.world.get(card_id) {
+                commands.entity(*entity).remove::<HandSlot>();
+            }
+        }
+        // Cards we weren't shown can change hands as we learn who really holds them, and whatever stood in for them
+        // goes back on its deck
+        let decks = [
+            (game_state.decks.traitor.card_ids(), -0.3),
+            (game_state.decks.treachery.card_ids(), -0.87),
+        ];
+        for (card_ids, z) in decks {
+            for (i, id) in card_ids.iter().enumerate() {
+                if let Some(mut lerper) = object_entity
+                    .world
+                    .get(id)
+                    .and_then(|entity| hand_cards.get_mut(*entity).ok())
+                {
+                    lerper.replace(Lerp::world_to(
+                        Transform::from_translation(vec3(1.23, 0.0049 + 0.001 * i as f32, z))
+                            * Transform::from_rotation(Quat::from_rotation_z(PI)),
+                        0.1,
+                        0.0,
+                    ));
+                }
+            }
+        }
+        for player_id in game_state.players.keys().copied() {
+            for (id, slot) in hand_slots(&game_state, *my_id, player_id) {
+                if let Some(entity) = object_entity.world.get(&id) {
+                    if let Some(mut lerper) = hand_cards.get_mut(*entity).ok() {
+                        lerper.replace(Lerp::ui_to(slot, 0.1, 0.0));
+                    }
+                    if *my_id == player_id {
+                        commands.entity(*entity).insert(HandSlot(slot));
                     }
                 }
-            } else {
-                // TODO
+            }
+        }
+        // Deal cards one at a time
+        if let GameEvent::DealCard { .. } | GameEvent::DealTraitor { .. } = event {
+            pause.pause_for(Duration::from_millis(200));
+        }
+    }
+}
+
+/// Where each card in a player's hand belongs on screen.
+fn hand_slots(game_state: &GameState, my_id: PlayerId, player_id: PlayerId) -> Vec<(ObjectId, UITransform)> {
+    let player = match game_state.players.get(&player_id) {
+        Some(player) => player,
+        None => return Vec::new(),
+    };
+    let hand = player
+        .traitor_cards
+        .iter()
+        .map(|o| o.id)
+        .chain(player.treachery_cards.iter().map(|o| o.id))
+        .collect::<Vec<_>>();
+    // Our own hand is fanned along the bottom of the screen, and everyone else's is stacked along the top
+    let positions = if my_id == player_id {
+        hand_positions(hand.len())
+            .into_iter()
+            .map(|(pos, roll)| UITransform::from(pos).with_rotation(roll * Quat::from_rotation_x(PI / 2.0)))
+            .collect::<Vec<_>>()
+    } else {
+        let (seat, seats) = opponent_seat(game_state, my_id, player_id);
+        opponent_hand_positions(seat, seats, hand.len())
+            .into_iter()
+            .map(|pos| {
+                UITransform::from(pos)
+                    .with_rotation(Quat::from_rotation_x(PI / 2.0))
+                    .with_scale(0.5)
+            })
+            .collect()
+    };
+    hand.into_iter().zip(positions).collect()
+}
+
+/// Which seat another player has among our opponents, and how many opponents there are.
+pub fn opponent_seat(game_state: &GameState, my_id: PlayerId, player_id: PlayerId) -> (usize, usize) {
+    let opponents = game_state.seats.iter().filter(|id| **id != my_id).collect::<Vec<_>>();
+    let seat = opponents.iter().position(|id| **id == player_id).unwrap_or_default();
+    (seat, opponents.len())
+}
+
+/// Where a card sits in our hand, so it can be raised and returned when hovered.
+#[derive(Component)]
+pub struct HandSlot(UITransform);
+
+fn hover_hand(
+    mut hover_events: EventReader<PickingEvent>,
+    mut cards: Query<(&HandSlot, &mut Lerper)>,
+    parents: Query<&Parent>,
+) {
+    for event in hover_events.iter() {
+        if let PickingEvent::Hover(hover) = event {
+            let (mut entity, raised) = match hover {
+                HoverEvent::JustEntered(entity) => (*entity, true),
+                HoverEvent::JustLeft(entity) => (*entity, false),
+            };
+            loop {
+                if let Ok((HandSlot(slot), mut lerper)) = cards.get_mut(entity) {
+                    let dest = if raised {
+                        slot.with_translation(slot.translation + Vec2::Y * 0.25)
+                            .with_rotation(Quat::from_rotation_x(PI / 2.0))
+                            .with_scale(slot.scale * 1.5)
+                    } else {
+                        *slot
+                    };
+                    lerper.replace(Lerp::ui_to(dest, 0.1, 0.0));
+                    break;
+                } else if let Ok(parent) = parents.get(entity) {
+                    entity = parent.get();
+                } else {
+                    break;
+                }
             }
         }
     }
 }
 
-fn shuffle_traitors(game_events: Res<GameEvents>, mut commands: Commands, game_state: Res<GameState>) {
-    // TODO
+/// Gather the traitor deck into a pile before it is dealt. Only the server knows the order it was shuffled into, so
+/// the cards are stacked in whatever order we have them.
+fn shuffle_traitors(
+    game_events: Res<GameEvents>,
+    game_state: Res<GameState>,
+    object_entity: Res<ObjectEntityMap>,
+    mut traitor_cards: Query<&mut Lerper, With<TraitorCard>>,
+    mut pause: ResMut<GameEventPauser>,
+) {
+    if let (Some(GameEvent::AdvancePhase), Phase::Setup(SetupPhase::DealTraitors)) =
+        (game_events.peek(), game_state.phase)
+    {
+        for (i, id) in game_state.decks.traitor.card_ids().iter().enumerate() {
+            if let Some(mut lerper) = object_entity
+                .world
+                .get(id)
+                .and_then(|entity| traitor_cards.get_mut(*entity).ok())
+            {
+                lerper.push(Lerp::world_to(
+                    Transform::from_translation(vec3(1.23, 0.0049 + 0.001 * i as f32, -0.3))
+                        * Transform::from_rotation(Quat::from_rotation_z(PI)),
+                    0.1,
+                    0.0,
+                ));
+            }
+        }
+        pause.pause_for(Duration::from_millis(500));
+    }
 }
 
 fn ship_troop_input(
     game_state: Res<GameState>,
     mut picked_events: EventReader<PickedEvent<LocationSector>>,
     keyboard_input: Res<Input<KeyCode>>,
-    mut client: ResMut<RenetClient>,
+    move_plan: Res<MovePlan>,
+    mut client: GameClient,
     my_id: Res<PlayerId>,
 ) {
     for PickedEvent { inner, .. } in picked_events.iter() {
+        // Picking sectors when it isn't our turn is only to look at them
+        if game_state.active_player != Some(*my_id) {
+            continue;
+        }
+        // During movement, picking our own forces starts a move instead
+        if game_state.phase == Phase::Movement
+            && (move_plan.from.is_some() || movement::has_forces_in(&game_state, *my_id, inner))
+        {
+            continue;
+        }
         if let Some(player) = game_state.players.get(&my_id) {
             if !player.offworld_forces.is_empty() {
                 // TODO: Maybe add modifiers to the PickedEvents somehow?
@@ -478,26 +805,47 @@ fn ship_troop_input(
     }
 }
 
+fn play_card_input(
+    game_state: Res<GameState>,
+    mut picked_events: EventReader<PickedEvent<TreacheryCard>>,
+    cards: Query<&ObjectId, With<TreacheryCard>>,
+    mut client: GameClient,
+    my_id: Res<PlayerId>,
+) {
+    if let Some(Prompt::PlayCard { kind }) = game_state.prompts.get(&my_id) {
+        // Play the offered card from our hand. Passing on it is handled by the shortcuts.
+        for PickedEvent { picked, inner } in picked_events.iter() {
+            if &inner.kind == kind {
+                if let Ok(card_id) = cards.get(*picked) {
+                    client.send_event(GameEvent::PlayCard {
+                        player_id: *my_id,
+                        card_id: *card_id,
+                    });
+                    return;
+                }
+            }
+        }
+    }
+}
+
 fn ship_forces(
     game_events: Res<GameEvents>,
     game_state: Res<GameState>,
     data: Res<Data>,
     object_entity: Res<ObjectEntityMap>,
-    mut troops: Query<&mut Lerper, With<Troop>>,
+    mut troops: Query<(&mut Lerper, &mut Visibility), With<Troop>>,
 ) {
-    if let Some(GameEvent::ShipForces {
-        player_id: _,
-        to,
-        forces,
-    }) = game_events.peek()
-    {
-        let idx = game_state.board[&to.location].sectors[&to.sector].forces.len();
+    if let Some(GameEvent::ShipForces { player_id, to, forces }) = game_events.peek() {
+        let sector = &game_state.board[&to.location].sectors[&to.sector];
+        let idx = sector.forces.len();
         let node = data.locations[&to.location].sectors[&to.sector].fighters[idx];
+        let rotation = troop_rotation(sector.forces[player_id].advisors);
         for entity in forces.iter().filter_map(|id| object_entity.world.get(id)) {
-            if let Ok(mut lerper) = troops.get_mut(*entity) {
+            if let Ok((mut lerper, mut visibility)) = troops.get_mut(*entity) {
+                visibility.is_visible = true;
                 // TODO: stack
                 lerper.replace(Lerp::world_to(
-                    Transform::from_translation(Vec3::new(node.x, node.z, -node.y)),
+                    Transform::from_translation(Vec3::new(node.x, node.z, -node.y)).with_rotation(rotation),
                     0.1,
                     0.0,
                 ));
@@ -506,6 +854,50 @@ fn ship_forces(
     }
 }
 
+/// Advisors are shown flipped over.
+fn troop_rotation(advisors: bool) -> Quat {
+    if advisors {
+        Quat::from_rotation_x(PI)
+    } else {
+        Quat::IDENTITY
+    }
+}
+
+fn flip_advisors(
+    game_events: Res<GameEvents>,
+    game_state: Res<GameState>,
+    object_entity: Res<ObjectEntityMap>,
+    mut troops: Query<(&Transform, &mut Lerper), With<Troop>>,
+) {
+    if let Some(GameEvent::ChooseAdvisors {
+        player_id,
+        location,
+        advisors,
+    }) = game_events.peek()
+    {
+        if let Some(forces) = game_state
+            .board
+            .get(&location.location)
+            .and_then(|location_state| location_state.sectors.get(&location.sector))
+            .and_then(|sector| sector.forces.get(player_id))
+        {
+            for entity in forces
+                .forces
+                .iter()
+                .filter_map(|force| object_entity.world.get(&force.id))
+            {
+                if let Ok((transform, mut lerper)) = troops.get_mut(*entity) {
+                    lerper.replace(Lerp::world_to(
+                        transform.with_rotation(troop_rotation(*advisors)),
+                        0.3,
+                        0.0,
+                    ));
+                }
+            }
+        }
+    }
+}
+
 fn discard_card(
     game_events: Res<GameEvents>,
     object_entity: Res<ObjectEntityMap>,
@@ -515,17 +907,119 @@ fn discard_card(
     if let Some(GameEvent::DiscardCard { player_id, card_id, to }) = game_events.peek() {
         if *my_id == *player_id {
             let entity = object_entity.world[&card_id];
-            let transform = match to {
-                DeckType::Traitor => Transform::from_translation(vec3(1.5, 0.0049, -0.3)),
-                DeckType::Treachery => Transform::from_translation(vec3(1.5, 0.0049, -0.87)),
-                DeckType::Storm => Transform::from_translation(vec3(1.5, 0.0049, 0.87)),
-                DeckType::Spice => Transform::from_translation(vec3(1.5, 0.0049, 0.3)),
-            };
             if let Ok(mut lerper) = cards.get_mut(entity) {
-                lerper.replace(Lerp::world_to(transform, 0.1, 0.0));
+                lerper.replace(Lerp::world_to(discard_transform(to), 0.1, 0.0));
             }
         } else {
             // TODO: do something else for other players
         }
     }
 }
+
+/// Where each deck's discard pile sits on the board.
+fn discard_transform(deck_type: &DeckType) -> Transform {
+    match deck_type {
+        DeckType::Traitor => Transform::from_translation(vec3(1.5, 0.0049, -0.3)),
+        DeckType::Treachery => Transform::from_translation(vec3(1.5, 0.0049, -0.87)),
+        DeckType::Storm => Transform::from_translation(vec3(1.5, 0.0049, 0.87)),
+        DeckType::Spice => Transform::from_translation(vec3(1.5, 0.0049, 0.3)),
+    }
+}
+
+fn reveal_card(
+    mut commands: Commands,
+    game_events: Res<GameEvents>,
+    object_entity: Res<ObjectEntityMap>,
+    mut cards: Query<&mut Lerper>,
+    mut pause: ResMut<GameEventPauser>,
+) {
+    if let Some(GameEvent::RevealCard { card_id }) = game_events.peek() {
+        if let Some(entity) = object_entity.world.get(card_id) {
+            commands.entity(*entity).remove::<HandSlot>();
+            if let Ok(mut lerper) = cards.get_mut(*entity) {
+                // Show the card to everyone, then put it on the discard pile
+                lerper.replace(Lerp::ui_to(
+                    UITransform::from(Vec2::ZERO)
+                        .with_rotation(Quat::from_rotation_x(PI / 2.0))
+                        .with_scale(1.5),
+                    0.3,
+                    0.0,
+                ));
+                lerper.push(Lerp::world_to(discard_transform(&DeckType::Treachery), 0.3, 1.0));
+            }
+            pause.pause_for(Duration::from_millis(1500));
+        }
+    }
+}
+
+fn inspect_shield(
+    mut commands: Commands,
+    mut picked_events: EventReader<PickedEvent<PlayerShield>>,
+    game_state: Res<GameState>,
+    data: Res<Data>,
+    asset_server: Res<AssetServer>,
+    panels: Query<Entity, With<ShieldPanel>>,
+    my_id: Res<PlayerId>,
+) {
+    for PickedEvent {
+        inner: PlayerShield { player_id },
+        ..
+    } in picked_events.iter()
+    {
+        // Only our own hidden information may be inspected
+        if *player_id != *my_id {
+            continue;
+        }
+        if !panels.is_empty() {
+            for entity in panels.iter() {
+                commands.entity(entity).despawn_recursive();
+            }
+            continue;
+        }
+        if let Some(player) = game_state.players.get(&my_id) {
+            let mut s = "Treachery Cards:".to_string();
+            for card in player.treachery_cards.iter() {
+                s += "\n  ";
+                s += data.treachery_cards[&card.inner.kind].name.as_str();
+            }
+            s += "\nTraitors:";
+            for card in player.traitor_cards.iter() {
+                s += "\n  ";
+                s += data.leaders[&card.inner.leader].name.as_str();
+            }
+            s += "\nLeaders:";
+            for leader in player.living_leaders.keys() {
+                s += "\n  ";
+                s += data.leaders[&leader.inner].name.as_str();
+            }
+            if player.faction == Faction::BeneGesserit {
+                if let (Some(faction), Some(turn)) = (game_state.bg_predictions.faction, game_state.bg_predictions.turn)
+                {
+                    s += format!("\nPrediction:\n  {} on turn {}", faction, turn).as_str();
+                }
+            }
+            commands
+                .spawn_bundle(TextBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        position: UiRect {
+                            top: Val::Px(60.0),
+                            right: Val::Px(5.0),
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    text: Text::from_section(
+                        s,
+                        TextStyle {
+                            font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                            font_size: 20.0,
+                            color: Color::WHITE,
+                        },
+                    ),
+                    ..default()
+                })
+                .insert(ShieldPanel);
+        }
+    }
+}
@@ -0,0 +1,535 @@
+use std::{f32::consts::PI, time::Duration};
+
+use bevy::{math::vec2, prelude::*};
+use iyes_loopless::prelude::{AppLooplessStateExt, IntoConditionalSystem};
+
+use crate::{
+    components::{Leader, Location, LocationSector, TreacheryCard},
+    data::Data,
+    game::{
+        prompt::{reset_on_cancel, PromptUi},
+        state::{BattlePlan, ForceDial, GameEvent, GameState, PlayerId, Prompt},
+        CardFace, GameEventPauser, GameEventStage, ObjectId, PickedEvent,
+    },
+    lerper::{Lerp, Lerper, UITransform},
+    network::{GameClient, GameEvents},
+    settings::Settings,
+    Screen,
+};
+
+pub struct BattlePlugin;
+
+impl Plugin for BattlePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BattleOrder>()
+            .init_resource::<PlanDial>()
+            .add_enter_system(Screen::Game, init_plan_status)
+            .add_enter_system(Screen::Game, init_plan_dial_text)
+            .add_system(battle_order_input.run_in_state(Screen::Game))
+            .add_system(plan_status.run_in_state(Screen::Game))
+            .add_system(battle_plan_input.run_in_state(Screen::Game))
+            .add_system(plan_dial_text.run_in_state(Screen::Game))
+            .add_system(gray_out_unusable.run_in_state(Screen::Game))
+            .add_system(turn_wheel.run_in_state(Screen::Game))
+            .add_system_to_stage(GameEventStage, reveal_wheels)
+            .add_system(reset_on_cancel::<BattleOrder>)
+            .add_system(reset_on_cancel::<PlanDial>);
+    }
+}
+
+/// The territories we have picked so far, in the order we want to fight in them.
+#[derive(Default)]
+struct BattleOrder {
+    locations: Vec<Location>,
+}
+
+/// The battle plan we are putting together, before we commit to it.
+#[derive(Default, PartialEq)]
+struct PlanDial {
+    forces: ForceDial,
+    leader: Option<ObjectId>,
+}
+
+impl PlanDial {
+    fn plan(&self) -> BattlePlan {
+        BattlePlan {
+            forces: self.forces,
+            leader: self.leader,
+            treachery_cards: Vec::new(),
+        }
+    }
+}
+
+/// The breakdown of the plan we are dialing.
+#[derive(Component)]
+struct PlanDialText;
+
+/// A leader or card in hand which can't go into the battle plan we're making, and so is grayed out.
+#[derive(Component)]
+struct Unusable;
+
+const UNUSABLE_COLOR: Color = Color::rgb(0.3, 0.3, 0.3);
+
+/// A battle wheel, like the ones in the board game, belonging to one of the combatants.
+#[derive(Component)]
+struct BattleWheel(PlayerId);
+
+/// The part of a battle wheel which turns to show how many forces are dialed.
+#[derive(Component)]
+struct WheelDial;
+
+/// A wheel showing a plan which has been revealed, which stays out until the next event.
+#[derive(Component)]
+struct RevealedWheel;
+
+/// How many numbers are printed around a battle wheel, starting from 0.
+const WHEEL_NUMBERS: u8 = 21;
+const WHEEL_SCALE: f32 = 0.25;
+
+/// How far the dial is turned to show a number through the window in the cover.
+fn dial_rotation(value: u8) -> Quat {
+    Quat::from_rotation_y(-2.0 * PI * value.min(WHEEL_NUMBERS - 1) as f32 / WHEEL_NUMBERS as f32)
+}
+
+/// A wheel lying face up in front of the camera.
+fn wheel_face_up(pos: Vec2) -> UITransform {
+    UITransform::from(pos)
+        .with_rotation(Quat::from_rotation_x(PI / 2.0))
+        .with_scale(WHEEL_SCALE)
+}
+
+/// A wheel lying face down in front of the camera, before it is revealed.
+fn wheel_face_down(pos: Vec2) -> UITransform {
+    UITransform::from(pos)
+        .with_rotation(Quat::from_rotation_x(PI / 2.0) * Quat::from_rotation_z(PI))
+        .with_scale(WHEEL_SCALE)
+}
+
+fn spawn_wheel(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    materials: &mut Assets<StandardMaterial>,
+    player_id: PlayerId,
+    cover: usize,
+    lerper: Lerper,
+    dial_lerper: Lerper,
+) -> Entity {
+    let dial_mesh = asset_server.get_handle("wheel.gltf#Mesh0/Primitive0");
+    let cover_mesh = asset_server.get_handle("wheel.gltf#Mesh1/Primitive0");
+    let dial_material = materials.add(StandardMaterial::from(asset_server.get_handle("wheel_dial.png")));
+    let cover_material = materials.add(StandardMaterial {
+        base_color_texture: Some(asset_server.get_handle(format!("wheel_cover_{}.png", cover % 2 + 1).as_str())),
+        alpha_mode: AlphaMode::Mask(0.5),
+        ..default()
+    });
+    commands
+        .spawn_bundle(SpatialBundle::default())
+        .insert_bundle((BattleWheel(player_id), lerper))
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(PbrBundle {
+                    mesh: dial_mesh,
+                    material: dial_material,
+                    ..default()
+                })
+                .insert_bundle((WheelDial, dial_lerper));
+            parent.spawn_bundle(PbrBundle {
+                mesh: cover_mesh,
+                material: cover_material,
+                transform: Transform::from_translation(Vec3::Y * 0.01),
+                ..default()
+            });
+        })
+        .id()
+}
+
+/// Bring out our battle wheel while we are making a battle plan. It is put away with the prompt.
+pub fn show_battle_wheel(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    materials: &mut Assets<StandardMaterial>,
+    my_id: PlayerId,
+) {
+    let wheel = spawn_wheel(
+        commands,
+        asset_server,
+        materials,
+        my_id,
+        0,
+        Lerper::from(Lerp::ui_from_to(
+            UITransform::default().with_rotation(Quat::from_rotation_x(PI / 2.0)),
+            wheel_face_up(vec2(0.7, -0.5)),
+            0.5,
+            0.0,
+        )),
+        Lerper::default(),
+    );
+    commands.entity(wheel).insert(PromptUi);
+}
+
+/// Turn our wheel to the number of forces we are dialing.
+fn turn_wheel(
+    dial: Res<PlanDial>,
+    my_id: Res<PlayerId>,
+    wheels: Query<(&BattleWheel, &Children), Without<RevealedWheel>>,
+    mut dials: Query<&mut Lerper, With<WheelDial>>,
+) {
+    if !dial.is_changed() {
+        return;
+    }
+    for (BattleWheel(player_id), children) in wheels.iter() {
+        if player_id != &*my_id {
+            continue;
+        }
+        for child in children.iter() {
+            if let Ok(mut lerper) = dials.get_mut(*child) {
+                lerper.replace(Lerp::world_to(
+                    Transform::from_rotation(dial_rotation(dial.forces.total())),
+                    0.2,
+                    0.0,
+                ));
+            }
+        }
+    }
+}
+
+/// When a battle is resolved, lay out both combatants' wheels face down, flip them over together, and turn each to
+/// the forces that were dialed. They are cleared away once the game moves on.
+fn reveal_wheels(
+    mut commands: Commands,
+    game_events: Res<GameEvents>,
+    asset_server: Res<AssetServer>,
+    my_id: Res<PlayerId>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut pause: ResMut<GameEventPauser>,
+    revealed: Query<Entity, With<RevealedWheel>>,
+) {
+    let plans = match game_events.peek() {
+        Some(GameEvent::ResolveBattle { plans }) => plans,
+        Some(_) => {
+            for entity in revealed.iter() {
+                commands.entity(entity).despawn_recursive();
+            }
+            return;
+        }
+        None => return,
+    };
+    for entity in revealed.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    // Our own wheel goes on the left
+    let mut plans = plans.iter().collect::<Vec<_>>();
+    plans.sort_by_key(|(player_id, _)| (**player_id != *my_id, **player_id));
+    for (i, (player_id, plan)) in plans.into_iter().enumerate() {
+        let pos = vec2(if i == 0 { -0.4 } else { 0.4 }, 0.2);
+        let wheel = spawn_wheel(
+            &mut commands,
+            &asset_server,
+            &mut materials,
+            *player_id,
+            i,
+            Lerper::from(Lerp::ui_from_to(wheel_face_down(pos), wheel_face_down(pos), 0.0, 0.0)).with(Lerp::ui_to(
+                wheel_face_up(pos),
+                0.5,
+                0.5,
+            )),
+            Lerper::from(Lerp::world_to(
+                Transform::from_rotation(dial_rotation(plan.forces.total())),
+                0.8,
+                1.0,
+            )),
+        );
+        commands.entity(wheel).insert(RevealedWheel);
+    }
+    pause.pause_for(Duration::from_secs(3));
+}
+
+/// A line under the prompt saying which combatants have committed to their battle plans.
+#[derive(Component)]
+struct PlanStatus;
+
+/// Pick the territories of our battles one after another, in the order they should be fought.
+fn battle_order_input(
+    game_state: Res<GameState>,
+    settings: Res<Settings>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut picked_events: EventReader<PickedEvent<LocationSector>>,
+    mut order: ResMut<BattleOrder>,
+    mut client: GameClient,
+    my_id: Res<PlayerId>,
+) {
+    let locations = match game_state.prompts.get(&my_id) {
+        Some(Prompt::BattleOrder { locations }) => locations,
+        _ => {
+            if !order.locations.is_empty() {
+                order.locations.clear();
+            }
+            return;
+        }
+    };
+    if keyboard_input.just_pressed(settings.key_bindings.cancel) {
+        order.locations.clear();
+        return;
+    }
+    for PickedEvent { inner, .. } in picked_events.iter() {
+        if locations.contains(&inner.location) && !order.locations.contains(&inner.location) {
+            order.locations.push(inner.location);
+        }
+    }
+    if order.locations.len() == locations.len() {
+        client.send_event(GameEvent::ChooseBattleOrder {
+            player_id: *my_id,
+            order: std::mem::take(&mut order.locations),
+        });
+    }
+}
+
+fn init_plan_status(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    top: Val::Px(35.0),
+                    left: Val::Percent(40.0),
+                    ..default()
+                },
+                ..default()
+            },
+            text: Text::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                    font_size: 20.0,
+                    color: Color::WHITE,
+                },
+            ),
+            ..default()
+        })
+        .insert(PlanStatus);
+}
+
+/// While the current battle is being planned, show who has committed to a plan and who is still deciding. Nothing
+/// about the plans themselves is known until they are revealed.
+fn plan_status(
+    game_state: Res<GameState>,
+    data: Res<Data>,
+    my_id: Res<PlayerId>,
+    mut text: Query<&mut Text, With<PlanStatus>>,
+) {
+    if !game_state.is_changed() {
+        return;
+    }
+    let planning = game_state.battles.front().filter(|battle| {
+        [battle.aggressor, battle.defender].into_iter().any(|player_id| {
+            game_state.committed_plans.contains(&player_id)
+                || matches!(game_state.prompts.get(&player_id), Some(Prompt::BattlePlan { .. }))
+        })
+    });
+    let status = planning.map_or_else(String::new, |battle| {
+        [battle.aggressor, battle.defender]
+            .into_iter()
+            .map(|player_id| {
+                let name = if player_id == *my_id {
+                    "You".to_string()
+                } else {
+                    data.factions[&game_state.players[&player_id].faction].name.clone()
+                };
+                if game_state.committed_plans.contains(&player_id) {
+                    format!("{}: committed", name)
+                } else {
+                    format!("{}: deciding...", name)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("    ")
+    });
+    for mut text in text.iter_mut() {
+        if text.sections[0].value != status {
+            text.sections[0].value = status.clone();
+        }
+    }
+}
+
+/// Leaders we can still send into battle this turn, in a stable order to cycle through.
+fn available_leaders(game_state: &GameState, my_id: PlayerId) -> Vec<ObjectId> {
+    let mut leaders = game_state.players.get(&my_id).map_or_else(Vec::new, |player| {
+        player
+            .living_leaders
+            .iter()
+            .filter_map(|(leader, fought)| (!fought).then_some(leader.id))
+            .collect()
+    });
+    leaders.sort();
+    leaders
+}
+
+/// Dial normal and special forces separately, choose a leader, and commit to the plan.
+fn battle_plan_input(
+    game_state: Res<GameState>,
+    settings: Res<Settings>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut dial: ResMut<PlanDial>,
+    mut client: GameClient,
+    my_id: Res<PlayerId>,
+) {
+    let battle = match game_state.prompts.get(&my_id) {
+        Some(Prompt::BattlePlan { battle }) => *battle,
+        _ => {
+            if *dial != PlanDial::default() {
+                *dial = PlanDial::default();
+            }
+            return;
+        }
+    };
+    let bindings = &settings.key_bindings;
+    let present = game_state.forces_present(*my_id, battle.location);
+    let dial = &mut *dial;
+    let (count, max) = if keyboard_input.pressed(bindings.dial_special) {
+        (&mut dial.forces.special, present.special)
+    } else {
+        (&mut dial.forces.normal, present.normal)
+    };
+    if keyboard_input.just_pressed(bindings.dial_up) && *count < max {
+        *count += 1;
+    }
+    if keyboard_input.just_pressed(bindings.dial_down) && *count > 0 {
+        *count -= 1;
+    }
+    if keyboard_input.just_pressed(bindings.next_leader) {
+        let leaders = available_leaders(&game_state, *my_id);
+        let next = match dial.leader {
+            None => 0,
+            Some(leader) => leaders.iter().position(|id| *id == leader).map_or(0, |i| i + 1),
+        };
+        dial.leader = leaders.get(next).copied();
+    }
+    if keyboard_input.just_pressed(bindings.confirm) {
+        client.send_event(GameEvent::SetBattlePlan {
+            player_id: *my_id,
+            forces: dial.forces,
+            leader: dial.leader,
+            treachery_cards: Vec::new(),
+        });
+    }
+}
+
+/// While we make a battle plan, gray out the leaders which have already fought this turn and the cards in hand which
+/// can't be played in battle, so only what we can actually commit stands out.
+fn gray_out_unusable(
+    mut commands: Commands,
+    game_state: Res<GameState>,
+    data: Res<Data>,
+    my_id: Res<PlayerId>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    leaders: Query<(Entity, &ObjectId, &Handle<StandardMaterial>, Option<&Unusable>), With<Leader>>,
+    cards: Query<(&ObjectId, &TreacheryCard)>,
+    card_faces: Query<(Entity, &Parent, &Handle<StandardMaterial>, Option<&Unusable>), With<CardFace>>,
+) {
+    if !game_state.is_changed() {
+        return;
+    }
+    let planning = matches!(game_state.prompts.get(&my_id), Some(Prompt::BattlePlan { .. }));
+    let available = available_leaders(&game_state, *my_id);
+    let hand = game_state.players.get(&my_id).map(|player| &player.treachery_cards);
+    let mut gray_out = |entity: Entity, material: &Handle<StandardMaterial>, grayed: bool, unusable: bool| {
+        if grayed == unusable {
+            return;
+        }
+        if let Some(material) = materials.get_mut(material) {
+            material.base_color = if unusable { UNUSABLE_COLOR } else { Color::WHITE };
+        }
+        if unusable {
+            commands.entity(entity).insert(Unusable);
+        } else {
+            commands.entity(entity).remove::<Unusable>();
+        }
+    };
+    for (entity, id, material, grayed) in leaders.iter() {
+        gray_out(entity, material, grayed.is_some(), planning && !available.contains(id));
+    }
+    for (entity, parent, material, grayed) in card_faces.iter() {
+        if let Ok((id, card)) = cards.get(parent.get()) {
+            let unusable = planning
+                && hand.map_or(false, |hand| hand.contains(id))
+                && !data.treachery_cards[&card.kind].effect.is_battle_card();
+            gray_out(entity, material, grayed.is_some(), unusable);
+        }
+    }
+}
+
+fn init_plan_dial_text(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    bottom: Val::Px(80.0),
+                    left: Val::Percent(40.0),
+                    ..default()
+                },
+                ..default()
+            },
+            text: Text::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                    font_size: 20.0,
+                    color: Color::WHITE,
+                },
+            ),
+            ..default()
+        })
+        .insert(PlanDialText);
+}
+
+/// Show how many of each kind of force we are dialing out of those we have in the territory, and how strong the plan
+/// is.
+fn plan_dial_text(
+    game_state: Res<GameState>,
+    data: Res<Data>,
+    settings: Res<Settings>,
+    dial: Res<PlanDial>,
+    my_id: Res<PlayerId>,
+    mut text: Query<&mut Text, With<PlanDialText>>,
+) {
+    if !game_state.is_changed() && !dial.is_changed() {
+        return;
+    }
+    let breakdown = match game_state.prompts.get(&my_id) {
+        Some(Prompt::BattlePlan { battle }) => {
+            let present = game_state.forces_present(*my_id, battle.location);
+            let leader = dial
+                .leader
+                .and_then(|id| {
+                    game_state.players[&my_id]
+                        .living_leaders
+                        .keys()
+                        .find(|leader| leader.id == id)
+                })
+                .map_or_else(|| "None".to_string(), |leader| data.leaders[&leader.inner].name.clone());
+            let bindings = &settings.key_bindings;
+            format!(
+                "Normal forces: {}/{}\nSpecial forces: {}/{}\nLeader: {}\nStrength: {}\n[{:?}/{:?}] Dial forces, hold [{:?}] for \
+                 special forces\n[{:?}] Change leader   [{:?}] Commit",
+                dial.forces.normal,
+                present.normal,
+                dial.forces.special,
+                present.special,
+                leader,
+                game_state.battle_strength(&data, *my_id, battle.opponent(*my_id), &dial.plan()),
+                bindings.dial_up,
+                bindings.dial_down,
+                bindings.dial_special,
+                bindings.next_leader,
+                bindings.confirm,
+            )
+        }
+        _ => String::new(),
+    };
+    for mut text in text.iter_mut() {
+        if text.sections[0].value != breakdown {
+            text.sections[0].value = breakdown.clone();
+        }
+    }
+}
@@ -1,9 +1,8 @@
 use std::f32::consts::PI;
 
-use bevy::prelude::*;
+use bevy::{math::vec3, prelude::*};
 use derive_more::Display;
 use iyes_loopless::prelude::IntoConditionalSystem;
-use renet::RenetClient;
 use serde::{Deserialize, Serialize};
 
 use crate::{
@@ -13,7 +12,7 @@ use crate::{
         GameEventStage, ObjectEntityMap, ObjectId, PickedEvent,
     },
     lerper::{Lerp, Lerper, UITransform},
-    network::{GameEvents, SendEvent},
+    network::{GameClient, GameEvents},
     util::bid_positions,
     Screen,
 };
@@ -23,7 +22,7 @@ pub struct BiddingPlugin;
 impl Plugin for BiddingPlugin {
     fn build(&self, app: &mut App) {
         app.add_system_to_stage(GameEventStage, bid)
-            .add_system_to_stage(GameEventStage, win_bid)
+            .add_system_to_stage(GameEventStage, return_bid_card)
             .add_system(make_bid.run_in_state(Screen::Game));
     }
 }
@@ -40,7 +39,9 @@ fn bid(
     object_entity: Res<ObjectEntityMap>,
     mut bid_cards: Query<&mut Lerper>,
 ) {
-    if let Some(GameEvent::StartBidding | GameEvent::WinBid { .. }) = game_events.peek() {
+    if let Some(GameEvent::StartBidding | GameEvent::WinBid { .. } | GameEvent::ReturnBidCard { .. }) =
+        game_events.peek()
+    {
         let positions = bid_positions(game_state.bidding_cards.len());
         for (bid_state, pos) in game_state.bidding_cards.iter().zip(positions.into_iter()) {
             if let Ok(mut lerper) = bid_cards.get_mut(object_entity.world[&bid_state.card.id]) {
@@ -55,7 +56,7 @@ fn bid(
 }
 
 fn make_bid(
-    mut client: ResMut<RenetClient>,
+    mut client: GameClient,
     game_state: Res<GameState>,
     mut picked_events: EventReader<PickedEvent<TreacheryCard>>,
     cards: Query<&ObjectId, With<TreacheryCard>>,
@@ -75,16 +76,28 @@ fn make_bid(
     }
 }
 
-fn win_bid(
-    mut commands: Commands,
+/// Put the card nobody bid on back under the deck. We aren't told which card it was, but it is now the bottom one.
+fn return_bid_card(
     game_events: Res<GameEvents>,
+    game_state: Res<GameState>,
     object_entity: Res<ObjectEntityMap>,
-    my_id: Res<PlayerId>,
+    mut cards: Query<&mut Lerper>,
 ) {
-    if let Some(GameEvent::WinBid { player_id, card_id }) = game_events.peek() {
-        if *my_id != *player_id {
-            // TODO: animate into a UI component
-            commands.entity(object_entity.world[card_id]).despawn_recursive();
+    if let Some(GameEvent::ReturnBidCard { .. }) = game_events.peek() {
+        if let Some(mut lerper) = game_state
+            .decks
+            .treachery
+            .card_order
+            .first()
+            .and_then(|card_id| object_entity.world.get(card_id))
+            .and_then(|entity| cards.get_mut(*entity).ok())
+        {
+            lerper.replace(Lerp::world_to(
+                Transform::from_translation(vec3(1.23, 0.0049, -0.87))
+                    * Transform::from_rotation(Quat::from_rotation_z(PI)),
+                0.3,
+                0.0,
+            ));
         }
     }
 }
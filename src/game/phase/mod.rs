@@ -1,4 +1,7 @@
+pub mod battle;
 pub mod bidding;
+pub mod movement;
+pub mod revival;
 pub mod setup;
 pub mod spice_blow;
 pub mod storm;
@@ -9,16 +12,19 @@ use iyes_loopless::prelude::AppLooplessStateExt;
 use serde::{Deserialize, Serialize};
 
 use self::{
+    battle::BattlePlugin,
     bidding::{BiddingPhase, BiddingPlugin},
+    movement::MovementPlugin,
+    revival::RevivalPlugin,
     setup::*,
     spice_blow::{SpiceBlowPhase, SpiceBlowPlugin},
     storm::*,
 };
 use super::{
-    state::{GameEvent, GameState},
+    state::{GameEvent, GameState, RuleSet},
     GameEventStage,
 };
-use crate::{network::GameEvents, Screen};
+use crate::{lang::Lang, network::GameEvents, Screen};
 
 pub struct PhasePlugin;
 
@@ -27,10 +33,15 @@ impl Plugin for PhasePlugin {
         app.add_plugin(SetupPlugin)
             .add_plugin(StormPlugin)
             .add_plugin(SpiceBlowPlugin)
-            .add_plugin(BiddingPlugin);
+            .add_plugin(BiddingPlugin)
+            .add_plugin(RevivalPlugin)
+            .add_plugin(MovementPlugin)
+            .add_plugin(BattlePlugin);
 
         app.add_enter_system(Screen::Game, init_phase_text)
-            .add_system_to_stage(GameEventStage, phase_text);
+            .add_enter_system(Screen::Game, init_turn_text)
+            .add_system_to_stage(GameEventStage, phase_text)
+            .add_system_to_stage(GameEventStage, turn_text);
     }
 }
 
@@ -50,39 +61,10 @@ pub enum Phase {
 }
 
 impl Phase {
-    pub fn next(&self) -> Self {
-        match self {
-            Phase::Setup(subphase) => match subphase {
-                SetupPhase::ChooseFactions => Phase::Setup(SetupPhase::Prediction),
-                SetupPhase::Prediction => Phase::Setup(SetupPhase::AtStart),
-                SetupPhase::AtStart => Phase::Setup(SetupPhase::DealTraitors),
-                SetupPhase::DealTraitors => Phase::Setup(SetupPhase::PlaceForces),
-                SetupPhase::PlaceForces => Phase::Setup(SetupPhase::DealTreachery),
-                SetupPhase::DealTreachery => Phase::Storm(StormPhase::Reveal),
-            },
-            Phase::Storm(subphase) => match subphase {
-                StormPhase::Reveal => Phase::Storm(StormPhase::WeatherControl),
-                StormPhase::WeatherControl => Phase::Storm(StormPhase::FamilyAtomics),
-                StormPhase::FamilyAtomics => Phase::Storm(StormPhase::MoveStorm),
-                StormPhase::MoveStorm => Phase::SpiceBlow(SpiceBlowPhase::Reveal),
-            },
-            Phase::SpiceBlow(subphase) => match subphase {
-                SpiceBlowPhase::Reveal => Phase::SpiceBlow(SpiceBlowPhase::ShaiHalud),
-                SpiceBlowPhase::ShaiHalud => Phase::SpiceBlow(SpiceBlowPhase::PlaceSpice),
-                SpiceBlowPhase::PlaceSpice => Phase::Nexus,
-            },
-            Phase::Nexus => Phase::Bidding(BiddingPhase::DealCards),
-            Phase::Bidding(subphase) => match subphase {
-                BiddingPhase::DealCards => Phase::Bidding(BiddingPhase::Bidding),
-                BiddingPhase::Bidding => Phase::Revival,
-            },
-            Phase::Revival => Phase::Movement,
-            Phase::Movement => Phase::Battle,
-            Phase::Battle => Phase::Collection,
-            Phase::Collection => Phase::Control,
-            Phase::Control => Phase::Storm(StormPhase::Reveal),
-            Phase::EndGame => Phase::Setup(SetupPhase::ChooseFactions),
-        }
+    /// Whether the phase is skipped when nobody has a decision to make in it. Phases with optional plays, which
+    /// someone might make even when nothing calls for them, are never skipped.
+    pub fn skippable(&self) -> bool {
+        matches!(self, Phase::Revival | Phase::Bidding(_))
     }
 }
 
@@ -92,6 +74,80 @@ impl Default for Phase {
     }
 }
 
+/// The order a game steps through its phases, sub-phases included. The setup phases are played once, then the turn
+/// phases repeat until the game ends.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PhaseSequence {
+    pub setup: Vec<Phase>,
+    pub turn: Vec<Phase>,
+}
+
+impl PhaseSequence {
+    /// The sequence of the base game, adjusted for whichever optional rules change it.
+    pub fn new(rules: &RuleSet) -> Self {
+        let storm = vec![
+            Phase::Storm(StormPhase::Reveal),
+            Phase::Storm(StormPhase::WeatherControl),
+            Phase::Storm(StormPhase::FamilyAtomics),
+            Phase::Storm(StormPhase::MoveStorm),
+        ];
+        let spice_blow = vec![
+            Phase::SpiceBlow(SpiceBlowPhase::Reveal),
+            Phase::SpiceBlow(SpiceBlowPhase::ShaiHalud),
+            Phase::SpiceBlow(SpiceBlowPhase::PlaceSpice),
+        ];
+        let mut turn = if rules.spice_blow_first {
+            [spice_blow, storm].concat()
+        } else {
+            [storm, spice_blow].concat()
+        };
+        turn.extend([
+            Phase::Nexus,
+            Phase::Bidding(BiddingPhase::DealCards),
+            Phase::Bidding(BiddingPhase::Bidding),
+            Phase::Revival,
+            Phase::Movement,
+            Phase::Battle,
+            Phase::Collection,
+            Phase::Control,
+        ]);
+        Self {
+            setup: vec![
+                Phase::Setup(SetupPhase::ChooseFactions),
+                Phase::Setup(SetupPhase::Prediction),
+                Phase::Setup(SetupPhase::AtStart),
+                Phase::Setup(SetupPhase::DealTraitors),
+                Phase::Setup(SetupPhase::PlaceForces),
+                Phase::Setup(SetupPhase::DealTreachery),
+            ],
+            turn,
+        }
+    }
+
+    /// The phase each turn starts with.
+    pub fn turn_start(&self) -> Phase {
+        self.turn.first().copied().unwrap_or_default()
+    }
+
+    /// The phase after the given one. Setup leads into the first turn, the last phase of a turn leads into the next
+    /// turn, and a finished game goes back to setup.
+    pub fn next(&self, phase: Phase) -> Phase {
+        if let Some(i) = self.setup.iter().position(|p| *p == phase) {
+            self.setup.get(i + 1).copied().unwrap_or_else(|| self.turn_start())
+        } else if let Some(i) = self.turn.iter().position(|p| *p == phase) {
+            self.turn[(i + 1) % self.turn.len()]
+        } else {
+            self.setup.first().copied().unwrap_or_else(|| self.turn_start())
+        }
+    }
+}
+
+impl Default for PhaseSequence {
+    fn default() -> Self {
+        Self::new(&RuleSet::default())
+    }
+}
+
 #[derive(Component)]
 struct PhaseText;
 
@@ -121,29 +177,89 @@ fn init_phase_text(mut commands: Commands, asset_server: Res<AssetServer>) {
         .insert(PhaseText);
 }
 
-fn phase_text(game_events: Res<GameEvents>, game_state: Res<GameState>, mut text: Query<&mut Text, With<PhaseText>>) {
+fn phase_text(
+    game_events: Res<GameEvents>,
+    game_state: Res<GameState>,
+    lang: Res<Lang>,
+    mut text: Query<&mut Text, With<PhaseText>>,
+) {
     if let Some(GameEvent::AdvancePhase) = game_events.peek() {
-        let s = match game_state.phase {
+        let key = match game_state.phase {
             Phase::Setup(subphase) => match subphase {
-                SetupPhase::ChooseFactions => "Choosing Factions...".to_string(),
-                SetupPhase::Prediction => "Bene Gesserit are making a prediction...".to_string(),
-                SetupPhase::AtStart => "Start of Game Setup...".to_string(),
-                SetupPhase::DealTraitors => "Picking Traitor Cards...".to_string(),
-                SetupPhase::PlaceForces => "Placing Forces...".to_string(),
-                SetupPhase::DealTreachery => "Dealing Treachery Cards...".to_string(),
+                SetupPhase::ChooseFactions => "phase.setup.choose_factions",
+                SetupPhase::Prediction => "phase.setup.prediction",
+                SetupPhase::AtStart => "phase.setup.at_start",
+                SetupPhase::DealTraitors => "phase.setup.deal_traitors",
+                SetupPhase::PlaceForces => "phase.setup.place_forces",
+                SetupPhase::DealTreachery => "phase.setup.deal_treachery",
             },
-            Phase::Storm(_) => "Storm Phase".to_string(),
-            Phase::SpiceBlow(_) => "Spice Blow Phase".to_string(),
-            Phase::Nexus => "Nexus Phase".to_string(),
-            Phase::Bidding(_) => "Bidding Phase".to_string(),
-            Phase::Revival => "Revival Phase".to_string(),
-            Phase::Movement => "Movement Phase".to_string(),
-            Phase::Battle => "Battle Phase".to_string(),
-            Phase::Collection => "Collection Phase".to_string(),
-            Phase::Control => "Control Phase".to_string(),
-            Phase::EndGame => "".to_string(),
+            Phase::Storm(_) => "phase.storm",
+            Phase::SpiceBlow(_) => "phase.spice_blow",
+            Phase::Nexus => "phase.nexus",
+            Phase::Bidding(_) => "phase.bidding",
+            Phase::Revival => "phase.revival",
+            Phase::Movement => "phase.movement",
+            Phase::Battle => "phase.battle",
+            Phase::Collection => "phase.collection",
+            Phase::Control => "phase.control",
+            Phase::EndGame => "",
+        };
+        let s = if key.is_empty() { String::new() } else { lang.get(key) };
+        // Where we are in the turn, counting each phase once however many sub-phases it has
+        let mut phases = game_state.sequence.turn.clone();
+        phases.dedup_by_key(|phase| std::mem::discriminant(phase));
+        let s = match phases
+            .iter()
+            .position(|phase| std::mem::discriminant(phase) == std::mem::discriminant(&game_state.phase))
+        {
+            Some(i) => lang.fmt(
+                "phase.progress",
+                &[("phase", &s), ("current", &(i + 1)), ("total", &phases.len())],
+            ),
+            None => s,
         };
 
         text.single_mut().sections[0].value = s;
     }
 }
+
+#[derive(Component)]
+struct TurnText;
+
+fn init_turn_text(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    top: Val::Px(50.0),
+                    left: Val::Px(5.0),
+                    ..default()
+                },
+                ..default()
+            },
+            text: Text::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                    font_size: 24.0,
+                    color: Color::WHITE,
+                },
+            ),
+            ..default()
+        })
+        .insert(TurnText);
+}
+
+fn turn_text(
+    game_events: Res<GameEvents>,
+    game_state: Res<GameState>,
+    lang: Res<Lang>,
+    mut text: Query<&mut Text, With<TurnText>>,
+) {
+    if let Some(GameEvent::AdvanceTurn) = game_events.peek() {
+        for mut text in text.iter_mut() {
+            text.sections[0].value = lang.fmt("turn", &[("turn", &game_state.game_turn)]);
+        }
+    }
+}
@@ -0,0 +1,214 @@
+use bevy::{math::vec3, prelude::*};
+use bevy_mod_picking::{HoverEvent, PickingEvent};
+use iyes_loopless::prelude::ConditionSet;
+
+use crate::{
+    components::{LocationSector, TreacheryCard, TreacheryCardKind},
+    data::Data,
+    game::{
+        phase::Phase,
+        prompt::reset_on_cancel,
+        state::{GameEvent, GameState, PlayerId},
+        ObjectId, PickedEvent,
+    },
+    network::GameClient,
+    palette::{illegal_color, legal_color},
+    settings::Settings,
+    Screen,
+};
+
+pub struct MovementPlugin;
+
+impl Plugin for MovementPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MovePlan>()
+            .add_system(reset_on_cancel::<MovePlan>)
+            .add_system_set(
+                ConditionSet::new()
+                    .run_in_state(Screen::Game)
+                    .with_system(plan_move)
+                    .with_system(play_hajr)
+                    .with_system(hover_destination)
+                    .with_system(move_preview)
+                    .into(),
+            );
+    }
+}
+
+/// The move we are planning, from the sector whose forces we picked to the sector under the cursor.
+#[derive(Default)]
+pub struct MovePlan {
+    pub from: Option<LocationSector>,
+    hovered: Option<LocationSector>,
+}
+
+/// A segment of the line drawn along a planned move.
+#[derive(Component)]
+struct MovePreview;
+
+/// Whether we have forces in a sector.
+pub fn has_forces_in(game_state: &GameState, my_id: PlayerId, sector: &LocationSector) -> bool {
+    game_state
+        .board
+        .get(&sector.location)
+        .and_then(|location| location.sectors.get(&sector.sector))
+        .and_then(|sector| sector.forces.get(&my_id))
+        .map_or(false, |forces| !forces.forces.is_empty())
+}
+
+fn can_move(game_state: &GameState, my_id: PlayerId) -> bool {
+    game_state.phase == Phase::Movement
+        && game_state.active_player == Some(my_id)
+        && game_state.players.get(&my_id).map_or(false, |player| player.can_move())
+}
+
+/// Play a Hajr from our hand on our turn to move, to move once more.
+fn play_hajr(
+    game_state: Res<GameState>,
+    mut picked_events: EventReader<PickedEvent<TreacheryCard>>,
+    cards: Query<&ObjectId, With<TreacheryCard>>,
+    mut client: GameClient,
+    my_id: Res<PlayerId>,
+) {
+    let my_turn = game_state.phase == Phase::Movement && game_state.active_player == Some(*my_id);
+    for PickedEvent { picked, inner } in picked_events.iter() {
+        if my_turn && inner.kind == TreacheryCardKind::Hajr {
+            if let Ok(card_id) = cards.get(*picked) {
+                client.send_event(GameEvent::PlayHajr {
+                    player_id: *my_id,
+                    card_id: *card_id,
+                });
+            }
+        }
+    }
+}
+
+/// Pick a sector of our forces to move, then pick where they should go.
+fn plan_move(
+    game_state: Res<GameState>,
+    data: Res<Data>,
+    settings: Res<Settings>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut picked_events: EventReader<PickedEvent<LocationSector>>,
+    mut plan: ResMut<MovePlan>,
+    mut client: GameClient,
+    my_id: Res<PlayerId>,
+) {
+    if !can_move(&game_state, *my_id) || keyboard_input.just_pressed(settings.key_bindings.cancel) {
+        if plan.from.is_some() {
+            plan.from = None;
+        }
+        return;
+    }
+    for PickedEvent { inner, .. } in picked_events.iter() {
+        match plan.from {
+            None if has_forces_in(&game_state, *my_id, inner) => plan.from = Some(*inner),
+            None => (),
+            Some(from) if from == *inner => plan.from = None,
+            Some(from) => {
+                if let Some(path) = game_state.find_path(&data, from, *inner) {
+                    if game_state.is_legal_path(&data, *my_id, &path) {
+                        let forces = game_state.board[&from.location].sectors[&from.sector].forces[&my_id]
+                            .forces
+                            .iter()
+                            .map(|force| force.id)
+                            .collect();
+                        client.send_event(GameEvent::MoveForces {
+                            player_id: *my_id,
+                            path,
+                            forces,
+                        });
+                        plan.from = None;
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn hover_destination(
+    mut hover_events: EventReader<PickingEvent>,
+    sectors: Query<&LocationSector>,
+    mut plan: ResMut<MovePlan>,
+) {
+    for event in hover_events.iter() {
+        match event {
+            PickingEvent::Hover(HoverEvent::JustEntered(entity)) => {
+                if let Ok(sector) = sectors.get(*entity) {
+                    plan.hovered = Some(*sector);
+                }
+            }
+            PickingEvent::Hover(HoverEvent::JustLeft(entity)) => {
+                if sectors.get(*entity).ok() == plan.hovered.as_ref() {
+                    plan.hovered = None;
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Draw the path the forces would take, in green if the move is legal and red if it isn't.
+fn move_preview(
+    mut commands: Commands,
+    plan: Res<MovePlan>,
+    settings: Res<Settings>,
+    game_state: Res<GameState>,
+    data: Res<Data>,
+    my_id: Res<PlayerId>,
+    previews: Query<Entity, With<MovePreview>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    if !plan.is_changed() {
+        return;
+    }
+    for entity in previews.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    let (from, to) = match (plan.from, plan.hovered) {
+        (Some(from), Some(to)) if from != to => (from, to),
+        _ => return,
+    };
+    let (path, legal) = match game_state.find_path(&data, from, to) {
+        Some(path) => {
+            let legal = game_state.is_legal_path(&data, *my_id, &path);
+            (path, legal)
+        }
+        None => (vec![from, to], false),
+    };
+    let color = if legal {
+        legal_color(settings.colorblind)
+    } else {
+        illegal_color(settings.colorblind)
+    };
+    let material = materials.add(StandardMaterial {
+        base_color: color,
+        unlit: true,
+        ..default()
+    });
+    let mesh = meshes.add(Mesh::from(shape::Cube { size: 1.0 }));
+    let points = path
+        .iter()
+        .map(|sector| {
+            let center = data.locations[&sector.location].sectors[&sector.sector].center();
+            vec3(center.x, 0.02, -center.y)
+        })
+        .collect::<Vec<_>>();
+    for segment in points.windows(2) {
+        let offset = segment[1] - segment[0];
+        if offset.length() < f32::EPSILON {
+            continue;
+        }
+        commands
+            .spawn_bundle(PbrBundle {
+                mesh: mesh.clone(),
+                material: material.clone(),
+                transform: Transform::from_translation((segment[0] + segment[1]) / 2.0)
+                    .with_rotation(Quat::from_rotation_arc(Vec3::X, offset.normalize()))
+                    .with_scale(vec3(offset.length(), 0.005, 0.005)),
+                ..default()
+            })
+            .insert(MovePreview);
+    }
+}
@@ -0,0 +1,369 @@
+use bevy::prelude::*;
+use iyes_loopless::prelude::ConditionSet;
+
+use crate::{
+    components::Troop,
+    data::Data,
+    game::{
+        prompt::reset_on_cancel,
+        state::{GameEvent, GameState, PlayerId, Prompt, TleilaxuTanks, MAX_REVIVAL, MAX_SPECIAL_REVIVAL},
+        Object, ObjectId,
+    },
+    lang::Lang,
+    network::GameClient,
+    Screen,
+};
+
+pub struct RevivalPlugin;
+
+impl Plugin for RevivalPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RevivalSelection>()
+            .add_system(reset_on_cancel::<RevivalSelection>)
+            .add_system_set(
+                ConditionSet::new()
+                    .run_in_state(Screen::Game)
+                    .with_system(revival_panel)
+                    .with_system(revival_buttons)
+                    .into(),
+            );
+    }
+}
+
+/// What we have chosen to bring back from the tanks so far.
+#[derive(Default)]
+struct RevivalSelection {
+    leader: Option<ObjectId>,
+    forces: usize,
+    special_forces: usize,
+}
+
+impl RevivalSelection {
+    /// The forces in the tanks which would be revived.
+    fn troops<'a>(&self, tanks: &'a TleilaxuTanks) -> Vec<&'a Object<Troop>> {
+        let normal = tanks
+            .forces
+            .iter()
+            .filter(|force| !force.inner.is_special)
+            .take(self.forces);
+        let special = tanks
+            .forces
+            .iter()
+            .filter(|force| force.inner.is_special)
+            .take(self.special_forces);
+        normal.chain(special).collect()
+    }
+
+    /// What the revival would cost, or why it can't be made.
+    fn cost(&self, game_state: &GameState, data: &Data, lang: &Lang, player_id: PlayerId) -> Result<u8, String> {
+        let player = game_state.players.get(&player_id).ok_or_else(String::new)?;
+        if self.leader.is_none() && self.forces + self.special_forces == 0 {
+            return Err(lang.get("revival.nothing_chosen"));
+        }
+        if self.forces + self.special_forces > MAX_REVIVAL {
+            return Err(lang.fmt("revival.too_many_forces", &[("limit", &MAX_REVIVAL)]));
+        }
+        if self.special_forces > MAX_SPECIAL_REVIVAL {
+            return Err(lang.fmt("revival.too_many_special_forces", &[("limit", &MAX_SPECIAL_REVIVAL)]));
+        }
+        let troops = self
+            .troops(&player.tanks)
+            .into_iter()
+            .map(|force| force.inner)
+            .collect::<Vec<_>>();
+        let leader = self.leader.and_then(|leader_id| {
+            player
+                .tanks
+                .leaders
+                .iter()
+                .find(|leader| leader.id == leader_id)
+                .map(|leader| leader.inner)
+        });
+        let cost = game_state.revival_cost(data, player_id, &troops, leader);
+        if cost > player.spice {
+            return Err(lang.fmt("revival.unaffordable", &[("cost", &cost), ("spice", &player.spice)]));
+        }
+        Ok(cost)
+    }
+}
+
+/// The panel for choosing what to revive, shown while we are being asked to.
+#[derive(Component)]
+struct RevivalPanel;
+
+#[derive(Copy, Clone, PartialEq)]
+enum RevivalAction {
+    Leader(ObjectId),
+    More { special: bool },
+    Fewer { special: bool },
+    Revive,
+    Decline,
+}
+
+/// A button on the revival panel, with the color it goes back to once it is no longer hovered.
+#[derive(Component)]
+struct RevivalButton {
+    action: RevivalAction,
+    color: Color,
+}
+
+const BUTTON_COLOR: Color = Color::rgb(0.15, 0.15, 0.15);
+const SELECTED_COLOR: Color = Color::rgb(0.45, 0.35, 0.1);
+const DISABLED_COLOR: Color = Color::rgb(0.08, 0.08, 0.08);
+
+fn spawn_button(parent: &mut ChildBuilder, font: &Handle<Font>, label: String, action: RevivalAction, color: Color) {
+    parent
+        .spawn_bundle(ButtonBundle {
+            style: Style {
+                padding: UiRect::all(Val::Px(6.0)),
+                margin: UiRect::all(Val::Px(3.0)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            color: color.into(),
+            ..default()
+        })
+        .insert(RevivalButton { action, color })
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle::from_section(
+                label,
+                TextStyle {
+                    font: font.clone(),
+                    font_size: 18.0,
+                    color: Color::ANTIQUE_WHITE,
+                },
+            ));
+        });
+}
+
+fn spawn_row(parent: &mut ChildBuilder, children: impl FnOnce(&mut ChildBuilder)) {
+    parent
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            color: Color::NONE.into(),
+            ..default()
+        })
+        .with_children(children);
+}
+
+fn revival_panel(
+    mut commands: Commands,
+    game_state: Res<GameState>,
+    data: Res<Data>,
+    my_id: Res<PlayerId>,
+    lang: Res<Lang>,
+    asset_server: Res<AssetServer>,
+    mut selection: ResMut<RevivalSelection>,
+    panel: Query<Entity, With<RevivalPanel>>,
+) {
+    let player = game_state
+        .players
+        .get(&my_id)
+        .filter(|_| game_state.prompts.get(&my_id) == Some(&Prompt::Revival));
+    let player = match player {
+        Some(player) => player,
+        None => {
+            // Start from nothing the next time we are asked
+            if !panel.is_empty() {
+                for entity in panel.iter() {
+                    commands.entity(entity).despawn_recursive();
+                }
+                *selection = default();
+            }
+            return;
+        }
+    };
+    if !panel.is_empty() && !selection.is_changed() && !game_state.is_changed() {
+        return;
+    }
+    for entity in panel.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    let text_style = TextStyle {
+        font: font.clone(),
+        font_size: 18.0,
+        color: Color::WHITE,
+    };
+    let mut leaders = player.tanks.leaders.iter().collect::<Vec<_>>();
+    leaders.sort_by_key(|leader| &data.leaders[&leader.inner].name);
+    let in_tanks = |special: bool| {
+        player
+            .tanks
+            .forces
+            .iter()
+            .filter(|force| force.inner.is_special == special)
+            .count()
+    };
+    let cost = selection.cost(&game_state, &data, &lang, *my_id);
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    top: Val::Percent(15.0),
+                    left: Val::Percent(35.0),
+                    ..default()
+                },
+                size: Size::new(Val::Percent(30.0), Val::Undefined),
+                flex_direction: FlexDirection::ColumnReverse,
+                padding: UiRect::all(Val::Px(10.0)),
+                ..default()
+            },
+            color: Color::rgba(0.0, 0.0, 0.0, 0.8).into(),
+            ..default()
+        })
+        .insert(RevivalPanel)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle::from_section(
+                lang.fmt("revival.spice", &[("spice", &player.spice)]),
+                text_style.clone(),
+            ));
+            if !leaders.is_empty() {
+                parent.spawn_bundle(TextBundle::from_section(
+                    lang.get("revival.leaders"),
+                    text_style.clone(),
+                ));
+                spawn_row(parent, |parent| {
+                    for leader in leaders {
+                        let leader_data = &data.leaders[&leader.inner];
+                        let color = if selection.leader == Some(leader.id) {
+                            SELECTED_COLOR
+                        } else {
+                            BUTTON_COLOR
+                        };
+                        spawn_button(
+                            parent,
+                            &font,
+                            format!("{} ({})", leader_data.name, leader_data.power),
+                            RevivalAction::Leader(leader.id),
+                            color,
+                        );
+                    }
+                });
+            }
+            for (special, count, label) in [
+                (false, selection.forces, "revival.forces"),
+                (true, selection.special_forces, "revival.special_forces"),
+            ] {
+                let available = in_tanks(special);
+                if available == 0 {
+                    continue;
+                }
+                spawn_row(parent, |parent| {
+                    parent.spawn_bundle(TextBundle::from_section(
+                        lang.fmt(label, &[("count", &count), ("available", &available)]),
+                        text_style.clone(),
+                    ));
+                    spawn_button(
+                        parent,
+                        &font,
+                        "-".to_string(),
+                        RevivalAction::Fewer { special },
+                        BUTTON_COLOR,
+                    );
+                    spawn_button(
+                        parent,
+                        &font,
+                        "+".to_string(),
+                        RevivalAction::More { special },
+                        BUTTON_COLOR,
+                    );
+                });
+            }
+            parent.spawn_bundle(TextBundle::from_section(
+                match &cost {
+                    Ok(cost) => lang.fmt("revival.cost", &[("cost", cost)]),
+                    Err(reason) => reason.clone(),
+                },
+                text_style.clone(),
+            ));
+            spawn_row(parent, |parent| {
+                let color = if cost.is_ok() { BUTTON_COLOR } else { DISABLED_COLOR };
+                spawn_button(parent, &font, lang.get("revival.revive"), RevivalAction::Revive, color);
+                spawn_button(
+                    parent,
+                    &font,
+                    lang.get("revival.decline"),
+                    RevivalAction::Decline,
+                    BUTTON_COLOR,
+                );
+            });
+        });
+}
+
+fn revival_buttons(
+    mut client: GameClient,
+    game_state: Res<GameState>,
+    data: Res<Data>,
+    my_id: Res<PlayerId>,
+    lang: Res<Lang>,
+    mut selection: ResMut<RevivalSelection>,
+    mut interactions: Query<(&Interaction, &mut UiColor, &RevivalButton), Changed<Interaction>>,
+) {
+    for (interaction, mut color, button) in interactions.iter_mut() {
+        match interaction {
+            Interaction::Clicked => {
+                let tanks = match game_state.players.get(&my_id) {
+                    Some(player) => &player.tanks,
+                    None => continue,
+                };
+                match button.action {
+                    RevivalAction::Leader(leader_id) => {
+                        // Picking the chosen leader again leaves them in the tanks
+                        if selection.leader == Some(leader_id) {
+                            selection.leader.take();
+                        } else {
+                            selection.leader.replace(leader_id);
+                        }
+                    }
+                    RevivalAction::More { special } => {
+                        let available = tanks
+                            .forces
+                            .iter()
+                            .filter(|force| force.inner.is_special == special)
+                            .count();
+                        let count = if special {
+                            &mut selection.special_forces
+                        } else {
+                            &mut selection.forces
+                        };
+                        if *count < available.min(MAX_REVIVAL) {
+                            *count += 1;
+                        }
+                    }
+                    RevivalAction::Fewer { special } => {
+                        let count = if special {
+                            &mut selection.special_forces
+                        } else {
+                            &mut selection.forces
+                        };
+                        *count = count.saturating_sub(1);
+                    }
+                    RevivalAction::Revive => {
+                        if selection.cost(&game_state, &data, &lang, *my_id).is_ok() {
+                            client.send_event(GameEvent::Revive {
+                                player_id: *my_id,
+                                forces: selection.troops(tanks).into_iter().map(|force| force.id).collect(),
+                                leader: selection.leader,
+                            });
+                        }
+                    }
+                    RevivalAction::Decline => client.send_event(GameEvent::Decline { player_id: *my_id }),
+                }
+            }
+            Interaction::Hovered => {
+                if button.color != DISABLED_COLOR {
+                    *color = Color::rgb(0.25, 0.25, 0.25).into();
+                }
+            }
+            Interaction::None => *color = button.color.into(),
+        }
+    }
+}
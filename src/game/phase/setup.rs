@@ -1,4 +1,4 @@
-use std::f32::consts::PI;
+use std::{collections::HashSet, f32::consts::PI};
 
 use bevy::{
     math::{vec2, vec3},
@@ -6,21 +6,23 @@ use bevy::{
 };
 use bevy_mod_picking::PickableBundle;
 use derive_more::Display;
-use iyes_loopless::prelude::ConditionSet;
-use renet::RenetClient;
+use iyes_loopless::prelude::{AppLooplessStateExt, ConditionSet};
 use serde::{Deserialize, Serialize};
 
 use super::Phase;
 use crate::{
-    components::{FactionChoiceCard, FactionPredictionCard, Spice, TraitorCard, TurnPredictionCard},
+    components::{Faction, FactionChoiceCard, FactionPredictionCard, TraitorCard, TurnPredictionCard},
     data::Data,
     game::{
-        state::{GameEvent, GameState, PlayerId, Prompt},
-        GameEventStage, ObjectEntityMap, ObjectId, PickedEvent, PlayerFactionText,
+        prompt::{reset_on_cancel, PromptUi},
+        spice::spawn_stash,
+        state::{GameEvent, GameState, PlayerId},
+        GameEventStage, ObjectEntityMap, ObjectId, PickedEvent, PlayerFactionText, PlayerShield,
     },
+    lang::Lang,
     lerper::{Lerp, Lerper, UITransform},
-    network::{GameEvents, SendEvent},
-    util::divide_spice,
+    network::{GameClient, GameEvents},
+    settings::Settings,
     Screen,
 };
 
@@ -28,10 +30,18 @@ pub struct SetupPlugin;
 
 impl Plugin for SetupPlugin {
     fn build(&self, app: &mut App) {
+        app.init_resource::<PreviewedFaction>()
+            .add_exit_system(Screen::Game, clear_preview)
+            .add_enter_system(Screen::Game, init_faction_wait_text)
+            .add_system(reset_on_cancel::<PreviewedFaction>);
+
         app.add_system_set(
             ConditionSet::new()
                 .run_in_state(Screen::Game)
                 .with_system(faction_pick)
+                .with_system(faction_preview)
+                .with_system(faction_abilities)
+                .with_system(faction_wait_text)
                 .with_system(faction_prediction)
                 .with_system(turn_prediction)
                 .with_system(pick_traitor)
@@ -39,12 +49,7 @@ impl Plugin for SetupPlugin {
         );
 
         app.stage(GameEventStage, |stage: &mut SystemStage| {
-            stage
-                .add_system(prompt_factions)
-                .add_system(faction_init)
-                .add_system(prompt_predictions)
-                .add_system(positions)
-                .add_system(prompt_traitors);
+            stage.add_system(faction_init).add_system(positions);
             stage
         });
     }
@@ -60,82 +65,381 @@ pub enum SetupPhase {
     DealTreachery,
 }
 
-fn prompt_factions(
-    game_events: Res<GameEvents>,
-    data: Res<Data>,
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-    my_id: Res<PlayerId>,
+/// The faction choice whose starting setup is being shown, which is only chosen once it is confirmed.
+#[derive(Default)]
+struct PreviewedFaction(Option<Faction>);
+
+/// The panel describing the previewed faction.
+#[derive(Component)]
+struct FactionPreview;
+
+/// Says who we are waiting on while someone else chooses their faction.
+#[derive(Component)]
+struct FactionWaitText;
+
+/// The panel summarizing the powers of every faction left to choose from.
+#[derive(Component)]
+struct FactionAbilities;
+
+/// How much of a faction's description is shown in the abilities panel. The rest can be read by previewing it.
+const ABILITY_SUMMARY_LENGTH: usize = 120;
+
+/// Lay out the factions which are left to choose from. A faction with no data can't be played, so its card can't be
+/// picked.
+pub fn show_faction_choices(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    materials: &mut Assets<StandardMaterial>,
+    data: &Data,
+    remaining: &HashSet<Faction>,
 ) {
-    if let Some(GameEvent::ShowPrompt {
-        player_id,
-        prompt: Prompt::Faction { remaining },
-    }) = game_events.peek()
-    {
-        if *my_id == *player_id {
-            let card_face = asset_server.get_handle("card.gltf#Mesh0/Primitive0");
-            let card_back = asset_server.get_handle("card.gltf#Mesh0/Primitive1");
-            let prediction_back_texture = asset_server.get_handle("predictions/prediction_back.png");
-            for (i, faction) in remaining.iter().enumerate() {
-                let prediction_front_texture =
-                    asset_server.get_handle(format!("predictions/prediction_{}.png", faction.code()).as_str());
-
-                let node = data.prediction_nodes.factions[i];
-
-                commands
-                    .spawn_bundle((FactionChoiceCard { faction: *faction },))
-                    .insert(Lerper::from(Lerp::ui_from_to(
-                        UITransform::default().with_rotation(Quat::from_rotation_x(PI / 2.0)),
-                        UITransform::from(node).with_rotation(Quat::from_rotation_x(PI / 2.0)),
-                        0.5,
-                        0.03 * i as f32,
-                    )))
-                    .insert_bundle(SpatialBundle::default())
-                    .with_children(|parent| {
-                        parent
-                            .spawn_bundle(PbrBundle {
-                                mesh: card_face.clone(),
-                                material: materials.add(StandardMaterial::from(prediction_front_texture)),
-                                ..default()
-                            })
-                            .insert_bundle(PickableBundle::default());
-                        parent
-                            .spawn_bundle(PbrBundle {
-                                mesh: card_back.clone(),
-                                material: materials.add(StandardMaterial::from(prediction_back_texture.clone())),
-                                ..default()
-                            })
-                            .insert_bundle(PickableBundle::default());
-                    });
-            }
+    let card_face = asset_server.get_handle("card.gltf#Mesh0/Primitive0");
+    let card_back = asset_server.get_handle("card.gltf#Mesh0/Primitive1");
+    let prediction_back_texture = asset_server.get_handle("predictions/prediction_back.png");
+    for (i, faction) in remaining.iter().enumerate() {
+        let prediction_front_texture =
+            asset_server.get_handle(format!("predictions/prediction_{}.png", faction.code()).as_str());
+
+        let node = data.prediction_nodes.factions[i];
+        let pickable = data.factions.contains_key(faction);
+        if !pickable {
+            warn!("No data for faction {}", faction);
         }
+
+        commands
+            .spawn_bundle((FactionChoiceCard { faction: *faction },))
+            .insert(PromptUi)
+            .insert(Lerper::from(Lerp::ui_from_to(
+                UITransform::default().with_rotation(Quat::from_rotation_x(PI / 2.0)),
+                UITransform::from(node).with_rotation(Quat::from_rotation_x(PI / 2.0)),
+                0.5,
+                0.03 * i as f32,
+            )))
+            .insert_bundle(SpatialBundle::default())
+            .with_children(|parent| {
+                let mut face = parent.spawn_bundle(PbrBundle {
+                    mesh: card_face.clone(),
+                    material: materials.add(StandardMaterial::from(prediction_front_texture)),
+                    ..default()
+                });
+                if pickable {
+                    face.insert_bundle(PickableBundle::default());
+                }
+                let mut back = parent.spawn_bundle(PbrBundle {
+                    mesh: card_back.clone(),
+                    material: materials.add(StandardMaterial::from(prediction_back_texture.clone())),
+                    ..default()
+                });
+                if pickable {
+                    back.insert_bundle(PickableBundle::default());
+                }
+            });
     }
 }
 
+/// Picking a faction shows its starting setup. Picking it again, or pressing confirm, chooses it.
 fn faction_pick(
-    mut commands: Commands,
+    settings: Res<Settings>,
+    keyboard_input: Res<Input<KeyCode>>,
+    data: Res<Data>,
     mut picked_events: EventReader<PickedEvent<FactionChoiceCard>>,
-    mut client: ResMut<RenetClient>,
-    faction_cards: Query<Entity, With<FactionChoiceCard>>,
+    mut previewed: ResMut<PreviewedFaction>,
+    mut client: GameClient,
     my_id: Res<PlayerId>,
+    game_state: Res<GameState>,
+    cards: Query<&FactionChoiceCard>,
 ) {
+    // Only the player whose turn it is can choose, even if the cards are still out when the turn moves on
+    if cards.is_empty() || game_state.active_player != Some(*my_id) {
+        picked_events.clear();
+        if previewed.0.is_some() {
+            previewed.0.take();
+        }
+        return;
+    }
+    let mut confirmed = None;
     for PickedEvent {
         picked: _,
         inner: FactionChoiceCard { faction },
     } in picked_events.iter()
     {
-        for entity in faction_cards.iter() {
-            // TODO: animate them away~
-            commands.entity(entity).despawn_recursive();
+        if !data.factions.contains_key(faction) {
+            continue;
         }
+        if previewed.0 == Some(*faction) {
+            confirmed = Some(*faction);
+        } else {
+            previewed.0.replace(*faction);
+        }
+    }
+    if keyboard_input.just_pressed(settings.key_bindings.confirm) {
+        confirmed = confirmed.or(previewed.0);
+    } else if keyboard_input.just_pressed(settings.key_bindings.cancel) && previewed.0.is_some() {
+        previewed.0.take();
+    }
+    if let Some(faction) = confirmed {
+        previewed.0.take();
         client.send_event(GameEvent::ChooseFaction {
             player_id: *my_id,
-            faction: *faction,
+            faction,
         });
     }
 }
 
+fn clear_preview(mut previewed: ResMut<PreviewedFaction>) {
+    previewed.0.take();
+}
+
+/// What a faction starts the game with, and what it can do.
+fn describe_faction(data: &Data, settings: &Settings, lang: &Lang, faction: Faction) -> Option<String> {
+    let faction_data = data.factions.get(&faction)?;
+    let starting_values = &faction_data.starting_values;
+    let mut forces = lang.fmt("setup.starting_forces", &[("forces", &starting_values.units)]);
+    if starting_values.units > 0 {
+        if let Some(locations) = &starting_values.possible_locations {
+            let mut names = locations
+                .iter()
+                .map(|location| data.locations[location].name.clone())
+                .collect::<Vec<_>>();
+            names.sort();
+            let locations = names.join(&lang.get("setup.starting_location_separator"));
+            forces += &lang.fmt("setup.starting_locations", &[("locations", &locations)]);
+        } else {
+            forces += &lang.get("setup.starting_anywhere");
+        }
+        if let Some(limit) = starting_values.territory_limit {
+            forces += &lang.fmt("setup.territory_limit", &[("limit", &limit)]);
+        }
+    }
+    let mut lines = vec![
+        lang.fmt("setup.starting_spice", &[("spice", &starting_values.spice)]),
+        forces,
+    ];
+    if faction_data.special_forces > 0 {
+        lines.push(lang.fmt("setup.special_forces", &[("count", &faction_data.special_forces)]));
+    }
+    lines.push(lang.fmt("setup.free_revivals", &[("count", &faction_data.free_revival)]));
+    let mut leaders = data
+        .leaders
+        .values()
+        .filter(|leader| leader.faction == faction)
+        .map(|leader| format!("{} ({})", leader.name, leader.power))
+        .collect::<Vec<_>>();
+    leaders.sort();
+    if !leaders.is_empty() {
+        lines.push(lang.fmt("setup.leaders", &[("leaders", &leaders.join(", "))]));
+    }
+    let confirm = format!("{:?}", settings.key_bindings.confirm);
+    Some(format!(
+        "{}\n{}\n\n{}\n\n{}",
+        faction_data.name,
+        faction_data.description,
+        lines.join("\n"),
+        lang.fmt("setup.confirm_faction", &[("key", &confirm)])
+    ))
+}
+
+fn faction_preview(
+    mut commands: Commands,
+    previewed: Res<PreviewedFaction>,
+    settings: Res<Settings>,
+    lang: Res<Lang>,
+    data: Res<Data>,
+    asset_server: Res<AssetServer>,
+    panel: Query<Entity, With<FactionPreview>>,
+) {
+    if !previewed.is_changed() {
+        return;
+    }
+    for entity in panel.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    if let Some(description) = previewed
+        .0
+        .and_then(|faction| describe_faction(&data, &settings, &lang, faction))
+    {
+        commands
+            .spawn_bundle(NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: UiRect {
+                        top: Val::Px(10.0),
+                        right: Val::Px(10.0),
+                        ..default()
+                    },
+                    max_size: Size::new(Val::Percent(30.0), Val::Undefined),
+                    padding: UiRect::all(Val::Px(8.0)),
+                    ..default()
+                },
+                color: Color::rgba(0.0, 0.0, 0.0, 0.7).into(),
+                ..default()
+            })
+            .insert(FactionPreview)
+            .with_children(|parent| {
+                parent.spawn_bundle(TextBundle::from_section(
+                    description,
+                    TextStyle {
+                        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                        font_size: 18.0,
+                        color: Color::WHITE,
+                    },
+                ));
+            });
+    }
+}
+
+/// A faction's description, cut short at a word if it is too long to list alongside the others.
+fn summarize_abilities(lang: &Lang, description: &str) -> String {
+    if description.chars().count() <= ABILITY_SUMMARY_LENGTH {
+        return description.to_string();
+    }
+    let cut = description
+        .char_indices()
+        .nth(ABILITY_SUMMARY_LENGTH)
+        .map_or(description.len(), |(i, _)| i);
+    let summary = &description[..cut];
+    let summary = summary.rsplit_once(' ').map_or(summary, |(start, _)| start);
+    let summary = summary.trim_end_matches(|c: char| c.is_ascii_punctuation());
+    lang.fmt("setup.abilities_cut_short", &[("summary", &summary)])
+}
+
+fn faction_abilities(
+    mut commands: Commands,
+    previewed: Res<PreviewedFaction>,
+    data: Res<Data>,
+    lang: Res<Lang>,
+    asset_server: Res<AssetServer>,
+    cards: Query<&FactionChoiceCard>,
+    added: Query<(), Added<FactionChoiceCard>>,
+    panel: Query<Entity, With<FactionAbilities>>,
+) {
+    if cards.is_empty() {
+        for entity in panel.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        return;
+    }
+    if !panel.is_empty() && added.is_empty() && !previewed.is_changed() {
+        return;
+    }
+    for entity in panel.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    let mut factions = cards
+        .iter()
+        .filter_map(|card| {
+            data.factions
+                .get(&card.faction)
+                .map(|faction_data| (card.faction, faction_data))
+        })
+        .collect::<Vec<_>>();
+    factions.sort_by(|(_, a), (_, b)| a.name.cmp(&b.name));
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    bottom: Val::Px(10.0),
+                    right: Val::Px(10.0),
+                    ..default()
+                },
+                size: Size::new(Val::Percent(25.0), Val::Undefined),
+                flex_direction: FlexDirection::ColumnReverse,
+                padding: UiRect::all(Val::Px(8.0)),
+                ..default()
+            },
+            color: Color::rgba(0.0, 0.0, 0.0, 0.7).into(),
+            ..default()
+        })
+        .insert(FactionAbilities)
+        .with_children(|parent| {
+            for (faction, faction_data) in factions {
+                let color = if previewed.0 == Some(faction) {
+                    Color::GOLD
+                } else {
+                    Color::WHITE
+                };
+                parent.spawn_bundle(
+                    TextBundle::from_sections([
+                        TextSection::new(
+                            format!("{}\n", faction_data.name),
+                            TextStyle {
+                                font: font.clone(),
+                                font_size: 18.0,
+                                color,
+                            },
+                        ),
+                        TextSection::new(
+                            summarize_abilities(&lang, &faction_data.description),
+                            TextStyle {
+                                font: font.clone(),
+                                font_size: 14.0,
+                                color: Color::rgb(0.8, 0.8, 0.8),
+                            },
+                        ),
+                    ])
+                    .with_style(Style {
+                        margin: UiRect::new(Val::Undefined, Val::Undefined, Val::Undefined, Val::Px(6.0)),
+                        ..default()
+                    }),
+                );
+            }
+        });
+}
+
+fn init_faction_wait_text(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    top: Val::Px(35.0),
+                    left: Val::Percent(40.0),
+                    ..default()
+                },
+                ..default()
+            },
+            text: Text::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                    font_size: 20.0,
+                    color: Color::WHITE,
+                },
+            ),
+            ..default()
+        })
+        .insert(FactionWaitText);
+}
+
+/// While factions are being chosen by someone else, say who, by their seat at the table.
+fn faction_wait_text(
+    game_state: Res<GameState>,
+    my_id: Res<PlayerId>,
+    lang: Res<Lang>,
+    mut text: Query<&mut Text, With<FactionWaitText>>,
+) {
+    if !game_state.is_changed() {
+        return;
+    }
+    let waiting_on = game_state
+        .active_player
+        .filter(|player_id| *player_id != *my_id && game_state.phase == Phase::Setup(SetupPhase::ChooseFactions));
+    let status = waiting_on.map_or_else(String::new, |player_id| {
+        match game_state.play_order.iter().position(|id| *id == player_id) {
+            Some(seat) => lang.fmt("setup.waiting_for_seat", &[("seat", &(seat + 1))]),
+            None => lang.get("setup.waiting_for_someone"),
+        }
+    });
+    for mut text in text.iter_mut() {
+        if text.sections[0].value != status {
+            text.sections[0].value = status.clone();
+        }
+    }
+}
+
 fn faction_init(
     game_events: Res<GameEvents>,
     mut commands: Commands,
@@ -152,8 +456,6 @@ fn faction_init(
             let shield_face = asset_server.get_handle("shield.gltf#Mesh0/Primitive1");
             let shield_back = asset_server.get_handle("shield.gltf#Mesh0/Primitive2");
 
-            let spice_token = asset_server.get_handle("spice_token.gltf#Mesh0/Primitive0");
-
             let shield_front_texture =
                 asset_server.get_handle(format!("shields/{}_shield_front.png", faction.code()).as_str());
             let shield_back_texture =
@@ -164,6 +466,7 @@ fn faction_init(
                     0.0, 0.27, 1.34,
                 ))))
                 .insert(data.camera_nodes.shield)
+                .insert(PlayerShield { player_id: *player_id })
                 .with_children(|parent| {
                     parent
                         .spawn_bundle(PbrBundle {
@@ -181,158 +484,122 @@ fn faction_init(
                         .insert_bundle(PickableBundle::default());
                 });
 
-            let spice_1_texture = asset_server.get_handle("tokens/spice_1.png");
-            let spice_1_material = materials.add(StandardMaterial::from(spice_1_texture));
-            let spice_2_texture = asset_server.get_handle("tokens/spice_2.png");
-            let spice_2_material = materials.add(StandardMaterial::from(spice_2_texture));
-            let spice_5_texture = asset_server.get_handle("tokens/spice_5.png");
-            let spice_5_material = materials.add(StandardMaterial::from(spice_5_texture));
-            let spice_10_texture = asset_server.get_handle("tokens/spice_10.png");
-            let spice_10_material = materials.add(StandardMaterial::from(spice_10_texture));
-
-            let spice = data.factions.get(&faction).unwrap().starting_values.spice;
-
-            let (tens, fives, twos, ones) = divide_spice(spice as i32);
-            for (i, (value, s)) in (0..tens)
-                .zip(std::iter::repeat((10, 0)))
-                .chain((0..fives).zip(std::iter::repeat((5, 1))))
-                .chain((0..twos).zip(std::iter::repeat((2, 2))))
-                .chain((0..ones).zip(std::iter::repeat((1, 3))))
-            {
-                let material = match value {
-                    1 => spice_1_material.clone(),
-                    2 => spice_2_material.clone(),
-                    5 => spice_5_material.clone(),
-                    _ => spice_10_material.clone(),
-                };
-                commands
-                    .spawn_bundle(SpatialBundle::from_transform(Transform::from_translation(
-                        data.token_nodes.spice[s] + (i as f32 * 0.0036 * Vec3::Y),
-                    )))
-                    .insert_bundle(PickableBundle::default())
-                    .insert(Spice { value })
-                    .insert_bundle(PbrBundle {
-                        mesh: spice_token.clone(),
-                        material,
-                        ..Default::default()
-                    });
-            }
+            spawn_stash(
+                &mut commands,
+                &asset_server,
+                &mut materials,
+                &data,
+                game_state.players[&my_id].spice,
+            );
         } else {
             // TODO: display other player's faction picks
         }
     }
 }
 
-fn prompt_predictions(
-    game_events: Res<GameEvents>,
-    mut commands: Commands,
-    game_state: Res<GameState>,
-    data: Res<Data>,
-    asset_server: Res<AssetServer>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-    my_id: Res<PlayerId>,
+/// Lay out a card for each faction in the game, for the Bene Gesserit to predict the winner.
+pub fn show_faction_predictions(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    materials: &mut Assets<StandardMaterial>,
+    data: &Data,
+    game_state: &GameState,
 ) {
-    if let Some(GameEvent::ShowPrompt { prompt, player_id }) = game_events.peek() {
-        match prompt {
-            Prompt::FactionPrediction => {
-                if *my_id == *player_id {
-                    let card_face = asset_server.get_handle("card.gltf#Mesh0/Primitive0");
-                    let card_back = asset_server.get_handle("card.gltf#Mesh0/Primitive1");
-
-                    let prediction_back_texture = asset_server.get_handle("predictions/prediction_back.png");
-
-                    for (i, faction) in game_state.players.values().map(|player| player.faction).enumerate() {
-                        let prediction_front_texture =
-                            asset_server.get_handle(format!("predictions/prediction_{}.png", faction.code()).as_str());
-
-                        let node = data.prediction_nodes.factions[i];
-
-                        commands
-                            .spawn_bundle((FactionPredictionCard { faction },))
-                            .insert(Lerper::from(Lerp::ui_from_to(
-                                UITransform::default().with_rotation(Quat::from_rotation_x(PI / 2.0)),
-                                UITransform::from(node).with_rotation(Quat::from_rotation_x(PI / 2.0)),
-                                0.5,
-                                0.03 * i as f32,
-                            )))
-                            .insert_bundle(SpatialBundle::default())
-                            .with_children(|parent| {
-                                parent
-                                    .spawn_bundle(PbrBundle {
-                                        mesh: card_face.clone(),
-                                        material: materials.add(StandardMaterial::from(prediction_front_texture)),
-                                        ..default()
-                                    })
-                                    .insert_bundle(PickableBundle::default());
-                                parent
-                                    .spawn_bundle(PbrBundle {
-                                        mesh: card_back.clone(),
-                                        material: materials
-                                            .add(StandardMaterial::from(prediction_back_texture.clone())),
-                                        ..default()
-                                    })
-                                    .insert_bundle(PickableBundle::default());
-                            });
-                    }
-                }
-            }
-            Prompt::TurnPrediction => {
-                if *my_id == *player_id {
-                    let card_face = asset_server.get_handle("card.gltf#Mesh0/Primitive0");
-                    let card_back = asset_server.get_handle("card.gltf#Mesh0/Primitive1");
-
-                    let prediction_back_texture = asset_server.get_handle("predictions/prediction_back.png");
-
-                    (1..=15).for_each(|turn| {
-                        let prediction_front_texture =
-                            asset_server.get_handle(format!("predictions/prediction_t{}.png", turn).as_str());
-
-                        let i = turn as usize - 1;
-                        let node = data.prediction_nodes.turns[i];
-
-                        commands
-                            .spawn_bundle(SpatialBundle::default())
-                            .insert(Lerper::from(Lerp::ui_from_to(
-                                UITransform::default()
-                                    .with_rotation(Quat::from_rotation_x(PI / 2.0))
-                                    .with_scale(0.6),
-                                UITransform::from(node)
-                                    .with_rotation(Quat::from_rotation_x(PI / 2.0))
-                                    .with_scale(0.6),
-                                0.5,
-                                0.01 * i as f32,
-                            )))
-                            .insert(TurnPredictionCard { turn })
-                            .with_children(|parent| {
-                                parent
-                                    .spawn_bundle(PbrBundle {
-                                        mesh: card_face.clone(),
-                                        material: materials.add(StandardMaterial::from(prediction_front_texture)),
-                                        ..Default::default()
-                                    })
-                                    .insert_bundle(PickableBundle::default());
-                                parent
-                                    .spawn_bundle(PbrBundle {
-                                        mesh: card_back.clone(),
-                                        material: materials
-                                            .add(StandardMaterial::from(prediction_back_texture.clone())),
-                                        ..Default::default()
-                                    })
-                                    .insert_bundle(PickableBundle::default());
-                            });
-                    });
-                }
-            }
-            _ => (),
-        }
+    let card_face = asset_server.get_handle("card.gltf#Mesh0/Primitive0");
+    let card_back = asset_server.get_handle("card.gltf#Mesh0/Primitive1");
+
+    let prediction_back_texture = asset_server.get_handle("predictions/prediction_back.png");
+
+    for (i, faction) in game_state.players.values().map(|player| player.faction).enumerate() {
+        let prediction_front_texture =
+            asset_server.get_handle(format!("predictions/prediction_{}.png", faction.code()).as_str());
+
+        let node = data.prediction_nodes.factions[i];
+
+        commands
+            .spawn_bundle((FactionPredictionCard { faction },))
+            .insert(PromptUi)
+            .insert(Lerper::from(Lerp::ui_from_to(
+                UITransform::default().with_rotation(Quat::from_rotation_x(PI / 2.0)),
+                UITransform::from(node).with_rotation(Quat::from_rotation_x(PI / 2.0)),
+                0.5,
+                0.03 * i as f32,
+            )))
+            .insert_bundle(SpatialBundle::default())
+            .with_children(|parent| {
+                parent
+                    .spawn_bundle(PbrBundle {
+                        mesh: card_face.clone(),
+                        material: materials.add(StandardMaterial::from(prediction_front_texture)),
+                        ..default()
+                    })
+                    .insert_bundle(PickableBundle::default());
+                parent
+                    .spawn_bundle(PbrBundle {
+                        mesh: card_back.clone(),
+                        material: materials.add(StandardMaterial::from(prediction_back_texture.clone())),
+                        ..default()
+                    })
+                    .insert_bundle(PickableBundle::default());
+            });
     }
 }
 
+/// Lay out a card for each turn, for the Bene Gesserit to predict when the winner wins.
+pub fn show_turn_predictions(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    materials: &mut Assets<StandardMaterial>,
+    data: &Data,
+) {
+    let card_face = asset_server.get_handle("card.gltf#Mesh0/Primitive0");
+    let card_back = asset_server.get_handle("card.gltf#Mesh0/Primitive1");
+
+    let prediction_back_texture = asset_server.get_handle("predictions/prediction_back.png");
+
+    (1..=15).for_each(|turn| {
+        let prediction_front_texture =
+            asset_server.get_handle(format!("predictions/prediction_t{}.png", turn).as_str());
+
+        let i = turn as usize - 1;
+        let node = data.prediction_nodes.turns[i];
+
+        commands
+            .spawn_bundle(SpatialBundle::default())
+            .insert(Lerper::from(Lerp::ui_from_to(
+                UITransform::default()
+                    .with_rotation(Quat::from_rotation_x(PI / 2.0))
+                    .with_scale(0.6),
+                UITransform::from(node)
+                    .with_rotation(Quat::from_rotation_x(PI / 2.0))
+                    .with_scale(0.6),
+                0.5,
+                0.01 * i as f32,
+            )))
+            .insert(TurnPredictionCard { turn })
+            .insert(PromptUi)
+            .with_children(|parent| {
+                parent
+                    .spawn_bundle(PbrBundle {
+                        mesh: card_face.clone(),
+                        material: materials.add(StandardMaterial::from(prediction_front_texture)),
+                        ..Default::default()
+                    })
+                    .insert_bundle(PickableBundle::default());
+                parent
+                    .spawn_bundle(PbrBundle {
+                        mesh: card_back.clone(),
+                        material: materials.add(StandardMaterial::from(prediction_back_texture.clone())),
+                        ..Default::default()
+                    })
+                    .insert_bundle(PickableBundle::default());
+            });
+    });
+}
+
 fn faction_prediction(
-    mut commands: Commands,
-    mut client: ResMut<RenetClient>,
+    mut client: GameClient,
     mut picked_events: EventReader<PickedEvent<FactionPredictionCard>>,
-    cards: Query<Entity, With<FactionPredictionCard>>,
     my_id: Res<PlayerId>,
 ) {
     for PickedEvent {
@@ -340,10 +607,6 @@ fn faction_prediction(
         inner: FactionPredictionCard { faction },
     } in picked_events.iter()
     {
-        for entity in cards.iter() {
-            // TODO: animate them away~
-            commands.entity(entity).despawn_recursive();
-        }
         client.send_event(GameEvent::MakeFactionPrediction {
             player_id: *my_id,
             faction: *faction,
@@ -352,10 +615,8 @@ fn faction_prediction(
 }
 
 fn turn_prediction(
-    mut commands: Commands,
-    mut client: ResMut<RenetClient>,
+    mut client: GameClient,
     mut picked_events: EventReader<PickedEvent<TurnPredictionCard>>,
-    cards: Query<Entity, With<TurnPredictionCard>>,
     my_id: Res<PlayerId>,
 ) {
     for PickedEvent {
@@ -363,10 +624,6 @@ fn turn_prediction(
         inner: TurnPredictionCard { turn },
     } in picked_events.iter()
     {
-        for entity in cards.iter() {
-            // TODO: animate them away~
-            commands.entity(entity).despawn_recursive();
-        }
         client.send_event(GameEvent::MakeTurnPrediction {
             player_id: *my_id,
             turn: *turn,
@@ -402,43 +659,35 @@ fn positions(
     }
 }
 
-fn prompt_traitors(
-    game_events: Res<GameEvents>,
-    game_state: Res<GameState>,
-    my_id: Res<PlayerId>,
-    object_entity: Res<ObjectEntityMap>,
-    mut traitors: Query<&mut Lerper>,
+/// Bring the traitors we were dealt up in front of us to choose from.
+pub fn show_traitors(
+    game_state: &GameState,
+    my_id: PlayerId,
+    object_entity: &ObjectEntityMap,
+    traitors: &mut Query<&mut Lerper>,
 ) {
-    if let Some(GameEvent::ShowPrompt {
-        player_id,
-        prompt: Prompt::Traitor,
-    }) = game_events.peek()
+    let nodes = [vec2(-0.6, 0.0), vec2(-0.2, 0.0), vec2(0.2, 0.0), vec2(0.6, 0.0)];
+    for (i, (card, node)) in game_state
+        .players
+        .get(&my_id)
+        .unwrap()
+        .traitor_cards
+        .iter()
+        .zip(nodes)
+        .enumerate()
     {
-        if *my_id == *player_id {
-            let nodes = [vec2(-0.6, 0.0), vec2(-0.2, 0.0), vec2(0.2, 0.0), vec2(0.6, 0.0)];
-            for (i, (card, node)) in game_state
-                .players
-                .get(player_id)
-                .unwrap()
-                .traitor_cards
-                .iter()
-                .zip(nodes)
-                .enumerate()
-            {
-                if let Ok(mut lerper) = traitors.get_mut(object_entity.world[&card.id]) {
-                    lerper.push(Lerp::world_to_ui(
-                        UITransform::from(node).with_rotation(Quat::from_rotation_x(PI / 2.0)),
-                        0.5,
-                        0.03 * i as f32,
-                    ));
-                }
-            }
+        if let Ok(mut lerper) = traitors.get_mut(object_entity.world[&card.id]) {
+            lerper.push(Lerp::world_to_ui(
+                UITransform::from(node).with_rotation(Quat::from_rotation_x(PI / 2.0)),
+                0.5,
+                0.03 * i as f32,
+            ));
         }
     }
 }
 
 fn pick_traitor(
-    mut client: ResMut<RenetClient>,
+    mut client: GameClient,
     mut picked_events: EventReader<PickedEvent<TraitorCard>>,
     mut cards: Query<&ObjectId, With<TraitorCard>>,
     my_id: Res<PlayerId>,
@@ -5,23 +5,32 @@ use bevy::{
     prelude::*,
 };
 use derive_more::Display;
+use iyes_loopless::prelude::IntoConditionalSystem;
 use serde::{Deserialize, Serialize};
 
 use crate::{
+    components::{LocationSector, Troop},
+    data::Data,
     game::{
-        state::{GameEvent, GameState},
-        GameEventPauser, GameEventStage, ObjectEntityMap,
+        state::{EventReduce, GameEvent, GameState, PlayerId, Prompt},
+        GameEventPauser, GameEventStage, ObjectEntityMap, PickedEvent,
     },
     lerper::{Lerp, Lerper, UITransform},
-    network::GameEvents,
+    network::{GameClient, GameEvents},
+    settings::Settings,
+    Screen,
 };
 
 pub struct SpiceBlowPlugin;
 
 impl Plugin for SpiceBlowPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system_to_stage(GameEventStage, reveal)
-            .add_system_to_stage(GameEventStage, place_spice);
+        app.add_system(ride_worm_input.run_in_state(Screen::Game))
+            .add_system(peek_input.run_in_state(Screen::Game))
+            .add_system_to_stage(GameEventStage, peek)
+            .add_system_to_stage(GameEventStage, reveal)
+            .add_system_to_stage(GameEventStage, place_spice)
+            .add_system_to_stage(GameEventStage, ride_worm);
     }
 }
 
@@ -35,6 +44,39 @@ pub enum SpiceBlowPhase {
 #[derive(Component)]
 pub struct RevealedSpiceCard;
 
+/// How long we get to look at a spice blow we peeked at before it goes back on the deck.
+const PEEK_TIME: f32 = 2.0;
+
+/// Lift the spice blow we peeked at off the deck for a moment, then put it back. Nobody else is sent the peek, and
+/// the next event waits until the card is back, so the public reveal can't start while we are still looking.
+fn peek(
+    game_events: Res<GameEvents>,
+    object_entity: Res<ObjectEntityMap>,
+    my_id: Res<PlayerId>,
+    mut spice_cards: Query<(&Transform, &mut Lerper)>,
+    mut pause: ResMut<GameEventPauser>,
+) {
+    if let Some(GameEvent::ShowSpiceBlow { player_id, card_id }) = game_events.peek() {
+        if player_id != &*my_id {
+            return;
+        }
+        if let Some((transform, mut lerper)) = object_entity
+            .world
+            .get(card_id)
+            .and_then(|entity| spice_cards.get_mut(*entity).ok())
+        {
+            let on_deck = *transform;
+            lerper.push(Lerp::world_to_ui(
+                UITransform::from(vec2(0.6, 0.0)).with_rotation(Quat::from_rotation_x(PI / 2.0)),
+                0.3,
+                0.0,
+            ));
+            lerper.push(Lerp::ui_to_world(on_deck, 0.3, PEEK_TIME));
+            pause.pause_for(Duration::from_secs_f32(PEEK_TIME + 0.6));
+        }
+    }
+}
+
 fn reveal(
     mut commands: Commands,
     game_events: Res<GameEvents>,
@@ -42,8 +84,9 @@ fn reveal(
     object_entity: Res<ObjectEntityMap>,
     mut spice_cards: Query<&mut Lerper>,
     mut pause: ResMut<GameEventPauser>,
+    settings: Res<Settings>,
 ) {
-    if let Some(GameEvent::RevealSpiceBlow) = game_events.peek() {
+    if let Some(GameEvent::RevealSpiceBlow { .. }) = game_events.peek() {
         let entity = object_entity.world[&game_state.spice_card.as_ref().unwrap().id];
         if let Ok(mut lerper) = spice_cards.get_mut(entity) {
             lerper.push(Lerp::ui_to(
@@ -51,7 +94,10 @@ fn reveal(
                 0.1,
                 0.0,
             ));
-            pause.pause_for(Duration::from_secs(3));
+            // Give everyone a moment to read the card before the spice or the worm appears
+            if !settings.skip_spice_blow_delay {
+                pause.pause_for(Duration::from_secs(3));
+            }
             commands.entity(entity).insert(RevealedSpiceCard);
         }
     }
@@ -75,3 +121,84 @@ fn place_spice(
         }
     }
 }
+
+fn ride_worm_input(
+    game_state: Res<GameState>,
+    mut picked_events: EventReader<PickedEvent<LocationSector>>,
+    mut client: GameClient,
+    my_id: Res<PlayerId>,
+) {
+    if let Some(Prompt::RideWorm { location }) = game_state.prompts.get(&my_id) {
+        // Pick a sector to ride to. Staying put is handled by the shortcuts.
+        if let Some(PickedEvent { inner, .. }) = picked_events.iter().last() {
+            client.send_event(GameEvent::RideTheWorm {
+                player_id: *my_id,
+                from: *location,
+                to: Some(*inner),
+            });
+        }
+    }
+}
+
+fn ride_worm(
+    game_events: Res<GameEvents>,
+    game_state: Res<GameState>,
+    data: Res<Data>,
+    object_entity: Res<ObjectEntityMap>,
+    mut troops: Query<&mut Lerper, With<Troop>>,
+) {
+    if let Some(GameEvent::RideTheWorm {
+        player_id,
+        from,
+        to: Some(to),
+    }) = game_events.peek()
+    {
+        let idx = game_state
+            .board
+            .get(&to.location)
+            .and_then(|location| location.sectors.get(&to.sector))
+            .map_or(0, |sector| sector.forces.len());
+        // There are only so many places for stacks, so any more share the last one
+        let location_data = &data.locations[&to.location];
+        let node = location_data.sectors[&to.sector]
+            .fighters
+            .get(idx)
+            .or_else(|| location_data.sectors[&to.sector].fighters.last())
+            .copied()
+            .unwrap_or(location_data.center);
+        if let Some(location) = game_state.board.get(from) {
+            for force in location
+                .sectors
+                .values()
+                .filter_map(|sector| sector.forces.get(player_id))
+                .flat_map(|forces| forces.forces.iter())
+            {
+                if let Some(mut lerper) = object_entity.world.get(&force.id).and_then(|e| troops.get_mut(*e).ok()) {
+                    // TODO: stack
+                    lerper.replace(Lerp::world_to(
+                        Transform::from_translation(Vec3::new(node.x, node.z, -node.y)),
+                        0.1,
+                        0.0,
+                    ));
+                }
+            }
+        }
+    }
+}
+
+/// Ask to look at the next spice blow. Its face is shown on top of the deck, to us alone.
+fn peek_input(
+    game_state: Res<GameState>,
+    data: Res<Data>,
+    settings: Res<Settings>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut client: GameClient,
+    my_id: Res<PlayerId>,
+) {
+    if keyboard_input.just_pressed(settings.key_bindings.peek_spice_blow) {
+        let event = GameEvent::PeekSpiceBlow { player_id: *my_id };
+        if game_state.validate(&data, &event) {
+            client.send_event(event);
+        }
+    }
+}
@@ -5,16 +5,20 @@ use bevy::{
     prelude::*,
 };
 use derive_more::Display;
+use iyes_loopless::prelude::IntoConditionalSystem;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    components::StormCard,
+    components::{Location, LocationSector, StormCard},
     game::{
-        state::{GameEvent, GameState},
+        state::{DeckType, GameEvent, GameState},
         GameEventPauser, GameEventStage, ObjectEntityMap,
     },
     lerper::{Lerp, Lerper, UITransform},
     network::GameEvents,
+    palette::storm_preview_color,
+    settings::Settings,
+    Screen,
 };
 
 pub struct StormPlugin;
@@ -22,7 +26,9 @@ pub struct StormPlugin;
 impl Plugin for StormPlugin {
     fn build(&self, app: &mut App) {
         app.add_system_to_stage(GameEventStage, reveal)
-            .add_system_to_stage(GameEventStage, move_storm);
+            .add_system_to_stage(GameEventStage, discard_storm)
+            .add_system_to_stage(GameEventStage, reshuffle)
+            .add_system(storm_preview.run_in_state(Screen::Game));
     }
 }
 
@@ -34,6 +40,10 @@ pub enum StormPhase {
     MoveStorm,
 }
 
+/// Sectors which are tinted because the storm is about to sweep through them.
+#[derive(Component)]
+pub struct StormPreview;
+
 fn reveal(
     game_events: Res<GameEvents>,
     game_state: Res<GameState>,
@@ -41,7 +51,7 @@ fn reveal(
     mut storm_cards: Query<&mut Lerper>,
     mut pause: ResMut<GameEventPauser>,
 ) {
-    if let Some(GameEvent::RevealStorm) = game_events.peek() {
+    if let Some(GameEvent::RevealStorm { .. }) = game_events.peek() {
         if let Ok(mut lerper) = storm_cards.get_mut(object_entity.world[&game_state.storm_card.as_ref().unwrap().id]) {
             lerper.push(Lerp::ui_to(
                 UITransform::from(vec2(0.0, 0.0)).with_rotation(Quat::from_rotation_x(PI / 2.0)),
@@ -53,16 +63,88 @@ fn reveal(
     }
 }
 
-fn move_storm(game_events: Res<GameEvents>, mut storm_cards: Query<&mut Lerper, With<StormCard>>) {
-    if let Some(GameEvent::MoveStorm { sectors }) = game_events.peek() {
+/// Put the storm card on the discard pile once it has been used, or cancelled by Weather Control.
+fn discard_storm(
+    game_events: Res<GameEvents>,
+    game_state: Res<GameState>,
+    object_entity: Res<ObjectEntityMap>,
+    mut storm_cards: Query<&mut Lerper, With<StormCard>>,
+) {
+    if let Some(GameEvent::MoveStorm { .. } | GameEvent::PlayCard { .. }) = game_events.peek() {
         // TODO move storm
-        for mut lerper in storm_cards.iter_mut() {
-            // TODO: shuffle
-            lerper.push(Lerp::world_to(
-                Transform::from_translation(vec3(1.5, 0.0049, 0.87)),
-                0.1,
-                0.0,
-            ));
+        let discards = &game_state.decks.storm.discard_order;
+        if let Some(id) = discards.last() {
+            if let Ok(mut lerper) = storm_cards.get_mut(object_entity.world[id]) {
+                lerper.push(Lerp::world_to(
+                    Transform::from_translation(vec3(1.5, 0.0049 + 0.001 * discards.len() as f32, 0.87)),
+                    0.1,
+                    0.0,
+                ));
+            }
+        }
+    }
+}
+
+/// Gather the discarded storm cards back into the deck once it runs out.
+fn reshuffle(
+    game_events: Res<GameEvents>,
+    game_state: Res<GameState>,
+    object_entity: Res<ObjectEntityMap>,
+    mut storm_cards: Query<&mut Lerper, With<StormCard>>,
+) {
+    if let Some(GameEvent::SetDeckOrder {
+        deck_type: DeckType::Storm,
+        ..
+    }) = game_events.peek()
+    {
+        for (i, id) in game_state.decks.storm.card_order.iter().enumerate() {
+            if let Ok(mut lerper) = storm_cards.get_mut(object_entity.world[id]) {
+                lerper.push(Lerp::world_to(
+                    Transform::from_translation(vec3(1.23, 0.0049 + 0.001 * i as f32, 0.87))
+                        * Transform::from_rotation(Quat::from_rotation_z(PI)),
+                    0.1,
+                    0.0,
+                ));
+            }
+        }
+    }
+}
+
+/// Tint the sectors the storm is about to sweep through, once we know how far it will move, so everyone can see what
+/// is at risk.
+fn storm_preview(
+    mut commands: Commands,
+    game_state: Res<GameState>,
+    settings: Res<Settings>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    sectors: Query<(
+        Entity,
+        &LocationSector,
+        &Handle<StandardMaterial>,
+        Option<&StormPreview>,
+    )>,
+) {
+    if !game_state.is_changed() && !settings.is_changed() {
+        return;
+    }
+    let path = game_state.storm_path();
+    for (entity, sector, material, previewed) in sectors.iter() {
+        let at_risk = sector.location != Location::PolarSink && path.contains(&sector.sector);
+        // The palette may have changed under sectors which are already tinted
+        if at_risk == previewed.is_some() && !(at_risk && settings.is_changed()) {
+            continue;
+        }
+        if let Some(material) = materials.get_mut(material) {
+            material.base_color = if at_risk {
+                storm_preview_color(settings.colorblind)
+            } else {
+                Color::rgba(1.0, 1.0, 1.0, 0.0)
+            };
+        }
+        if at_risk {
+            commands.entity(entity).insert(StormPreview);
+        } else {
+            commands.entity(entity).remove::<StormPreview>();
         }
     }
 }
@@ -0,0 +1,328 @@
+use bevy::prelude::*;
+use iyes_loopless::prelude::{AppLooplessStateExt, IntoConditionalSystem};
+
+use super::{
+    bot::needs_action,
+    phase::{battle, setup, Phase},
+    state::{GameEvent, GameState, KaramaEffect, PlayerId, Prompt},
+    GameEventStage, ObjectEntityMap,
+};
+use crate::{
+    data::Data,
+    lerper::{Lerp, Lerper},
+    network::{GameClient, GameEvents},
+    Screen,
+};
+
+pub struct PromptPlugin;
+
+impl Plugin for PromptPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<PromptCancelled>()
+            .add_system_to_stage(GameEventStage, cancel_stale_prompt);
+
+        app.add_enter_system(Screen::Game, init_prompt_text)
+            .add_system(handle_prompt.run_in_state(Screen::Game))
+            .add_system(prompt_text.run_in_state(Screen::Game))
+            .add_system(continue_button.run_in_state(Screen::Game));
+    }
+}
+
+/// Part of the UI for the prompt we are answering, which is removed once the prompt is.
+#[derive(Component)]
+pub struct PromptUi;
+
+/// Sent when the game moves on while we are still deciding something, such as when the phase is advanced without us.
+pub struct PromptCancelled;
+
+/// Notice when the phase moves on, or someone else is made active, while we were still being waited on. The UI for the
+/// prompt goes with the prompt itself, but the camera is brought back to the board and anything we were part way
+/// through choosing is thrown away.
+fn cancel_stale_prompt(
+    game_events: Res<GameEvents>,
+    game_state: Res<GameState>,
+    data: Res<Data>,
+    my_id: Res<PlayerId>,
+    mut was_waiting: Local<bool>,
+    mut camera: Query<&mut Lerper, With<Camera>>,
+    mut cancelled: EventWriter<PromptCancelled>,
+) {
+    let waiting = needs_action(&game_state, *my_id);
+    if let Some(GameEvent::AdvancePhase | GameEvent::SetActive { .. }) = game_events.peek() {
+        if *was_waiting && !waiting {
+            cancelled.send(PromptCancelled);
+            if let Some(mut lerper) = camera.iter_mut().next() {
+                lerper.replace(Lerp::move_camera(data.camera_nodes.main, 1.0));
+            }
+        }
+    }
+    *was_waiting = waiting;
+}
+
+/// Forget a choice we were in the middle of making once the prompt it was for has been cancelled.
+pub fn reset_on_cancel<R: Default + Send + Sync + 'static>(
+    mut cancelled: EventReader<PromptCancelled>,
+    mut choice: ResMut<R>,
+) {
+    if cancelled.iter().count() > 0 {
+        *choice = R::default();
+    }
+}
+
+/// A line at the top of the screen saying what we are being asked to decide.
+#[derive(Component)]
+struct PromptText;
+
+/// Show the UI for whatever we are being asked to decide, replacing the UI for anything we were asked before. A prompt
+/// which is still pending when the phase changes is shown again from scratch.
+fn handle_prompt(
+    mut commands: Commands,
+    game_state: Res<GameState>,
+    data: Res<Data>,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    object_entity: Res<ObjectEntityMap>,
+    mut lerpers: Query<&mut Lerper>,
+    prompt_ui: Query<Entity, With<PromptUi>>,
+    my_id: Res<PlayerId>,
+    mut shown: Local<Option<(Prompt, Phase)>>,
+) {
+    if !game_state.is_changed() {
+        return;
+    }
+    let current = game_state
+        .prompts
+        .get(&my_id)
+        .map(|prompt| (prompt.clone(), game_state.phase));
+    if *shown == current {
+        return;
+    }
+    // TODO: animate them away~
+    for entity in prompt_ui.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    if let Some((prompt, _)) = &current {
+        match prompt {
+            Prompt::Faction { remaining } => {
+                setup::show_faction_choices(&mut commands, &asset_server, &mut materials, &data, remaining)
+            }
+            Prompt::FactionPrediction => {
+                setup::show_faction_predictions(&mut commands, &asset_server, &mut materials, &data, &game_state)
+            }
+            Prompt::TurnPrediction => setup::show_turn_predictions(&mut commands, &asset_server, &mut materials, &data),
+            Prompt::Traitor => setup::show_traitors(&game_state, *my_id, &object_entity, &mut lerpers),
+            Prompt::Continue => show_continue(&mut commands, &asset_server),
+            Prompt::BattlePlan { .. } => {
+                battle::show_battle_wheel(&mut commands, &asset_server, &mut materials, *my_id)
+            }
+            // Everything else is answered on the board or with the shortcuts, guided by the prompt text
+            _ => (),
+        }
+    }
+    *shown = current;
+}
+
+/// The button to press once we're ready for the next turn.
+#[derive(Component)]
+struct ContinueButton;
+
+fn show_continue(commands: &mut Commands, asset_server: &AssetServer) {
+    commands
+        .spawn_bundle(ButtonBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    top: Val::Px(40.0),
+                    left: Val::Percent(45.0),
+                    ..default()
+                },
+                size: Size::new(Val::Percent(10.0), Val::Px(40.0)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            color: Color::rgb(0.15, 0.15, 0.15).into(),
+            ..default()
+        })
+        .insert_bundle((PromptUi, ContinueButton))
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle::from_section(
+                "Continue",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                    font_size: 20.0,
+                    color: Color::ANTIQUE_WHITE,
+                },
+            ));
+        });
+}
+
+fn continue_button(
+    mut client: GameClient,
+    my_id: Res<PlayerId>,
+    mut interactions: Query<(&Interaction, &mut UiColor), (With<ContinueButton>, Changed<Interaction>)>,
+) {
+    for (interaction, mut color) in interactions.iter_mut() {
+        match interaction {
+            Interaction::Clicked => client.send_event(GameEvent::Decline { player_id: *my_id }),
+            Interaction::Hovered => *color = Color::rgb(0.25, 0.25, 0.25).into(),
+            Interaction::None => *color = Color::rgb(0.15, 0.15, 0.15).into(),
+        }
+    }
+}
+
+/// What a prompt is asking of us.
+fn describe(prompt: &Prompt, game_state: &GameState, data: &Data) -> String {
+    let faction = |player_id: PlayerId| {
+        game_state
+            .players
+            .get(&player_id)
+            .map_or_else(String::new, |player| data.factions[&player.faction].name.clone())
+    };
+    match prompt {
+        Prompt::Faction { .. } => "Choose a faction".to_string(),
+        Prompt::Traitor => "Choose a traitor to keep".to_string(),
+        Prompt::FactionPrediction => "Predict which faction will win".to_string(),
+        Prompt::TurnPrediction => "Predict which turn they will win on".to_string(),
+        Prompt::GuildShip => "Ship now, or wait".to_string(),
+        Prompt::Bid => "Bid on the card, or pass".to_string(),
+        Prompt::RideWorm { location } => format!("Ride the worm out of {}, or stay", data.locations[location].name),
+        Prompt::PlayCard { kind } => format!("Play {}, or decline", data.treachery_cards[kind].name),
+        Prompt::Karama {
+            effect: KaramaEffect::BlockShipment { target },
+        } => format!(
+            "Play a Karama to stop {} shipping this turn, or decline",
+            faction(*target)
+        ),
+        Prompt::AdvisorOrFighter { location } => format!(
+            "Keep your forces in {} as advisors, or flip them to fighters",
+            data.locations[&location.location].name
+        ),
+        Prompt::AdvisorDecision { location } => format!(
+            "Someone shipped into {}. Keep your advisors there, or flip them to fighters and fight for it",
+            data.locations[&location.location].name
+        ),
+        Prompt::BattleOrder { .. } => "Choose the order of your battles".to_string(),
+        Prompt::BattlePlan { battle } => format!(
+            "Plan the battle in {} between {} and {}",
+            data.locations[&battle.location].name,
+            faction(battle.aggressor),
+            faction(battle.defender)
+        ),
+        Prompt::Voice { battle } => format!(
+            "Use the Voice on your opponent in {}",
+            data.locations[&battle.location].name
+        ),
+        Prompt::Prescience { battle } => format!(
+            "Ask to see part of your opponent's plan in {}",
+            data.locations[&battle.location].name
+        ),
+        Prompt::CallTraitor { battle } => format!(
+            "Call a traitor in the battle in {}, or decline",
+            data.locations[&battle.location].name
+        ),
+        Prompt::Charity => "Claim CHOAM charity".to_string(),
+        Prompt::Revival => "Choose forces and leaders to revive".to_string(),
+        Prompt::Discard { count } => format!("Discard {} treachery card(s)", count),
+        Prompt::Continue => {
+            let (ready, waiting) = game_state
+                .response_window
+                .as_ref()
+                .map_or((0, 0), |window| (window.responded.len(), window.eligible.len()));
+            format!("Review the turn, then continue ({} of {} ready)", ready, waiting)
+        }
+    }
+}
+
+fn init_prompt_text(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    top: Val::Px(5.0),
+                    left: Val::Percent(40.0),
+                    ..default()
+                },
+                ..default()
+            },
+            text: Text::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                    font_size: 24.0,
+                    color: Color::WHITE,
+                },
+            ),
+            ..default()
+        })
+        .insert(PromptText);
+}
+
+fn prompt_text(
+    game_state: Res<GameState>,
+    data: Res<Data>,
+    my_id: Res<PlayerId>,
+    mut text: Query<&mut Text, With<PromptText>>,
+) {
+    if !game_state.is_changed() {
+        return;
+    }
+    let description = game_state
+        .prompts
+        .get(&my_id)
+        .map_or_else(String::new, |prompt| describe(prompt, &game_state, &data));
+    for mut text in text.iter_mut() {
+        if text.sections[0].value != description {
+            text.sections[0].value = description.clone();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{components::Location, game::phase::movement::MovePlan};
+
+    #[test]
+    fn a_choice_is_forgotten_when_the_game_moves_on_without_us() {
+        let me = PlayerId(1);
+        let mut game_state = GameState::default();
+        game_state.active_player = Some(me);
+        let mut app = App::new();
+        app.add_event::<PromptCancelled>()
+            .insert_resource(game_state)
+            .insert_resource(Data::default())
+            .insert_resource(me)
+            .init_resource::<GameEvents>()
+            .init_resource::<MovePlan>()
+            .add_system(cancel_stale_prompt)
+            .add_system(reset_on_cancel::<MovePlan>.after(cancel_stale_prompt));
+        app.update();
+
+        let plan =
+            |app: &mut App| app.world.resource_mut::<MovePlan>().from = Some(Location::HaggaBasin.with_sector(11));
+        let planned = |app: &App| app.world.resource::<MovePlan>().from.is_some();
+        let next = |app: &mut App, event: GameEvent, active: Option<PlayerId>| {
+            app.world.resource_mut::<GameState>().active_player = active;
+            let mut game_events = app.world.resource_mut::<GameEvents>();
+            game_events.next();
+            game_events.push(event);
+            app.update();
+        };
+
+        // Still being waited on, so whatever we were choosing is kept
+        plan(&mut app);
+        next(&mut app, GameEvent::SetActive { player_id: me }, Some(me));
+        assert!(planned(&app));
+
+        // The phase is advanced while we were still choosing
+        next(&mut app, GameEvent::AdvancePhase, None);
+        assert!(!planned(&app));
+
+        // Once we weren't being waited on, there is nothing to cancel
+        plan(&mut app);
+        next(&mut app, GameEvent::AdvancePhase, None);
+        assert!(planned(&app));
+    }
+}
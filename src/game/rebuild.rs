@@ -0,0 +1,301 @@
+use std::{collections::HashSet, f32::consts::PI};
+
+use bevy::{ecs::entity::Entities, math::vec2, prelude::*};
+use iyes_loopless::prelude::{ConditionHelpers, IntoConditionalSystem};
+
+use super::{
+    discard_transform, hand_slots, spawn_visual,
+    spice::{spawn_bribe_stash, spawn_stash, BribeSpice},
+    state::{DeckType, GameState, PlayerId, SpawnType},
+    troop_rotation, HandSlot, ObjectEntityMap, ObjectId,
+};
+use crate::{
+    components::Spice,
+    data::Data,
+    lerper::{Lerp, Lerper, UITransform},
+    network::SceneOutOfDate,
+    util::bid_positions,
+    Screen,
+};
+
+pub struct RebuildPlugin;
+
+impl Plugin for RebuildPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(
+            rebuild_scene_from_state
+                .run_in_state(Screen::Game)
+                .run_if_resource_exists::<SceneOutOfDate>(),
+        )
+        .add_system(prune_object_entities.run_in_state(Screen::Game));
+    }
+}
+
+/// Every object in the game, in the form it would have been spawned in.
+fn objects(game_state: &GameState) -> Vec<SpawnType> {
+    let mut objects = Vec::new();
+    for (&player_id, player) in game_state.players.iter() {
+        let leaders = player.living_leaders.keys().chain(player.tanks.leaders.iter());
+        objects.extend(leaders.map(|leader| SpawnType::Leader {
+            player_id,
+            leader: leader.clone(),
+        }));
+        let on_board = game_state
+            .board
+            .values()
+            .flat_map(|location| location.sectors.values())
+            .filter_map(|sector| sector.forces.get(&player_id))
+            .flat_map(|forces| forces.forces.iter());
+        let forces = player
+            .offworld_forces
+            .iter()
+            .chain(player.tanks.forces.iter())
+            .chain(on_board);
+        objects.extend(forces.map(|unit| SpawnType::Troop {
+            player_id,
+            unit: unit.clone(),
+        }));
+        objects.extend(player.traitor_cards.iter().cloned().map(SpawnType::TraitorCard));
+        objects.extend(player.treachery_cards.iter().cloned().map(SpawnType::TreacheryCard));
+    }
+    objects.extend(
+        game_state
+            .board
+            .iter()
+            .filter_map(|(&location, location_state)| location_state.worm.map(|id| SpawnType::Worm { location, id })),
+    );
+    let decks = &game_state.decks;
+    objects.extend(decks.traitor.iter().cloned().map(SpawnType::TraitorCard));
+    objects.extend(
+        decks
+            .treachery
+            .iter()
+            .chain(game_state.bidding_cards.iter().map(|bid_state| &bid_state.card))
+            .cloned()
+            .map(SpawnType::TreacheryCard),
+    );
+    objects.extend(
+        decks
+            .spice
+            .iter()
+            .chain(game_state.spice_card.iter())
+            .cloned()
+            .map(SpawnType::SpiceCard),
+    );
+    objects.extend(
+        decks
+            .storm
+            .iter()
+            .chain(game_state.storm_card.iter())
+            .cloned()
+            .map(SpawnType::StormCard),
+    );
+    objects
+}
+
+/// Forget any entity whose object the game state no longer has, or which has been despawned without being forgotten,
+/// so that nothing looks up an entity which isn't there any more.
+fn prune_object_entities(
+    mut commands: Commands,
+    game_state: Res<GameState>,
+    entities: &Entities,
+    mut object_entity: ResMut<ObjectEntityMap>,
+) {
+    if !game_state.is_changed() {
+        return;
+    }
+    let live = objects(&game_state).iter().map(SpawnType::id).collect::<HashSet<_>>();
+    object_entity.world.retain(|id, entity| {
+        if !entities.contains(*entity) {
+            return false;
+        }
+        if !live.contains(id) {
+            commands.entity(*entity).despawn_recursive();
+            return false;
+        }
+        true
+    });
+}
+
+/// Where an object belongs, if it isn't where it would have been spawned.
+fn placement(game_state: &GameState, data: &Data, my_id: PlayerId, id: ObjectId) -> Option<Lerp> {
+    // Forces on the board
+    for (location, location_state) in game_state.board.iter() {
+        for (sector, sector_state) in location_state.sectors.iter() {
+            for (i, forces) in sector_state.forces.values().enumerate() {
+                if let Some(stack) = forces.forces.iter().position(|force| force.id == id) {
+                    let node = data.locations[location].sectors[sector].fighters[i];
+                    return Some(Lerp::world_to(
+                        Transform::from_translation(Vec3::new(node.x, node.z + 0.0036 * stack as f32, -node.y))
+                            .with_rotation(troop_rotation(forces.advisors)),
+                        0.1,
+                        0.0,
+                    ));
+                }
+            }
+        }
+    }
+    // Cards in hand
+    for &player_id in game_state.players.keys() {
+        if let Some((_, slot)) = hand_slots(game_state, my_id, player_id)
+            .into_iter()
+            .find(|(card_id, _)| *card_id == id)
+        {
+            return Some(Lerp::ui_to(slot, 0.1, 0.0));
+        }
+    }
+    // Cards up for bid
+    let positions = bid_positions(game_state.bidding_cards.len());
+    if let Some(pos) = game_state
+        .bidding_cards
+        .iter()
+        .zip(positions)
+        .find_map(|(bid_state, pos)| (bid_state.card.id == id).then_some(pos))
+    {
+        return Some(Lerp::ui_to(
+            UITransform::from(pos).with_rotation(Quat::from_rotation_x(PI / 2.0) * Quat::from_rotation_z(PI)),
+            0.1,
+            0.0,
+        ));
+    }
+    // The storm card which has just been revealed
+    if game_state.storm_card.as_ref().map_or(false, |card| card.id == id) {
+        return Some(Lerp::ui_to(
+            UITransform::from(vec2(0.0, 0.0)).with_rotation(Quat::from_rotation_x(PI / 2.0)),
+            0.1,
+            0.0,
+        ));
+    }
+    // Discard piles, with the spice blow on top of its pile
+    let decks = &game_state.decks;
+    let discards = [
+        (DeckType::Traitor, &decks.traitor.discard_order),
+        (DeckType::Treachery, &decks.treachery.discard_order),
+        (DeckType::Spice, &decks.spice.discard_order),
+        (DeckType::Storm, &decks.storm.discard_order),
+    ];
+    for (deck_type, order) in discards {
+        if let Some(i) = order.iter().position(|card_id| *card_id == id) {
+            let transform = discard_transform(&deck_type);
+            return Some(Lerp::world_to(
+                transform.with_translation(transform.translation + Vec3::Y * 0.001 * i as f32),
+                0.1,
+                0.0,
+            ));
+        }
+    }
+    if game_state.spice_card.as_ref().map_or(false, |card| card.id == id) {
+        let transform = discard_transform(&DeckType::Spice);
+        return Some(Lerp::world_to(
+            transform
+                .with_translation(transform.translation + Vec3::Y * 0.001 * decks.spice.discard_order.len() as f32),
+            0.1,
+            0.0,
+        ));
+    }
+    None
+}
+
+/// Replace everything on screen with what the game state says is there, after catching up on a game in progress.
+fn rebuild_scene_from_state(
+    mut commands: Commands,
+    game_state: Res<GameState>,
+    data: Res<Data>,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut object_entity: ResMut<ObjectEntityMap>,
+    stash: Query<Entity, Or<(With<Spice>, With<BribeSpice>)>>,
+    my_id: Res<PlayerId>,
+) {
+    commands.remove_resource::<SceneOutOfDate>();
+
+    let ObjectEntityMap { world, ui } = &mut *object_entity;
+    for entity in world
+        .drain()
+        .chain(ui.drain())
+        .map(|(_, entity)| entity)
+        .chain(stash.iter())
+    {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    for spawn_type in objects(&game_state) {
+        if let Some((id, entity)) = spawn_visual(
+            &mut commands,
+            &asset_server,
+            &mut materials,
+            &data,
+            &game_state,
+            *my_id,
+            &spawn_type,
+        ) {
+            object_entity.world.insert(id, entity);
+            if let Some(lerp) = placement(&game_state, &data, *my_id, id) {
+                commands.entity(entity).insert(Lerper::from(lerp));
+            }
+        }
+    }
+
+    // Only our own hand can be inspected up close
+    for (id, slot) in hand_slots(&game_state, *my_id, *my_id) {
+        if let Some(entity) = object_entity.world.get(&id) {
+            commands.entity(*entity).insert(HandSlot(slot));
+        }
+    }
+
+    if let Some(player) = game_state.players.get(&my_id) {
+        spawn_stash(&mut commands, &asset_server, &mut materials, &data, player.spice);
+        spawn_bribe_stash(&mut commands, &asset_server, &mut materials, &data, player.bribe_spice);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        components::{TreacheryCard, TreacheryCardKind},
+        game::ObjectIdGenerator,
+    };
+
+    #[test]
+    fn entities_are_forgotten_once_their_objects_are_gone() {
+        let mut ids = ObjectIdGenerator::default();
+        let mut card = || {
+            ids.spawn(TreacheryCard {
+                kind: TreacheryCardKind::Karama,
+                variant: 0,
+            })
+        };
+        let (in_deck, discarded, despawned, gone) = (card(), card(), card(), card());
+        let mut game_state = GameState::default();
+        game_state.decks.treachery.add(in_deck.clone());
+        game_state.decks.treachery.add(despawned.clone());
+        game_state.decks.treachery.discard(discarded.clone());
+
+        let mut app = App::new();
+        app.insert_resource(game_state).add_system(prune_object_entities);
+        let mut object_entity = ObjectEntityMap::default();
+        for card in [&in_deck, &discarded, &despawned, &gone] {
+            object_entity.world.insert(card.id, app.world.spawn().id());
+        }
+        let (kept, stale, orphan) = (
+            object_entity.world[&in_deck.id],
+            object_entity.world[&despawned.id],
+            object_entity.world[&gone.id],
+        );
+        app.world.despawn(stale);
+        app.insert_resource(object_entity);
+        app.update();
+
+        let object_entity = app.world.resource::<ObjectEntityMap>();
+        let mut remembered = object_entity.world.keys().copied().collect::<Vec<_>>();
+        remembered.sort();
+        // Discarded cards are still on the table, in the discard pile
+        let mut expected = vec![in_deck.id, discarded.id];
+        expected.sort();
+        assert_eq!(remembered, expected);
+        assert_eq!(object_entity.world[&in_deck.id], kept);
+        // The entity of an object the game no longer has is despawned along with it
+        assert!(app.world.get_entity(orphan).is_none());
+    }
+}
@@ -0,0 +1,208 @@
+use bevy::prelude::*;
+use iyes_loopless::prelude::{AppLooplessStateExt, ConditionSet};
+
+use super::{driver::GameDriver, state::GameState};
+use crate::{
+    data::Data,
+    network::{EventLog, SceneOutOfDate},
+    settings::Settings,
+    Screen,
+};
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            ConditionSet::new()
+                .run_in_state(Screen::Game)
+                .run_if_resource_exists::<Replay>()
+                .with_system(replay_input)
+                .with_system(play_replay)
+                .with_system(timeline_input)
+                .with_system(show_step)
+                .into(),
+        )
+        .add_exit_system(Screen::Game, end_replay);
+    }
+}
+
+/// Seconds between each step while a replay is playing.
+const STEP_TIME: f32 = 0.5;
+
+/// A finished game which is being stepped through. Everything is shown, since there are no more secrets to keep.
+pub struct Replay {
+    /// How many events of the log have been applied.
+    step: usize,
+    playing: bool,
+    timer: Timer,
+}
+
+impl Replay {
+    fn set_step(&mut self, step: usize, log: &EventLog) {
+        self.step = step.min(log.events.len());
+    }
+}
+
+/// The bar which shows how far through the game the replay is, and can be clicked to jump to any point.
+#[derive(Component)]
+struct Timeline;
+
+#[derive(Component)]
+struct TimelineProgress;
+
+#[derive(Component)]
+struct TimelineLabel;
+
+/// Start stepping through a finished game, from its final state.
+pub fn start_replay(commands: &mut Commands, asset_server: &AssetServer, event_log: &EventLog) {
+    commands.insert_resource(Replay {
+        step: event_log.events.len(),
+        playing: false,
+        timer: Timer::from_seconds(STEP_TIME, true),
+    });
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    bottom: Val::Px(40.0),
+                    left: Val::Percent(20.0),
+                    ..default()
+                },
+                size: Size::new(Val::Percent(60.0), Val::Px(12.0)),
+                ..default()
+            },
+            color: Color::DARK_GRAY.into(),
+            ..default()
+        })
+        .insert(Interaction::default())
+        .insert(Timeline)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                        ..default()
+                    },
+                    color: Color::ORANGE.into(),
+                    ..default()
+                })
+                .insert(TimelineProgress);
+        });
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    bottom: Val::Px(56.0),
+                    left: Val::Percent(20.0),
+                    ..default()
+                },
+                ..default()
+            },
+            text: Text::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                    font_size: 20.0,
+                    color: Color::WHITE,
+                },
+            ),
+            ..default()
+        })
+        .insert(TimelineLabel);
+}
+
+fn replay_input(
+    settings: Res<Settings>,
+    keyboard_input: Res<Input<KeyCode>>,
+    event_log: Res<EventLog>,
+    mut replay: ResMut<Replay>,
+) {
+    let bindings = &settings.key_bindings;
+    if keyboard_input.just_pressed(bindings.replay_back) {
+        replay.playing = false;
+        let step = replay.step.saturating_sub(1);
+        replay.set_step(step, &event_log);
+    }
+    if keyboard_input.just_pressed(bindings.replay_forward) {
+        replay.playing = false;
+        let step = replay.step + 1;
+        replay.set_step(step, &event_log);
+    }
+    if keyboard_input.just_pressed(bindings.replay_play) {
+        replay.playing = !replay.playing;
+        // Playing from the end starts over
+        if replay.playing && replay.step == event_log.events.len() {
+            replay.step = 0;
+        }
+    }
+}
+
+fn play_replay(time: Res<Time>, event_log: Res<EventLog>, mut replay: ResMut<Replay>) {
+    if !replay.playing || !replay.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    let step = replay.step + 1;
+    replay.set_step(step, &event_log);
+    if replay.step == event_log.events.len() {
+        replay.playing = false;
+    }
+}
+
+/// Jump to the point in the game under the cursor when the timeline is clicked.
+fn timeline_input(
+    windows: Res<Windows>,
+    event_log: Res<EventLog>,
+    timeline: Query<(&Interaction, &Node, &GlobalTransform), (With<Timeline>, Changed<Interaction>)>,
+    mut replay: ResMut<Replay>,
+) {
+    for (interaction, node, transform) in timeline.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+        if let Some(cursor) = windows.get_primary().and_then(|window| window.cursor_position()) {
+            let left = transform.translation().x - node.size.x / 2.0;
+            let fraction = ((cursor.x - left) / node.size.x).clamp(0.0, 1.0);
+            replay.playing = false;
+            let step = (fraction * event_log.events.len() as f32).round() as usize;
+            replay.set_step(step, &event_log);
+        }
+    }
+}
+
+/// Rebuild the game as it was at the current step of the replay.
+fn show_step(
+    mut commands: Commands,
+    replay: Res<Replay>,
+    data: Res<Data>,
+    event_log: Res<EventLog>,
+    mut game_state: ResMut<GameState>,
+    mut progress: Query<&mut Style, With<TimelineProgress>>,
+    mut label: Query<&mut Text, With<TimelineLabel>>,
+) {
+    if !replay.is_changed() {
+        return;
+    }
+    let total = event_log.events.len().max(1);
+    for mut style in progress.iter_mut() {
+        style.size.width = Val::Percent(100.0 * replay.step as f32 / total as f32);
+    }
+    for mut text in label.iter_mut() {
+        text.sections[0].value = format!("Replay: event {} of {}", replay.step, event_log.events.len());
+    }
+    // The game is already in its final state when the replay starts
+    if replay.is_added() {
+        return;
+    }
+    *game_state = GameDriver::replay(&data, &event_log.events[..replay.step]);
+    commands.insert_resource(SceneOutOfDate);
+}
+
+fn end_replay(mut commands: Commands, timeline: Query<Entity, Or<(With<Timeline>, With<TimelineLabel>)>>) {
+    commands.remove_resource::<Replay>();
+    for entity in timeline.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
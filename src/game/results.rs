@@ -0,0 +1,242 @@
+use bevy::prelude::*;
+use iyes_loopless::prelude::{IntoConditionalSystem, NextState};
+use renet::RenetClient;
+
+use super::{
+    replay::start_replay,
+    state::{Adjustment, EndGameReason, GameEvent, GameState, PlayerId},
+    GameEventStage,
+};
+use crate::{
+    data::Data,
+    network::{EventLog, GameEvents, RenetServer, SendEvent, ServerEvent},
+    Screen,
+};
+
+pub struct ResultsPlugin;
+
+impl Plugin for ResultsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_to_stage(GameEventStage, show_results)
+            .add_system(results_buttons.run_in_state(Screen::Game));
+    }
+}
+
+/// The summary shown once a game is over.
+#[derive(Component)]
+struct Results;
+
+#[derive(Component)]
+enum ResultsButton {
+    ReturnToLobby,
+    ViewReplay,
+    Rematch,
+}
+
+/// The name of a player's faction, even if they have since left the game.
+fn faction_name(game_state: &GameState, data: &Data, player_id: PlayerId) -> String {
+    game_state
+        .factions
+        .iter()
+        .find_map(|(faction, id)| (*id == player_id).then(|| data.factions[faction].name.clone()))
+        .unwrap_or_else(|| format!("Player {}", player_id))
+}
+
+/// Who won and why, followed by everything about the final state of the game worth knowing.
+fn summary(game_state: &GameState, data: &Data) -> String {
+    let name = |player_id: PlayerId| faction_name(game_state, data, player_id);
+    let mut s = match &game_state.end_reason {
+        Some(EndGameReason::Victory { winners }) => format!(
+            "{} won by controlling the strongholds!",
+            winners
+                .iter()
+                .map(|winner| name(*winner))
+                .collect::<Vec<_>>()
+                .join(" and ")
+        ),
+        Some(EndGameReason::Prediction { player_id }) => {
+            format!("{} won by predicting the outcome!", name(*player_id))
+        }
+        Some(EndGameReason::Conceded { player_id }) => format!("{} conceded the game.", name(*player_id)),
+        Some(EndGameReason::PlayerLeft { player_id }) => format!("{} left the game.", name(*player_id)),
+        None => "The game is over.".to_string(),
+    };
+
+    s += "\n\nStrongholds:";
+    let control = game_state.stronghold_control(data);
+    if control.is_empty() {
+        s += "\n  Nobody controls a stronghold";
+    }
+    for (location, player_id) in control {
+        s += format!("\n  {}: {}", data.locations[&location].name, name(player_id)).as_str();
+    }
+
+    s += "\n\nSpice:";
+    for player_id in game_state.play_order.iter() {
+        if let Some(player) = game_state.players.get(player_id) {
+            s += format!("\n  {}: {}", name(*player_id), player.spice + player.bribe_spice).as_str();
+        }
+    }
+
+    if game_state.rules.kanly {
+        s += "\n\nKanly:";
+        for player_id in game_state.play_order.iter() {
+            let killed = game_state.kanly.get(player_id).map_or_else(Vec::new, |leaders| {
+                leaders.iter().map(|leader| data.leaders[leader].name.clone()).collect()
+            });
+            s += format!("\n  {}: {} killed", name(*player_id), killed.len()).as_str();
+            if !killed.is_empty() {
+                s += format!(" ({})", killed.join(", ")).as_str();
+            }
+        }
+    }
+
+    if !game_state.adjustments.is_empty() {
+        s += "\n\nAdjusted by the host:";
+        for (player_id, adjustment) in game_state.adjustments.iter() {
+            let adjustment = match adjustment {
+                Adjustment::Spice(delta) => format!("{:+} spice", delta),
+                Adjustment::KillForces(count) => format!("{} forces sent to the tanks", count),
+                Adjustment::ReviveForces(count) => format!("{} forces revived", count),
+            };
+            s += format!("\n  {}: {}", name(*player_id), adjustment).as_str();
+        }
+    }
+
+    if let (Some(faction), Some(turn)) = (game_state.bg_predictions.faction, game_state.bg_predictions.turn) {
+        s += format!(
+            "\n\nThe Bene Gesserit predicted that {} would win on turn {}.",
+            data.factions[&faction].name, turn
+        )
+        .as_str();
+    }
+    s
+}
+
+fn show_results(
+    mut commands: Commands,
+    game_events: Res<GameEvents>,
+    game_state: Res<GameState>,
+    data: Res<Data>,
+    asset_server: Res<AssetServer>,
+    server: Option<Res<RenetServer>>,
+) {
+    if let Some(GameEvent::RevealAll { .. }) = game_events.peek() {
+        let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+        commands
+            .spawn_bundle(NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: UiRect {
+                        top: Val::Percent(20.0),
+                        left: Val::Percent(35.0),
+                        ..default()
+                    },
+                    size: Size::new(Val::Percent(30.0), Val::Percent(50.0)),
+                    padding: UiRect::all(Val::Px(10.0)),
+                    ..default()
+                },
+                color: Color::rgba(0.0, 0.0, 0.0, 0.8).into(),
+                ..default()
+            })
+            .insert(Results)
+            .with_children(|parent| {
+                parent.spawn_bundle(TextBundle::from_section(
+                    summary(&game_state, &data),
+                    TextStyle {
+                        font: font.clone(),
+                        font_size: 20.0,
+                        color: Color::WHITE,
+                    },
+                ));
+            });
+        commands
+            .spawn_bundle(NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: UiRect {
+                        top: Val::Percent(72.0),
+                        left: Val::Percent(35.0),
+                        ..default()
+                    },
+                    size: Size::new(Val::Percent(30.0), Val::Px(40.0)),
+                    justify_content: JustifyContent::SpaceBetween,
+                    ..default()
+                },
+                color: Color::NONE.into(),
+                ..default()
+            })
+            .insert(Results)
+            .with_children(|parent| {
+                let mut buttons = vec![
+                    ("Return to Lobby", ResultsButton::ReturnToLobby),
+                    ("View Replay", ResultsButton::ViewReplay),
+                ];
+                // Only the host can start everyone on another game
+                if server.is_some() {
+                    buttons.push(("Rematch", ResultsButton::Rematch));
+                }
+                let width = 90.0 / buttons.len() as f32;
+                for (label, action) in buttons {
+                    parent
+                        .spawn_bundle(ButtonBundle {
+                            style: Style {
+                                size: Size::new(Val::Percent(width), Val::Percent(100.0)),
+                                justify_content: JustifyContent::Center,
+                                align_items: AlignItems::Center,
+                                ..default()
+                            },
+                            color: Color::rgb(0.15, 0.15, 0.15).into(),
+                            ..default()
+                        })
+                        .insert(action)
+                        .with_children(|parent| {
+                            parent.spawn_bundle(TextBundle::from_section(
+                                label,
+                                TextStyle {
+                                    font: font.clone(),
+                                    font_size: 20.0,
+                                    color: Color::ANTIQUE_WHITE,
+                                },
+                            ));
+                        });
+                }
+            });
+    }
+}
+
+fn results_buttons(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    event_log: Res<EventLog>,
+    mut client: Option<ResMut<RenetClient>>,
+    mut interactions: Query<(&Interaction, &mut UiColor, &ResultsButton), Changed<Interaction>>,
+    results: Query<Entity, With<Results>>,
+) {
+    for (interaction, mut color, action) in interactions.iter_mut() {
+        match interaction {
+            Interaction::Clicked => match action {
+                ResultsButton::ReturnToLobby => {
+                    if let Some(client) = client.as_mut() {
+                        client.disconnect();
+                    }
+                    commands.remove_resource::<RenetClient>();
+                    commands.insert_resource(NextState(Screen::MainMenu));
+                }
+                ResultsButton::ViewReplay => {
+                    for entity in results.iter() {
+                        commands.entity(entity).despawn_recursive();
+                    }
+                    start_replay(&mut commands, &asset_server, &event_log);
+                }
+                ResultsButton::Rematch => {
+                    if let Some(client) = client.as_mut() {
+                        client.send_event(ServerEvent::Rematch);
+                    }
+                }
+            },
+            Interaction::Hovered => *color = Color::rgb(0.25, 0.25, 0.25).into(),
+            Interaction::None => *color = Color::rgb(0.15, 0.15, 0.15).into(),
+        }
+    }
+}
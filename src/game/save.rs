@@ -0,0 +1,257 @@
+use std::{
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::{
+    state::{GameEvent, GameState},
+    ObjectIdGenerator,
+};
+
+/// Bump this whenever a change to the game state would stop older saves from loading correctly.
+pub const SAVE_VERSION: u32 = 1;
+
+#[derive(Debug, Error)]
+pub enum SaveError {
+    #[error(transparent)]
+    IO(#[from] std::io::Error),
+    #[error(transparent)]
+    Serialization(#[from] ron::Error),
+    #[error(transparent)]
+    Deserialization(#[from] ron::de::SpannedError),
+    #[error("save was made with version {found}, but only version {expected} can be loaded")]
+    IncompatibleVersion { found: u32, expected: u32 },
+}
+
+/// Where state dumps for bug reports are written.
+const DUMP_DIR: &str = "dumps";
+
+/// Where the host's games are saved automatically at the start of each turn.
+const AUTOSAVE_DIR: &str = "autosaves";
+
+/// How many autosaves are kept before the oldest are deleted.
+pub const AUTOSAVES_KEPT: usize = 5;
+
+/// How many of the most recent events are included in a state dump.
+pub const DUMP_EVENTS: usize = 50;
+
+/// Just enough of a save to check whether the rest of it can be loaded.
+#[derive(Deserialize)]
+struct SaveHeader {
+    version: u32,
+}
+
+/// A game saved to disk, along with the version of the format it was saved in.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SaveFile {
+    pub version: u32,
+    pub state: GameState,
+    pub ids: ObjectIdGenerator,
+    /// The seed the game was played with. A resumed game draws from it again, so it plays out the same way each time
+    /// it is resumed from this save.
+    #[serde(default)]
+    pub seed: Option<u64>,
+}
+
+impl SaveFile {
+    pub fn new(state: GameState, ids: ObjectIdGenerator, seed: u64) -> Self {
+        Self {
+            version: SAVE_VERSION,
+            state,
+            ids,
+            seed: Some(seed),
+        }
+    }
+
+    pub fn write(&self, path: impl AsRef<Path>) -> Result<(), SaveError> {
+        std::fs::write(path, ron::ser::to_string(self)?)?;
+        Ok(())
+    }
+
+    pub fn read(path: impl AsRef<Path>) -> Result<Self, SaveError> {
+        let save = std::fs::read_to_string(path)?;
+        // Check the version before trying to make sense of the rest
+        let SaveHeader { version } = ron::from_str(&save)?;
+        if version != SAVE_VERSION {
+            return Err(SaveError::IncompatibleVersion {
+                found: version,
+                expected: SAVE_VERSION,
+            });
+        }
+        Ok(ron::from_str(&save)?)
+    }
+
+    /// Write the game as the newest autosave, deleting the oldest ones beyond those kept, and return where it was
+    /// written.
+    pub fn autosave(&self) -> Result<PathBuf, SaveError> {
+        self.autosave_in(AUTOSAVE_DIR)
+    }
+
+    /// Autosave into the given directory rather than the usual one.
+    fn autosave_in(&self, dir: impl AsRef<Path>) -> Result<PathBuf, SaveError> {
+        let dir = dir.as_ref();
+        let time = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        std::fs::create_dir_all(dir)?;
+        let path = dir.join(format!("{}-turn{}.ron", time, self.state.game_turn));
+        self.write(&path)?;
+        let autosaves = autosaves(dir)?;
+        for old in autosaves.iter().take(autosaves.len().saturating_sub(AUTOSAVES_KEPT)) {
+            std::fs::remove_file(old)?;
+        }
+        Ok(path)
+    }
+
+    /// The most recent autosave, if there are any.
+    pub fn latest_autosave() -> Option<PathBuf> {
+        autosaves(AUTOSAVE_DIR).ok()?.pop()
+    }
+}
+
+/// Every autosave in a directory, oldest first.
+fn autosaves(dir: impl AsRef<Path>) -> std::io::Result<Vec<PathBuf>> {
+    let mut autosaves = std::fs::read_dir(dir)?
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let modified = entry.metadata().and_then(|metadata| metadata.modified()).ok()?;
+            Some((modified, entry.path()))
+        })
+        .filter(|(_, path)| path.extension().map_or(false, |ext| ext == "ron"))
+        .collect::<Vec<_>>();
+    autosaves.sort();
+    Ok(autosaves.into_iter().map(|(_, path)| path).collect())
+}
+
+/// Everything worth knowing about a game which has gone wrong, for attaching to a bug report. Unlike a save, this is
+/// never loaded again, so it has no version.
+#[derive(Serialize)]
+pub struct StateDump<'a> {
+    pub state: &'a GameState,
+    /// The events leading up to the dump, oldest first.
+    pub recent_events: &'a [GameEvent],
+}
+
+impl<'a> StateDump<'a> {
+    pub fn new(state: &'a GameState, events: &'a [GameEvent]) -> Self {
+        Self {
+            state,
+            recent_events: &events[events.len().saturating_sub(DUMP_EVENTS)..],
+        }
+    }
+
+    /// Write the dump to a timestamped file, labelled with where it came from, and return where it was written.
+    pub fn write(&self, label: &str) -> Result<PathBuf, SaveError> {
+        let time = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        std::fs::create_dir_all(DUMP_DIR)?;
+        let path = Path::new(DUMP_DIR).join(format!("{}-{}.ron", label, time));
+        std::fs::write(&path, ron::ser::to_string_pretty(self, Default::default())?)?;
+        Ok(path)
+    }
+
+    /// A few lines describing the state at a glance.
+    pub fn summary(&self) -> String {
+        let mut s = format!(
+            "Turn {}, {} phase, {} players",
+            self.state.game_turn,
+            self.state.phase,
+            self.state.players.len()
+        );
+        if let Some(player_id) = self.state.active_player {
+            s += format!(", waiting on player {}", player_id).as_str();
+        }
+        for (player_id, player) in self.state.players.iter() {
+            s += format!("\n  Player {} ({}): {} spice", player_id, player.faction, player.spice).as_str();
+        }
+        if let Some(event) = self.recent_events.last() {
+            s += format!("\nLast event: {:?}", event).as_str();
+        }
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        components::Faction,
+        game::{driver::tests::TestGame, state::RuleSet},
+    };
+
+    /// A file of its own in the temp directory, so tests running at once don't share one.
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("dune-{}-{}.ron", name, std::process::id()))
+    }
+
+    fn save() -> SaveFile {
+        let state = GameState {
+            game_turn: 3,
+            storm_sector: 7,
+            ..Default::default()
+        };
+        SaveFile::new(state, ObjectIdGenerator::default(), 42)
+    }
+
+    #[test]
+    fn a_save_loads_back_the_same() {
+        let path = temp_path("round-trip");
+        let save = save();
+        save.write(&path).unwrap();
+        let loaded = SaveFile::read(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap(), save);
+    }
+
+    #[test]
+    fn saves_from_another_version_are_refused() {
+        let path = temp_path("old-version");
+        let save = SaveFile {
+            version: SAVE_VERSION + 1,
+            ..save()
+        };
+        save.write(&path).unwrap();
+        let loaded = SaveFile::read(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(
+            loaded,
+            Err(SaveError::IncompatibleVersion { found, expected })
+                if found == SAVE_VERSION + 1 && expected == SAVE_VERSION
+        ));
+    }
+
+    #[test]
+    fn garbage_is_refused_rather_than_loaded() {
+        let path = temp_path("garbage");
+        std::fs::write(&path, "not a save at all").unwrap();
+        let loaded = SaveFile::read(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(loaded, Err(SaveError::Deserialization(_))));
+    }
+
+    #[test]
+    fn autosaves_load_back_the_same_and_only_the_newest_are_kept() {
+        let dir = std::env::temp_dir().join(format!("dune-autosaves-{}", std::process::id()));
+        let game = TestGame::setup(&[Faction::Atreides, Faction::Harkonnen], RuleSet::default());
+        let mut written = Vec::new();
+        for turn in 1..=AUTOSAVES_KEPT as u8 + 2 {
+            let state = GameState {
+                game_turn: turn,
+                ..game.driver.state.clone()
+            };
+            let save = SaveFile::new(state, ObjectIdGenerator::default(), 42);
+            let path = save.autosave_in(&dir).unwrap();
+            assert_eq!(SaveFile::read(&path).unwrap(), save);
+            written.push(path);
+        }
+        let kept = autosaves(&dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(kept.unwrap(), written[written.len() - AUTOSAVES_KEPT..]);
+    }
+}
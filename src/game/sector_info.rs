@@ -0,0 +1,134 @@
+use bevy::prelude::*;
+use iyes_loopless::prelude::{AppLooplessStateExt, ConditionSet};
+
+use super::{state::GameState, PickedEvent};
+use crate::{
+    components::{Location, LocationSector},
+    data::Data,
+    settings::Settings,
+    Screen,
+};
+
+pub struct SectorInfoPlugin;
+
+impl Plugin for SectorInfoPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SelectedSector>()
+            .add_exit_system(Screen::Game, clear_selection)
+            .add_system_set(
+                ConditionSet::new()
+                    .run_in_state(Screen::Game)
+                    .with_system(select_sector)
+                    .with_system(sector_info)
+                    .into(),
+            );
+    }
+}
+
+/// The sector which was last clicked, whose details are shown until it is dismissed.
+#[derive(Default)]
+struct SelectedSector(Option<LocationSector>);
+
+/// The panel describing the selected sector.
+#[derive(Component)]
+struct SectorInfo;
+
+fn select_sector(
+    settings: Res<Settings>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut selected: ResMut<SelectedSector>,
+    mut picked_events: EventReader<PickedEvent<LocationSector>>,
+) {
+    if let Some(picked) = picked_events.iter().last() {
+        selected.0.replace(picked.inner);
+    } else if keyboard_input.just_pressed(settings.key_bindings.cancel) {
+        selected.0.take();
+    }
+}
+
+fn clear_selection(mut selected: ResMut<SelectedSector>) {
+    selected.0.take();
+}
+
+/// Everything worth knowing about a sector, as the game state has it.
+fn describe(game_state: &GameState, data: &Data, sector: &LocationSector) -> String {
+    let mut s = format!("{}, sector {}", data.locations[&sector.location].name, sector.sector);
+    if game_state.in_storm(sector) {
+        s += "\nIn the storm";
+    } else if sector.location != Location::PolarSink && game_state.storm_path().contains(&sector.sector) {
+        s += "\nThe storm will pass over it";
+    }
+    let sector_state = game_state
+        .board
+        .get(&sector.location)
+        .and_then(|location_state| location_state.sectors.get(&sector.sector));
+    s += format!("\nSpice: {}", sector_state.map_or(0, |sector_state| sector_state.spice)).as_str();
+    if let Some(sector_state) = sector_state {
+        for player_id in game_state.play_order.iter() {
+            if let Some(forces) = sector_state
+                .forces
+                .get(player_id)
+                .filter(|forces| !forces.forces.is_empty())
+            {
+                let special = forces.forces.iter().filter(|force| force.inner.is_special).count();
+                s += format!(
+                    "\n{}: {}",
+                    data.factions[&game_state.players[player_id].faction].name,
+                    forces.forces.len()
+                )
+                .as_str();
+                if special > 0 {
+                    s += format!(" ({} special)", special).as_str();
+                }
+                if forces.advisors {
+                    s += " advising";
+                }
+            }
+        }
+    }
+    s
+}
+
+fn sector_info(
+    mut commands: Commands,
+    selected: Res<SelectedSector>,
+    game_state: Res<GameState>,
+    data: Res<Data>,
+    asset_server: Res<AssetServer>,
+    panel: Query<Entity, With<SectorInfo>>,
+) {
+    if !selected.is_changed() && !game_state.is_changed() {
+        return;
+    }
+    for entity in panel.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    if let Some(sector) = selected.0.as_ref() {
+        commands
+            .spawn_bundle(NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: UiRect {
+                        bottom: Val::Px(10.0),
+                        right: Val::Px(10.0),
+                        ..default()
+                    },
+                    padding: UiRect::all(Val::Px(8.0)),
+                    ..default()
+                },
+                color: Color::rgba(0.0, 0.0, 0.0, 0.7).into(),
+                ..default()
+            })
+            .insert(SectorInfo)
+            .with_children(|parent| {
+                parent.spawn_bundle(TextBundle::from_section(
+                    describe(&game_state, &data, sector),
+                    TextStyle {
+                        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                        font_size: 18.0,
+                        color: Color::WHITE,
+                    },
+                ));
+            });
+    }
+}
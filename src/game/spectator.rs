@@ -0,0 +1,148 @@
+use bevy::prelude::*;
+use iyes_loopless::prelude::ConditionSet;
+
+use super::state::GameState;
+use crate::{data::Data, network::Spectator, settings::Settings, Screen};
+
+pub struct SpectatorPlugin;
+
+impl Plugin for SpectatorPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            ConditionSet::new()
+                .run_in_state(Screen::Game)
+                .with_system(toggle_director_view)
+                .with_system(director_view)
+                .into(),
+        );
+    }
+}
+
+/// The overlay showing spectators everything every player is hiding.
+#[derive(Component)]
+struct DirectorView;
+
+fn toggle_director_view(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut settings: ResMut<Settings>,
+    spectator: Option<Res<Spectator>>,
+) {
+    if spectator.is_some() && keyboard_input.just_pressed(settings.key_bindings.toggle_director_view) {
+        settings.show_director_view = !settings.show_director_view;
+    }
+}
+
+/// Everything each player is hiding, along with the Bene Gesserit prediction.
+fn hidden_information(game_state: &GameState, data: &Data) -> String {
+    let names = |mut names: Vec<String>| {
+        if names.is_empty() {
+            "none".to_string()
+        } else {
+            names.sort();
+            names.join(", ")
+        }
+    };
+    let mut s = "Director's view".to_string();
+    for player_id in game_state.play_order.iter() {
+        let player = match game_state.players.get(player_id) {
+            Some(player) => player,
+            None => continue,
+        };
+        s += format!(
+            "\n\n{} (player {}): {} spice",
+            data.factions[&player.faction].name, player_id, player.spice
+        )
+        .as_str();
+        if player.bribe_spice > 0 {
+            s += format!(", {} from bribes", player.bribe_spice).as_str();
+        }
+        let treachery = player
+            .treachery_cards
+            .iter()
+            .map(|card| data.treachery_cards[&card.inner.kind].name.clone())
+            .collect();
+        s += format!("\n  Treachery cards: {}", names(treachery)).as_str();
+        let traitors = player
+            .traitor_cards
+            .iter()
+            .map(|card| data.leaders[&card.inner.leader].name.clone())
+            .collect();
+        s += format!("\n  Traitors: {}", names(traitors)).as_str();
+        let leaders = player
+            .tanks
+            .leaders
+            .iter()
+            .map(|leader| data.leaders[&leader.inner].name.clone())
+            .collect();
+        let special = player
+            .tanks
+            .forces
+            .iter()
+            .filter(|force| force.inner.is_special)
+            .count();
+        s += format!(
+            "\n  Tanks: {} forces ({} special), leaders: {}",
+            player.tanks.forces.len(),
+            special,
+            names(leaders)
+        )
+        .as_str();
+    }
+    if let (Some(faction), Some(turn)) = (game_state.bg_predictions.faction, game_state.bg_predictions.turn) {
+        s += format!(
+            "\n\nThe Bene Gesserit predict that {} will win on turn {}.",
+            data.factions[&faction].name, turn
+        )
+        .as_str();
+    }
+    s
+}
+
+fn director_view(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    game_state: Res<GameState>,
+    data: Res<Data>,
+    asset_server: Res<AssetServer>,
+    spectator: Option<Res<Spectator>>,
+    mut view: Query<(Entity, &mut Text), With<DirectorView>>,
+) {
+    // Only spectators are sent everyone's secrets, so there is nothing to show anyone else
+    let shown = spectator.is_some() && settings.show_director_view;
+    if !shown {
+        for (entity, _) in view.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        return;
+    }
+    if !view.is_empty() && !game_state.is_changed() {
+        return;
+    }
+    let s = hidden_information(&game_state, &data);
+    if let Ok((_, mut text)) = view.get_single_mut() {
+        text.sections[0].value = s;
+        return;
+    }
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    top: Val::Px(60.0),
+                    left: Val::Px(5.0),
+                    ..default()
+                },
+                ..default()
+            },
+            text: Text::from_section(
+                s,
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                    font_size: 16.0,
+                    color: Color::WHITE,
+                },
+            ),
+            ..default()
+        })
+        .insert(DirectorView);
+}
@@ -0,0 +1,373 @@
+use std::time::Duration;
+
+use bevy::{
+    math::{vec2, vec3},
+    prelude::*,
+};
+use bevy_mod_picking::PickableBundle;
+use iyes_loopless::prelude::IntoConditionalSystem;
+
+use crate::{
+    components::Spice,
+    data::Data,
+    game::{
+        opponent_seat,
+        state::{GameEvent, GameState, PaymentReason, PlayerId},
+        GameEventPauser, GameEventStage,
+    },
+    lerper::{Lerp, Lerper, UITransform},
+    network::GameEvents,
+    util::{divide_spice, opponent_hand_positions},
+    Screen,
+};
+
+pub struct SpicePlugin;
+
+impl Plugin for SpicePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_to_stage(GameEventStage, pay_spice)
+            .add_system_to_stage(GameEventStage, restash)
+            .add_system_to_stage(GameEventStage, collect_bribes)
+            .add_system_to_stage(GameEventStage, collect_spice)
+            .add_system(land_spice.run_in_state(Screen::Game))
+            .add_system(fade_collection_summary.run_in_state(Screen::Game));
+    }
+}
+
+/// A spice token on its way between two players, which disappears once it arrives.
+#[derive(Component)]
+struct SpiceInFlight {
+    timer: Timer,
+}
+
+/// How far our bribe spice sits from the rest of our spice, so the two piles can't be mistaken for each other.
+const BRIBE_OFFSET: Vec3 = vec3(-0.15, 0.0, 0.0);
+
+/// A spice token in our pile of bribes, which can't be spent until next turn.
+#[derive(Component)]
+pub struct BribeSpice;
+
+/// A list of what everyone collected, shown for a little while after collection.
+#[derive(Component)]
+struct CollectionSummary {
+    timer: Timer,
+}
+
+/// Where spice comes from or goes to on screen.
+#[derive(Copy, Clone)]
+enum SpiceEndpoint {
+    World(Vec3),
+    UI(Vec2),
+}
+
+impl SpiceEndpoint {
+    fn of(game_state: &GameState, data: &Data, my_id: PlayerId, player_id: Option<PlayerId>) -> Self {
+        match player_id {
+            Some(player_id) if player_id == my_id => SpiceEndpoint::World(data.token_nodes.spice[0]),
+            Some(player_id) => {
+                let (seat, seats) = opponent_seat(game_state, my_id, player_id);
+                SpiceEndpoint::UI(opponent_hand_positions(seat, seats, 1)[0])
+            }
+            // The spice bank
+            None => SpiceEndpoint::UI(vec2(0.0, 1.2)),
+        }
+    }
+}
+
+fn spice_material(
+    asset_server: &AssetServer,
+    materials: &mut Assets<StandardMaterial>,
+    value: i32,
+) -> Handle<StandardMaterial> {
+    let texture = asset_server.get_handle(format!("tokens/spice_{}.png", value).as_str());
+    materials.add(StandardMaterial::from(texture))
+}
+
+/// The tokens which make up a pile of spice, largest first, along with which stack each belongs in.
+fn spice_tokens(spice: u8) -> impl Iterator<Item = (i32, usize)> {
+    let (tens, fives, twos, ones) = divide_spice(spice as i32);
+    std::iter::repeat((10, 0))
+        .take(tens as usize)
+        .chain(std::iter::repeat((5, 1)).take(fives as usize))
+        .chain(std::iter::repeat((2, 2)).take(twos as usize))
+        .chain(std::iter::repeat((1, 3)).take(ones as usize))
+}
+
+/// Spawn our pile of spice tokens in front of our shield.
+pub fn spawn_stash(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    materials: &mut Assets<StandardMaterial>,
+    data: &Data,
+    spice: u8,
+) {
+    let spice_token = asset_server.get_handle("spice_token.gltf#Mesh0/Primitive0");
+    let mut counts = [0; 4];
+    for (value, s) in spice_tokens(spice) {
+        commands
+            .spawn_bundle(SpatialBundle::from_transform(Transform::from_translation(
+                data.token_nodes.spice[s] + (counts[s] as f32 * 0.0036 * Vec3::Y),
+            )))
+            .insert_bundle(PickableBundle::default())
+            .insert(Spice { value })
+            .insert_bundle(PbrBundle {
+                mesh: spice_token.clone(),
+                material: spice_material(asset_server, materials, value),
+                ..Default::default()
+            });
+        counts[s] += 1;
+    }
+}
+
+/// Spawn our pile of bribes beside our spice. These can't be picked since they can't be spent yet.
+pub fn spawn_bribe_stash(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    materials: &mut Assets<StandardMaterial>,
+    data: &Data,
+    spice: u8,
+) {
+    let spice_token = asset_server.get_handle("spice_token.gltf#Mesh0/Primitive0");
+    let mut counts = [0; 4];
+    for (value, s) in spice_tokens(spice) {
+        commands
+            .spawn_bundle(PbrBundle {
+                mesh: spice_token.clone(),
+                material: spice_material(asset_server, materials, value),
+                transform: Transform::from_translation(
+                    data.token_nodes.spice[s] + BRIBE_OFFSET + (counts[s] as f32 * 0.0036 * Vec3::Y),
+                ),
+                ..Default::default()
+            })
+            .insert(BribeSpice);
+        counts[s] += 1;
+    }
+}
+
+fn pay_spice(
+    mut commands: Commands,
+    game_events: Res<GameEvents>,
+    game_state: Res<GameState>,
+    data: Res<Data>,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    my_id: Res<PlayerId>,
+    mut pause: ResMut<GameEventPauser>,
+) {
+    if let Some(GameEvent::PaySpice {
+        from,
+        to,
+        amount,
+        reason,
+    }) = game_events.peek()
+    {
+        let src = SpiceEndpoint::of(&game_state, &data, *my_id, Some(*from));
+        let dest = match SpiceEndpoint::of(&game_state, &data, *my_id, *to) {
+            SpiceEndpoint::World(dest) if *reason == PaymentReason::Bribe => SpiceEndpoint::World(dest + BRIBE_OFFSET),
+            dest => dest,
+        };
+        send_tokens(&mut commands, &asset_server, &mut materials, src, dest, *amount);
+        pause.pause_for(Duration::from_secs(1));
+    }
+}
+
+/// Send spice tokens flying from one place to another, where they disappear.
+fn send_tokens(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    materials: &mut Assets<StandardMaterial>,
+    src: SpiceEndpoint,
+    dest: SpiceEndpoint,
+    amount: u8,
+) {
+    let spice_token = asset_server.get_handle("spice_token.gltf#Mesh0/Primitive0");
+    for (i, (value, _)) in spice_tokens(amount).enumerate() {
+        let delay = 0.1 * i as f32;
+        let (transform, lerp) = match (src, dest) {
+            (SpiceEndpoint::World(src), SpiceEndpoint::World(dest)) => (
+                Transform::from_translation(src),
+                Lerp::world_to(Transform::from_translation(dest), 0.5, delay),
+            ),
+            (SpiceEndpoint::World(src), SpiceEndpoint::UI(dest)) => (
+                Transform::from_translation(src),
+                Lerp::world_to_ui(UITransform::from(dest).with_scale(0.5), 0.5, delay),
+            ),
+            (SpiceEndpoint::UI(src), SpiceEndpoint::World(dest)) => (
+                Transform::default(),
+                Lerp::ui_from_to_world(
+                    UITransform::from(src).with_scale(0.5),
+                    Transform::from_translation(dest),
+                    0.5,
+                    delay,
+                ),
+            ),
+            (SpiceEndpoint::UI(src), SpiceEndpoint::UI(dest)) => (
+                Transform::default(),
+                Lerp::ui_from_to(
+                    UITransform::from(src).with_scale(0.5),
+                    UITransform::from(dest).with_scale(0.5),
+                    0.5,
+                    delay,
+                ),
+            ),
+        };
+        commands
+            .spawn_bundle(PbrBundle {
+                mesh: spice_token.clone(),
+                material: spice_material(asset_server, materials, value),
+                transform,
+                ..Default::default()
+            })
+            .insert(Lerper::from(lerp))
+            .insert(SpiceInFlight {
+                timer: Timer::from_seconds(0.6 + delay, false),
+            });
+    }
+}
+
+/// Rebuild our piles of spice whenever we spend or receive some.
+fn restash(
+    mut commands: Commands,
+    game_events: Res<GameEvents>,
+    game_state: Res<GameState>,
+    data: Res<Data>,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    stash: Query<Entity, Or<(With<Spice>, With<BribeSpice>)>>,
+    my_id: Res<PlayerId>,
+) {
+    let changed = match game_events.peek() {
+        Some(GameEvent::PaySpice { from, to, .. }) => *from == *my_id || *to == Some(*my_id),
+        Some(GameEvent::CollectSpice { player_id, .. }) => *player_id == *my_id,
+        Some(GameEvent::CollectBribes) => true,
+        _ => false,
+    };
+    if changed {
+        for entity in stash.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        if let Some(player) = game_state.players.get(&my_id) {
+            spawn_stash(&mut commands, &asset_server, &mut materials, &data, player.spice);
+            spawn_bribe_stash(&mut commands, &asset_server, &mut materials, &data, player.bribe_spice);
+        }
+    }
+}
+
+/// Slide our bribes over into the rest of our spice once they can be spent.
+fn collect_bribes(
+    mut commands: Commands,
+    game_events: Res<GameEvents>,
+    bribes: Query<(&Transform, &Handle<Mesh>, &Handle<StandardMaterial>), With<BribeSpice>>,
+    mut pause: ResMut<GameEventPauser>,
+) {
+    if let Some(GameEvent::CollectBribes) = game_events.peek() {
+        // The pile itself is about to be rebuilt, so send copies of its tokens over
+        for (i, (transform, mesh, material)) in bribes.iter().enumerate() {
+            let delay = 0.1 * i as f32;
+            let dest = Transform::from_translation(transform.translation - BRIBE_OFFSET);
+            commands
+                .spawn_bundle(PbrBundle {
+                    mesh: mesh.clone(),
+                    material: material.clone(),
+                    transform: *transform,
+                    ..Default::default()
+                })
+                .insert(Lerper::from(Lerp::world_to(dest, 0.5, delay)))
+                .insert(SpiceInFlight {
+                    timer: Timer::from_seconds(0.6 + delay, false),
+                });
+        }
+        if !bribes.is_empty() {
+            pause.pause_for(Duration::from_secs(1));
+        }
+    }
+}
+
+/// Carry collected spice off the board to whoever collected it, and add it to the summary of the collection.
+fn collect_spice(
+    mut commands: Commands,
+    game_events: Res<GameEvents>,
+    game_state: Res<GameState>,
+    data: Res<Data>,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut summary: Query<(&mut Text, &mut CollectionSummary)>,
+    my_id: Res<PlayerId>,
+    mut pause: ResMut<GameEventPauser>,
+) {
+    if let Some(GameEvent::CollectSpice {
+        player_id,
+        spice,
+        from: Some(from),
+    }) = game_events.peek()
+    {
+        let location = &data.locations[&from.location];
+        let src = location
+            .spice
+            .unwrap_or_else(|| location.sectors[&from.sector].center());
+        send_tokens(
+            &mut commands,
+            &asset_server,
+            &mut materials,
+            SpiceEndpoint::World(vec3(src.x, src.z, -src.y)),
+            SpiceEndpoint::of(&game_state, &data, *my_id, Some(*player_id)),
+            *spice,
+        );
+        pause.pause_for(Duration::from_secs(1));
+
+        let line = format!(
+            "{} collected {} spice from {}",
+            data.factions[&game_state.players[player_id].faction].name, spice, location.name
+        );
+        if let Ok((mut text, mut summary)) = summary.get_single_mut() {
+            text.sections[0].value.push('\n');
+            text.sections[0].value.push_str(&line);
+            summary.timer.reset();
+        } else {
+            commands
+                .spawn_bundle(TextBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        position: UiRect {
+                            top: Val::Px(5.0),
+                            right: Val::Px(5.0),
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    text: Text::from_section(
+                        line,
+                        TextStyle {
+                            font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                            font_size: 20.0,
+                            color: Color::WHITE,
+                        },
+                    ),
+                    ..default()
+                })
+                .insert(CollectionSummary {
+                    timer: Timer::from_seconds(5.0, false),
+                });
+        }
+    }
+}
+
+fn fade_collection_summary(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut summary: Query<(Entity, &mut CollectionSummary)>,
+) {
+    for (entity, mut summary) in summary.iter_mut() {
+        if summary.timer.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+fn land_spice(mut commands: Commands, time: Res<Time>, mut tokens: Query<(Entity, &mut SpiceInFlight)>) {
+    for (entity, mut in_flight) in tokens.iter_mut() {
+        if in_flight.timer.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
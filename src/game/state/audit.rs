@@ -0,0 +1,107 @@
+use std::fmt;
+
+use super::{DeckType, GameEvent, PlayerId, RuleSet};
+
+/// Something a player was sent which they had no business knowing.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Leak {
+    pub player_id: PlayerId,
+    pub event: GameEvent,
+    pub reason: &'static str,
+}
+
+impl fmt::Display for Leak {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "player {} was told {}: {:?}",
+            self.player_id, self.reason, self.event
+        )
+    }
+}
+
+impl GameEvent {
+    /// What a player would learn from being sent this event that they shouldn't, if anything. This is kept apart from
+    /// [`GameEvent::visible_to`] and [`GameEvent::redacted_for`] on purpose, so a mistake in deciding who sees what
+    /// shows up here rather than being repeated.
+    pub fn leak_to(&self, player_id: PlayerId, rules: &RuleSet) -> Option<&'static str> {
+        use GameEvent::*;
+        match self {
+            DealTraitor {
+                player_id: dealt_to,
+                card_id: Some(_),
+            } if *dealt_to != player_id => Some("which traitor another player was dealt"),
+            DealCard {
+                player_id: dealt_to,
+                card_id: Some(_),
+                ..
+            } if *dealt_to != player_id => Some("which card another player was dealt"),
+            WinBid {
+                player_id: winner,
+                card_id: Some(_),
+            } if *winner != player_id => Some("which card another player won"),
+            ReturnBidCard { card_id: Some(_) } => Some("which card nobody bid on"),
+            // The traitor deck is never even seen being shuffled, as it never leaves the server
+            SetDeckOrder { deck_order, deck_type } if deck_order.is_some() || *deck_type == DeckType::Traitor => {
+                Some(match deck_type {
+                    DeckType::Traitor => "the order of the traitor deck",
+                    DeckType::Treachery => "the order of the treachery deck",
+                    DeckType::Storm => "the order of the storm deck",
+                    DeckType::Spice => "the order of the spice deck",
+                })
+            }
+            ChooseTraitor { player_id: chooser, .. } if *chooser != player_id => {
+                Some("which traitor another player kept")
+            }
+            MakeFactionPrediction {
+                player_id: predictor, ..
+            }
+            | MakeTurnPrediction {
+                player_id: predictor, ..
+            } if *predictor != player_id => Some("the Bene Gesserit prediction"),
+            SetBattlePlan { player_id: planner, .. } if *planner != player_id => {
+                Some("another player's battle plan before it was revealed")
+            }
+            ShowSpiceBlow { player_id: peeker, .. } if *peeker != player_id => {
+                Some("the spice card another player peeked at")
+            }
+            PaySpice { from, to, .. } if rules.hidden_spice && *from != player_id && *to != Some(player_id) => {
+                Some("a payment between other players while spice is hidden")
+            }
+            Bribe {
+                player_id: briber,
+                other_player_id,
+                ..
+            } if rules.hidden_spice && *briber != player_id && *other_player_id != player_id => {
+                Some("a bribe between other players while spice is hidden")
+            }
+            CollectSpice {
+                player_id: collector,
+                from: None,
+                ..
+            } if rules.hidden_spice && *collector != player_id => Some("another player's income while spice is hidden"),
+            _ => None,
+        }
+    }
+}
+
+/// Check what each player would be sent of a stream of events, and list everything they would learn that they
+/// shouldn't. Events are filtered and redacted just as the server does before sending them.
+pub fn audit_events(events: &[GameEvent], players: &[PlayerId], rules: &RuleSet) -> Vec<Leak> {
+    events
+        .iter()
+        .flat_map(|event| {
+            players.iter().filter_map(move |player_id| {
+                if !event.visible_to(*player_id, rules) {
+                    return None;
+                }
+                let sent = event.redacted_for(*player_id);
+                sent.leak_to(*player_id, rules).map(|reason| Leak {
+                    player_id: *player_id,
+                    event: sent,
+                    reason,
+                })
+            })
+        })
+        .collect()
+}
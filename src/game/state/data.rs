@@ -4,10 +4,13 @@ use bevy::prelude::{Deref, DerefMut};
 use derive_more::{Display, From};
 use serde::{Deserialize, Serialize};
 
-use super::{GameEvent, Object, ObjectId};
+use super::{GameEvent, Object, ObjectId, RuleSet};
 use crate::{
-    components::{Bonus, Faction, Leader, Location, SpiceCard, StormCard, TraitorCard, TreacheryCard, Troop},
-    game::phase::Phase,
+    components::{
+        Bonus, Faction, Leader, Location, LocationSector, SpiceCard, StormCard, TraitorCard, TreacheryCard,
+        TreacheryCardKind, Troop,
+    },
+    game::phase::{Phase, PhaseSequence},
 };
 
 #[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
@@ -15,12 +18,22 @@ use crate::{
 pub struct GameState {
     pub phase: Phase,
     pub game_turn: u8,
+    pub rules: RuleSet,
+    /// The phases this game steps through, decided by the rules it is played with.
+    pub sequence: PhaseSequence,
+    /// Why the game ended, once it has.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_reason: Option<EndGameReason>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub active_player: Option<PlayerId>,
     #[serde(skip_serializing_if = "HashMap::is_empty")]
     pub players: HashMap<PlayerId, Player>,
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub play_order: Vec<PlayerId>,
+    /// Where players sit around the board, in storm order from the first sector. Play order starts wherever the storm
+    /// is, but seats never change.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub seats: Vec<PlayerId>,
     #[serde(skip_serializing_if = "HashMap::is_empty")]
     pub factions: HashMap<Faction, PlayerId>,
     #[serde(skip_serializing_if = "HashMap::is_empty")]
@@ -30,13 +43,65 @@ pub struct GameState {
     #[serde(skip_serializing_if = "HashMap::is_empty")]
     pub board: HashMap<Location, LocationState>,
     pub storm_sector: u8,
+    /// Whether the storm has been given its random starting sector yet.
+    pub storm_placed: bool,
+    /// Family Atomics have been used, so the Shield Wall no longer protects the cities from the storm.
+    pub shield_wall_destroyed: bool,
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub bidding_cards: BidStates,
+    /// The spice blow a worm surfaced in, which calls a Nexus at the end of the Spice Blow. Cleared once the Nexus is
+    /// over.
     pub nexus: Option<Object<SpiceCard>>,
+    /// Territories where a worm has surfaced this turn and has yet to devour.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub worms: Vec<Location>,
+    /// Battles still to be fought this turn, in the order they will be fought.
+    #[serde(skip_serializing_if = "VecDeque::is_empty")]
+    pub battles: VecDeque<Battle>,
+    /// Whether the aggressor in the current battle has chosen the order of their battles.
+    pub battle_order_chosen: bool,
+    /// The plans submitted so far for the current battle. Until the battle is resolved, players only know their own.
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub battle_plans: HashMap<PlayerId, BattlePlan>,
+    /// Players who have committed to a plan for the current battle, which everyone knows even if they can't see it.
+    #[serde(default, skip_serializing_if = "HashSet::is_empty")]
+    pub committed_plans: HashSet<PlayerId>,
+    /// The enemy leaders each player has killed in battle, when playing with kanly.
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub kanly: HashMap<PlayerId, Vec<Leader>>,
+    /// Tokens from expansions which players hold, by kind. Always empty in the base game.
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub resources: HashMap<ResourceKind, HashMap<PlayerId, u8>>,
+    /// Players who have used a Maker Hook to ride the next worm to devour.
+    #[serde(default, skip_serializing_if = "HashSet::is_empty")]
+    pub worm_riders: HashSet<PlayerId>,
+    /// Players who have been stopped from shipping for the rest of the turn.
+    #[serde(default, skip_serializing_if = "HashSet::is_empty")]
+    pub shipment_blocked: HashSet<PlayerId>,
+    /// The player hosting the game, who may correct it by hand when the rules allow.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub host: Option<PlayerId>,
+    /// Every correction the host has made by hand, and who it was made to, so nobody mistakes them for play.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub adjustments: Vec<(PlayerId, Adjustment)>,
     pub bg_predictions: BeneGesseritPredictions,
+    /// Groups of players who have allied, and win together.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub alliances: Vec<HashSet<PlayerId>>,
+    /// How many spice blows have been resolved this turn.
+    pub spice_blows: u8,
+    /// The last spice card placed by each of the turn's spice blows, in order. A worm surfaces where the last card of
+    /// its own blow was placed, not wherever the other blow placed spice this turn.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub spice_piles: Vec<Object<SpiceCard>>,
+    /// The next spice blow, if we have been allowed to look at it before it is revealed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub peeked_spice_blow: Option<ObjectId>,
     pub storm_card: Option<Object<StormCard>>,
     pub spice_card: Option<Object<SpiceCard>>,
     pub history: VecDeque<GameEvent>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_window: Option<ResponseWindow>,
 }
 
 #[derive(Copy, Clone, Debug, PartialOrd, Ord, PartialEq, Eq, Serialize, Deserialize, Hash, From, Display)]
@@ -49,13 +114,44 @@ pub struct Player {
     pub treachery_cards: HashSet<Object<TreacheryCard>>,
     pub traitor_cards: HashSet<Object<TraitorCard>>,
     pub spice: u8,
+    /// Spice received as bribes, which can't be spent until the next turn.
+    #[serde(default)]
+    pub bribe_spice: u8,
     pub living_leaders: HashMap<Object<Leader>, bool>,
     pub offworld_forces: HashSet<Object<Troop>>,
     pub shipped: bool,
+    #[serde(default)]
+    pub moved: bool,
+    /// Moves granted this turn on top of the usual one, by playing a Hajr.
+    #[serde(default)]
+    pub extra_moves: u8,
     pub tanks: TleilaxuTanks,
     pub bonuses: HashSet<Bonus>,
 }
 
+impl Player {
+    /// Whether the player has a move left this turn.
+    pub fn can_move(&self) -> bool {
+        !self.moved || self.extra_moves > 0
+    }
+
+    /// Whether the player holds a treachery card of some kind.
+    pub fn holds(&self, kind: TreacheryCardKind) -> bool {
+        self.treachery_cards.iter().any(|card| card.inner.kind == kind)
+    }
+}
+
+/// A correction the host makes by hand, for a rule the game doesn't handle yet.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Adjustment {
+    /// Give a player spice, or take it away if negative.
+    Spice(i16),
+    /// Send forces from a player's reserves to the tanks.
+    KillForces(u8),
+    /// Bring forces back from the tanks to a player's reserves.
+    ReviveForces(u8),
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Bid {
     pub player_id: PlayerId,
@@ -75,6 +171,11 @@ impl BidStates {
     pub fn win(&mut self) -> Option<BidState> {
         self.bidding_cards.pop()
     }
+
+    /// Take the card up for bid off the table when nobody wants it.
+    pub fn unsold(&mut self) -> Option<BidState> {
+        self.bidding_cards.pop()
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
@@ -86,20 +187,155 @@ pub struct BidState {
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Prompt {
-    Faction { remaining: HashSet<Faction> },
+    Faction {
+        remaining: HashSet<Faction>,
+    },
     Traitor,
     FactionPrediction,
     TurnPrediction,
     GuildShip,
     Bid,
+    RideWorm {
+        location: Location,
+    },
+    /// An optional chance to play a treachery card of the given kind, which may be declined.
+    PlayCard {
+        kind: TreacheryCardKind,
+    },
+    /// Bene Gesserit forces which have just shipped in as advisors may become fighters instead.
+    AdvisorOrFighter {
+        location: LocationSector,
+    },
+    /// Bene Gesserit advisors in a sector another faction has just shipped into may flip to fighters and fight for it.
+    AdvisorDecision {
+        location: LocationSector,
+    },
+    /// The aggressor in several battles chooses which to fight first.
+    BattleOrder {
+        locations: Vec<Location>,
+    },
+    /// Secretly choose forces, a leader and treachery cards to fight with.
+    BattlePlan {
+        battle: Battle,
+    },
+    /// The Bene Gesserit may command their opponent to play or not play a kind of card.
+    Voice {
+        battle: Battle,
+    },
+    /// The Atreides may ask to see one element of their opponent's battle plan.
+    Prescience {
+        battle: Battle,
+    },
+    /// Reveal a traitor card naming the leader our opponent fought with.
+    CallTraitor {
+        battle: Battle,
+    },
+    /// Players with little or no spice may claim CHOAM charity.
+    Charity,
+    /// Choose which forces and leader to revive from the tanks.
+    Revival,
+    /// Too many cards are held, so some must be discarded.
+    Discard {
+        count: u8,
+    },
+    /// A chance to play a Karama card for a faction's special use of it, which may be declined.
+    Karama {
+        effect: KaramaEffect,
+    },
+    /// Take a moment to look over the turn which just ended before the next one begins.
+    Continue,
 }
 
-#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+/// What a Karama card does when it is played for a faction's special use of it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum KaramaEffect {
+    /// The Guild stops a player from shipping for the rest of the turn.
+    BlockShipment { target: PlayerId },
+}
+
+/// A fight between two players over a territory.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Battle {
+    pub location: Location,
+    pub aggressor: PlayerId,
+    pub defender: PlayerId,
+}
+
+impl Battle {
+    pub fn involves(&self, player_id: PlayerId) -> bool {
+        self.aggressor == player_id || self.defender == player_id
+    }
+
+    pub fn opponent(&self, player_id: PlayerId) -> PlayerId {
+        if self.aggressor == player_id {
+            self.defender
+        } else {
+            self.aggressor
+        }
+    }
+}
+
+/// How many of each kind of force a player dials in battle.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ForceDial {
+    pub normal: u8,
+    /// Sardaukar or Fedaykin, which usually count double.
+    pub special: u8,
+}
+
+impl ForceDial {
+    pub fn total(&self) -> u8 {
+        self.normal + self.special
+    }
+}
+
+/// What a player has chosen to fight a battle with.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BattlePlan {
+    pub forces: ForceDial,
+    pub leader: Option<ObjectId>,
+    pub treachery_cards: Vec<ObjectId>,
+}
+
+/// A chance for every eligible player to respond to something before the game continues. Once everyone has responded
+/// or declined, the `on_complete` event is run.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResponseWindow {
+    pub prompt: Prompt,
+    pub eligible: HashSet<PlayerId>,
+    #[serde(default)]
+    pub responded: HashSet<PlayerId>,
+    pub on_complete: Box<GameEvent>,
+}
+
+impl ResponseWindow {
+    pub fn new(prompt: Prompt, eligible: HashSet<PlayerId>, on_complete: GameEvent) -> Self {
+        Self {
+            prompt,
+            eligible,
+            responded: Default::default(),
+            on_complete: Box::new(on_complete),
+        }
+    }
+
+    /// Players who may still respond.
+    pub fn pending(&self) -> impl Iterator<Item = &PlayerId> {
+        self.eligible.difference(&self.responded)
+    }
+
+    pub fn can_respond(&self, player_id: &PlayerId) -> bool {
+        self.eligible.contains(player_id) && !self.responded.contains(player_id)
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.pending().next().is_none()
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(default)]
 pub struct BeneGesseritPredictions {
-    #[serde(skip_serializing_if = "Option::is_none")]
     pub faction: Option<Faction>,
-    #[serde(skip_serializing_if = "Option::is_none")]
     pub turn: Option<u8>,
 }
 
@@ -129,12 +365,49 @@ impl<C> Deck<C> {
         }
     }
 
+    /// Take a particular card out of the deck, wherever it is in the order.
+    pub fn take(&mut self, id: ObjectId) -> Option<Object<C>> {
+        let card = self.cards.take(&id)?;
+        self.card_order.retain(|card_id| *card_id != id);
+        Some(card)
+    }
+
     pub fn get(&self, id: ObjectId) -> Option<&Object<C>> {
         self.cards.get(&id).or(self.discards.get(&id))
     }
 
+    /// Whether there is nothing left to draw, though there may still be discarded cards.
+    pub fn is_empty(&self) -> bool {
+        self.cards.is_empty()
+    }
+
+    /// The cards left to draw, from the bottom of the deck to the top.
+    pub fn card_ids(&self) -> Vec<ObjectId> {
+        self.card_order.clone()
+    }
+
+    /// Every card which belongs to the deck, whether it is still to be drawn or has been discarded.
+    pub fn iter(&self) -> impl Iterator<Item = &Object<C>> {
+        self.cards.iter().chain(self.discards.iter())
+    }
+
+    pub fn is_discarded(&self, id: ObjectId) -> bool {
+        self.discards.contains(&id)
+    }
+
+    /// The discard pile, from the first card discarded to the last.
+    pub fn discard_pile(&self) -> impl DoubleEndedIterator<Item = &Object<C>> {
+        self.discard_order.iter().filter_map(|id| self.discards.get(id))
+    }
+
     pub fn last_discarded(&self) -> Option<&Object<C>> {
-        self.discard_order.last().and_then(|id| self.discards.get(id))
+        self.discard_pile().next_back()
+    }
+
+    /// Put a card back underneath the rest of the deck, where it will be drawn last.
+    pub fn put_bottom(&mut self, card: Object<C>) {
+        self.card_order.insert(0, card.id);
+        self.cards.insert(card);
     }
 
     pub fn discard(&mut self, card: Object<C>) {
@@ -142,7 +415,28 @@ impl<C> Deck<C> {
         self.discards.insert(card);
     }
 
+    /// Shuffle discarded cards back into the deck in the given order, beneath whatever is left to draw.
+    pub fn reshuffle_discard(&mut self, order: impl IntoIterator<Item = ObjectId>) {
+        let mut reshuffled = Vec::new();
+        for id in order {
+            if let Some(card) = self.discards.take(&id) {
+                self.cards.insert(card);
+                reshuffled.push(id);
+            }
+        }
+        self.discard_order.retain(|id| !reshuffled.contains(id));
+        self.card_order.splice(0..0, reshuffled);
+    }
+
+    /// Shuffle every discarded card back into the deck without being told the order. Whatever order the deck ends up
+    /// in here stands in for the real one.
+    pub fn reshuffle_unseen(&mut self) {
+        self.reshuffle_discard(self.discard_order.clone());
+    }
+
+    /// Put the deck in the given order, shuffling back in any discarded cards which are part of it.
     pub fn set_order(&mut self, order: Vec<ObjectId>) {
+        self.reshuffle_discard(order.iter().copied());
         self.card_order = order;
     }
 }
@@ -179,7 +473,7 @@ impl Decks {
     }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum DeckType {
     Traitor,
     Treachery,
@@ -193,9 +487,25 @@ pub struct TleilaxuTanks {
     pub forces: HashSet<Object<Troop>>,
 }
 
+impl TleilaxuTanks {
+    pub fn is_empty(&self) -> bool {
+        self.leaders.is_empty() && self.forces.is_empty()
+    }
+}
+
 #[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Forces {
     pub forces: HashSet<Object<Troop>>,
+    /// Bene Gesserit advisors share a territory without fighting for it, collecting spice or controlling it.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub advisors: bool,
+}
+
+impl Forces {
+    /// Whether these forces occupy their sector, as opposed to being absent or only advising.
+    pub fn occupies(&self) -> bool {
+        !self.forces.is_empty() && !self.advisors
+    }
 }
 
 #[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
@@ -204,15 +514,56 @@ pub struct SectorState {
     pub spice: u8,
 }
 
+impl SectorState {
+    /// The only player occupying this sector, if there is exactly one. Advisors don't count.
+    pub fn sole_occupant(&self) -> Option<(PlayerId, &Forces)> {
+        let mut occupants = self.forces.iter().filter(|(_, forces)| forces.occupies());
+        match (occupants.next(), occupants.next()) {
+            (Some((&player_id, forces)), None) => Some((player_id, forces)),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct LocationState {
     pub sectors: HashMap<u8, SectorState>,
     pub worm: Option<ObjectId>,
 }
 
+/// Tokens which expansions give players on top of the base game's spice, forces and cards.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Display, Serialize, Deserialize)]
+pub enum ResourceKind {
+    /// A tech token which yields spice to whoever holds it every Collection phase.
+    #[display(fmt = "Spice Production")]
+    SpiceProduction,
+}
+
+/// Why spice changed hands, so it can be logged and shown to players.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PaymentReason {
+    Bid,
+    Shipment,
+    Bribe,
+    Revival,
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum EndGameReason {
-    PlayerLeft { player_id: PlayerId },
+    PlayerLeft {
+        player_id: PlayerId,
+    },
+    Conceded {
+        player_id: PlayerId,
+    },
+    /// Enough strongholds were controlled at the end of a turn. Allies win together.
+    Victory {
+        winners: Vec<PlayerId>,
+    },
+    /// The Bene Gesserit correctly predicted who would win, and when, so they win instead.
+    Prediction {
+        player_id: PlayerId,
+    },
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
@@ -234,3 +585,18 @@ pub enum SpawnType {
         id: ObjectId,
     },
 }
+
+impl SpawnType {
+    /// The id of the object being spawned.
+    pub fn id(&self) -> ObjectId {
+        match self {
+            SpawnType::Leader { leader, .. } => leader.id,
+            SpawnType::Troop { unit, .. } => unit.id,
+            SpawnType::TraitorCard(card) => card.id,
+            SpawnType::TreacheryCard(card) => card.id,
+            SpawnType::SpiceCard(card) => card.id,
+            SpawnType::StormCard(card) => card.id,
+            SpawnType::Worm { id, .. } => *id,
+        }
+    }
+}
@@ -1,23 +1,66 @@
+mod audit;
 mod data;
+mod rules;
+#[cfg(test)]
+mod tests;
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet, VecDeque};
 
 use bevy::prelude::info;
 use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+use thiserror::Error;
 
-pub use self::data::*;
+pub use self::{audit::*, data::*, rules::*};
 use super::{Object, ObjectId};
 use crate::{
-    components::{Faction, Location, LocationSector, SpiceCard},
+    components::{
+        Faction, Leader, Location, LocationSector, SpiceCard, Terrain, TraitorCard, TreacheryCard, TreacheryCardKind,
+        Troop,
+    },
     data::Data,
-    game::phase::{setup::SetupPhase, Phase},
+    game::phase::{bidding::BiddingPhase, setup::SetupPhase, spice_blow::SpiceBlowPhase, Phase},
 };
 
+/// How many strongholds a player on their own must control at the end of a turn to win, unless the rules say otherwise.
+pub const STRONGHOLDS_TO_WIN: usize = 3;
+/// Games with at most this many players have fewer rivals to stop anyone, so winning takes one more stronghold.
+pub const SMALL_GAME: usize = 3;
+/// How many forces a player may revive from the tanks each turn, of which only one may be a special force.
+pub const MAX_REVIVAL: usize = 3;
+pub const MAX_SPECIAL_REVIVAL: usize = 1;
+/// What each force beyond a faction's free revivals costs to revive.
+const REVIVAL_COST: usize = 2;
+
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum GameEvent {
     EndGame {
         reason: EndGameReason,
     },
+    SetRules {
+        rules: RuleSet,
+    },
+    SetHost {
+        player_id: PlayerId,
+    },
+    /// The host corrects the game by hand.
+    AdminAdjust {
+        player_id: PlayerId,
+        target: PlayerId,
+        adjustment: Adjustment,
+    },
+    /// Everything which was kept secret during the game is revealed once it is over.
+    RevealAll {
+        bg_predictions: BeneGesseritPredictions,
+        /// The traitors each player was holding, which nobody else was told.
+        #[serde(default)]
+        traitors: HashMap<PlayerId, HashSet<ObjectId>>,
+    },
+    /// Everyone's spice is put on show, when it has been hidden behind their shields.
+    RevealSpice {
+        spice: HashMap<PlayerId, u8>,
+        bribe_spice: HashMap<PlayerId, u8>,
+    },
     PlayerJoined {
         player_id: PlayerId,
     },
@@ -27,9 +70,29 @@ pub enum GameEvent {
     SetActive {
         player_id: PlayerId,
     },
+    /// The active player is done, and play moves on to the next player in play order. Once the last player passes
+    /// nobody is active, and it is up to the phase whether to end or to go around again with `StartRound`.
     Pass {
         player_id: PlayerId,
     },
+    Concede {
+        player_id: PlayerId,
+    },
+    OpenWindow {
+        window: ResponseWindow,
+    },
+    CloseWindow,
+    PlayCard {
+        player_id: PlayerId,
+        card_id: ObjectId,
+    },
+    Decline {
+        player_id: PlayerId,
+    },
+    RevealCard {
+        card_id: ObjectId,
+    },
+    /// Go around the table again, starting with the first player in play order.
     StartRound,
     AdvancePhase,
     SpawnObject {
@@ -42,17 +105,30 @@ pub enum GameEvent {
     SetPlayOrder {
         play_order: Vec<PlayerId>,
     },
+    /// Start a new turn, with the first player decided by where the storm is.
+    AdvanceTurn,
+    /// Deal the top card of a deck to a player. Like traitors, only the player it is dealt to is told which card it
+    /// was.
     DealCard {
         player_id: PlayerId,
         from: DeckType,
+        card_id: Option<ObjectId>,
+    },
+    /// Deal the top traitor card to a player. Traitors are dealt face down, so only the player they are dealt to is
+    /// told which card it was.
+    DealTraitor {
+        player_id: PlayerId,
+        card_id: Option<ObjectId>,
     },
     DiscardCard {
         player_id: PlayerId,
         card_id: ObjectId,
         to: DeckType,
     },
+    /// Shuffle a deck, along with its discards. Only the server knows the order, so everyone else is just told the
+    /// deck was shuffled.
     SetDeckOrder {
-        deck_order: Vec<ObjectId>,
+        deck_order: Option<Vec<ObjectId>>,
         deck_type: DeckType,
     },
     ChooseFaction {
@@ -63,6 +139,10 @@ pub enum GameEvent {
         player_id: PlayerId,
         card_id: ObjectId,
     },
+    /// Everyone is told that a player has kept a traitor, but only that player is told which.
+    TraitorChosen {
+        player_id: PlayerId,
+    },
     MakeFactionPrediction {
         player_id: PlayerId,
         faction: Faction,
@@ -76,11 +156,28 @@ pub enum GameEvent {
         spice: u8,
         from: Option<LocationSector>,
     },
+    GrantResource {
+        player_id: PlayerId,
+        kind: ResourceKind,
+        amount: u8,
+    },
+    SpendResource {
+        player_id: PlayerId,
+        kind: ResourceKind,
+        amount: u8,
+    },
     Bribe {
         player_id: PlayerId,
         other_player_id: PlayerId,
         spice: u8,
     },
+    PaySpice {
+        from: PlayerId,
+        to: Option<PlayerId>,
+        amount: u8,
+        reason: PaymentReason,
+    },
+    CollectBribes,
     ShipForces {
         player_id: PlayerId,
         to: LocationSector,
@@ -91,38 +188,849 @@ pub enum GameEvent {
         path: Vec<LocationSector>,
         forces: HashSet<ObjectId>,
     },
-    RevealStorm,
+    RevealStorm {
+        card_id: ObjectId,
+    },
     MoveStorm {
         sectors: u8,
     },
-    RevealSpiceBlow,
+    RevealSpiceBlow {
+        card_id: ObjectId,
+    },
+    /// The Fremen ask to look at the next spice blow.
+    PeekSpiceBlow {
+        player_id: PlayerId,
+    },
+    /// Show a player the next spice blow, without showing anyone else.
+    ShowSpiceBlow {
+        player_id: PlayerId,
+        card_id: ObjectId,
+    },
     PlaceSpice {
         location: LocationSector,
         spice: u8,
     },
+    Devour {
+        location: Location,
+    },
     RideTheWorm {
+        player_id: PlayerId,
+        from: Location,
+        to: Option<LocationSector>,
+    },
+    PlayThumper {
+        player_id: PlayerId,
+        card_id: ObjectId,
         location: Location,
     },
+    /// Play a Hajr to move once more this turn.
+    PlayHajr {
+        player_id: PlayerId,
+        card_id: ObjectId,
+    },
+    ChooseAdvisors {
+        player_id: PlayerId,
+        location: LocationSector,
+        advisors: bool,
+    },
     StartBidding,
     MakeBid {
         player_id: PlayerId,
         spice: u8,
     },
+    /// Cards are bid on face down, so only the winner is told which card they won.
     WinBid {
         player_id: PlayerId,
-        card_id: ObjectId,
+        card_id: Option<ObjectId>,
+    },
+    /// Nobody bid on the card, so it goes to the bottom of the treachery deck without anyone being told what it was.
+    ReturnBidCard {
+        card_id: Option<ObjectId>,
     },
     Revive {
         player_id: PlayerId,
         forces: HashSet<ObjectId>,
         leader: Option<ObjectId>,
     },
+    SetBattles {
+        battles: Vec<Battle>,
+    },
+    ChooseBattleOrder {
+        player_id: PlayerId,
+        order: Vec<Location>,
+    },
     SetBattlePlan {
         player_id: PlayerId,
-        forces: u8,
+        forces: ForceDial,
         leader: Option<ObjectId>,
         treachery_cards: Vec<ObjectId>,
     },
+    /// Everyone is told that a player has committed to a battle plan, but only that player is told what it is.
+    BattlePlanSubmitted {
+        player_id: PlayerId,
+    },
+    /// Both plans are revealed together when the battle is resolved.
+    ResolveBattle {
+        plans: HashMap<PlayerId, BattlePlan>,
+    },
+}
+
+impl GameEvent {
+    /// The player an event claims to be sent by, if it is one players are allowed to send.
+    pub fn sender(&self) -> Option<PlayerId> {
+        use GameEvent::*;
+        match self {
+            Pass { player_id }
+            | Concede { player_id }
+            | PlayCard { player_id, .. }
+            | Decline { player_id }
+            | ChooseFaction { player_id, .. }
+            | ChooseTraitor { player_id, .. }
+            | MakeFactionPrediction { player_id, .. }
+            | MakeTurnPrediction { player_id, .. }
+            | Bribe { player_id, .. }
+            | ShipForces { player_id, .. }
+            | MoveForces { player_id, .. }
+            | RideTheWorm { player_id, .. }
+            | PlayThumper { player_id, .. }
+            | PlayHajr { player_id, .. }
+            | ChooseAdvisors { player_id, .. }
+            | MakeBid { player_id, .. }
+            | Revive { player_id, .. }
+            | ChooseBattleOrder { player_id, .. }
+            | SetBattlePlan { player_id, .. }
+            | PeekSpiceBlow { player_id }
+            | AdminAdjust { player_id, .. } => Some(*player_id),
+            _ => None,
+        }
+    }
+
+    /// The only player who may see an event, if its contents are kept secret from everyone else.
+    pub fn secret_to(&self) -> Option<PlayerId> {
+        use GameEvent::*;
+        match self {
+            SetBattlePlan { player_id, .. }
+            | ChooseTraitor { player_id, .. }
+            | ShowSpiceBlow { player_id, .. }
+            | MakeFactionPrediction { player_id, .. }
+            | MakeTurnPrediction { player_id, .. } => Some(*player_id),
+            _ => None,
+        }
+    }
+
+    /// Whether a player gets to see this event. Secrets are only seen by the player they're for, and when spice is
+    /// hidden, payments, bribes and income are only seen by the players they involve.
+    pub fn visible_to(&self, player_id: PlayerId, rules: &RuleSet) -> bool {
+        match self {
+            GameEvent::PaySpice { from, to, .. } if rules.hidden_spice => *from == player_id || *to == Some(player_id),
+            GameEvent::Bribe {
+                player_id: briber,
+                other_player_id,
+                ..
+            } if rules.hidden_spice => *briber == player_id || *other_player_id == player_id,
+            // Spice collected from the board is seen leaving it, so only income from elsewhere can be kept hidden
+            GameEvent::CollectSpice {
+                player_id: collector,
+                from: None,
+                ..
+            } if rules.hidden_spice => *collector == player_id,
+            // The order of the traitor deck would give away everyone's traitors, so it never leaves the server
+            GameEvent::SetDeckOrder {
+                deck_type: DeckType::Traitor,
+                ..
+            } => false,
+            _ => self.secret_to().map_or(true, |id| id == player_id),
+        }
+    }
+
+    /// The event as a player is told it, with anything they shouldn't know left out.
+    pub fn redacted_for(&self, player_id: PlayerId) -> GameEvent {
+        match self {
+            GameEvent::DealTraitor {
+                player_id: dealt_to,
+                card_id: Some(_),
+            } if *dealt_to != player_id => GameEvent::DealTraitor {
+                player_id: *dealt_to,
+                card_id: None,
+            },
+            GameEvent::DealCard {
+                player_id: dealt_to,
+                from,
+                card_id: Some(_),
+            } if *dealt_to != player_id => GameEvent::DealCard {
+                player_id: *dealt_to,
+                from: *from,
+                card_id: None,
+            },
+            GameEvent::WinBid {
+                player_id: winner,
+                card_id: Some(_),
+            } if *winner != player_id => GameEvent::WinBid {
+                player_id: *winner,
+                card_id: None,
+            },
+            GameEvent::ReturnBidCard { card_id: Some(_) } => GameEvent::ReturnBidCard { card_id: None },
+            // Players only see a deck being shuffled, never the order it ends up in
+            GameEvent::SetDeckOrder {
+                deck_order: Some(_),
+                deck_type,
+            } => GameEvent::SetDeckOrder {
+                deck_order: None,
+                deck_type: *deck_type,
+            },
+            _ => self.clone(),
+        }
+    }
+
+    /// What to tell a player who tries to send this event when it isn't legal.
+    pub fn rejection(&self) -> String {
+        use GameEvent::*;
+        match self {
+            Pass { .. } => "It isn't your turn to pass".to_string(),
+            Concede { .. } => "You can't concede now".to_string(),
+            PlayCard { .. } => "That card can't be played now".to_string(),
+            Decline { .. } => "There is nothing to decline".to_string(),
+            ChooseFaction { .. } => "It isn't your turn to choose a faction".to_string(),
+            ChooseTraitor { .. } => "You can't choose that traitor".to_string(),
+            MakeFactionPrediction { .. } | MakeTurnPrediction { .. } => "You can't make that prediction".to_string(),
+            Bribe { .. } => "You can't afford that bribe".to_string(),
+            ShipForces { .. } => "You can't ship those forces there".to_string(),
+            MoveForces { .. } => "You can't move those forces there".to_string(),
+            ChooseAdvisors { .. } => "You can't choose advisors now".to_string(),
+            RideTheWorm { .. } => "You can't ride the worm there".to_string(),
+            PlayThumper { .. } => "You can't use a thumper there".to_string(),
+            PlayHajr { .. } => "A Hajr can only be played on your turn to move".to_string(),
+            MakeBid { .. } => "You can't make that bid".to_string(),
+            Revive { .. } => "You can't revive those".to_string(),
+            ChooseBattleOrder { .. } => "That isn't a valid battle order".to_string(),
+            SpendResource { kind, .. } => format!("You don't have enough {}", kind),
+            PeekSpiceBlow { .. } => "You can't look at the spice deck now".to_string(),
+            SetBattlePlan { .. } => "That battle plan isn't valid".to_string(),
+            AdminAdjust { .. } => "Only the host can adjust the game, and only when the rules allow it".to_string(),
+            _ => "Only the server can do that".to_string(),
+        }
+    }
+}
+
+impl GameState {
+    /// How many of a player's forces are in a territory.
+    pub fn forces_in(&self, player_id: PlayerId, location: Location) -> usize {
+        self.board.get(&location).map_or(0, |location_state| {
+            location_state
+                .sectors
+                .values()
+                .filter_map(|sector| sector.forces.get(&player_id))
+                .map(|forces| forces.forces.len())
+                .sum()
+        })
+    }
+
+    /// How many of each kind of a player's forces are in a territory.
+    pub fn forces_present(&self, player_id: PlayerId, location: Location) -> ForceDial {
+        let mut present = ForceDial::default();
+        for force in self
+            .board
+            .get(&location)
+            .iter()
+            .flat_map(|location_state| location_state.sectors.values())
+            .filter_map(|sector| sector.forces.get(&player_id))
+            .flat_map(|forces| forces.forces.iter())
+        {
+            if force.inner.is_special {
+                present.special += 1;
+            } else {
+                present.normal += 1;
+            }
+        }
+        present
+    }
+
+    /// The aggressor and defender of the next battle in a territory, if there is one to be fought. The first player in
+    /// storm order who occupies it is the aggressor, and the next is the defender. Anyone else there fights whoever is
+    /// left afterwards.
+    pub fn combatants(&self, location: Location) -> Option<(PlayerId, PlayerId)> {
+        if location == Location::PolarSink {
+            return None;
+        }
+        let location_state = self.board.get(&location)?;
+        let mut occupants = self.play_order.iter().copied().filter(|player_id| {
+            location_state
+                .sectors
+                .values()
+                .any(|sector| sector.forces.get(player_id).map_or(false, |forces| forces.occupies()))
+        });
+        Some((occupants.next()?, occupants.next()?))
+    }
+
+    /// Every battle to start the turn with, one for each contested territory. Battles are fought in storm order of
+    /// their aggressors.
+    pub fn find_battles(&self) -> Vec<Battle> {
+        // Battles in the same storm order are fought in board order, so they always come up the same way
+        let mut battles = Location::iter()
+            .filter(|location| self.board.contains_key(location))
+            .filter_map(|location| {
+                self.combatants(location).map(|(aggressor, defender)| Battle {
+                    location,
+                    aggressor,
+                    defender,
+                })
+            })
+            .collect::<Vec<_>>();
+        battles.sort_by_key(|battle| self.storm_order(battle.aggressor));
+        battles
+    }
+
+    fn storm_order(&self, player_id: PlayerId) -> Option<usize> {
+        self.play_order.iter().position(|other| *other == player_id)
+    }
+
+    /// Territories where the aggressor of the current battle still has battles to fight.
+    pub fn aggressor_battle_locations(&self) -> Vec<Location> {
+        let mut locations = Vec::new();
+        if let Some(aggressor) = self.battles.front().map(|battle| battle.aggressor) {
+            for battle in self.battles.iter().filter(|battle| battle.aggressor == aggressor) {
+                if !locations.contains(&battle.location) {
+                    locations.push(battle.location);
+                }
+            }
+        }
+        locations
+    }
+
+    /// Whoever dialed the most forces, plus the strength of their leader, wins the battle, with the aggressor winning
+    /// ties. The loser loses everything in the territory along with their leader and the cards they played, and the
+    /// winner loses the forces they dialed.
+    // TODO: weapons, defenses and traitors
+    fn resolve_battle(&mut self, data: &Data, battle: Battle) {
+        let strength = |player_id: PlayerId| {
+            self.battle_plans.get(&player_id).map_or(0, |plan| {
+                self.battle_strength(data, player_id, battle.opponent(player_id), plan)
+            })
+        };
+        let (winner, loser) = if strength(battle.aggressor) >= strength(battle.defender) {
+            (battle.aggressor, battle.defender)
+        } else {
+            (battle.defender, battle.aggressor)
+        };
+        info!(
+            "{} won the battle in {} against {}",
+            self.players[&winner].faction, battle.location, self.players[&loser].faction
+        );
+
+        for (player_id, plan) in self.battle_plans.iter() {
+            let player = self.players.get_mut(player_id).unwrap();
+            if let Some(leader) = plan.leader {
+                if let Some(fought) = player.living_leaders.iter_mut().find(|(l, _)| l.id == leader) {
+                    *fought.1 = true;
+                }
+            }
+            if *player_id == loser {
+                if let Some(leader) = plan.leader {
+                    if let Some(leader) = player.living_leaders.keys().find(|l| l.id == leader).cloned() {
+                        player.living_leaders.remove(&leader);
+                        if self.rules.kanly {
+                            self.kanly.entry(winner).or_default().push(leader.inner);
+                        }
+                        player.tanks.leaders.insert(leader);
+                    }
+                }
+                for card_id in plan.treachery_cards.iter() {
+                    if let Some(card) = player.treachery_cards.take(card_id) {
+                        self.decks.treachery.discard(card);
+                    }
+                }
+            }
+        }
+
+        let mut losses = self
+            .battle_plans
+            .get(&winner)
+            .map_or_else(ForceDial::default, |plan| plan.forces);
+        if let Some(location_state) = self.board.get_mut(&battle.location) {
+            for sector in location_state.sectors.values_mut() {
+                if let Some(forces) = sector.forces.remove(&loser) {
+                    self.players.get_mut(&loser).unwrap().tanks.forces.extend(forces.forces);
+                }
+                if let Some(forces) = sector.forces.get_mut(&winner) {
+                    // The winner loses exactly the forces they dialed, of each kind
+                    let dead = forces
+                        .forces
+                        .iter()
+                        .filter(|force| {
+                            let remaining = if force.inner.is_special {
+                                &mut losses.special
+                            } else {
+                                &mut losses.normal
+                            };
+                            if *remaining > 0 {
+                                *remaining -= 1;
+                                true
+                            } else {
+                                false
+                            }
+                        })
+                        .cloned()
+                        .collect::<Vec<_>>();
+                    for force in dead {
+                        forces.forces.remove(&force);
+                        self.players.get_mut(&winner).unwrap().tanks.forces.insert(force);
+                    }
+                    if forces.forces.is_empty() {
+                        sector.forces.remove(&winner);
+                    }
+                }
+            }
+            location_state
+                .sectors
+                .retain(|_, sector| !sector.forces.is_empty() || sector.spice > 0);
+        }
+    }
+
+    /// How strong a battle plan is: the forces dialed, with special forces counting double, plus the power of the
+    /// leader. Sardaukar are only worth as much as anyone else against the Fremen.
+    pub fn battle_strength(&self, data: &Data, player_id: PlayerId, opponent: PlayerId, plan: &BattlePlan) -> u32 {
+        let faction = self.players.get(&player_id).map(|player| player.faction);
+        let special_value = match (faction, self.players.get(&opponent).map(|player| player.faction)) {
+            (Some(Faction::Emperor), Some(Faction::Fremen)) => 1,
+            (Some(Faction::Emperor | Faction::Fremen), _) => 2,
+            _ => 1,
+        };
+        let leader = plan.leader.map_or(0, |leader| {
+            self.players[&player_id]
+                .living_leaders
+                .keys()
+                .find(|l| l.id == leader)
+                .map_or(0, |leader| data.leaders[&leader.inner].power as u32)
+        });
+        plan.forces.normal as u32 + plan.forces.special as u32 * special_value + leader
+    }
+
+    /// The sector of the board where a seat is, with seats spread evenly around it.
+    pub fn seat_sector(&self, seat: usize) -> u8 {
+        (seat * 18 / self.seats.len().max(1)) as u8
+    }
+
+    /// Reset everything players may only do once per turn, and start play with whoever's seat the storm will reach
+    /// next. Before the storm has been placed, play starts with the first seat.
+    fn begin_turn(&mut self) {
+        self.shipment_blocked.clear();
+        for player in self.players.values_mut() {
+            player.shipped = false;
+            player.moved = false;
+            player.extra_moves = 0;
+            // Each leader may fight once per turn
+            player.living_leaders.values_mut().for_each(|fought| *fought = false);
+        }
+        let seated = self
+            .seats
+            .iter()
+            .copied()
+            .enumerate()
+            .filter(|(_, player_id)| self.play_order.contains(player_id))
+            .collect::<Vec<_>>();
+        if seated.is_empty() {
+            return;
+        }
+        let first = if self.storm_placed {
+            seated
+                .iter()
+                .position(|(seat, _)| self.seat_sector(*seat) > self.storm_sector)
+                .unwrap_or(0)
+        } else {
+            0
+        };
+        self.play_order = seated[first..]
+            .iter()
+            .chain(&seated[..first])
+            .map(|(_, player_id)| *player_id)
+            .collect();
+    }
+
+    /// Whether anyone has won at the end of the turn. Controlling enough strongholds wins the game, unless the Bene
+    /// Gesserit predicted that winner on this turn.
+    pub fn check_victory(&self, data: &Data) -> Option<EndGameReason> {
+        let control = self.stronghold_control(data);
+        // Allies win together, with whatever strongholds they control between them
+        let mut winners = Vec::new();
+        for player_id in self.play_order.iter() {
+            let group = self.allies(*player_id);
+            let controlled = control.values().filter(|controller| group.contains(controller)).count();
+            if controlled >= self.rules.strongholds_to_win(self.play_order.len(), group.len()) {
+                winners.push(*player_id);
+            }
+        }
+        if winners.is_empty() {
+            return None;
+        }
+        if let (Some(&player_id), Some(faction), Some(turn)) = (
+            self.factions.get(&Faction::BeneGesserit),
+            self.bg_predictions.faction,
+            self.bg_predictions.turn,
+        ) {
+            if turn == self.game_turn && winners.iter().any(|winner| self.players[winner].faction == faction) {
+                return Some(EndGameReason::Prediction { player_id });
+            }
+        }
+        Some(EndGameReason::Victory { winners })
+    }
+
+    /// A player and everyone still playing who they are allied with.
+    pub fn allies(&self, player_id: PlayerId) -> HashSet<PlayerId> {
+        self.alliances
+            .iter()
+            .find(|alliance| alliance.contains(&player_id))
+            .into_iter()
+            .flatten()
+            .chain(std::iter::once(&player_id))
+            .filter(|ally| **ally == player_id || self.play_order.contains(ally))
+            .copied()
+            .collect()
+    }
+
+    /// Strongholds which are occupied by exactly one player.
+    pub fn stronghold_control(&self, data: &Data) -> HashMap<Location, PlayerId> {
+        self.board
+            .iter()
+            .filter(|(location, _)| data.locations[location].terrain == Terrain::Stronghold)
+            .filter_map(|(location, location_state)| {
+                let mut occupants = location_state
+                    .sectors
+                    .values()
+                    .flat_map(|sector| sector.forces.iter())
+                    .filter_map(|(player_id, forces)| forces.occupies().then_some(*player_id))
+                    .collect::<HashSet<_>>()
+                    .into_iter();
+                match (occupants.next(), occupants.next()) {
+                    (Some(player_id), None) => Some((*location, player_id)),
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
+    /// The amount of spice each force collects for the given player. Controlling Arrakeen or Carthag grants
+    /// ornithopters, which increases the rate.
+    pub fn collection_rate(&self, data: &Data, player_id: PlayerId) -> u8 {
+        if self.stronghold_control(data).into_iter().any(|(location, controller)| {
+            controller == player_id && matches!(location, Location::Arrakeen | Location::Carthag)
+        }) {
+            3
+        } else {
+            2
+        }
+    }
+
+    pub fn sector_exists(&self, data: &Data, sector: &LocationSector) -> bool {
+        data.locations
+            .get(&sector.location)
+            .map_or(false, |location| location.sectors.contains_key(&sector.sector))
+    }
+
+    /// Whether the storm is over a sector. The Polar Sink is never in the storm, and nowhere is before the storm has
+    /// been placed.
+    pub fn in_storm(&self, sector: &LocationSector) -> bool {
+        self.storm_placed && sector.location != Location::PolarSink && sector.sector == self.storm_sector
+    }
+
+    /// Destroy whatever the storm sweeps over as it moves through the given sectors. Each sector of a territory is hit
+    /// on its own, so anything in the same territory outside the storm is untouched. Spice is always lost, but rock
+    /// and the strongholds shelter forces until the Shield Wall falls, and the Fremen only lose half their forces.
+    fn sweep_storm(&mut self, data: &Data, swept: &[u8]) {
+        let fremen = self.factions.get(&Faction::Fremen).copied();
+        let exposed = |location: &Location| match data.locations[location].terrain {
+            Terrain::Sand => true,
+            Terrain::PolarSink => false,
+            Terrain::Rock | Terrain::Stronghold => {
+                self.shield_wall_destroyed
+                    && matches!(
+                        location,
+                        Location::ImperialBasin | Location::Arrakeen | Location::Carthag
+                    )
+            }
+        };
+        let locations = self
+            .board
+            .keys()
+            .filter(|location| **location != Location::PolarSink)
+            .map(|location| (*location, exposed(location)))
+            .collect::<Vec<_>>();
+        for (location, exposed) in locations {
+            let location_state = self.board.get_mut(&location).unwrap();
+            for sector in swept {
+                let sector_state = match location_state.sectors.get_mut(sector) {
+                    Some(sector_state) => sector_state,
+                    None => continue,
+                };
+                sector_state.spice = 0;
+                if !exposed {
+                    continue;
+                }
+                for (player_id, forces) in sector_state.forces.iter_mut() {
+                    // Lose normal forces before special ones, in a set order so every client loses the same ones
+                    let mut troops = forces.forces.iter().cloned().collect::<Vec<_>>();
+                    troops.sort_by_key(|troop| (troop.inner.is_special, troop.id));
+                    let lost = if Some(*player_id) == fremen {
+                        (troops.len() + 1) / 2
+                    } else {
+                        troops.len()
+                    };
+                    let tanks = &mut self.players.get_mut(player_id).unwrap().tanks;
+                    for troop in troops.into_iter().take(lost) {
+                        forces.forces.remove(&troop);
+                        tanks.forces.insert(troop);
+                    }
+                }
+                sector_state.forces.retain(|_, forces| !forces.forces.is_empty());
+            }
+            location_state
+                .sectors
+                .retain(|_, sector| !sector.forces.is_empty() || sector.spice > 0);
+        }
+    }
+
+    /// How many of an expansion's tokens a player holds.
+    pub fn resource(&self, player_id: PlayerId, kind: ResourceKind) -> u8 {
+        self.resources
+            .get(&kind)
+            .and_then(|holders| holders.get(&player_id))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Whether anyone could have a decision to make in the current phase.
+    pub fn has_decisions(&self) -> bool {
+        match self.phase {
+            Phase::Revival => self
+                .play_order
+                .iter()
+                .filter_map(|player_id| self.players.get(player_id))
+                .any(|player| !player.tanks.is_empty()),
+            Phase::Bidding(BiddingPhase::DealCards) => !self.decks.treachery.is_empty(),
+            Phase::Bidding(BiddingPhase::Bidding) => !self.bidding_cards.is_empty(),
+            _ => true,
+        }
+    }
+
+    /// Who plays after the given player this round, if anyone. Rounds don't wrap around on their own.
+    pub fn player_after(&self, player_id: PlayerId) -> Option<PlayerId> {
+        let i = self.play_order.iter().position(|id| *id == player_id)?;
+        self.play_order.get(i + 1).copied()
+    }
+
+    /// The phase after the current one. The spice blow is repeated until every blow for the turn has been resolved.
+    pub fn next_phase(&self) -> Phase {
+        if self.phase == Phase::SpiceBlow(SpiceBlowPhase::PlaceSpice) && self.spice_blows + 1 < self.rules.spice_blows {
+            Phase::SpiceBlow(SpiceBlowPhase::Reveal)
+        } else {
+            self.sequence.next(self.phase)
+        }
+    }
+
+    /// The sectors the storm will sweep through when it next moves, on from where it is now. Empty until the
+    /// storm card has been revealed, and once Weather Control has cancelled it.
+    pub fn storm_path(&self) -> Vec<u8> {
+        match (&self.storm_card, self.storm_placed) {
+            (Some(card), true) => self.storm_sweep(card.inner.val),
+            _ => Vec::new(),
+        }
+    }
+
+    /// The sectors the storm passes over as it moves on by the given number of sectors, not counting the one it
+    /// starts from.
+    pub fn storm_sweep(&self, sectors: u8) -> Vec<u8> {
+        (1..=sectors).map(|i| (self.storm_sector + i) % 18).collect()
+    }
+
+    /// How much spice it costs to ship forces from off-planet. The Guild ships at half price.
+    pub fn shipment_cost(&self, data: &Data, player_id: PlayerId, to: Location, forces: usize) -> u8 {
+        // TODO: Fremen don't ship, they send forces onto the Great Flat for free
+        let per_force = if data.locations[&to].terrain == Terrain::Stronghold {
+            1
+        } else {
+            2
+        };
+        let cost = (per_force * forces).min(u8::MAX as usize) as u8;
+        match self.players.get(&player_id).map(|player| player.faction) {
+            Some(Faction::SpacingGuild) => (cost + 1) / 2,
+            _ => cost,
+        }
+    }
+
+    /// How much spice it costs to revive forces and a leader from the tanks. Each faction revives a few normal forces
+    /// for free, but special forces are always paid for, and a leader costs their strength.
+    pub fn revival_cost(&self, data: &Data, player_id: PlayerId, forces: &[Troop], leader: Option<Leader>) -> u8 {
+        let free = self
+            .players
+            .get(&player_id)
+            .map_or(0, |player| data.factions[&player.faction].free_revival as usize);
+        let normal = forces.iter().filter(|force| !force.is_special).count();
+        let special = forces.len() - normal;
+        let cost = REVIVAL_COST * (normal.saturating_sub(free) + special)
+            + leader.map_or(0, |leader| data.leaders[&leader].power as usize);
+        cost.min(u8::MAX as usize) as u8
+    }
+
+    /// Take a traitor card to be dealt, from the deck or from whichever player was holding it as a stand-in for a card
+    /// we weren't told. They are given another stand-in from the deck in its place.
+    fn take_traitor(&mut self, card_id: ObjectId) -> Option<Object<TraitorCard>> {
+        if let Some(card) = self.decks.traitor.take(card_id) {
+            return Some(card);
+        }
+        let holder = self
+            .players
+            .values_mut()
+            .find(|player| player.traitor_cards.contains(&card_id))?;
+        let card = holder.traitor_cards.take(&card_id);
+        if let Some(stand_in) = self.decks.traitor.draw() {
+            holder.traitor_cards.insert(stand_in);
+        }
+        card
+    }
+
+    /// Learn that a player is holding a traitor card. If we only had a stand-in for it, the stand-in goes back in the
+    /// deck.
+    fn reveal_traitor(&mut self, player_id: PlayerId, card_id: ObjectId) {
+        let stand_in = match self.players.get_mut(&player_id) {
+            Some(player) if !player.traitor_cards.contains(&card_id) => player
+                .traitor_cards
+                .iter()
+                .next()
+                .map(|card| card.id)
+                .and_then(|id| player.traitor_cards.take(&id)),
+            _ => return,
+        };
+        if let Some(card) = self.take_traitor(card_id) {
+            if let Some(player) = self.players.get_mut(&player_id) {
+                player.traitor_cards.insert(card);
+            }
+        }
+        if let Some(stand_in) = stand_in {
+            self.decks.traitor.add(stand_in);
+        }
+    }
+
+    /// Take a treachery card to be dealt or won, from the deck or from wherever another card stood in for it: on the
+    /// table up for bid, or in a player's hand. The stand-in is replaced by another from the deck.
+    fn take_treachery(&mut self, card_id: ObjectId) -> Option<Object<TreacheryCard>> {
+        if let Some(card) = self.decks.treachery.take(card_id) {
+            return Some(card);
+        }
+        if let Some(bid_state) = self
+            .bidding_cards
+            .iter_mut()
+            .find(|bid_state| bid_state.card.id == card_id)
+        {
+            let stand_in = self.decks.treachery.draw()?;
+            return Some(std::mem::replace(&mut bid_state.card, stand_in));
+        }
+        let holder = self
+            .players
+            .values_mut()
+            .find(|player| player.treachery_cards.contains(&card_id))?;
+        let card = holder.treachery_cards.take(&card_id);
+        if let Some(stand_in) = self.decks.treachery.draw() {
+            holder.treachery_cards.insert(stand_in);
+        }
+        card
+    }
+
+    /// Learn that a player is holding a treachery card, as they play or discard it. If we only had a stand-in for it,
+    /// the stand-in goes back in the deck.
+    fn reveal_treachery(&mut self, player_id: PlayerId, card_id: ObjectId) {
+        let stand_in = match self.players.get_mut(&player_id) {
+            Some(player) if !player.treachery_cards.contains(&card_id) => player
+                .treachery_cards
+                .iter()
+                .next()
+                .map(|card| card.id)
+                .and_then(|id| player.treachery_cards.take(&id)),
+            _ => return,
+        };
+        if let Some(card) = self.take_treachery(card_id) {
+            if let Some(player) = self.players.get_mut(&player_id) {
+                player.treachery_cards.insert(card);
+            }
+        }
+        if let Some(stand_in) = stand_in {
+            self.decks.treachery.add(stand_in);
+        }
+    }
+
+    /// How many territories a player's forces may move through in one move.
+    pub fn movement_range(&self, player_id: PlayerId) -> usize {
+        let has_ornithopters = [Location::Arrakeen, Location::Carthag].iter().any(|location| {
+            self.board.get(location).map_or(false, |location| {
+                location.sectors.values().any(|sector| {
+                    sector
+                        .forces
+                        .get(&player_id)
+                        .map_or(false, |forces| !forces.forces.is_empty())
+                })
+            })
+        });
+        if has_ornithopters {
+            3
+        } else if self.players.get(&player_id).map(|player| player.faction) == Some(Faction::Fremen) {
+            2
+        } else {
+            1
+        }
+    }
+
+    /// Whether forces can follow a path, stepping between sectors of the same territory or into adjacent
+    /// territories, without entering the storm or going further than the player's movement range.
+    pub fn is_legal_path(&self, data: &Data, player_id: PlayerId, path: &[LocationSector]) -> bool {
+        if path
+            .iter()
+            .any(|sector| !self.sector_exists(data, sector) || self.in_storm(sector))
+        {
+            return false;
+        }
+        let mut territories = 0;
+        for step in path.windows(2) {
+            if step[0].location != step[1].location {
+                if !data.are_adjacent(step[0].location, step[1].location) {
+                    return false;
+                }
+                territories += 1;
+            }
+        }
+        territories <= self.movement_range(player_id)
+    }
+
+    /// The shortest path between two sectors which avoids the storm, regardless of how far it goes.
+    pub fn find_path(&self, data: &Data, from: LocationSector, to: LocationSector) -> Option<Vec<LocationSector>> {
+        let mut previous = HashMap::new();
+        let mut queue = VecDeque::from([from]);
+        previous.insert(from, from);
+        while let Some(current) = queue.pop_front() {
+            if current == to {
+                let mut path = vec![to];
+                while path.last() != Some(&from) {
+                    path.push(previous[path.last().unwrap()]);
+                }
+                path.reverse();
+                return Some(path);
+            }
+            let neighbours = data.locations[&current.location]
+                .adjacent
+                .iter()
+                .chain(std::iter::once(&current.location))
+                .flat_map(|location| {
+                    data.locations[location]
+                        .sectors
+                        .keys()
+                        .map(|&sector| location.with_sector(sector))
+                });
+            for next in neighbours {
+                if !self.in_storm(&next) && !previous.contains_key(&next) {
+                    previous.insert(next, current);
+                    queue.push_back(next);
+                }
+            }
+        }
+        None
+    }
 }
 
 impl EventReduce for GameState {
@@ -132,6 +1040,29 @@ impl EventReduce for GameState {
         use GameEvent::*;
         match event {
             Pass { player_id } => return Some(player_id) == self.active_player.as_ref(),
+            Concede { player_id } => {
+                return !matches!(self.phase, Phase::EndGame) && self.play_order.contains(player_id);
+            }
+            PlayCard { player_id, card_id } => {
+                if let Some(window) = &self.response_window {
+                    let kind = match &window.prompt {
+                        Prompt::PlayCard { kind } => Some(*kind),
+                        Prompt::Karama { .. } => Some(TreacheryCardKind::Karama),
+                        _ => None,
+                    };
+                    if let (Some(kind), true) = (kind, window.can_respond(player_id)) {
+                        if let Some(card) = self.players.get(player_id).and_then(|p| p.treachery_cards.get(card_id)) {
+                            return card.inner.kind == kind;
+                        }
+                    }
+                }
+            }
+            Decline { player_id } => {
+                return self
+                    .response_window
+                    .as_ref()
+                    .map_or(false, |window| window.can_respond(player_id));
+            }
             ChooseFaction { player_id, .. } => {
                 if matches!(self.phase, Phase::Setup(SetupPhase::ChooseFactions)) {
                     return Some(player_id) == self.active_player.as_ref();
@@ -169,40 +1100,129 @@ impl EventReduce for GameState {
                 other_player_id,
                 spice,
             } => {
-                todo!()
+                if player_id != other_player_id && self.players.contains_key(other_player_id) {
+                    if let Some(player) = self.players.get(player_id) {
+                        return *spice > 0 && player.spice >= *spice;
+                    }
+                }
             }
             ShipForces { player_id, to, forces } => {
                 if Some(player_id) == self.active_player.as_ref() {
                     let player = &self.players[player_id];
                     if forces.iter().all(|id| player.offworld_forces.contains(id)) {
                         if matches!(self.phase, Phase::Setup(SetupPhase::PlaceForces)) {
-                            if let Some(possible_locations) =
-                                &data.factions[&player.faction].starting_values.possible_locations
-                            {
-                                if possible_locations.contains(&to.location) {
-                                    return true;
-                                }
-                            } else {
-                                return true;
-                            }
-                        } else {
-                            // TODO: validate ship n' move
+                            let starting_values = &data.factions[&player.faction].starting_values;
+                            let within_limit = starting_values.territory_limit.map_or(true, |limit| {
+                                self.forces_in(*player_id, to.location) + forces.len() <= limit as usize
+                            });
+                            return !forces.is_empty()
+                                && self.sector_exists(data, to)
+                                && within_limit
+                                && starting_values
+                                    .possible_locations
+                                    .as_ref()
+                                    .map_or(true, |possible_locations| possible_locations.contains(&to.location));
+                        } else if matches!(self.phase, Phase::Movement)
+                            && !player.shipped
+                            && !self.shipment_blocked.contains(player_id)
+                            && self.response_window.is_none()
+                        {
+                            return !forces.is_empty()
+                                && self.sector_exists(data, to)
+                                && !self.in_storm(to)
+                                && player.spice >= self.shipment_cost(data, *player_id, to.location, forces.len());
                         }
                     }
                 }
             }
+            AdminAdjust {
+                player_id,
+                target,
+                adjustment,
+            } => {
+                if self.rules.admin_adjustments && self.host == Some(*player_id) {
+                    if let Some(player) = self.players.get(target) {
+                        return match *adjustment {
+                            Adjustment::Spice(delta) => (0..=u8::MAX as i16).contains(&(player.spice as i16 + delta)),
+                            Adjustment::KillForces(count) => {
+                                count > 0 && player.offworld_forces.len() >= count as usize
+                            }
+                            Adjustment::ReviveForces(count) => count > 0 && player.tanks.forces.len() >= count as usize,
+                        };
+                    }
+                }
+            }
             MoveForces {
                 player_id,
                 path,
                 forces,
             } => {
-                todo!()
+                if matches!(self.phase, Phase::Movement) && Some(player_id) == self.active_player.as_ref() {
+                    let player = &self.players[player_id];
+                    if let (true, [from, .., _]) = (player.can_move(), path.as_slice()) {
+                        let owned = self
+                            .board
+                            .get(&from.location)
+                            .and_then(|location| location.sectors.get(&from.sector))
+                            .and_then(|sector| sector.forces.get(player_id))
+                            .map_or(false, |owned| forces.iter().all(|id| owned.forces.contains(id)));
+                        return !forces.is_empty() && owned && self.is_legal_path(data, *player_id, path);
+                    }
+                }
+            }
+            ChooseAdvisors {
+                player_id, location, ..
+            } => {
+                return self.players.get(player_id).map(|player| player.faction) == Some(Faction::BeneGesserit)
+                    && matches!(
+                        self.prompts.get(player_id),
+                        Some(Prompt::AdvisorOrFighter { location: prompted } | Prompt::AdvisorDecision { location: prompted })
+                            if prompted == location
+                    );
+            }
+            RideTheWorm { player_id, from, to } => {
+                if self.prompts.get(player_id) == Some(&Prompt::RideWorm { location: *from }) {
+                    return to.map_or(true, |to| self.sector_exists(data, &to) && !self.in_storm(&to));
+                }
+            }
+            PlayThumper {
+                player_id,
+                card_id,
+                location,
+            } => {
+                // A thumper may be used while worms are surfacing, or on the player's turn to move
+                let timing = match self.phase {
+                    Phase::SpiceBlow(SpiceBlowPhase::ShaiHalud) => !self.worms.is_empty(),
+                    Phase::Movement => self.active_player.as_ref() == Some(player_id),
+                    _ => false,
+                };
+                return timing
+                    && self
+                        .players
+                        .get(player_id)
+                        .and_then(|player| player.treachery_cards.get(card_id))
+                        .map_or(false, |card| card.inner.kind == TreacheryCardKind::Thumper)
+                    && data
+                        .locations
+                        .get(location)
+                        .map_or(false, |location| location.terrain == Terrain::Sand);
+            }
+            PlayHajr { player_id, card_id } => {
+                return matches!(self.phase, Phase::Movement)
+                    && self.active_player.as_ref() == Some(player_id)
+                    && self
+                        .players
+                        .get(player_id)
+                        .and_then(|player| player.treachery_cards.get(card_id))
+                        .map_or(false, |card| card.inner.kind == TreacheryCardKind::Hajr);
             }
             MakeBid { player_id, spice } => {
                 if Some(player_id) == self.active_player.as_ref() {
-                    if let Some(bid_state) = self.bidding_cards.current() {
+                    if let (Some(bid_state), Some(player)) = (self.bidding_cards.current(), self.players.get(player_id))
+                    {
+                        // Nobody may bid more than they could pay
                         if let Some(current_bid) = &bid_state.current_bid {
-                            return *spice > current_bid.spice;
+                            return *spice > current_bid.spice && player.spice >= *spice;
                         }
                     }
                 }
@@ -212,7 +1232,52 @@ impl EventReduce for GameState {
                 forces,
                 leader,
             } => {
-                todo!()
+                let responding = self.response_window.as_ref().map_or(false, |window| {
+                    window.prompt == Prompt::Revival && window.can_respond(player_id)
+                });
+                if let (Phase::Revival, true, Some(player)) = (self.phase, responding, self.players.get(player_id)) {
+                    let revived = player
+                        .tanks
+                        .forces
+                        .iter()
+                        .filter(|force| forces.contains(&force.id))
+                        .map(|force| force.inner)
+                        .collect::<Vec<_>>();
+                    let revived_leader = match leader {
+                        Some(leader) => match player.tanks.leaders.get(leader) {
+                            Some(leader) => Some(leader.inner),
+                            None => return false,
+                        },
+                        None => None,
+                    };
+                    return (!forces.is_empty() || leader.is_some())
+                        && revived.len() == forces.len()
+                        && revived.len() <= MAX_REVIVAL
+                        && revived.iter().filter(|force| force.is_special).count() <= MAX_SPECIAL_REVIVAL
+                        && player.spice >= self.revival_cost(data, *player_id, &revived, revived_leader);
+                }
+            }
+            ChooseBattleOrder { player_id, order } => {
+                if let Some(Prompt::BattleOrder { locations }) = self.prompts.get(player_id) {
+                    return order.len() == locations.len()
+                        && order.iter().collect::<HashSet<_>>() == locations.iter().collect::<HashSet<_>>();
+                }
+            }
+            SpendResource {
+                player_id,
+                kind,
+                amount,
+            } => return self.resource(*player_id, *kind) >= *amount,
+            PeekSpiceBlow { player_id } => {
+                // Only with the advanced rules, and only while the spice is blowing. The window closes once the blow
+                // is being revealed to everyone, and a card already looked at isn't shown again.
+                let top = self.decks.spice.peek().map(|card| card.id);
+                return self.rules.advanced
+                    && matches!(self.phase, Phase::SpiceBlow(_))
+                    && self.phase != Phase::SpiceBlow(SpiceBlowPhase::Reveal)
+                    && self.factions.get(&Faction::Fremen) == Some(player_id)
+                    && top.is_some()
+                    && self.peeked_spice_blow != top;
             }
             SetBattlePlan {
                 player_id,
@@ -220,31 +1285,58 @@ impl EventReduce for GameState {
                 leader,
                 treachery_cards,
             } => {
-                todo!()
+                if let (Some(battle), Some(player)) = (self.battles.front(), self.players.get(player_id)) {
+                    let present = self.forces_present(*player_id, battle.location);
+                    return self.prompts.get(player_id) == Some(&Prompt::BattlePlan { battle: *battle })
+                        && forces.normal <= present.normal
+                        && forces.special <= present.special
+                        && leader.map_or(true, |leader| {
+                            player.living_leaders.get(&leader).map_or(false, |fought| !*fought)
+                        })
+                        && treachery_cards.iter().all(|card| player.treachery_cards.contains(card));
+                }
             }
 
             // These events should only be created by the server, and are always invalid if coming from a client
             ShowPrompt { .. } => (),
+            OpenWindow { .. } => (),
+            CloseWindow => (),
+            RevealCard { .. } => (),
             DealCard { .. } => (),
+            DealTraitor { .. } => (),
             // TODO: there may be situations where clients can send this event
             DiscardCard { .. } => (),
             SetActive { .. } => (),
             SetDeckOrder { .. } => (),
             EndGame { .. } => (),
+            SetRules { .. } => (),
+            SetHost { .. } => (),
+            RevealAll { .. } => (),
+            RevealSpice { .. } => (),
             PlayerJoined { .. } => (),
             PlayerDisconnected { .. } => (),
             SetPlayOrder { .. } => (),
+            AdvanceTurn => (),
             AdvancePhase => (),
             StartBidding => (),
-            RevealStorm => (),
+            RevealStorm { .. } => (),
             MoveStorm { .. } => (),
-            RevealSpiceBlow => (),
+            RevealSpiceBlow { .. } => (),
+            ShowSpiceBlow { .. } => (),
             CollectSpice { .. } => (),
+            GrantResource { .. } => (),
+            PaySpice { .. } => (),
+            CollectBribes => (),
             SpawnObject { .. } => (),
             StartRound => (),
             PlaceSpice { .. } => (),
-            RideTheWorm { .. } => (),
+            Devour { .. } => (),
             WinBid { .. } => (),
+            ReturnBidCard { .. } => (),
+            SetBattles { .. } => (),
+            TraitorChosen { .. } => (),
+            BattlePlanSubmitted { .. } => (),
+            ResolveBattle { .. } => (),
         }
         false
     }
@@ -261,8 +1353,60 @@ impl EventReduce for GameState {
             }
         }
         match event {
-            EndGame { .. } => {
+            EndGame { reason } => {
                 self.phase = Phase::EndGame;
+                self.end_reason.replace(reason);
+            }
+            SetRules { rules } => {
+                self.sequence = rules.phase_sequence();
+                self.rules = rules;
+            }
+            RevealAll {
+                bg_predictions,
+                traitors,
+            } => {
+                self.bg_predictions = bg_predictions;
+                for (player_id, card_ids) in traitors {
+                    for card_id in card_ids {
+                        self.reveal_traitor(player_id, card_id);
+                    }
+                }
+            }
+            RevealSpice { spice, bribe_spice } => {
+                for (player_id, player) in self.players.iter_mut() {
+                    player.spice = spice.get(player_id).copied().unwrap_or_default();
+                    player.bribe_spice = bribe_spice.get(player_id).copied().unwrap_or_default();
+                }
+            }
+            SetHost { player_id } => {
+                self.host.replace(player_id);
+            }
+            AdminAdjust { target, adjustment, .. } => {
+                if let Some(player) = self.players.get_mut(&target) {
+                    match adjustment {
+                        Adjustment::Spice(delta) => {
+                            player.spice = (player.spice as i16 + delta).clamp(0, u8::MAX as i16) as u8;
+                        }
+                        // Take normal forces before special ones, in a fixed order so every client agrees
+                        Adjustment::KillForces(count) => {
+                            let mut forces = player.offworld_forces.iter().cloned().collect::<Vec<_>>();
+                            forces.sort_by_key(|force| (force.inner.is_special, force.id));
+                            for force in forces.into_iter().take(count as usize) {
+                                player.offworld_forces.remove(&force);
+                                player.tanks.forces.insert(force);
+                            }
+                        }
+                        Adjustment::ReviveForces(count) => {
+                            let mut forces = player.tanks.forces.iter().cloned().collect::<Vec<_>>();
+                            forces.sort_by_key(|force| (force.inner.is_special, force.id));
+                            for force in forces.into_iter().take(count as usize) {
+                                player.tanks.forces.remove(&force);
+                                player.offworld_forces.insert(force);
+                            }
+                        }
+                    }
+                }
+                self.adjustments.push((target, adjustment));
             }
             PlayerJoined { .. } => {}
             PlayerDisconnected { player_id } => {
@@ -272,7 +1416,22 @@ impl EventReduce for GameState {
                 self.prompts.insert(player_id, prompt);
             }
             AdvancePhase => {
-                self.phase = self.phase.next();
+                let next = self.next_phase();
+                if self.phase == Phase::SpiceBlow(SpiceBlowPhase::PlaceSpice) {
+                    self.spice_blows += 1;
+                }
+                if self.sequence.turn.last() == Some(&self.phase) {
+                    self.spice_blows = 0;
+                }
+                if self.phase == Phase::Nexus {
+                    self.nexus.take();
+                }
+                // Nobody else sees the predictions which answer these, so they are put away here instead
+                if self.phase == Phase::Setup(SetupPhase::Prediction) {
+                    self.prompts
+                        .retain(|_, prompt| !matches!(prompt, Prompt::FactionPrediction | Prompt::TurnPrediction));
+                }
+                self.phase = next;
                 self.active_player.take();
             }
             SpawnObject { spawn_type } => match spawn_type {
@@ -303,9 +1462,17 @@ impl EventReduce for GameState {
                 }
             },
             SetPlayOrder { play_order } => {
+                self.seats = play_order.clone();
                 self.play_order = play_order;
             }
-            SetDeckOrder { deck_order, deck_type } => match deck_type {
+            AdvanceTurn => {
+                self.game_turn += 1;
+                self.begin_turn();
+            }
+            SetDeckOrder {
+                deck_order: Some(deck_order),
+                deck_type,
+            } => match deck_type {
                 DeckType::Traitor => {
                     self.decks.traitor.set_order(deck_order);
                 }
@@ -319,6 +1486,16 @@ impl EventReduce for GameState {
                     self.decks.spice.set_order(deck_order);
                 }
             },
+            // Without being told the order, all we know is that the discards went back in
+            SetDeckOrder {
+                deck_order: None,
+                deck_type,
+            } => match deck_type {
+                DeckType::Traitor => self.decks.traitor.reshuffle_unseen(),
+                DeckType::Treachery => self.decks.treachery.reshuffle_unseen(),
+                DeckType::Storm => self.decks.storm.reshuffle_unseen(),
+                DeckType::Spice => self.decks.spice.reshuffle_unseen(),
+            },
             ChooseFaction { player_id, faction } => {
                 self.players.remove(&player_id);
                 let faction_data = &data.factions[&faction];
@@ -328,18 +1505,21 @@ impl EventReduce for GameState {
                     Player {
                         faction,
                         spice: faction_data.starting_values.spice,
+                        bribe_spice: Default::default(),
                         treachery_cards: Default::default(),
                         traitor_cards: Default::default(),
                         living_leaders: Default::default(),
                         offworld_forces: Default::default(),
                         shipped: Default::default(),
+                        moved: Default::default(),
+                        extra_moves: Default::default(),
                         tanks: Default::default(),
                         bonuses: Default::default(),
                     },
                 );
                 self.prompts.remove(&player_id);
             }
-            ChooseTraitor { player_id, .. } => {
+            ChooseTraitor { player_id, .. } | TraitorChosen { player_id } => {
                 self.prompts.remove(&player_id);
             }
             MakeFactionPrediction { faction, .. } => {
@@ -354,15 +1534,172 @@ impl EventReduce for GameState {
                 self.active_player.replace(player_id);
             }
             Pass { player_id } => {
-                let current_turn = self.play_order.iter().position(|id| &player_id == id).unwrap();
-                if current_turn + 1 == self.play_order.len() {
-                    self.active_player.take();
-                } else {
-                    self.active_player.replace(self.play_order[current_turn + 1]);
+                // Advisors which were never flipped stay advisors
+                if let Some(Prompt::AdvisorOrFighter { .. } | Prompt::AdvisorDecision { .. }) =
+                    self.prompts.get(&player_id)
+                {
+                    self.prompts.remove(&player_id);
+                }
+                self.active_player = self.player_after(player_id);
+            }
+            Concede { player_id } => {
+                if self.active_player == Some(player_id) {
+                    self.active_player = self.player_after(player_id);
+                }
+                self.play_order.retain(|id| id != &player_id);
+                self.prompts.remove(&player_id);
+                if let Some(window) = self.response_window.as_mut() {
+                    window.eligible.remove(&player_id);
+                }
+                // A bid they can no longer pay for is withdrawn, and the card is bid on again
+                if let Some(bid_state) = self.bidding_cards.last_mut() {
+                    if bid_state.current_bid.as_ref().map(|bid| bid.player_id) == Some(player_id) {
+                        bid_state.current_bid.take();
+                    }
+                }
+                // Nobody is left to fight their battles. If they were in the one being fought, the plans made for it
+                // are thrown away, and an order of battles which still lists theirs is asked for again.
+                let aggressor = self.battles.front().map(|battle| battle.aggressor);
+                if self.battles.front().map_or(false, |battle| battle.involves(player_id)) {
+                    self.battle_plans.clear();
+                    self.committed_plans.clear();
+                    self.prompts
+                        .retain(|_, prompt| !matches!(prompt, Prompt::BattlePlan { .. }));
+                }
+                if self.battles.iter().any(|battle| battle.involves(player_id)) {
+                    self.prompts
+                        .retain(|_, prompt| !matches!(prompt, Prompt::BattleOrder { .. }));
+                }
+                self.battles.retain(|battle| !battle.involves(player_id));
+                if self.battles.front().map(|battle| battle.aggressor) != aggressor {
+                    self.battle_order_chosen = false;
+                }
+                // Their forces leave the board for the tanks and their cards go to the discard piles. They keep their
+                // seat at the table, so what they leave behind can still be seen.
+                if let Some(player) = self.players.get_mut(&player_id) {
+                    for location in self.board.values_mut() {
+                        for sector in location.sectors.values_mut() {
+                            if let Some(Forces { forces, .. }) = sector.forces.remove(&player_id) {
+                                player.tanks.forces.extend(forces);
+                            }
+                        }
+                        location
+                            .sectors
+                            .retain(|_, sector| !sector.forces.is_empty() || sector.spice > 0);
+                    }
+                    self.factions.remove(&player.faction);
+                    for card in player.treachery_cards.drain() {
+                        self.decks.treachery.discard(card);
+                    }
+                    for card in player.traitor_cards.drain() {
+                        self.decks.traitor.discard(card);
+                    }
+                }
+            }
+            PlayCard { player_id, card_id } => {
+                self.reveal_treachery(player_id, card_id);
+                let kind = self.players[&player_id]
+                    .treachery_cards
+                    .get(&card_id)
+                    .unwrap()
+                    .inner
+                    .kind;
+                let karama = match self.response_window.as_ref().map(|window| &window.prompt) {
+                    Some(Prompt::Karama { effect }) => Some(*effect),
+                    _ => None,
+                };
+                // Only one card may be played in a window, so nobody else gets to respond
+                if let Some(window) = self.response_window.as_mut() {
+                    window.responded.extend(window.eligible.iter().copied());
+                }
+                self.prompts.remove(&player_id);
+                match kind {
+                    TreacheryCardKind::WeatherControl => {
+                        // TODO: Let the player move the storm up to 10 sectors instead
+                        if let Some(storm_card) = self.storm_card.take() {
+                            self.decks.storm.discard(storm_card);
+                        }
+                    }
+                    TreacheryCardKind::MakerHook => {
+                        self.worm_riders.insert(player_id);
+                    }
+                    TreacheryCardKind::Karama => match karama {
+                        Some(KaramaEffect::BlockShipment { target }) => {
+                            self.shipment_blocked.insert(target);
+                        }
+                        None => (),
+                    },
+                    TreacheryCardKind::FamilyAtomics => {
+                        if let Some(location_state) = self.board.remove(&Location::ShieldWall) {
+                            for (player_id, Forces { forces, .. }) in
+                                location_state.sectors.into_values().flat_map(|sector| sector.forces)
+                            {
+                                let tanks = &mut self.players.get_mut(&player_id).unwrap().tanks;
+                                tanks.forces.extend(forces);
+                            }
+                        }
+                        self.shield_wall_destroyed = true;
+                    }
+                    _ => (),
+                }
+            }
+            RevealCard { card_id } => {
+                // Played cards are shown to everyone on their way to the discard pile
+                if let Some(card) = self
+                    .players
+                    .values_mut()
+                    .find_map(|player| player.treachery_cards.take(&card_id))
+                {
+                    self.decks.treachery.discard(card);
+                }
+            }
+            Decline { player_id } => {
+                if let Some(window) = self.response_window.as_mut() {
+                    window.responded.insert(player_id);
+                }
+                self.prompts.remove(&player_id);
+            }
+            OpenWindow { window } => {
+                for player_id in window.eligible.iter() {
+                    self.prompts.insert(*player_id, window.prompt.clone());
+                }
+                self.response_window.replace(window);
+            }
+            CloseWindow => {
+                if let Some(window) = self.response_window.take() {
+                    for player_id in window.eligible.iter() {
+                        if self.prompts.get(player_id) == Some(&window.prompt) {
+                            self.prompts.remove(player_id);
+                        }
+                    }
                 }
             }
             StartRound => {
-                self.active_player.replace(self.play_order[0]);
+                self.active_player = self.play_order.first().copied();
+            }
+            GrantResource {
+                player_id,
+                kind,
+                amount,
+            } => {
+                *self.resources.entry(kind).or_default().entry(player_id).or_default() += amount;
+            }
+            SpendResource {
+                player_id,
+                kind,
+                amount,
+            } => {
+                if let Some(holders) = self.resources.get_mut(&kind) {
+                    if let Some(held) = holders.get_mut(&player_id) {
+                        *held = held.saturating_sub(amount);
+                        if *held == 0 {
+                            holders.remove(&player_id);
+                        }
+                    }
+                    if holders.is_empty() {
+                        self.resources.remove(&kind);
+                    }
+                }
             }
             CollectSpice { player_id, spice, from } => {
                 if let Some(from) = from {
@@ -374,15 +1711,44 @@ impl EventReduce for GameState {
                         .or_default()
                         .spice -= spice;
                 }
-                self.players.get_mut(&player_id).unwrap().spice += spice;
+                let player = self.players.get_mut(&player_id).unwrap();
+                player.spice = player.spice.saturating_add(spice);
             }
-            Bribe {
-                player_id,
-                other_player_id,
-                spice,
+            // The spice itself changes hands with a PaySpice
+            Bribe { .. } => (),
+            PaySpice {
+                from,
+                to,
+                amount,
+                reason,
             } => {
-                self.players.get_mut(&player_id).unwrap().spice -= spice;
-                self.players.get_mut(&other_player_id).unwrap().spice += spice;
+                // Nobody can pay more than they have, and only what was paid changes hands
+                if let Some(payer) = self.players.get_mut(&from) {
+                    let paid = amount.min(payer.spice);
+                    payer.spice -= paid;
+                    let faction = payer.faction;
+                    info!(
+                        "{} paid {} spice to {} for {:?}",
+                        faction,
+                        paid,
+                        to.and_then(|to| self.players.get(&to))
+                            .map_or("the bank".to_string(), |to| to.faction.to_string()),
+                        reason
+                    );
+                    if let Some(to) = to.and_then(|to| self.players.get_mut(&to)) {
+                        // Bribes sit behind the shield until the next turn
+                        if reason == PaymentReason::Bribe {
+                            to.bribe_spice = to.bribe_spice.saturating_add(paid);
+                        } else {
+                            to.spice = to.spice.saturating_add(paid);
+                        }
+                    }
+                }
+            }
+            CollectBribes => {
+                for player in self.players.values_mut() {
+                    player.spice = player.spice.saturating_add(std::mem::take(&mut player.bribe_spice));
+                }
             }
             ShipForces { player_id, to, forces } => {
                 let sector = self
@@ -396,13 +1762,18 @@ impl EventReduce for GameState {
                     .entry(self.active_player.unwrap())
                     .or_default();
                 let player = self.players.get_mut(&player_id).unwrap();
+                // Bene Gesserit arrive somewhere new as advisors unless they choose otherwise
+                if sector.forces.is_empty() && player.faction == Faction::BeneGesserit && self.phase == Phase::Movement
+                {
+                    sector.advisors = true;
+                }
                 for force_id in forces {
                     sector.forces.insert(player.offworld_forces.take(&force_id).unwrap());
                 }
                 player.shipped = true;
             }
             MoveForces {
-                player_id: _,
+                player_id,
                 path,
                 forces,
             } => {
@@ -417,11 +1788,13 @@ impl EventReduce for GameState {
                     .forces
                     .get_mut(self.active_player.as_ref().unwrap())
                     .unwrap();
+                let advisors = from.advisors;
                 let forces = forces
                     .into_iter()
                     .map(|id| from.forces.take(&id).unwrap())
                     .collect::<HashSet<_>>();
-                self.board
+                let to = self
+                    .board
                     .entry(to.location)
                     .or_default()
                     .sectors
@@ -429,64 +1802,174 @@ impl EventReduce for GameState {
                     .or_default()
                     .forces
                     .entry(self.active_player.unwrap())
-                    .or_default()
-                    .forces
-                    .extend(forces);
+                    .or_default();
+                if to.forces.is_empty() {
+                    to.advisors = advisors;
+                }
+                to.forces.extend(forces);
+                // The usual move is used up before any granted by a Hajr
+                let player = self.players.get_mut(&player_id).unwrap();
+                if player.moved {
+                    player.extra_moves -= 1;
+                } else {
+                    player.moved = true;
+                }
             }
-            RevealStorm => {
-                self.storm_card.replace(self.decks.storm.draw().unwrap());
+            RevealStorm { card_id } => {
+                self.storm_card.replace(self.decks.storm.take(card_id).unwrap());
             }
             MoveStorm { sectors } => {
+                // Setting the storm down on the first turn sweeps over nothing
+                if self.storm_placed {
+                    let swept = self.storm_sweep(sectors);
+                    self.sweep_storm(data, &swept);
+                }
                 self.storm_sector = (self.storm_sector + sectors) % 18;
+                self.storm_placed = true;
                 if let Some(storm_card) = self.storm_card.take() {
-                    self.decks.storm.add(storm_card);
+                    self.decks.storm.discard(storm_card);
+                }
+            }
+            PeekSpiceBlow { .. } => (),
+            ShowSpiceBlow { card_id, .. } => {
+                // Whatever order we thought the deck was in, this card is on top of it
+                if let Some(card) = self.decks.spice.take(card_id) {
+                    self.decks.spice.add(card);
                 }
+                self.peeked_spice_blow.replace(card_id);
             }
-            RevealSpiceBlow => {
-                let card = self.decks.spice.draw().unwrap();
+            RevealSpiceBlow { card_id } => {
+                self.peeked_spice_blow.take();
+                let card = self.decks.spice.take(card_id).unwrap();
                 if let SpiceCard::ShaiHalud = &card.inner {
-                    if self.game_turn > 0 && self.nexus.is_none() {
-                        self.nexus = self.decks.spice.last_discarded().cloned();
+                    // Worms which surface on the first turn are ignored, and call no Nexus
+                    if self.game_turn > 1 {
+                        // The worm surfaces in the territory of the last spice placed by this blow. Games saved before
+                        // the blows were told apart fall back to the last spice placed by either.
+                        let last_blow = self.spice_piles.get(self.spice_blows as usize).cloned().or_else(|| {
+                            self.decks
+                                .spice
+                                .discard_pile()
+                                .rev()
+                                .find(|card| !matches!(card.inner, SpiceCard::ShaiHalud))
+                                .cloned()
+                        });
+                        if let Some(last_blow) = last_blow {
+                            if let Some(location_data) = data.spice_cards[&last_blow.inner].location_data {
+                                self.worms.push(location_data.location);
+                            }
+                            if self.nexus.is_none() {
+                                self.nexus.replace(last_blow);
+                            }
+                        }
                     }
                 }
                 if let Some(old_card) = self.spice_card.replace(card) {
                     self.decks.spice.discard(old_card);
                 }
             }
-            PlaceSpice {
-                location: LocationSector { location, sector },
-                spice,
-            } => {
+            PlaceSpice { location, spice } => {
                 if let Some(spice_card) = self.spice_card.take() {
+                    // Each blow keeps track of its own last card, for the next worm it reveals
+                    let pile = self.spice_blows as usize;
+                    if pile < self.spice_piles.len() {
+                        self.spice_piles[pile] = spice_card.clone();
+                    } else {
+                        self.spice_piles.push(spice_card.clone());
+                    }
                     self.decks.spice.discard(spice_card);
                 }
-                if self.storm_sector != sector {
+                // Spice blown into the storm is lost, but anywhere else it adds to whatever is already there
+                if !self.in_storm(&location) {
                     self.board
-                        .entry(location)
+                        .entry(location.location)
                         .or_default()
                         .sectors
-                        .entry(sector)
+                        .entry(location.sector)
                         .or_default()
                         .spice += spice;
                 }
             }
-            RideTheWorm { location } => {
-                for forces in self
+            Devour { location } => {
+                // Fremen and anyone holding on with a Maker Hook are never devoured, but everyone else's forces and
+                // the spice are lost
+                let fremen = self.factions.get(&Faction::Fremen).copied();
+                let riders = std::mem::take(&mut self.worm_riders);
+                if let Some(location_state) = self.board.get_mut(&location) {
+                    for sector in location_state.sectors.values_mut() {
+                        sector.spice = 0;
+                        for (player_id, Forces { forces, .. }) in sector
+                            .forces
+                            .drain_filter(|player_id, _| Some(*player_id) != fremen && !riders.contains(player_id))
+                        {
+                            let tanks = &mut self.players.get_mut(&player_id).unwrap().tanks;
+                            tanks.forces.extend(forces);
+                        }
+                    }
+                    location_state.sectors.retain(|_, sector| !sector.forces.is_empty());
+                }
+                if let Some(idx) = self.worms.iter().position(|worm| worm == &location) {
+                    self.worms.remove(idx);
+                }
+            }
+            ChooseAdvisors {
+                player_id,
+                location,
+                advisors,
+            } => {
+                self.prompts.remove(&player_id);
+                if let Some(forces) = self
                     .board
-                    .get_mut(&location)
-                    .unwrap()
-                    .sectors
-                    .drain()
-                    .map(|(_, s)| s.forces)
+                    .get_mut(&location.location)
+                    .and_then(|location_state| location_state.sectors.get_mut(&location.sector))
+                    .and_then(|sector| sector.forces.get_mut(&player_id))
                 {
-                    for (player_id, Forces { forces }) in forces {
-                        let tanks = &mut self.players.get_mut(&player_id).unwrap().tanks;
-                        tanks.forces.extend(forces);
+                    forces.advisors = advisors;
+                }
+            }
+            PlayThumper {
+                player_id,
+                card_id,
+                location,
+            } => {
+                self.reveal_treachery(player_id, card_id);
+                self.worms.push(location);
+            }
+            PlayHajr { player_id, card_id } => {
+                self.reveal_treachery(player_id, card_id);
+                if let Some(player) = self.players.get_mut(&player_id) {
+                    player.extra_moves += 1;
+                }
+            }
+            RideTheWorm { player_id, from, to } => {
+                self.prompts.remove(&player_id);
+                if let Some(to) = to {
+                    let mut riders = HashSet::new();
+                    if let Some(location_state) = self.board.get_mut(&from) {
+                        for sector in location_state.sectors.values_mut() {
+                            if let Some(Forces { forces, .. }) = sector.forces.remove(&player_id) {
+                                riders.extend(forces);
+                            }
+                        }
+                        location_state
+                            .sectors
+                            .retain(|_, sector| !sector.forces.is_empty() || sector.spice > 0);
                     }
+                    self.board
+                        .entry(to.location)
+                        .or_default()
+                        .sectors
+                        .entry(to.sector)
+                        .or_default()
+                        .forces
+                        .entry(player_id)
+                        .or_default()
+                        .forces
+                        .extend(riders);
                 }
             }
             StartBidding => {
-                for _ in 0..self.players.len() {
+                for _ in 0..self.play_order.len() {
                     if let Some(card) = self.decks.treachery.draw() {
                         self.bidding_cards.push(BidState {
                             card,
@@ -504,20 +1987,35 @@ impl EventReduce for GameState {
                     bid_state.current_bid.replace(Bid { player_id, spice });
                 }
             }
-            WinBid { player_id, .. } => {
+            WinBid { player_id, card_id } => {
                 let bid_state = self.bidding_cards.win().unwrap();
-                self.players
-                    .get_mut(&player_id)
-                    .unwrap()
-                    .treachery_cards
-                    .insert(bid_state.card);
+                // The card on the table may only have stood in for the one the winner is told they won
+                let card = match card_id {
+                    Some(card_id) if card_id != bid_state.card.id => {
+                        self.decks.treachery.add(bid_state.card);
+                        self.take_treachery(card_id)
+                    }
+                    _ => Some(bid_state.card),
+                };
+                if let Some(card) = card {
+                    self.players.get_mut(&player_id).unwrap().treachery_cards.insert(card);
+                }
+            }
+            ReturnBidCard { .. } => {
+                if let Some(bid_state) = self.bidding_cards.unsold() {
+                    self.decks.treachery.put_bottom(bid_state.card);
+                }
             }
             Revive {
                 player_id,
                 forces,
                 leader,
             } => {
-                let player = self.players.get_mut(self.active_player.as_ref().unwrap()).unwrap();
+                if let Some(window) = self.response_window.as_mut() {
+                    window.responded.insert(player_id);
+                }
+                self.prompts.remove(&player_id);
+                let player = self.players.get_mut(&player_id).unwrap();
                 if let Some(leader) = leader {
                     player
                         .living_leaders
@@ -527,29 +2025,115 @@ impl EventReduce for GameState {
                     .offworld_forces
                     .extend(player.tanks.forces.drain_filter(|f| forces.contains(&f.id)));
             }
+            SetBattles { battles } => {
+                self.battles = battles.into();
+                self.battle_order_chosen = false;
+            }
+            ChooseBattleOrder { player_id, order } => {
+                self.prompts.remove(&player_id);
+                self.battle_order_chosen = true;
+                // Only this player's battles are reordered, and they are still fought before anyone else's
+                let (mut theirs, others): (Vec<_>, Vec<_>) =
+                    self.battles.drain(..).partition(|battle| battle.aggressor == player_id);
+                theirs.sort_by_key(|battle| order.iter().position(|location| *location == battle.location));
+                self.battles.extend(theirs.into_iter().chain(others));
+            }
             SetBattlePlan {
                 player_id,
                 forces,
                 leader,
                 treachery_cards,
-            } => todo!(),
-            DealCard { player_id, from } => {
-                let player = self.players.get_mut(&player_id).unwrap();
+            } => {
+                self.prompts.remove(&player_id);
+                self.battle_plans.insert(
+                    player_id,
+                    BattlePlan {
+                        forces,
+                        leader,
+                        treachery_cards,
+                    },
+                );
+            }
+            BattlePlanSubmitted { player_id } => {
+                self.prompts.remove(&player_id);
+                self.committed_plans.insert(player_id);
+            }
+            ResolveBattle { plans } => {
+                for (player_id, plan) in plans.iter() {
+                    for card_id in plan.treachery_cards.iter() {
+                        self.reveal_treachery(*player_id, *card_id);
+                    }
+                }
+                self.battle_plans = plans;
+                if let Some(battle) = self.battles.pop_front() {
+                    self.resolve_battle(data, battle);
+                    // Whoever else is in the territory fights next, in their turn
+                    if let Some((aggressor, defender)) = self.combatants(battle.location) {
+                        let idx = self
+                            .battles
+                            .iter()
+                            .position(|queued| self.storm_order(queued.aggressor) > self.storm_order(aggressor))
+                            .unwrap_or(self.battles.len());
+                        self.battles.insert(
+                            idx,
+                            Battle {
+                                location: battle.location,
+                                aggressor,
+                                defender,
+                            },
+                        );
+                    }
+                    if self.battles.front().map(|next| next.aggressor) != Some(battle.aggressor) {
+                        self.battle_order_chosen = false;
+                    }
+                }
+                self.battle_plans.clear();
+                self.committed_plans.clear();
+            }
+            DealCard {
+                player_id,
+                from,
+                card_id,
+            } => {
+                // Without being told the card, any card from the deck stands in for it
                 match from {
                     DeckType::Traitor => {
-                        if let Some(card) = self.decks.traitor.draw() {
+                        let card = match card_id {
+                            Some(card_id) => self.take_traitor(card_id),
+                            None => self.decks.traitor.draw(),
+                        };
+                        if let (Some(card), Some(player)) = (card, self.players.get_mut(&player_id)) {
                             player.traitor_cards.insert(card);
                         }
                     }
                     DeckType::Treachery => {
-                        if let Some(card) = self.decks.treachery.draw() {
+                        let card = match card_id {
+                            Some(card_id) => self.take_treachery(card_id),
+                            None => self.decks.treachery.draw(),
+                        };
+                        if let (Some(card), Some(player)) = (card, self.players.get_mut(&player_id)) {
                             player.treachery_cards.insert(card);
                         }
                     }
                     _ => unreachable!(),
                 }
             }
+            DealTraitor { player_id, card_id } => {
+                // Without being told the card, any card from the deck stands in for it
+                let card = match card_id {
+                    Some(card_id) => self.take_traitor(card_id),
+                    None => self.decks.traitor.draw(),
+                };
+                if let (Some(card), Some(player)) = (card, self.players.get_mut(&player_id)) {
+                    player.traitor_cards.insert(card);
+                }
+            }
             DiscardCard { player_id, card_id, to } => {
+                match to {
+                    DeckType::Traitor => self.reveal_traitor(player_id, card_id),
+                    DeckType::Treachery => self.reveal_treachery(player_id, card_id),
+                    _ => (),
+                }
                 let player = self.players.get_mut(&player_id).unwrap();
                 match to {
                     DeckType::Traitor => {
@@ -569,6 +2153,12 @@ impl EventReduce for GameState {
     }
 }
 
+#[derive(Debug, Error)]
+pub enum StateError {
+    #[error("invalid event: {0:?}")]
+    InvalidEvent(GameEvent),
+}
+
 pub trait EventReduce {
     type Event;
 
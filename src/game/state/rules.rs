@@ -0,0 +1,109 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use strum::Display;
+
+use super::{SMALL_GAME, STRONGHOLDS_TO_WIN};
+use crate::{
+    components::LocationSector,
+    game::{phase::PhaseSequence, save::SaveError},
+};
+
+/// Optional rules chosen by the host before the game starts. Everything defaults to the base game.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RuleSet {
+    /// Keep score of the enemy leaders each player kills in battle.
+    pub kanly: bool,
+    /// Play with the advanced faction powers.
+    pub advanced: bool,
+    /// Blow the spice before the storm moves, rather than after.
+    pub spice_blow_first: bool,
+    /// How many spice blows there are each turn.
+    pub spice_blows: u8,
+    /// Keep each player's spice behind their shield until the game ends.
+    pub hidden_spice: bool,
+    /// Play with tech tokens, which give whoever holds them an edge.
+    pub tech_tokens: bool,
+    /// Wait for everyone to be ready before starting each new turn.
+    pub mentat_pause: bool,
+    /// How players are seated around the board, which decides the order they play in all game.
+    pub seating: Seating,
+    /// Let the host correct spice and forces by hand, for rules the game doesn't handle yet.
+    pub admin_adjustments: bool,
+    /// Where spice regrows at the end of every Collection phase, as sandtrout seed the desert again. None regrows
+    /// in the base game.
+    pub spice_regrowth: Vec<SpiceRegrowth>,
+    /// The phases to play through, if not those of the base game.
+    pub phases: Option<PhaseSequence>,
+    /// How many strongholds a player on their own must control to win, if not the usual number.
+    pub strongholds_to_win: Option<u8>,
+}
+
+impl Default for RuleSet {
+    fn default() -> Self {
+        Self {
+            kanly: false,
+            advanced: false,
+            spice_blow_first: false,
+            spice_blows: 1,
+            hidden_spice: false,
+            tech_tokens: false,
+            mentat_pause: false,
+            seating: Seating::Random,
+            admin_adjustments: false,
+            spice_regrowth: Vec::new(),
+            phases: None,
+            strongholds_to_win: None,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Display)]
+pub enum Seating {
+    /// Seats are shuffled when the game starts.
+    Random,
+    /// Players sit in the order they joined the lobby.
+    #[strum(serialize = "Join Order")]
+    JoinOrder,
+}
+
+impl Seating {
+    /// The next way of seating players, to cycle through them in the lobby.
+    pub fn next(self) -> Self {
+        match self {
+            Seating::Random => Seating::JoinOrder,
+            Seating::JoinOrder => Seating::Random,
+        }
+    }
+}
+
+/// Spice which regrows in a sector once it has all been collected.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpiceRegrowth {
+    pub location: LocationSector,
+    pub spice: u8,
+}
+
+impl RuleSet {
+    pub fn read(path: impl AsRef<Path>) -> Result<Self, SaveError> {
+        Ok(ron::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    /// The phases a game with these rules steps through.
+    pub fn phase_sequence(&self) -> PhaseSequence {
+        self.phases.clone().unwrap_or_else(|| PhaseSequence::new(self))
+    }
+
+    /// How many strongholds a group of players must control between them at the end of a turn to win, in a game with
+    /// the given number of players. A player on their own needs one more in a small game, and every ally they have
+    /// adds one more again.
+    pub fn strongholds_to_win(&self, players: usize, group_size: usize) -> usize {
+        let solo = match self.strongholds_to_win {
+            Some(strongholds) => strongholds as usize,
+            None if players <= SMALL_GAME => STRONGHOLDS_TO_WIN + 1,
+            None => STRONGHOLDS_TO_WIN,
+        };
+        solo + group_size.saturating_sub(1)
+    }
+}
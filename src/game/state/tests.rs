@@ -0,0 +1,269 @@
+//! Events checked one at a time against the state, without playing a game.
+
+use super::*;
+use crate::{components::StormCard, game::ObjectIdGenerator};
+
+#[test]
+fn hidden_spice_is_only_told_to_those_involved() {
+    let (briber, bribed, collector) = (PlayerId(1), PlayerId(2), PlayerId(3));
+    let events = vec![
+        GameEvent::Bribe {
+            player_id: briber,
+            other_player_id: bribed,
+            spice: 3,
+        },
+        GameEvent::PaySpice {
+            from: briber,
+            to: Some(bribed),
+            amount: 3,
+            reason: PaymentReason::Bribe,
+        },
+        GameEvent::CollectSpice {
+            player_id: collector,
+            spice: 2,
+            from: None,
+        },
+        GameEvent::CollectSpice {
+            player_id: collector,
+            spice: 2,
+            from: Some(Location::HaggaBasin.with_sector(11)),
+        },
+    ];
+    let told = |player_id, rules: &RuleSet| {
+        events
+            .iter()
+            .filter(|event| event.visible_to(player_id, rules))
+            .map(|event| event.redacted_for(player_id))
+            .collect::<Vec<_>>()
+    };
+
+    // Spice is public unless the rules say otherwise
+    let rules = RuleSet::default();
+    for player_id in [briber, bribed, collector] {
+        assert_eq!(told(player_id, &rules), events);
+    }
+
+    let rules = RuleSet {
+        hidden_spice: true,
+        ..Default::default()
+    };
+    let bribe_and_board = vec![events[0].clone(), events[1].clone(), events[3].clone()];
+    assert_eq!(told(briber, &rules), bribe_and_board);
+    assert_eq!(told(bribed, &rules), bribe_and_board);
+    // Spice taken from the board is seen leaving it, even by those who didn't collect it
+    assert_eq!(told(collector, &rules), events[2..].to_vec());
+    assert!(audit_events(&events, &[briber, bribed, collector], &rules).is_empty());
+}
+
+#[test]
+fn deck_orders_and_face_down_cards_are_only_known_to_the_server() {
+    let (dealt_to, other) = (PlayerId(1), PlayerId(2));
+    let mut ids = ObjectIdGenerator::default();
+    let card_id = ids.spawn(StormCard { val: 1 }).id;
+    let events = vec![
+        GameEvent::SetDeckOrder {
+            deck_order: Some(vec![card_id]),
+            deck_type: DeckType::Treachery,
+        },
+        GameEvent::DealCard {
+            player_id: dealt_to,
+            from: DeckType::Treachery,
+            card_id: Some(card_id),
+        },
+        GameEvent::WinBid {
+            player_id: dealt_to,
+            card_id: Some(card_id),
+        },
+        GameEvent::ReturnBidCard { card_id: Some(card_id) },
+    ];
+    let rules = RuleSet::default();
+    let told = |player_id| {
+        events
+            .iter()
+            .filter(|event| event.visible_to(player_id, &rules))
+            .map(|event| event.redacted_for(player_id))
+            .collect::<Vec<_>>()
+    };
+
+    // Everyone sees the deck shuffled and the cards change hands, but only the player given a card is told which
+    let shuffled = GameEvent::SetDeckOrder {
+        deck_order: None,
+        deck_type: DeckType::Treachery,
+    };
+    let returned = GameEvent::ReturnBidCard { card_id: None };
+    assert_eq!(
+        told(dealt_to),
+        vec![shuffled.clone(), events[1].clone(), events[2].clone(), returned.clone()]
+    );
+    assert_eq!(
+        told(other),
+        vec![
+            shuffled,
+            GameEvent::DealCard {
+                player_id: dealt_to,
+                from: DeckType::Treachery,
+                card_id: None,
+            },
+            GameEvent::WinBid {
+                player_id: dealt_to,
+                card_id: None,
+            },
+            returned,
+        ]
+    );
+    assert!(audit_events(&events, &[dealt_to, other], &rules).is_empty());
+    // Sent as the server knows them, the audit catches every one
+    assert!(events.iter().all(|event| event.leak_to(other, &rules).is_some()));
+}
+
+#[test]
+fn victory_takes_more_strongholds_in_small_games_and_for_allies() {
+    let rules = RuleSet::default();
+    assert_eq!(rules.strongholds_to_win(6, 1), 3);
+    assert_eq!(rules.strongholds_to_win(4, 1), 3);
+    assert_eq!(rules.strongholds_to_win(3, 1), 4);
+    assert_eq!(rules.strongholds_to_win(2, 1), 4);
+    assert_eq!(rules.strongholds_to_win(6, 2), 4);
+    assert_eq!(rules.strongholds_to_win(6, 3), 5);
+    assert_eq!(rules.strongholds_to_win(3, 2), 5);
+
+    // Asking for a number replaces the usual one whatever the size of the game
+    let rules = RuleSet {
+        strongholds_to_win: Some(2),
+        ..Default::default()
+    };
+    assert_eq!(rules.strongholds_to_win(2, 1), 2);
+    assert_eq!(rules.strongholds_to_win(6, 1), 2);
+    assert_eq!(rules.strongholds_to_win(6, 2), 3);
+}
+
+#[test]
+fn rules_arrive_intact_over_the_network() {
+    for rules in [
+        RuleSet::default(),
+        RuleSet {
+            strongholds_to_win: Some(2),
+            ..Default::default()
+        },
+    ] {
+        let event = GameEvent::SetRules { rules };
+        let sent = bincode::serialize(&event).unwrap();
+        assert_eq!(bincode::deserialize::<GameEvent>(&sent).unwrap(), event);
+    }
+}
+
+#[test]
+fn the_end_of_the_game_arrives_intact_over_the_network() {
+    let traitors = HashMap::from([(PlayerId(1), HashSet::from([ObjectId(3), ObjectId(5)]))]);
+    for bg_predictions in [
+        BeneGesseritPredictions::default(),
+        BeneGesseritPredictions {
+            faction: Some(Faction::Atreides),
+            turn: None,
+        },
+        BeneGesseritPredictions {
+            faction: Some(Faction::Fremen),
+            turn: Some(4),
+        },
+    ] {
+        let event = GameEvent::RevealAll {
+            bg_predictions,
+            traitors: traitors.clone(),
+        };
+        let sent = bincode::serialize(&event).unwrap();
+        assert_eq!(bincode::deserialize::<GameEvent>(&sent).unwrap(), event);
+    }
+}
+
+/// A storm deck of cards worth 1 to `count`, added in that order so the last is on top.
+fn storm_deck(count: u8) -> (Deck<StormCard>, Vec<ObjectId>) {
+    let mut ids = ObjectIdGenerator::default();
+    let mut deck = Deck::default();
+    let mut order = Vec::new();
+    for val in 1..=count {
+        let card = ids.spawn(StormCard { val });
+        order.push(card.id);
+        deck.add(card);
+    }
+    (deck, order)
+}
+
+#[test]
+fn cards_are_drawn_from_the_top() {
+    let (mut deck, order) = storm_deck(3);
+    assert_eq!(deck.card_ids(), order);
+    assert_eq!(deck.peek().map(|card| card.id), Some(order[2]));
+    assert_eq!(deck.draw().map(|card| card.inner.val), Some(3));
+    assert_eq!(deck.draw().map(|card| card.inner.val), Some(2));
+    assert_eq!(deck.card_ids(), vec![order[0]]);
+    assert!(!deck.is_empty());
+    deck.draw();
+    assert!(deck.is_empty());
+    assert!(deck.draw().is_none());
+    assert!(deck.peek().is_none());
+}
+
+#[test]
+fn a_card_can_be_taken_from_anywhere_in_the_deck() {
+    let (mut deck, order) = storm_deck(3);
+    assert_eq!(deck.take(order[1]).map(|card| card.inner.val), Some(2));
+    assert_eq!(deck.card_ids(), vec![order[0], order[2]]);
+    assert!(deck.take(order[1]).is_none());
+}
+
+#[test]
+fn discarded_cards_still_belong_to_the_deck() {
+    let (mut deck, order) = storm_deck(3);
+    let first = deck.draw().unwrap();
+    let second = deck.draw().unwrap();
+    deck.discard(first);
+    deck.discard(second);
+    assert!(deck.is_discarded(order[2]) && deck.is_discarded(order[1]));
+    assert!(!deck.is_discarded(order[0]));
+    assert_eq!(
+        deck.discard_pile().map(|card| card.id).collect::<Vec<_>>(),
+        vec![order[2], order[1]]
+    );
+    assert_eq!(deck.last_discarded().map(|card| card.id), Some(order[1]));
+    // Whether still to be drawn or discarded, every card can be found
+    assert!(order.iter().all(|id| deck.get(*id).is_some()));
+    assert_eq!(deck.iter().count(), 3);
+}
+
+#[test]
+fn a_card_put_on_the_bottom_is_drawn_last() {
+    let (mut deck, order) = storm_deck(2);
+    let top = deck.draw().unwrap();
+    deck.put_bottom(top);
+    assert_eq!(deck.card_ids(), vec![order[1], order[0]]);
+    assert_eq!(deck.draw().map(|card| card.id), Some(order[0]));
+    assert_eq!(deck.draw().map(|card| card.id), Some(order[1]));
+}
+
+#[test]
+fn discards_are_shuffled_back_beneath_what_is_left() {
+    let (mut deck, order) = storm_deck(4);
+    for _ in 0..3 {
+        let card = deck.draw().unwrap();
+        deck.discard(card);
+    }
+    // Only the cards asked for come back, in the order asked for
+    deck.reshuffle_discard([order[1], order[3]]);
+    assert_eq!(deck.card_ids(), vec![order[1], order[3], order[0]]);
+    assert_eq!(
+        deck.discard_pile().map(|card| card.id).collect::<Vec<_>>(),
+        vec![order[2]]
+    );
+}
+
+#[test]
+fn setting_the_order_brings_back_every_card_in_it() {
+    let (mut deck, order) = storm_deck(3);
+    let card = deck.draw().unwrap();
+    deck.discard(card);
+    let shuffled = vec![order[2], order[0], order[1]];
+    deck.set_order(shuffled.clone());
+    assert_eq!(deck.card_ids(), shuffled);
+    assert!(deck.discard_pile().next().is_none());
+    assert_eq!(deck.draw().map(|card| card.id), Some(order[1]));
+}
@@ -0,0 +1,124 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    save::SaveError,
+    state::{EndGameReason, GameState, PlayerId},
+};
+use crate::components::Faction;
+
+/// Where the host keeps the results of every game it has finished.
+const STATS_FILE: &str = "stats.ron";
+
+/// The results of every finished game, kept so a regular group can see how they're doing over many games.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Stats {
+    pub games: Vec<GameRecord>,
+}
+
+/// How one game turned out.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GameRecord {
+    pub turns: u8,
+    pub players: Vec<PlayerRecord>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PlayerRecord {
+    pub name: String,
+    pub faction: Faction,
+    pub won: bool,
+}
+
+/// How many games someone, or some faction, has played and won.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+struct Tally {
+    played: usize,
+    won: usize,
+}
+
+impl GameRecord {
+    /// Record a game which has ended, naming players by the names they joined with.
+    pub fn new(state: &GameState, names: &HashMap<PlayerId, String>) -> Self {
+        let winners = winners(state);
+        let mut players = state
+            .factions
+            .iter()
+            .map(|(faction, player_id)| PlayerRecord {
+                name: names
+                    .get(player_id)
+                    .cloned()
+                    .unwrap_or_else(|| format!("Player {}", player_id)),
+                faction: *faction,
+                won: winners.contains(player_id),
+            })
+            .collect::<Vec<_>>();
+        players.sort_by_key(|player| player.faction.to_string());
+        Self {
+            turns: state.game_turn,
+            players,
+        }
+    }
+}
+
+/// Everyone who won the game. Allies who win together are all credited, as is the last player left when everyone else
+/// concedes. Nobody wins a game which was abandoned.
+fn winners(state: &GameState) -> Vec<PlayerId> {
+    match &state.end_reason {
+        Some(EndGameReason::Victory { winners }) => winners.clone(),
+        Some(EndGameReason::Prediction { player_id }) => vec![*player_id],
+        Some(EndGameReason::Conceded { .. }) => state.play_order.clone(),
+        Some(EndGameReason::PlayerLeft { .. }) | None => Vec::new(),
+    }
+}
+
+impl Stats {
+    /// The stats kept so far, which are empty until a game has been finished.
+    pub fn read() -> Result<Self, SaveError> {
+        match std::fs::read_to_string(STATS_FILE) {
+            Ok(stats) => Ok(ron::from_str(&stats)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Add a finished game to the stats on disk.
+    pub fn record(game: GameRecord) -> Result<(), SaveError> {
+        let mut stats = Self::read()?;
+        stats.games.push(game);
+        std::fs::write(STATS_FILE, ron::ser::to_string_pretty(&stats, Default::default())?)?;
+        Ok(())
+    }
+
+    /// Wins and games played for each player and each faction, and how long games tend to last.
+    pub fn summary(&self) -> String {
+        if self.games.is_empty() {
+            return "No games have been finished yet.".to_string();
+        }
+        let mut players = BTreeMap::<String, Tally>::new();
+        let mut factions = BTreeMap::<String, Tally>::new();
+        for player in self.games.iter().flat_map(|game| game.players.iter()) {
+            for tally in [
+                players.entry(player.name.clone()).or_default(),
+                factions.entry(player.faction.to_string()).or_default(),
+            ] {
+                tally.played += 1;
+                tally.won += player.won as usize;
+            }
+        }
+        let turns = self.games.iter().map(|game| game.turns as usize).sum::<usize>();
+        let mut s = format!(
+            "{} games, lasting {:.1} turns on average",
+            self.games.len(),
+            turns as f32 / self.games.len() as f32
+        );
+        for (heading, tallies) in [("Players", players), ("Factions", factions)] {
+            s += format!("\n\n{}:", heading).as_str();
+            for (name, Tally { played, won }) in tallies {
+                s += format!("\n  {}: won {} of {}", name, won, played).as_str();
+            }
+        }
+        s
+    }
+}
@@ -1,13 +1,25 @@
+use std::time::Duration;
+
 use bevy::{prelude::*, render::camera::Camera};
 use bevy_mod_picking::PickingEvent;
-use iyes_loopless::prelude::IntoConditionalSystem;
-use renet::RenetClient;
+use iyes_loopless::prelude::{AppLooplessStateExt, IntoConditionalSystem};
 
+#[cfg(feature = "debug")]
+use crate::{
+    components::LocationSector,
+    game::{phase::storm::StormPreview, save::StateDump},
+    network::{EventLog, RenetServer},
+};
 use crate::{
     data::{CameraNode, Data},
-    game::state::{GameEvent, PlayerId},
+    game::{
+        bot::legal_events,
+        phase::Phase,
+        state::{GameEvent, GameState, PlayerId, Prompt},
+    },
     lerper::{Lerp, Lerper},
-    network::SendEvent,
+    network::{GameClient, RejectedEvent},
+    settings::Settings,
     Screen,
 };
 
@@ -17,10 +29,20 @@ impl Plugin for GameInputPlugin {
     fn build(&self, app: &mut App) {
         app.add_system(lookaround.run_in_state(Screen::Game))
             .add_system(camera_reset.run_in_state(Screen::Game))
-            .add_system(pass.run_in_state(Screen::Game));
+            .add_system(shortcuts.run_in_state(Screen::Game))
+            .add_system(concede.run_in_state(Screen::Game))
+            .add_enter_system(Screen::Game, init_action_palette)
+            .add_system(action_palette.run_in_state(Screen::Game))
+            .add_enter_system(Screen::Game, init_auto_pass_text)
+            .add_system(toggle_auto_pass.run_in_state(Screen::Game))
+            .add_system(auto_pass.run_in_state(Screen::Game))
+            .add_enter_system(Screen::Game, init_rejection_text)
+            .add_system(rejection_text.run_in_state(Screen::Game));
 
         #[cfg(feature = "debug")]
-        app.add_system(debug_restart.run_in_state(Screen::Game));
+        app.add_system(debug_restart.run_in_state(Screen::Game))
+            .add_system(debug_dump.run_in_state(Screen::Game))
+            .add_system(debug_pick_meshes.run_in_state(Screen::Game));
     }
 }
 
@@ -30,6 +52,69 @@ fn debug_restart(keyboard_input: Res<Input<KeyCode>>) {
     }
 }
 
+/// Dump the game state for a bug report, along with the server's if we're hosting, and copy a summary to the clipboard.
+#[cfg(feature = "debug")]
+fn debug_dump(
+    keyboard_input: Res<Input<KeyCode>>,
+    game_state: Res<GameState>,
+    event_log: Res<EventLog>,
+    server: Option<Res<RenetServer>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F2) {
+        let dump = StateDump::new(&game_state, &event_log.events);
+        match dump.write("client") {
+            Ok(path) => {
+                info!("Dumped the game state to {}", path.display());
+                let summary = format!("{}\nFull state: {}", dump.summary(), path.display());
+                if let Err(e) = arboard::Clipboard::new().and_then(|mut clipboard| clipboard.set_text(summary)) {
+                    error!("Failed to copy the state summary to the clipboard: {}", e);
+                }
+            }
+            Err(e) => error!("Failed to dump the game state: {}", e),
+        }
+        if let Some(server) = server {
+            server.request_dump();
+        }
+    }
+}
+
+/// The tint of the sector meshes when they are shown, so the regions which can be clicked can be seen.
+#[cfg(feature = "debug")]
+const PICK_MESH_COLOR: Color = Color::rgba(1.0, 0.0, 1.0, 0.3);
+
+/// Show or hide the otherwise invisible meshes the sectors are picked with, to see where clicks will land.
+#[cfg(feature = "debug")]
+fn debug_pick_meshes(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut shown: Local<bool>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    sectors: Query<&Handle<StandardMaterial>, (With<LocationSector>, Without<StormPreview>)>,
+) {
+    let toggled = keyboard_input.just_pressed(KeyCode::F3);
+    if toggled {
+        *shown = !*shown;
+    }
+    // The storm preview clears whatever tint a sector had once the storm has passed, so keep putting it back
+    if !toggled && !*shown {
+        return;
+    }
+    let color = if *shown {
+        PICK_MESH_COLOR
+    } else {
+        Color::rgba(1.0, 1.0, 1.0, 0.0)
+    };
+    for handle in sectors.iter() {
+        if materials
+            .get(handle)
+            .map_or(false, |material| material.base_color != color)
+        {
+            if let Some(material) = materials.get_mut(handle) {
+                material.base_color = color;
+            }
+        }
+    }
+}
+
 fn lookaround(
     mut camera: Query<&mut Lerper, With<Camera>>,
     nodes: Query<&CameraNode>,
@@ -61,17 +146,348 @@ fn lookaround(
     }
 }
 
-fn camera_reset(data: Res<Data>, keyboard_input: Res<Input<KeyCode>>, mut camera: Query<&mut Lerper, With<Camera>>) {
-    if keyboard_input.just_pressed(KeyCode::Escape) {
+fn camera_reset(
+    data: Res<Data>,
+    settings: Res<Settings>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut camera: Query<&mut Lerper, With<Camera>>,
+) {
+    if keyboard_input.just_pressed(settings.key_bindings.cancel) {
         if let Some(mut lerper) = camera.iter_mut().next() {
             lerper.set_if_empty(Lerp::move_camera(data.camera_nodes.main, 1.0));
         }
     }
 }
 
-// Temporary pass input, TODO replace with a button or something
-fn pass(keyboard_input: Res<Input<KeyCode>>, mut client: ResMut<RenetClient>, my_id: Res<PlayerId>) {
-    if keyboard_input.just_pressed(KeyCode::P) {
-        client.send_event(GameEvent::Pass { player_id: *my_id });
+/// Something a key can do, given what we are able to do right now.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Shortcut {
+    Pass,
+    Confirm,
+    EndTurn,
+}
+
+impl Shortcut {
+    const ALL: [Shortcut; 3] = [Shortcut::Pass, Shortcut::Confirm, Shortcut::EndTurn];
+
+    fn key(&self, settings: &Settings) -> KeyCode {
+        match self {
+            Shortcut::Pass => settings.key_bindings.pass,
+            Shortcut::Confirm => settings.key_bindings.confirm,
+            Shortcut::EndTurn => settings.key_bindings.end_turn,
+        }
+    }
+
+    /// Pick the event this shortcut would send out of the legal ones, if there is one.
+    fn event(&self, legal: &[GameEvent]) -> Option<GameEvent> {
+        match self {
+            Shortcut::Pass => legal.iter().find(|event| is_pass(event)).cloned(),
+            Shortcut::EndTurn => legal
+                .iter()
+                .find(|event| matches!(event, GameEvent::Pass { .. }))
+                .cloned(),
+            Shortcut::Confirm => {
+                let options = legal.iter().filter(|event| !is_pass(event)).collect::<Vec<_>>();
+                if let Some(bid) = options
+                    .iter()
+                    .filter_map(|event| match event {
+                        GameEvent::MakeBid { spice, .. } => Some((*spice, *event)),
+                        _ => None,
+                    })
+                    .min_by_key(|(spice, _)| *spice)
+                {
+                    Some(bid.1.clone())
+                } else if options.len() == 1 {
+                    Some(options[0].clone())
+                } else {
+                    // Too many choices to guess which one is meant
+                    None
+                }
+            }
+        }
+    }
+}
+
+/// Whether an event means doing nothing.
+fn is_pass(event: &GameEvent) -> bool {
+    matches!(
+        event,
+        GameEvent::Pass { .. }
+            | GameEvent::Decline { .. }
+            | GameEvent::RideTheWorm { to: None, .. }
+            | GameEvent::ChooseAdvisors { advisors: true, .. }
+    )
+}
+
+fn describe(event: &GameEvent) -> String {
+    match event {
+        GameEvent::Pass { .. } => "Pass".to_string(),
+        GameEvent::Decline { .. } => "Decline".to_string(),
+        GameEvent::RideTheWorm { to: None, .. } => "Stay put".to_string(),
+        GameEvent::ChooseAdvisors { advisors: true, .. } => "Stay advisors".to_string(),
+        GameEvent::ChooseAdvisors { advisors: false, .. } => "Become fighters".to_string(),
+        GameEvent::MakeBid { spice, .. } => format!("Bid {}", spice),
+        GameEvent::PlayCard { .. } => "Play card".to_string(),
+        _ => "Confirm".to_string(),
+    }
+}
+
+fn shortcuts(
+    keyboard_input: Res<Input<KeyCode>>,
+    settings: Res<Settings>,
+    game_state: Res<GameState>,
+    data: Res<Data>,
+    mut client: GameClient,
+    my_id: Res<PlayerId>,
+) {
+    let pressed = Shortcut::ALL
+        .into_iter()
+        .filter(|shortcut| keyboard_input.just_pressed(shortcut.key(&settings)))
+        .collect::<Vec<_>>();
+    if pressed.is_empty() {
+        return;
+    }
+    let legal = legal_events(&game_state, &data, *my_id);
+    if let Some(event) = pressed.into_iter().find_map(|shortcut| shortcut.event(&legal)) {
+        client.send_event(event);
+    }
+}
+
+/// Lists the shortcuts which can be used right now.
+#[derive(Component)]
+struct ActionPalette;
+
+fn init_action_palette(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    bottom: Val::Px(5.0),
+                    left: Val::Px(5.0),
+                    ..default()
+                },
+                ..default()
+            },
+            text: Text::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                    font_size: 20.0,
+                    color: Color::WHITE,
+                },
+            ),
+            ..default()
+        })
+        .insert(ActionPalette);
+}
+
+fn action_palette(
+    settings: Res<Settings>,
+    game_state: Res<GameState>,
+    data: Res<Data>,
+    my_id: Res<PlayerId>,
+    mut text: Query<&mut Text, With<ActionPalette>>,
+) {
+    if !game_state.is_changed() && !settings.is_changed() {
+        return;
+    }
+    let legal = legal_events(&game_state, &data, *my_id);
+    let mut actions = Vec::new();
+    for shortcut in Shortcut::ALL {
+        if let Some(event) = shortcut.event(&legal) {
+            // End turn is just a pass, so don't list it twice
+            if shortcut == Shortcut::EndTurn && actions.iter().any(|(_, e)| e == &event) {
+                continue;
+            }
+            actions.push((shortcut.key(&settings), event));
+        }
+    }
+    let palette = actions
+        .iter()
+        .map(|(key, event)| format!("[{:?}] {}", key, describe(event)))
+        .collect::<Vec<_>>()
+        .join("   ");
+    for mut text in text.iter_mut() {
+        text.sections[0].value = palette.clone();
+    }
+}
+
+/// How long we have to cancel passing automatically.
+const AUTO_PASS_DELAY: Duration = Duration::from_secs(2);
+
+/// The pass to send for us, if there is nothing else we could sensibly do. Only situations where we know every
+/// option are considered, so nothing we might want to play is ever passed over.
+fn obvious_pass(game_state: &GameState, data: &Data, my_id: PlayerId) -> Option<GameEvent> {
+    let legal = legal_events(game_state, data, my_id);
+    let pass = legal
+        .iter()
+        .find(|event| matches!(event, GameEvent::Pass { .. }))?
+        .clone();
+    match game_state.prompts.get(&my_id) {
+        // We can't afford to outbid anyone
+        Some(Prompt::Bid) => (legal.len() == 1).then_some(pass),
+        // We have no forces to ship or move
+        None if game_state.phase == Phase::Movement && game_state.active_player == Some(my_id) => {
+            let player = game_state.players.get(&my_id)?;
+            let on_board = game_state
+                .board
+                .values()
+                .flat_map(|location| location.sectors.values())
+                .any(|sector| sector.forces.contains_key(&my_id));
+            (player.offworld_forces.is_empty() && !on_board).then_some(pass)
+        }
+        _ => None,
+    }
+}
+
+#[derive(Component)]
+struct AutoPassText;
+
+fn init_auto_pass_text(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    bottom: Val::Px(30.0),
+                    left: Val::Px(5.0),
+                    ..default()
+                },
+                ..default()
+            },
+            text: Text::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                    font_size: 20.0,
+                    color: Color::WHITE,
+                },
+            ),
+            ..default()
+        })
+        .insert(AutoPassText);
+}
+
+/// How long we're told why an event wasn't sent.
+const REJECTION_DURATION: Duration = Duration::from_secs(3);
+
+#[derive(Component)]
+struct RejectionText;
+
+fn init_rejection_text(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    bottom: Val::Px(55.0),
+                    left: Val::Px(5.0),
+                    ..default()
+                },
+                ..default()
+            },
+            text: Text::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                    font_size: 20.0,
+                    color: Color::ORANGE,
+                },
+            ),
+            ..default()
+        })
+        .insert(RejectionText);
+}
+
+/// Explain the last event which wasn't sent because it was illegal, for a little while.
+fn rejection_text(
+    time: Res<Time>,
+    mut rejected: EventReader<RejectedEvent>,
+    mut timer: Local<Timer>,
+    mut text: Query<&mut Text, With<RejectionText>>,
+) {
+    if let Some(RejectedEvent(reason)) = rejected.iter().last() {
+        *timer = Timer::new(REJECTION_DURATION, false);
+        for mut text in text.iter_mut() {
+            text.sections[0].value = reason.clone();
+        }
+    } else if timer.tick(time.delta()).just_finished() {
+        for mut text in text.iter_mut() {
+            text.sections[0].value.clear();
+        }
+    }
+}
+
+fn toggle_auto_pass(keyboard_input: Res<Input<KeyCode>>, mut settings: ResMut<Settings>) {
+    if keyboard_input.just_pressed(settings.key_bindings.toggle_auto_pass) {
+        settings.auto_pass = !settings.auto_pass;
+    }
+}
+
+/// A pass which will be sent for us unless we cancel it in time.
+#[derive(Default)]
+struct PendingPass {
+    event: Option<GameEvent>,
+    timer: Timer,
+    /// A pass we chose not to send, which won't be offered again.
+    cancelled: Option<GameEvent>,
+}
+
+fn auto_pass(
+    time: Res<Time>,
+    keyboard_input: Res<Input<KeyCode>>,
+    settings: Res<Settings>,
+    game_state: Res<GameState>,
+    data: Res<Data>,
+    mut client: GameClient,
+    my_id: Res<PlayerId>,
+    mut pending: Local<PendingPass>,
+    mut text: Query<&mut Text, With<AutoPassText>>,
+) {
+    if game_state.is_changed() || settings.is_changed() {
+        let pass = settings
+            .auto_pass
+            .then(|| obvious_pass(&game_state, &data, *my_id))
+            .flatten();
+        // Once the decision has passed, the next one can be passed automatically again
+        if pass.is_none() {
+            pending.cancelled.take();
+        }
+        let pass = pass.filter(|pass| pending.cancelled.as_ref() != Some(pass));
+        if pass != pending.event {
+            pending.event = pass;
+            pending.timer = Timer::new(AUTO_PASS_DELAY, false);
+        }
+    }
+    let status = if let Some(event) = pending.event.clone() {
+        if keyboard_input.just_pressed(settings.key_bindings.cancel) {
+            pending.event.take();
+            pending.cancelled.replace(event);
+            String::new()
+        } else if pending.timer.tick(time.delta()).just_finished() {
+            pending.event.take();
+            client.send_event(event);
+            String::new()
+        } else {
+            format!(
+                "Passing in {:.0}s   [{:?}] Cancel",
+                (pending.timer.percent_left() * AUTO_PASS_DELAY.as_secs_f32()).ceil(),
+                settings.key_bindings.cancel
+            )
+        }
+    } else {
+        String::new()
+    };
+    for mut text in text.iter_mut() {
+        if text.sections[0].value != status {
+            text.sections[0].value = status.clone();
+        }
+    }
+}
+
+fn concede(keyboard_input: Res<Input<KeyCode>>, settings: Res<Settings>, mut client: GameClient, my_id: Res<PlayerId>) {
+    if keyboard_input.just_pressed(settings.key_bindings.concede) {
+        client.send_event(GameEvent::Concede { player_id: *my_id });
     }
 }
@@ -0,0 +1,78 @@
+use std::{collections::HashMap, fmt::Display};
+
+use bevy::prelude::*;
+
+use crate::settings::Settings;
+
+/// The language every string is written in, and which fills in for any string a translation is missing.
+const DEFAULT_LANGUAGE: &str = "en";
+
+pub struct LangPlugin;
+
+impl Plugin for LangPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Lang>();
+    }
+}
+
+/// The text shown in the UI, looked up by key in the chosen language.
+pub struct Lang {
+    strings: HashMap<String, String>,
+    fallback: HashMap<String, String>,
+}
+
+impl FromWorld for Lang {
+    fn from_world(world: &mut World) -> Self {
+        let language = world
+            .get_resource::<Settings>()
+            .and_then(|settings| settings.language.clone());
+        Self::load(language.as_deref().unwrap_or(DEFAULT_LANGUAGE))
+    }
+}
+
+/// Read the strings for a language from `data/lang`. A language which can't be read has no strings of its own.
+fn read_strings(language: &str) -> HashMap<String, String> {
+    let path = format!("data/lang/{}.ron", language);
+    let strings = std::fs::read_to_string(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|strings| ron::from_str(&strings).map_err(|e| e.to_string()));
+    match strings {
+        Ok(strings) => strings,
+        Err(e) => {
+            error!("Failed to read the strings in {}: {}", path, e);
+            HashMap::new()
+        }
+    }
+}
+
+impl Lang {
+    pub fn load(language: &str) -> Self {
+        Self {
+            strings: read_strings(language),
+            fallback: if language == DEFAULT_LANGUAGE {
+                HashMap::new()
+            } else {
+                read_strings(DEFAULT_LANGUAGE)
+            },
+        }
+    }
+
+    /// The string for a key. Strings missing from a translation are shown in English, and strings missing
+    /// altogether are shown as their key, so they can be spotted and added.
+    pub fn get(&self, key: &str) -> String {
+        match self.strings.get(key).or_else(|| self.fallback.get(key)) {
+            Some(s) => s.clone(),
+            None => {
+                warn!("No string for {}", key);
+                key.to_string()
+            }
+        }
+    }
+
+    /// The string for a key, with each `{name}` in it replaced by the matching argument.
+    pub fn fmt(&self, key: &str, args: &[(&str, &dyn Display)]) -> String {
+        args.iter().fold(self.get(key), |s, (name, value)| {
+            s.replace(&format!("{{{}}}", name), &value.to_string())
+        })
+    }
+}
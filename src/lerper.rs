@@ -123,6 +123,13 @@ impl LerpType {
     pub fn ui_to_world(dest: Transform) -> Self {
         LerpType::UIToWorld { src: None, dest }
     }
+
+    pub fn ui_from_to_world(src: impl Into<UITransform>, dest: Transform) -> Self {
+        LerpType::UIToWorld {
+            src: Some(src.into()),
+            dest,
+        }
+    }
 }
 
 pub trait InterpolationFn {
@@ -216,6 +223,10 @@ impl Lerp {
         Lerp::new(LerpType::ui_to_world(dest), time, delay)
     }
 
+    pub fn ui_from_to_world(src: impl Into<UITransform>, dest: Transform, time: f32, delay: f32) -> Self {
+        Lerp::new(LerpType::ui_from_to_world(src, dest), time, delay)
+    }
+
     pub fn with_interpolation(mut self, interp_fn: impl InterpolationFn + Send + Sync + 'static) -> Self {
         self.interp_fn = Box::new(interp_fn);
         self
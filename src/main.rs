@@ -4,9 +4,12 @@ mod components;
 mod data;
 mod game;
 mod input;
+mod lang;
 mod lerper;
 mod menu;
 mod network;
+mod palette;
+mod settings;
 mod stack;
 mod util;
 
@@ -23,18 +26,19 @@ use bevy::{
 use bevy_editor_pls::EditorPlugin;
 use bevy_mod_picking::{DefaultPickingPlugins, PickableBundle, PickingCameraBundle};
 use bevy_renet::RenetClientPlugin;
-use data::Data;
+use data::{Data, LocationNodes};
 use iyes_loopless::{
     prelude::{AppLooplessStateExt, IntoConditionalSystem},
     state::NextState,
 };
+use lang::Lang;
 use lerper::{LerpUICamera, Lerper};
-use network::{SendEvent, ServerEvent};
+use network::{SendEvent, ServerEvent, Spectator};
 use renet::RenetClient;
 
 use self::{
-    components::*, game::*, input::GameInputPlugin, lerper::LerpPlugin, menu::MenuPlugin,
-    network::RenetNetworkingPlugin,
+    components::*, game::*, input::GameInputPlugin, lang::LangPlugin, lerper::LerpPlugin, menu::MenuPlugin,
+    network::RenetNetworkingPlugin, settings::SettingsPlugin,
 };
 
 pub const MAX_PLAYERS: u8 = 6;
@@ -44,13 +48,15 @@ pub enum Screen {
     MainMenu,
     Host,
     Join,
+    Stats,
     Loading,
     Game,
 }
 
+/// Every asset being loaded for the game, along with the path it was loaded from.
 #[derive(Default)]
 struct LoadingAssets {
-    assets: Vec<HandleUntyped>,
+    assets: Vec<(String, HandleUntyped)>,
 }
 
 fn main() {
@@ -78,13 +84,16 @@ fn main() {
 
     app.add_system(start_game);
     app.add_enter_system(Screen::Loading, tear_down.chain(init_loading_game));
-    app.add_system(load_game.run_in_state(Screen::Loading));
+    app.add_system(load_game.run_in_state(Screen::Loading))
+        .add_system(loading_error_buttons.run_in_state(Screen::Loading));
     app.add_enter_system(Screen::Game, tear_down.chain(init_scene));
 
     app.add_plugin(GamePlugin)
         .add_plugin(MenuPlugin)
         .add_plugin(GameInputPlugin)
-        .add_plugin(LerpPlugin);
+        .add_plugin(LerpPlugin)
+        .add_plugin(SettingsPlugin)
+        .add_plugin(LangPlugin);
 
     app.run();
 }
@@ -110,6 +119,8 @@ fn init_camera(mut commands: Commands) {
 fn start_game(mut commands: Commands, mut server_events: EventReader<ServerEvent>) {
     for event in server_events.iter() {
         if let ServerEvent::LoadAssets = event {
+            // Everyone still connected plays in a new game, including anyone who was only watching the last one
+            commands.remove_resource::<Spectator>();
             commands.insert_resource(NextState(Screen::Loading));
         }
     }
@@ -120,10 +131,15 @@ struct LoadingBar;
 
 fn init_loading_game(
     mut commands: Commands,
+    data: Res<Data>,
     asset_server: Res<AssetServer>,
     mut loading_assets: ResMut<LoadingAssets>,
 ) {
-    loading_assets.assets = asset_server.load_folder(".").unwrap();
+    loading_assets.assets = data
+        .assets
+        .iter()
+        .map(|path| (path.clone(), asset_server.load_untyped(path.as_str())))
+        .collect();
 
     commands
         .spawn_bundle(NodeBundle {
@@ -163,15 +179,42 @@ fn init_loading_game(
         });
 }
 
+/// The list of assets which failed to load, shown once everything else has.
+#[derive(Component)]
+struct LoadingError;
+
+#[derive(Component)]
+enum LoadingErrorButton {
+    Retry,
+    Continue,
+}
+
+/// The paths of every asset which failed to load.
+fn failed_assets(asset_server: &AssetServer, loading_assets: &LoadingAssets) -> Vec<String> {
+    loading_assets
+        .assets
+        .iter()
+        .filter(|(_, handle)| asset_server.get_load_state(handle) == LoadState::Failed)
+        .map(|(path, _)| path.clone())
+        .collect()
+}
+
+fn enter_game(commands: &mut Commands, client: &mut RenetClient) {
+    commands.insert_resource(NextState(Screen::Game));
+    client.send_event(ServerEvent::StartGame);
+}
+
 fn load_game(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
+    lang: Res<Lang>,
     loading_assets: Res<LoadingAssets>,
     mut loading_bar: Query<&mut Style, With<LoadingBar>>,
+    loading_error: Query<(), With<LoadingError>>,
     mut client: ResMut<RenetClient>,
 ) {
     let mut counts = HashMap::new();
-    for handle in loading_assets.assets.iter() {
+    for (_, handle) in loading_assets.assets.iter() {
         match asset_server.get_load_state(handle) {
             LoadState::NotLoaded => *counts.entry("loading").or_insert(0) += 1,
             LoadState::Loading => *counts.entry("loading").or_insert(0) += 1,
@@ -184,9 +227,117 @@ fn load_game(
         bar.size.width =
             Val::Percent(100.0 * (*counts.entry("loaded").or_insert(0) as f32 / loading_assets.assets.len() as f32));
     });
-    if *counts.entry("loading").or_insert(0) == 0 {
-        commands.insert_resource(NextState(Screen::Game));
-        client.send_event(ServerEvent::StartGame);
+    if *counts.entry("loading").or_insert(0) > 0 || !loading_error.is_empty() {
+        return;
+    }
+    if *counts.entry("failed").or_insert(0) == 0 {
+        enter_game(&mut commands, &mut client);
+        return;
+    }
+
+    // Something is missing, so let the player decide whether to try again or play without it
+    let failed = failed_assets(&asset_server, &loading_assets);
+    for path in failed.iter() {
+        error!("Failed to load {}", path);
+    }
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    let text_style = TextStyle {
+        font: font.clone(),
+        font_size: 20.0,
+        color: Color::WHITE,
+    };
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    top: Val::Percent(60.0),
+                    left: Val::Percent(25.0),
+                    ..default()
+                },
+                size: Size::new(Val::Percent(50.0), Val::Undefined),
+                flex_direction: FlexDirection::ColumnReverse,
+                padding: UiRect::all(Val::Px(10.0)),
+                ..default()
+            },
+            color: Color::rgba(0.0, 0.0, 0.0, 0.8).into(),
+            ..default()
+        })
+        .insert(LoadingError)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle::from_section(
+                lang.fmt("loading.failed", &[("count", &failed.len())]),
+                text_style.clone(),
+            ));
+            for path in failed.iter() {
+                parent.spawn_bundle(TextBundle::from_section(format!("  {}", path), text_style.clone()));
+            }
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        margin: UiRect::new(Val::Undefined, Val::Undefined, Val::Px(10.0), Val::Undefined),
+                        justify_content: JustifyContent::SpaceAround,
+                        ..default()
+                    },
+                    color: Color::NONE.into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    for (key, action) in [
+                        ("loading.retry", LoadingErrorButton::Retry),
+                        ("loading.continue", LoadingErrorButton::Continue),
+                    ] {
+                        parent
+                            .spawn_bundle(ButtonBundle {
+                                style: Style {
+                                    padding: UiRect::all(Val::Px(8.0)),
+                                    justify_content: JustifyContent::Center,
+                                    align_items: AlignItems::Center,
+                                    ..default()
+                                },
+                                color: Color::rgb(0.15, 0.15, 0.15).into(),
+                                ..default()
+                            })
+                            .insert(action)
+                            .with_children(|parent| {
+                                parent.spawn_bundle(TextBundle::from_section(
+                                    lang.get(key),
+                                    TextStyle {
+                                        font: font.clone(),
+                                        font_size: 20.0,
+                                        color: Color::ANTIQUE_WHITE,
+                                    },
+                                ));
+                            });
+                    }
+                });
+        });
+}
+
+fn loading_error_buttons(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    loading_assets: Res<LoadingAssets>,
+    mut client: ResMut<RenetClient>,
+    mut interactions: Query<(&Interaction, &mut UiColor, &LoadingErrorButton), Changed<Interaction>>,
+    loading_error: Query<Entity, With<LoadingError>>,
+) {
+    for (interaction, mut color, action) in interactions.iter_mut() {
+        match interaction {
+            Interaction::Clicked => match action {
+                LoadingErrorButton::Retry => {
+                    for path in failed_assets(&asset_server, &loading_assets) {
+                        asset_server.reload_asset(path.as_str());
+                    }
+                    for entity in loading_error.iter() {
+                        commands.entity(entity).despawn_recursive();
+                    }
+                }
+                LoadingErrorButton::Continue => enter_game(&mut commands, &mut client),
+            },
+            Interaction::Hovered => *color = Color::rgb(0.25, 0.25, 0.25).into(),
+            Interaction::None => *color = Color::rgb(0.15, 0.15, 0.15).into(),
+        }
     }
 }
 
@@ -243,24 +394,22 @@ fn init_scene(
         })
         .insert(PlayerFactionText);
 
+    let heights = sector_heights(data.locations.iter().flat_map(|(location, location_data)| {
+        location_data
+            .sectors
+            .iter()
+            .map(|(sector, nodes)| ((*location, *sector), nodes))
+    }));
+
     for (location, location_data) in data.locations.iter() {
         commands
             .spawn_bundle(SpatialBundle::default())
             .insert(*location)
             .with_children(|parent| {
                 for (&sector, nodes) in location_data.sectors.iter() {
-                    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
-                    mesh.insert_attribute(
-                        Mesh::ATTRIBUTE_POSITION,
-                        nodes.vertices.iter().map(|p| [p.x, 0.01, -p.y]).collect::<Vec<_>>(),
-                    );
-                    mesh.set_indices(Some(Indices::U32(nodes.indices.clone())));
-                    mesh.duplicate_vertices();
-                    mesh.compute_flat_normals();
-                    mesh.compute_aabb();
                     parent
                         .spawn_bundle(PbrBundle {
-                            mesh: meshes.add(mesh),
+                            mesh: meshes.add(sector_mesh(nodes, heights[&(*location, sector)])),
                             material: materials.add(StandardMaterial::from(Color::rgba(1.0, 1.0, 1.0, 0.0))),
                             visibility: Visibility { is_visible: true },
                             ..default()
@@ -279,8 +428,126 @@ fn init_scene(
     }
 }
 
+/// How far above the board the sector meshes sit, and how far apart they are stacked.
+const SECTOR_HEIGHT: f32 = 0.01;
+const SECTOR_STEP: f32 = 0.00001;
+
+/// The area of a sector's outline on the board.
+fn sector_area(nodes: &LocationNodes) -> f32 {
+    nodes
+        .indices
+        .chunks_exact(3)
+        .map(|tri| {
+            let [a, b, c] = [0, 1, 2].map(|i| nodes.vertices[tri[i] as usize].truncate());
+            (b - a).perp_dot(c - a).abs() / 2.0
+        })
+        .sum()
+}
+
+/// How high each sector's mesh sits. Smaller sectors sit slightly above larger ones, so where they overlap the smaller
+/// one is picked.
+fn sector_heights<'a>(
+    sectors: impl IntoIterator<Item = ((Location, u8), &'a LocationNodes)>,
+) -> HashMap<(Location, u8), f32> {
+    let mut by_area = sectors
+        .into_iter()
+        .map(|(sector, nodes)| (sector, sector_area(nodes)))
+        .collect::<Vec<_>>();
+    by_area.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+    by_area
+        .into_iter()
+        .enumerate()
+        .map(|(i, (sector, _))| (sector, SECTOR_HEIGHT + i as f32 * SECTOR_STEP))
+        .collect()
+}
+
+/// A flat mesh of a sector's outline. Every triangle is wound to face up, since picking ignores back faces.
+fn sector_mesh(nodes: &LocationNodes, height: f32) -> Mesh {
+    let positions = nodes.vertices.iter().map(|p| [p.x, height, -p.y]).collect::<Vec<_>>();
+    let indices = nodes
+        .indices
+        .chunks_exact(3)
+        .flat_map(|tri| {
+            let [a, b, c] = [0, 1, 2].map(|i| Vec3::from(positions[tri[i] as usize]));
+            if (b - a).cross(c - a).y < 0.0 {
+                [tri[0], tri[2], tri[1]]
+            } else {
+                [tri[0], tri[1], tri[2]]
+            }
+        })
+        .collect();
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh.duplicate_vertices();
+    mesh.compute_flat_normals();
+    mesh.compute_aabb();
+    mesh
+}
+
 fn tear_down(mut commands: Commands, screen_entities: Query<Entity, Without<Camera>>) {
     for entity in screen_entities.iter() {
         commands.entity(entity).despawn();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy::render::mesh::VertexAttributeValues;
+
+    use super::*;
+
+    /// A square sector in board space, with its bottom left corner at the given point.
+    fn square(x: f32, y: f32, size: f32) -> LocationNodes {
+        LocationNodes {
+            vertices: vec![
+                Vec3::new(x, y, 0.0),
+                Vec3::new(x + size, y, 0.0),
+                Vec3::new(x + size, y + size, 0.0),
+                Vec3::new(x, y + size, 0.0),
+            ],
+            // One triangle each way round, as outlines traced by hand tend to be
+            indices: vec![0, 1, 2, 0, 3, 2],
+            fighters: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn a_smaller_sector_is_stacked_above_a_larger_one_it_overlaps() {
+        let (large, small, beside) = (square(0.0, 0.0, 2.0), square(0.5, 0.5, 1.0), square(2.0, 0.0, 1.5));
+        assert_eq!(sector_area(&large), 4.0);
+        assert_eq!(sector_area(&small), 1.0);
+        let (large_id, small_id, beside_id) = (
+            (Location::HaggaBasin, 11),
+            (Location::HaggaBasin, 12),
+            (Location::BrokenLand, 11),
+        );
+        let heights = sector_heights([(large_id, &large), (beside_id, &beside), (small_id, &small)]);
+        assert!(heights[&small_id] > heights[&large_id]);
+        assert!(heights[&small_id] > heights[&beside_id]);
+        assert!(heights[&beside_id] > heights[&large_id]);
+        assert!(heights.values().all(|height| *height >= SECTOR_HEIGHT));
+    }
+
+    #[test]
+    fn every_sector_mesh_faces_up() {
+        let data = Data::default();
+        let drawn = square(0.0, 0.0, 1.0);
+        let sectors = data
+            .locations
+            .values()
+            .flat_map(|location_data| location_data.sectors.values())
+            .chain([&drawn]);
+        for nodes in sectors {
+            let mesh = sector_mesh(nodes, SECTOR_HEIGHT);
+            let normals = match mesh.attribute(Mesh::ATTRIBUTE_NORMAL) {
+                Some(VertexAttributeValues::Float32x3(normals)) => normals,
+                normals => panic!("unexpected normals {:?}", normals),
+            };
+            assert_eq!(normals.len(), nodes.indices.len());
+            for normal in normals {
+                assert!(normal[1] > 0.99, "{:?} faces away from the camera", normal);
+            }
+        }
+    }
+}
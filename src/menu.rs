@@ -3,13 +3,18 @@ use std::collections::HashSet;
 use bevy::prelude::*;
 use iyes_loopless::prelude::*;
 use renet::RenetClient;
+use strum::IntoEnumIterator;
 
 use crate::{
+    components::Faction,
     game::{
-        state::{GameEvent, PlayerId},
+        state::{GameEvent, PlayerId, RuleSet, Seating},
+        stats::Stats,
         GameEventStage,
     },
-    network::{connect_to_server, spawn_server, GameEvents, SendEvent, ServerEvent},
+    lang::Lang,
+    network::{connect_to_server, spawn_server, GameEvents, RenetServer, SendEvent, ServerEvent},
+    settings::Settings,
     tear_down, Screen,
 };
 
@@ -18,15 +23,22 @@ pub struct MenuPlugin;
 impl Plugin for MenuPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<ButtonColors>()
+            .init_resource::<LobbySeating>()
+            .init_resource::<LobbyFaction>()
             .add_enter_system(Screen::MainMenu, tear_down.chain(init_main_menu))
             .add_enter_system(Screen::Host, tear_down.chain(init_host_menu))
             .add_enter_system(Screen::Join, tear_down.chain(init_client_menu))
+            .add_enter_system(Screen::Stats, tear_down.chain(init_stats_menu))
             .add_system(button.run_not_in_state(Screen::Game))
             .add_system_set(
                 ConditionSet::new()
                     .run_not_in_state(Screen::Game)
                     .run_not_in_state(Screen::MainMenu)
                     .with_system(server_client_list)
+                    .with_system(lobby_message)
+                    .with_system(lobby_seating)
+                    .with_system(lobby_faction)
+                    .with_system(autosave_label)
                     .into(),
             )
             .add_system(start_game.run_if_resource_added::<StartGameMarker>());
@@ -39,6 +51,10 @@ impl Plugin for MenuPlugin {
 enum ButtonAction {
     HostGame,
     JoinGame,
+    ViewStats,
+    CycleSeating,
+    CycleFaction,
+    ToggleAutosave,
     StartGame,
     GoBack,
 }
@@ -59,9 +75,40 @@ impl Default for ButtonColors {
     }
 }
 
+/// How players will be seated in the game being set up, as the server last told us.
+struct LobbySeating(Seating);
+
+impl Default for LobbySeating {
+    fn default() -> Self {
+        Self(RuleSet::default().seating)
+    }
+}
+
+/// Shows how players will be seated.
+#[derive(Component)]
+struct SeatingLabel;
+
+/// The faction we picked in the game being set up, as the server last told us.
+#[derive(Default)]
+struct LobbyFaction(Option<Faction>);
+
+/// Shows which faction we picked.
+#[derive(Component)]
+struct FactionLabel;
+
+/// Shows whether the game we host will be saved every turn.
+#[derive(Component)]
+struct AutosaveLabel;
+
 fn button(
     mut commands: Commands,
     button_colors: Res<ButtonColors>,
+    seating: Res<LobbySeating>,
+    faction: Res<LobbyFaction>,
+    player_id: Option<Res<PlayerId>>,
+    mut settings: ResMut<Settings>,
+    server: Option<Res<RenetServer>>,
+    mut client: Option<ResMut<RenetClient>>,
     mut interactions: Query<(&Interaction, &mut UiColor, &ButtonAction), (Changed<Interaction>, With<Button>)>,
 ) {
     for (&interaction, mut color, action) in interactions.iter_mut() {
@@ -70,7 +117,7 @@ fn button(
                 *color = button_colors.pressed;
                 match action {
                     ButtonAction::HostGame => {
-                        spawn_server(&mut commands);
+                        spawn_server(&mut commands, settings.autosave);
                         connect_to_server(&mut commands).unwrap();
                         commands.insert_resource(NextState(Screen::Host));
                     }
@@ -78,6 +125,35 @@ fn button(
                         connect_to_server(&mut commands).unwrap();
                         commands.insert_resource(NextState(Screen::Join));
                     }
+                    ButtonAction::ViewStats => {
+                        commands.insert_resource(NextState(Screen::Stats));
+                    }
+                    ButtonAction::CycleSeating => {
+                        if let Some(client) = client.as_mut() {
+                            client.send_event(ServerEvent::SetSeating {
+                                seating: seating.0.next(),
+                            });
+                        }
+                    }
+                    ButtonAction::CycleFaction => {
+                        if let (Some(client), Some(player_id)) = (client.as_mut(), player_id.as_ref()) {
+                            // Go through every faction in turn, then back to taking whatever is left
+                            let next = match faction.0 {
+                                Some(picked) => Faction::iter().skip_while(|f| *f != picked).nth(1),
+                                None => Faction::iter().next(),
+                            };
+                            client.send_event(ServerEvent::PickFaction {
+                                player_id: **player_id,
+                                faction: next,
+                            });
+                        }
+                    }
+                    ButtonAction::ToggleAutosave => {
+                        settings.autosave = !settings.autosave;
+                        if let Some(server) = server.as_ref() {
+                            server.set_autosave(settings.autosave);
+                        }
+                    }
                     ButtonAction::StartGame => {
                         commands.insert_resource(StartGameMarker);
                     }
@@ -99,7 +175,12 @@ fn start_game(mut commands: Commands, mut client: ResMut<RenetClient>) {
     commands.remove_resource::<StartGameMarker>();
 }
 
-fn init_main_menu(mut commands: Commands, asset_server: Res<AssetServer>, button_colors: Res<ButtonColors>) {
+fn init_main_menu(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    button_colors: Res<ButtonColors>,
+    lang: Res<Lang>,
+) {
     commands
         .spawn_bundle(NodeBundle {
             style: Style {
@@ -126,7 +207,7 @@ fn init_main_menu(mut commands: Commands, asset_server: Res<AssetServer>, button
                 .insert(ButtonAction::HostGame)
                 .with_children(|parent| {
                     parent.spawn_bundle(TextBundle::from_section(
-                        "Host Game",
+                        lang.get("menu.host_game"),
                         TextStyle {
                             font: asset_server.load("fonts/FiraSans-Bold.ttf"),
                             font_size: 20.0,
@@ -148,7 +229,87 @@ fn init_main_menu(mut commands: Commands, asset_server: Res<AssetServer>, button
                 .insert(ButtonAction::JoinGame)
                 .with_children(|parent| {
                     parent.spawn_bundle(TextBundle::from_section(
-                        "Join Game",
+                        lang.get("menu.join_game"),
+                        TextStyle {
+                            font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                            font_size: 20.0,
+                            color: Color::ANTIQUE_WHITE,
+                        },
+                    ));
+                });
+            parent
+                .spawn_bundle(ButtonBundle {
+                    style: Style {
+                        size: Size::new(Val::Percent(10.0), Val::Percent(6.0)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    color: button_colors.normal,
+                    ..default()
+                })
+                .insert(ButtonAction::ViewStats)
+                .with_children(|parent| {
+                    parent.spawn_bundle(TextBundle::from_section(
+                        lang.get("menu.stats"),
+                        TextStyle {
+                            font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                            font_size: 20.0,
+                            color: Color::ANTIQUE_WHITE,
+                        },
+                    ));
+                });
+        });
+}
+
+/// The results of every game hosted on this machine, added up.
+fn init_stats_menu(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    button_colors: Res<ButtonColors>,
+    lang: Res<Lang>,
+) {
+    let summary = match Stats::read() {
+        Ok(stats) => stats.summary(),
+        Err(e) => lang.fmt("menu.stats_unreadable", &[("error", &e)]),
+    };
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                margin: UiRect::all(Val::Auto),
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle::from_section(
+                summary,
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                    font_size: 20.0,
+                    color: Color::BLACK,
+                },
+            ));
+            parent
+                .spawn_bundle(ButtonBundle {
+                    style: Style {
+                        size: Size::new(Val::Percent(10.0), Val::Percent(6.0)),
+                        margin: UiRect::all(Val::Px(10.0)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    color: button_colors.normal,
+                    ..default()
+                })
+                .insert(ButtonAction::GoBack)
+                .with_children(|parent| {
+                    parent.spawn_bundle(TextBundle::from_section(
+                        lang.get("menu.back"),
                         TextStyle {
                             font: asset_server.load("fonts/FiraSans-Bold.ttf"),
                             font_size: 20.0,
@@ -162,7 +323,66 @@ fn init_main_menu(mut commands: Commands, asset_server: Res<AssetServer>, button
 #[derive(Default, Component)]
 pub struct ServerList(HashSet<PlayerId>);
 
-fn init_host_menu(mut commands: Commands, asset_server: Res<AssetServer>, button_colors: Res<ButtonColors>) {
+/// Tells everyone in the lobby why the game couldn't be started.
+#[derive(Component)]
+struct LobbyMessage;
+
+fn spawn_lobby_message(parent: &mut ChildBuilder, asset_server: &AssetServer) {
+    parent
+        .spawn_bundle(TextBundle::from_section(
+            "",
+            TextStyle {
+                font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                font_size: 20.0,
+                color: Color::MAROON,
+            },
+        ))
+        .insert(LobbyMessage);
+}
+
+/// Lets a player pick the faction they want to play.
+fn spawn_faction_button(
+    parent: &mut ChildBuilder,
+    asset_server: &AssetServer,
+    button_colors: &ButtonColors,
+    lang: &Lang,
+    faction: Option<Faction>,
+) {
+    parent
+        .spawn_bundle(ButtonBundle {
+            style: Style {
+                size: Size::new(Val::Percent(20.0), Val::Percent(6.0)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            color: button_colors.normal,
+            ..default()
+        })
+        .insert(ButtonAction::CycleFaction)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle::from_section(
+                    faction_label(lang, faction),
+                    TextStyle {
+                        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                        font_size: 20.0,
+                        color: Color::ANTIQUE_WHITE,
+                    },
+                ))
+                .insert(FactionLabel);
+        });
+}
+
+fn init_host_menu(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    button_colors: Res<ButtonColors>,
+    seating: Res<LobbySeating>,
+    faction: Res<LobbyFaction>,
+    settings: Res<Settings>,
+    lang: Res<Lang>,
+) {
     commands
         .spawn_bundle(NodeBundle {
             style: Style {
@@ -177,7 +397,7 @@ fn init_host_menu(mut commands: Commands, asset_server: Res<AssetServer>, button
         .with_children(|parent| {
             parent
                 .spawn_bundle(TextBundle::from_section(
-                    "Joined Users:",
+                    lang.get("menu.joined_users"),
                     TextStyle {
                         font: asset_server.load("fonts/FiraSans-Bold.ttf"),
                         font_size: 20.0,
@@ -198,6 +418,56 @@ fn init_host_menu(mut commands: Commands, asset_server: Res<AssetServer>, button
             ..default()
         })
         .with_children(|parent| {
+            spawn_lobby_message(parent, &asset_server);
+            parent
+                .spawn_bundle(ButtonBundle {
+                    style: Style {
+                        size: Size::new(Val::Percent(20.0), Val::Percent(6.0)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    color: button_colors.normal,
+                    ..default()
+                })
+                .insert(ButtonAction::CycleSeating)
+                .with_children(|parent| {
+                    parent
+                        .spawn_bundle(TextBundle::from_section(
+                            seating_label(&lang, seating.0),
+                            TextStyle {
+                                font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                                font_size: 20.0,
+                                color: Color::ANTIQUE_WHITE,
+                            },
+                        ))
+                        .insert(SeatingLabel);
+                });
+            spawn_faction_button(parent, &asset_server, &button_colors, &lang, faction.0);
+            parent
+                .spawn_bundle(ButtonBundle {
+                    style: Style {
+                        size: Size::new(Val::Percent(20.0), Val::Percent(6.0)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    color: button_colors.normal,
+                    ..default()
+                })
+                .insert(ButtonAction::ToggleAutosave)
+                .with_children(|parent| {
+                    parent
+                        .spawn_bundle(TextBundle::from_section(
+                            autosave_text(&lang, settings.autosave),
+                            TextStyle {
+                                font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                                font_size: 20.0,
+                                color: Color::ANTIQUE_WHITE,
+                            },
+                        ))
+                        .insert(AutosaveLabel);
+                });
             parent
                 .spawn_bundle(ButtonBundle {
                     style: Style {
@@ -212,7 +482,7 @@ fn init_host_menu(mut commands: Commands, asset_server: Res<AssetServer>, button
                 .insert(ButtonAction::StartGame)
                 .with_children(|parent| {
                     parent.spawn_bundle(TextBundle::from_section(
-                        "Start Game",
+                        lang.get("menu.start_game"),
                         TextStyle {
                             font: asset_server.load("fonts/FiraSans-Bold.ttf"),
                             font_size: 20.0,
@@ -234,7 +504,7 @@ fn init_host_menu(mut commands: Commands, asset_server: Res<AssetServer>, button
                 .insert(ButtonAction::GoBack)
                 .with_children(|parent| {
                     parent.spawn_bundle(TextBundle::from_section(
-                        "Back",
+                        lang.get("menu.back"),
                         TextStyle {
                             font: asset_server.load("fonts/FiraSans-Bold.ttf"),
                             font_size: 20.0,
@@ -245,7 +515,14 @@ fn init_host_menu(mut commands: Commands, asset_server: Res<AssetServer>, button
         });
 }
 
-fn init_client_menu(mut commands: Commands, asset_server: Res<AssetServer>, button_colors: Res<ButtonColors>) {
+fn init_client_menu(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    button_colors: Res<ButtonColors>,
+    seating: Res<LobbySeating>,
+    faction: Res<LobbyFaction>,
+    lang: Res<Lang>,
+) {
     commands
         .spawn_bundle(NodeBundle {
             style: Style {
@@ -260,7 +537,7 @@ fn init_client_menu(mut commands: Commands, asset_server: Res<AssetServer>, butt
         .with_children(|parent| {
             parent
                 .spawn_bundle(TextBundle::from_section(
-                    "Joined Users:",
+                    lang.get("menu.joined_users"),
                     TextStyle {
                         font: asset_server.load("fonts/FiraSans-Bold.ttf"),
                         font_size: 20.0,
@@ -281,8 +558,20 @@ fn init_client_menu(mut commands: Commands, asset_server: Res<AssetServer>, butt
             ..default()
         })
         .with_children(|parent| {
+            spawn_lobby_message(parent, &asset_server);
+            parent
+                .spawn_bundle(TextBundle::from_section(
+                    seating_label(&lang, seating.0),
+                    TextStyle {
+                        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                        font_size: 20.0,
+                        color: Color::BLACK,
+                    },
+                ))
+                .insert(SeatingLabel);
+            spawn_faction_button(parent, &asset_server, &button_colors, &lang, faction.0);
             parent.spawn_bundle(TextBundle::from_section(
-                "Waiting for Server...",
+                lang.get("menu.waiting_for_server"),
                 TextStyle {
                     font: asset_server.load("fonts/FiraSans-Bold.ttf"),
                     font_size: 20.0,
@@ -303,7 +592,7 @@ fn init_client_menu(mut commands: Commands, asset_server: Res<AssetServer>, butt
                 .insert(ButtonAction::GoBack)
                 .with_children(|parent| {
                     parent.spawn_bundle(TextBundle::from_section(
-                        "Back",
+                        lang.get("menu.back"),
                         TextStyle {
                             font: asset_server.load("fonts/FiraSans-Bold.ttf"),
                             font_size: 20.0,
@@ -332,9 +621,9 @@ pub fn update_server_list(game_events: Res<GameEvents>, mut list: Query<&mut Ser
     }
 }
 
-fn server_client_list(mut list: Query<(&mut Text, &ServerList), Changed<ServerList>>) {
+fn server_client_list(lang: Res<Lang>, mut list: Query<(&mut Text, &ServerList), Changed<ServerList>>) {
     if let Ok((mut list, ServerList(players))) = list.get_single_mut() {
-        let mut s = "Joined Users:".to_string();
+        let mut s = lang.get("menu.joined_users");
         // TODO: Fix this
         for player_id in players.iter() {
             s += "\n";
@@ -343,3 +632,85 @@ fn server_client_list(mut list: Query<(&mut Text, &ServerList), Changed<ServerLi
         list.sections[0].value = s;
     }
 }
+
+fn seating_label(lang: &Lang, seating: Seating) -> String {
+    lang.fmt("menu.seating", &[("seating", &seating)])
+}
+
+fn lobby_seating(
+    mut server_events: EventReader<ServerEvent>,
+    mut seating: ResMut<LobbySeating>,
+    lang: Res<Lang>,
+    mut labels: Query<&mut Text, With<SeatingLabel>>,
+) {
+    for event in server_events.iter() {
+        if let ServerEvent::SetSeating { seating: chosen } = event {
+            seating.0 = *chosen;
+            for mut text in labels.iter_mut() {
+                text.sections[0].value = seating_label(&lang, *chosen);
+            }
+        }
+    }
+}
+
+fn faction_label(lang: &Lang, faction: Option<Faction>) -> String {
+    match faction {
+        Some(faction) => lang.fmt("menu.faction", &[("faction", &faction)]),
+        None => lang.get("menu.any_faction"),
+    }
+}
+
+fn lobby_faction(
+    mut server_events: EventReader<ServerEvent>,
+    mut faction: ResMut<LobbyFaction>,
+    lang: Res<Lang>,
+    player_id: Option<Res<PlayerId>>,
+    mut labels: Query<&mut Text, With<FactionLabel>>,
+) {
+    for event in server_events.iter() {
+        if let ServerEvent::PickFaction {
+            player_id: picked_by,
+            faction: picked,
+        } = event
+        {
+            // Everyone is told what everyone picked, but only our own pick is shown
+            if player_id.as_deref() == Some(picked_by) {
+                faction.0 = *picked;
+                for mut text in labels.iter_mut() {
+                    text.sections[0].value = faction_label(&lang, *picked);
+                }
+            }
+        }
+    }
+}
+
+fn autosave_text(lang: &Lang, autosave: bool) -> String {
+    lang.get(if autosave {
+        "menu.autosave_on"
+    } else {
+        "menu.autosave_off"
+    })
+}
+
+fn autosave_label(settings: Res<Settings>, lang: Res<Lang>, mut labels: Query<&mut Text, With<AutosaveLabel>>) {
+    if !settings.is_changed() {
+        return;
+    }
+    for mut text in labels.iter_mut() {
+        text.sections[0].value = autosave_text(&lang, settings.autosave);
+    }
+}
+
+fn lobby_message(
+    mut server_events: EventReader<ServerEvent>,
+    lang: Res<Lang>,
+    mut message: Query<&mut Text, With<LobbyMessage>>,
+) {
+    for event in server_events.iter() {
+        if let ServerEvent::CannotStart { reason } = event {
+            for mut text in message.iter_mut() {
+                text.sections[0].value = lang.fmt("menu.cannot_start", &[("reason", &reason)]);
+            }
+        }
+    }
+}
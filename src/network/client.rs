@@ -4,6 +4,10 @@ pub fn connect_to_server(commands: &mut Commands) -> Result<(), RenetNetworkingE
     let client = client()?;
     let client_id = client.client_id();
     commands.insert_resource(client);
+    // The server numbers the events it sends each client from the start
+    commands.insert_resource(EventSequence::default());
+    // Until the server says otherwise, we are playing rather than watching
+    commands.remove_resource::<Spectator>();
     commands.insert_resource(PlayerId(client_id));
     Ok(())
 }
@@ -15,7 +19,7 @@ fn client() -> Result<RenetClient, RenetNetworkingError> {
     let current_time = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap();
     let client_id = current_time.as_millis() as u64;
 
-    let user_data = [0u8; NETCODE_USER_DATA_BYTES];
+    let user_data = name_to_user_data(&std::env::var("PLAYER_NAME").unwrap_or_default());
 
     Ok(RenetClient::new(
         current_time,
@@ -2,15 +2,23 @@ mod client;
 mod server;
 
 use std::{
-    collections::VecDeque,
+    collections::{BTreeMap, VecDeque},
     env::VarError,
     net::{AddrParseError, SocketAddr, UdpSocket},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
     thread,
     time::{Duration, Instant, SystemTime},
 };
 
-use bevy::prelude::*;
+use bevy::{ecs::system::SystemParam, prelude::*};
 use iyes_loopless::prelude::IntoConditionalSystem;
+use miniz_oxide::{
+    deflate::compress_to_vec,
+    inflate::{decompress_to_vec, TINFLStatus},
+};
 use renet::{
     ClientAuthentication, RenetClient, RenetConnectionConfig, RenetError, ServerAuthentication, ServerConfig,
     NETCODE_USER_DATA_BYTES,
@@ -19,10 +27,19 @@ use serde::Serialize;
 use thiserror::Error;
 
 pub use self::{client::*, server::*};
-use crate::game::state::{EndGameReason, EventReduce, GameEvent, GameState, PlayerId};
+use crate::{
+    data::Data,
+    game::{
+        driver::GameDriver,
+        state::{EndGameReason, EventReduce, GameEvent, GameState, PlayerId},
+    },
+};
 
 pub const PROTOCOL_ID: u64 = 0;
 
+/// Messages at least this big are compressed before they are sent.
+const COMPRESSION_THRESHOLD: usize = 1024;
+
 #[derive(Debug, Error)]
 pub enum RenetNetworkingError {
     #[error(transparent)]
@@ -35,6 +52,59 @@ pub enum RenetNetworkingError {
     Serialization(#[from] bincode::Error),
     #[error(transparent)]
     Renet(#[from] RenetError),
+    #[error("failed to decompress a message: {0:?}")]
+    Decompression(TINFLStatus),
+}
+
+/// Serialize a message for sending. Large messages are compressed, and the first byte says whether they were.
+pub fn pack<T: Serialize>(message: &T) -> Result<Vec<u8>, bincode::Error> {
+    let serialized = bincode::serialize(message)?;
+    if serialized.len() < COMPRESSION_THRESHOLD {
+        let mut packed = Vec::with_capacity(serialized.len() + 1);
+        packed.push(0);
+        packed.extend(serialized);
+        Ok(packed)
+    } else {
+        let mut packed = vec![1];
+        packed.extend(compress_to_vec(&serialized, 6));
+        trace!(
+            "Compressed a message from {} to {} bytes",
+            serialized.len(),
+            packed.len()
+        );
+        Ok(packed)
+    }
+}
+
+/// Players introduce themselves by name when they connect, in the user data of the connection.
+pub fn name_to_user_data(name: &str) -> [u8; NETCODE_USER_DATA_BYTES] {
+    let mut user_data = [0u8; NETCODE_USER_DATA_BYTES];
+    // Names too long to fit are cut short, without splitting a character
+    let mut len = name.len().min(NETCODE_USER_DATA_BYTES);
+    while !name.is_char_boundary(len) {
+        len -= 1;
+    }
+    user_data[..len].copy_from_slice(&name.as_bytes()[..len]);
+    user_data
+}
+
+/// Undo [`name_to_user_data`], if the player gave a name at all.
+pub fn name_from_user_data(user_data: &[u8; NETCODE_USER_DATA_BYTES]) -> Option<String> {
+    let len = user_data
+        .iter()
+        .position(|b| *b == 0)
+        .unwrap_or(NETCODE_USER_DATA_BYTES);
+    let name = String::from_utf8_lossy(&user_data[..len]).trim().to_string();
+    (!name.is_empty()).then_some(name)
+}
+
+/// Undo [`pack`], giving back the serialized message.
+pub fn unpack(message: &[u8]) -> Result<Vec<u8>, RenetNetworkingError> {
+    match message.split_first() {
+        Some((1, compressed)) => decompress_to_vec(compressed).map_err(RenetNetworkingError::Decompression),
+        Some((_, serialized)) => Ok(serialized.to_vec()),
+        None => Ok(Vec::new()),
+    }
 }
 
 pub struct RenetNetworkingPlugin;
@@ -43,7 +113,10 @@ impl Plugin for RenetNetworkingPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
         app.init_resource::<GameState>()
             .init_resource::<GameEvents>()
+            .init_resource::<EventLog>()
+            .init_resource::<EventSequence>()
             .add_event::<ServerEvent>()
+            .add_event::<RejectedEvent>()
             .add_event::<RenetServerExitedEvent>()
             .add_system(await_server.run_if_resource_exists::<RenetServer>())
             .add_system(process_server_events.run_if_resource_exists::<RenetClient>());
@@ -52,6 +125,22 @@ impl Plugin for RenetNetworkingPlugin {
 
 pub struct RenetServer {
     handle: Option<thread::JoinHandle<Result<(), RenetNetworkingError>>>,
+    /// Set to ask the server to dump its state for a bug report.
+    dump_requested: Arc<AtomicBool>,
+    /// Whether the server saves the game at the start of every turn.
+    autosave: Arc<AtomicBool>,
+}
+
+impl RenetServer {
+    /// Ask the server to dump its state the next time it processes events.
+    pub fn request_dump(&self) {
+        self.dump_requested.store(true, Ordering::Relaxed);
+    }
+
+    /// Turn saving the game at the start of every turn on or off.
+    pub fn set_autosave(&self, autosave: bool) {
+        self.autosave.store(autosave, Ordering::Relaxed);
+    }
 }
 
 pub struct RenetServerExitedEvent {
@@ -92,21 +181,124 @@ impl GameEvents {
     }
 }
 
+/// Present once the game state has been caught up wholesale, until the scene has been rebuilt to match it.
+pub struct SceneOutOfDate;
+
+/// Present when we joined a game already under way, which we can only watch. The server tells spectators every
+/// secret, so our game state holds everyone's hidden information.
+pub struct Spectator;
+
+/// Where we are in the stream of events the server sends us, so events are applied in the order they were sent and
+/// we can tell when some went missing.
+#[derive(Debug, Default)]
+pub struct EventSequence {
+    /// The sequence number of the next event we expect.
+    next_seq: u64,
+    /// Events which arrived after one we are missing, held until they can be applied in order.
+    ahead: BTreeMap<u64, GameEvent>,
+    /// Where we last asked the server to catch us up from, until it does.
+    requested: Option<u64>,
+}
+
+impl EventSequence {
+    /// Start over after being sent the whole game, expecting the given event next.
+    fn reset(&mut self, next_seq: u64) {
+        *self = Self { next_seq, ..default() };
+    }
+
+    /// Take an event from the server. Gives back every event which can now be applied, in order, and where to ask
+    /// the server to catch us up from if some are missing and we haven't asked already.
+    fn receive(&mut self, seq: u64, event: GameEvent) -> (Vec<GameEvent>, Option<u64>) {
+        if seq < self.next_seq {
+            trace!("Ignoring event {}, which we already have", seq);
+            return (Vec::new(), None);
+        }
+        self.ahead.entry(seq).or_insert(event);
+        let mut ready = Vec::new();
+        while let Some(event) = self.ahead.remove(&self.next_seq) {
+            ready.push(event);
+            self.next_seq += 1;
+        }
+        if self.ahead.is_empty() {
+            self.requested = None;
+            return (ready, None);
+        }
+        // Whatever we already asked for covers everything from there on, so the gap will be filled as it arrives
+        if self.requested.map_or(false, |from_seq| from_seq <= self.next_seq) {
+            return (ready, None);
+        }
+        let last_missing = self.ahead.keys().next().map_or(self.next_seq, |seq| seq - 1);
+        warn!(
+            "Missed events {} to {}, asking the server to catch us up",
+            self.next_seq, last_missing
+        );
+        self.requested = Some(self.next_seq);
+        (ready, self.requested)
+    }
+}
+
+/// Every event of the game so far, in the order they happened.
+#[derive(Debug, Clone, Default)]
+pub struct EventLog {
+    pub events: Vec<GameEvent>,
+}
+
 fn process_server_events(
+    mut commands: Commands,
     mut client: ResMut<RenetClient>,
     mut game_events: ResMut<GameEvents>,
+    mut event_log: ResMut<EventLog>,
+    mut sequence: ResMut<EventSequence>,
+    mut game_state: ResMut<GameState>,
+    data: Res<Data>,
     mut server_events: EventWriter<ServerEvent>,
 ) {
     while let Some(message) = client.receive_message(0) {
+        let message = match unpack(&message) {
+            Ok(message) => message,
+            Err(e) => {
+                warn!("Received invalid message from the server: {}", e);
+                continue;
+            }
+        };
         // Route the message types appropriately
-        if let Ok(event) = bincode::deserialize::<GameEvent>(&message) {
-            trace!("{:#?}", event);
+        if let Ok(event) = bincode::deserialize::<ServerEvent>(&message) {
+            match event {
+                ServerEvent::Sequenced { seq, event } => {
+                    let (ready, missing) = sequence.receive(seq, event);
+                    if let Some(from_seq) = missing {
+                        client.send_event(ServerEvent::RequestCatchup { from_seq });
+                    }
+                    for event in ready {
+                        trace!("{:#?}", event);
 
-            game_events.push(event);
-        } else if let Ok(event) = bincode::deserialize::<ServerEvent>(&message) {
-            trace!("{:#?}", event);
+                        event_log.events.push(event.clone());
+                        game_events.push(event);
+                    }
+                }
+                ServerEvent::FullSync {
+                    events,
+                    next_seq,
+                    spectator,
+                } => {
+                    trace!("Syncing {} events", events.len());
+                    if spectator {
+                        commands.insert_resource(Spectator);
+                    } else {
+                        commands.remove_resource::<Spectator>();
+                    }
+                    // Catch up on everything at once rather than playing it all out
+                    *game_state = GameDriver::replay(&data, &events);
+                    event_log.events = events;
+                    sequence.reset(next_seq);
+                    commands.insert_resource(SceneOutOfDate);
+                }
+                event => {
+                    trace!("{:#?}", event);
 
-            server_events.send(event);
+                    server_events.send(event);
+                }
+            }
         } else {
             warn!("Received invalid message from the server: {:x?}", message);
         }
@@ -119,6 +311,119 @@ pub trait SendEvent {
 
 impl SendEvent for RenetClient {
     fn send_event<T: Serialize>(&mut self, event: T) {
-        self.send_message(0, bincode::serialize(&event).unwrap());
+        self.send_message(0, pack(&event).unwrap());
+    }
+}
+
+/// Why an event we tried to send was never sent.
+pub struct RejectedEvent(pub String);
+
+/// Sends game events to the server, but only once our own game state agrees they're legal. The server would only
+/// drop an illegal event, so the player is told what was wrong with it instead.
+#[derive(SystemParam)]
+pub struct GameClient<'w, 's> {
+    client: ResMut<'w, RenetClient>,
+    game_state: Res<'w, GameState>,
+    data: Res<'w, Data>,
+    rejected: EventWriter<'w, 's, RejectedEvent>,
+}
+
+impl<'w, 's> GameClient<'w, 's> {
+    pub fn send_event(&mut self, event: GameEvent) {
+        if self.game_state.validate(&self.data, &event) {
+            self.client.send_event(event);
+        } else {
+            debug!("Not sending illegal event {:?}", event);
+            self.rejected.send(RejectedEvent(event.rejection()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        components::Faction,
+        game::{driver::tests::TestGame, state::RuleSet},
+    };
+
+    #[test]
+    fn large_messages_are_compressed_and_unpack_the_same() {
+        let game = TestGame::setup(&[Faction::Atreides, Faction::Harkonnen], RuleSet::default());
+        let sync = ServerEvent::FullSync {
+            events: game.log.clone(),
+            next_seq: 7,
+            spectator: false,
+        };
+        let serialized = bincode::serialize(&sync).unwrap();
+        let packed = pack(&sync).unwrap();
+        assert_eq!(packed[0], 1);
+        assert!(packed.len() < serialized.len());
+
+        let unpacked = unpack(&packed).unwrap();
+        assert_eq!(unpacked, serialized);
+        match bincode::deserialize(&unpacked).unwrap() {
+            ServerEvent::FullSync {
+                events,
+                next_seq,
+                spectator,
+            } => {
+                assert_eq!(events, game.log);
+                assert_eq!(next_seq, 7);
+                assert!(!spectator);
+            }
+            event => panic!("unpacked {:?}", event),
+        }
+    }
+
+    #[test]
+    fn small_messages_are_sent_as_they_are() {
+        let event = ServerEvent::Sequenced {
+            seq: 3,
+            event: GameEvent::AdvancePhase,
+        };
+        let serialized = bincode::serialize(&event).unwrap();
+        let packed = pack(&event).unwrap();
+        assert_eq!(packed[0], 0);
+        assert_eq!(&packed[1..], &serialized[..]);
+        assert_eq!(unpack(&packed).unwrap(), serialized);
+    }
+
+    #[test]
+    fn corrupt_compressed_messages_are_an_error() {
+        assert!(matches!(
+            unpack(&[1, 0xff, 0xff, 0xff]),
+            Err(RenetNetworkingError::Decompression(_))
+        ));
+    }
+
+    #[test]
+    fn sequenced_events_are_applied_in_order_and_gaps_are_noticed() {
+        let event = |seq: u64| GameEvent::SetActive {
+            player_id: PlayerId(seq),
+        };
+        let events = |seqs: &[u64]| seqs.iter().map(|seq| event(*seq)).collect::<Vec<_>>();
+        let mut sequence = EventSequence::default();
+        assert_eq!(sequence.receive(0, event(0)), (events(&[0]), None));
+
+        // An event after a gap is held back, and the missing ones are asked for only once
+        assert_eq!(sequence.receive(2, event(2)), (Vec::new(), Some(1)));
+        assert_eq!(sequence.receive(3, event(3)), (Vec::new(), None));
+        // Anything we already have is ignored
+        assert_eq!(sequence.receive(0, event(0)), (Vec::new(), None));
+        assert_eq!(sequence.receive(1, event(1)), (events(&[1, 2, 3]), None));
+
+        // While missing events are being sent again, those still to come are already asked for
+        assert_eq!(sequence.receive(6, event(6)), (Vec::new(), Some(4)));
+        assert_eq!(sequence.receive(4, event(4)), (events(&[4]), None));
+        assert_eq!(sequence.receive(5, event(5)), (events(&[5, 6]), None));
+
+        // A new gap once the last has been filled is asked about again
+        assert_eq!(sequence.receive(8, event(8)), (Vec::new(), Some(7)));
+
+        // Being sent the whole game starts over from wherever the server says
+        sequence.reset(20);
+        assert_eq!(sequence.receive(8, event(8)), (Vec::new(), None));
+        assert_eq!(sequence.receive(20, event(20)), (events(&[20]), None));
     }
 }
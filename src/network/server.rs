@@ -1,23 +1,46 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet, VecDeque};
 
-use rand::{seq::SliceRandom, Rng};
 use serde::{Deserialize, Serialize};
-use strum::IntoEnumIterator;
 
 use super::*;
 use crate::{
-    components::{Faction, Leader, SpiceCard, StormCard, TraitorCard, Troop},
-    data::{Data, SpiceLocationData},
+    components::Faction,
     game::{
-        phase::{bidding::BiddingPhase, setup::SetupPhase, spice_blow::SpiceBlowPhase, storm::StormPhase, Phase},
-        state::{DeckType, Prompt, SpawnType},
-        Object, ObjectIdGenerator,
+        bot::{needs_action, Bot, Difficulty, Personality, TieredBot},
+        driver::GameDriver,
+        phase::{setup::SetupPhase, Phase},
+        save::{SaveFile, StateDump},
+        state::{audit_events, RuleSet, Seating},
+        stats::{GameRecord, Stats},
     },
 };
 
-pub fn spawn_server(commands: &mut Commands) {
+/// How many players a game can be played with. Everyone is given a faction of their own once the game starts, so
+/// there can't be more players than factions.
+const MIN_PLAYERS: usize = 2;
+const MAX_PLAYERS: usize = 6;
+
+/// How many more clients may connect beyond a full table, to watch the game.
+const MAX_SPECTATORS: usize = 4;
+
+/// How long players get to respond to a response window before the server declines for them.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How many of the events most recently sent to each client are kept, to send again if the client missed them. A
+/// client which has fallen further behind than this is sent the whole game instead.
+const CATCHUP_BUFFER: usize = 256;
+
+pub fn spawn_server(commands: &mut Commands, autosave: bool) {
+    let dump_requested = Arc::new(AtomicBool::new(false));
+    let autosave = Arc::new(AtomicBool::new(autosave));
     commands.insert_resource(RenetServer {
-        handle: Some(std::thread::spawn(server)),
+        handle: Some(std::thread::spawn({
+            let dump_requested = dump_requested.clone();
+            let autosave = autosave.clone();
+            move || server(dump_requested, autosave)
+        })),
+        dump_requested,
+        autosave,
     });
 }
 
@@ -25,447 +48,446 @@ pub fn spawn_server(commands: &mut Commands) {
 pub enum ServerEvent {
     LoadAssets,
     StartGame,
+    /// A game event, numbered in the order it was sent to this client so any which go missing can be noticed.
+    Sequenced {
+        seq: u64,
+        event: GameEvent,
+    },
+    /// Every event of the game so far, for clients which need to catch up, and the number of the next event which
+    /// will be sequenced for them. Spectators are sent every secret along with the rest, and are told so.
+    FullSync {
+        events: Vec<GameEvent>,
+        next_seq: u64,
+        spectator: bool,
+    },
+    /// A client missed some events, and asks for everything since the first one it missed.
+    RequestCatchup {
+        from_seq: u64,
+    },
+    /// The host asked to play again with everyone still connected, once the game is over.
+    Rematch,
+    /// The host chose how players will be seated, before the game starts.
+    SetSeating {
+        seating: Seating,
+    },
+    /// A player picked the faction they want to play, or none to take whatever is left, before the game starts.
+    PickFaction {
+        player_id: PlayerId,
+        faction: Option<Faction>,
+    },
+    /// The host asked to start the game, but the lobby isn't ready.
+    CannotStart {
+        reason: LobbyError,
+    },
+}
+
+/// Why a game can't be started from the lobby.
+#[derive(Debug, Error, Serialize, Deserialize)]
+pub enum LobbyError {
+    #[error("at least 2 players are needed, but only {0} joined")]
+    NotEnoughPlayers(usize),
+    #[error("at most 6 players can play, but {0} joined")]
+    TooManyPlayers(usize),
+    #[error("more than one player picked {0}")]
+    FactionTaken(Faction),
+}
+
+/// The events most recently sent to one client, so any it missed can be sent again.
+#[derive(Default)]
+struct SentEvents {
+    next_seq: u64,
+    recent: VecDeque<(u64, GameEvent)>,
+}
+
+impl SentEvents {
+    /// Number the next event sent to the client, keeping it in case it has to be sent again.
+    fn push(&mut self, event: GameEvent) -> u64 {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.recent.push_back((seq, event));
+        if self.recent.len() > CATCHUP_BUFFER {
+            self.recent.pop_front();
+        }
+        seq
+    }
+
+    /// Every event sent since the given one, or none if some of them have already been forgotten.
+    fn since(&self, from_seq: u64) -> Option<Vec<(u64, GameEvent)>> {
+        let oldest = self.recent.front().map_or(self.next_seq, |(seq, _)| *seq);
+        (from_seq >= oldest).then(|| {
+            self.recent
+                .iter()
+                .filter(|(seq, _)| *seq >= from_seq)
+                .cloned()
+                .collect()
+        })
+    }
 }
 
 pub struct Server {
     renet_server: renet::RenetServer,
-    state: GameState,
-    data: Data,
+    driver: GameDriver,
     waiting_players: HashSet<PlayerId>,
     ready_players: HashSet<PlayerId>,
-    ids: ObjectIdGenerator,
+    /// Everyone in the lobby, in the order they joined.
+    join_order: Vec<PlayerId>,
+    response_deadlines: HashMap<PlayerId, Instant>,
+    bots: HashMap<PlayerId, Box<dyn Bot>>,
+    /// The optional rules the game will be played with.
+    rules: RuleSet,
+    /// Every event which has been sent, including those only sent to one player.
+    log: Vec<GameEvent>,
+    /// The events recently sent to each client.
+    sent: HashMap<PlayerId, SentEvents>,
+    /// Clients who joined once the game had started, only to watch. Having no part in the game, they are told
+    /// everything as it happens, secrets included.
+    spectators: HashSet<PlayerId>,
+    /// The factions players picked in the lobby, which are chosen for them as soon as it's their turn to choose.
+    picked_factions: HashMap<PlayerId, Faction>,
+    /// What players called themselves when they joined, to keep stats under.
+    names: HashMap<PlayerId, String>,
+    /// The seed the host asked for, or none to draw a new one for each game.
+    seed: Option<u64>,
+    /// Whether the host wants the game saved at the start of every turn.
+    autosave: Arc<AtomicBool>,
 }
 
 impl Server {
-    /// This is the server logic, which is run whenever the game state changes.
-    fn game_logic(&mut self, last_event: GameEvent) -> Result<(), RenetNetworkingError> {
-        use GameEvent::*;
-        match last_event {
-            AdvancePhase => match &self.state.phase {
-                Phase::Setup(s) => match s {
-                    SetupPhase::ChooseFactions => {
-                        // TODO: Perhaps allow other ways to determine play order
-                        let mut play_order = self.ready_players.drain().collect::<Vec<_>>();
-                        let mut rng = rand::thread_rng();
-                        play_order.shuffle(&mut rng);
-                        self.generate(SetPlayOrder { play_order })?;
-                        self.generate(StartRound)?;
-                    }
-                    SetupPhase::Prediction => {
-                        if let Some(bg_player) = self.state.factions.get(&Faction::BeneGesserit).copied() {
-                            self.generate(SetActive { player_id: bg_player })?;
-                            self.generate(ShowPrompt {
-                                player_id: bg_player,
-                                prompt: Prompt::FactionPrediction,
-                            })?;
-                        } else {
-                            self.generate(AdvancePhase)?;
-                        }
-                    }
-                    SetupPhase::AtStart => {
-                        for card in self.data.treachery_deck.clone() {
-                            let card = self.spawn(card);
-                            self.generate(SpawnObject {
-                                spawn_type: SpawnType::TreacheryCard(card),
-                            })?;
-                        }
-                        for leader in Leader::iter() {
-                            let faction = self.data.leaders[&leader].faction;
-                            if self.state.factions.contains_key(&faction) {
-                                let card = self.spawn(TraitorCard { leader });
-                                self.generate(SpawnObject {
-                                    spawn_type: SpawnType::TraitorCard(card),
-                                })?;
-                            }
-                        }
-                        for card in self.data.spice_cards.keys().cloned().collect::<Vec<_>>() {
-                            let card = self.spawn(card);
-                            self.generate(SpawnObject {
-                                spawn_type: SpawnType::SpiceCard(card),
-                            })?;
-                        }
-                        for card in (1..=6).map(|val| StormCard { val }) {
-                            let card = self.spawn(card);
-                            self.generate(SpawnObject {
-                                spawn_type: SpawnType::StormCard(card),
-                            })?;
-                        }
+    fn new(renet_server: renet::RenetServer, seed: Option<u64>, autosave: Arc<AtomicBool>) -> Self {
+        Self {
+            renet_server,
+            driver: GameDriver::new(Default::default()),
+            waiting_players: Default::default(),
+            ready_players: Default::default(),
+            join_order: Default::default(),
+            response_deadlines: Default::default(),
+            bots: Default::default(),
+            rules: Default::default(),
+            log: Default::default(),
+            sent: Default::default(),
+            spectators: Default::default(),
+            picked_factions: Default::default(),
+            names: Default::default(),
+            seed,
+            autosave,
+        }
+    }
 
-                        let mut rng = rand::thread_rng();
-                        let mut deck_order = self
-                            .state
-                            .decks
-                            .traitor
-                            .cards
-                            .iter()
-                            .map(|card| card.id)
-                            .collect::<Vec<_>>();
-                        deck_order.shuffle(&mut rng);
-                        self.generate(SetDeckOrder {
-                            deck_order,
-                            deck_type: DeckType::Traitor,
-                        })?;
-
-                        let mut deck_order = self
-                            .state
-                            .decks
-                            .treachery
-                            .cards
-                            .iter()
-                            .map(|card| card.id)
-                            .collect::<Vec<_>>();
-                        deck_order.shuffle(&mut rng);
-                        self.generate(SetDeckOrder {
-                            deck_order,
-                            deck_type: DeckType::Treachery,
-                        })?;
-
-                        let mut deck_order = self
-                            .state
-                            .decks
-                            .spice
-                            .cards
-                            .iter()
-                            .map(|card| card.id)
-                            .collect::<Vec<_>>();
-                        deck_order.shuffle(&mut rng);
-                        self.generate(SetDeckOrder {
-                            deck_order,
-                            deck_type: DeckType::Spice,
-                        })?;
-
-                        let mut deck_order = self
-                            .state
-                            .decks
-                            .storm
-                            .cards
-                            .iter()
-                            .map(|card| card.id)
-                            .collect::<Vec<_>>();
-                        deck_order.shuffle(&mut rng);
-                        self.generate(SetDeckOrder {
-                            deck_order,
-                            deck_type: DeckType::Storm,
-                        })?;
-
-                        self.generate(AdvancePhase)?;
-                    }
-                    SetupPhase::DealTraitors => {
-                        for player_id in std::iter::repeat(self.state.play_order.clone()).take(4).flatten() {
-                            self.generate(DealCard {
-                                player_id,
-                                from: DeckType::Traitor,
-                            })?;
-                        }
-                        for player_id in self.state.play_order.clone() {
-                            if !matches!(self.state.players[&player_id].faction, Faction::Harkonnen) {
-                                self.generate(ShowPrompt {
-                                    player_id,
-                                    prompt: Prompt::Traitor,
-                                })?;
-                            }
-                        }
-                    }
-                    SetupPhase::PlaceForces => {
-                        self.generate(StartRound)?;
-                    }
-                    SetupPhase::DealTreachery => {
-                        for player_id in self.state.play_order.clone() {
-                            self.generate(DealCard {
-                                player_id,
-                                from: DeckType::Treachery,
-                            })?;
-                        }
-                        // Harkonnen gets two
-                        if let Some(hk_player) = self.state.factions.get(&Faction::Harkonnen).copied() {
-                            self.generate(DealCard {
-                                player_id: hk_player,
-                                from: DeckType::Treachery,
-                            })?;
-                        }
-                        self.generate(AdvancePhase)?;
-                    }
-                },
-                Phase::Storm(p) => match p {
-                    StormPhase::Reveal => {
-                        if self.state.game_turn > 0 {
-                            self.generate(RevealStorm)?;
-                        }
-                        self.generate(AdvancePhase)?;
-                    }
-                    StormPhase::WeatherControl => {
-                        if self.state.game_turn > 0 {
-                            // TODO allow players to play weather control
-                        }
-                        self.generate(AdvancePhase)?;
-                    }
-                    StormPhase::FamilyAtomics => {
-                        if self.state.game_turn > 0 {
-                            // TODO allow players to play family atomics
-                        }
-                        self.generate(AdvancePhase)?;
-                    }
-                    StormPhase::MoveStorm => {
-                        if self.state.game_turn == 0 {
-                            self.generate(MoveStorm {
-                                sectors: rand::thread_rng().gen_range(0..18),
-                            })?;
-                        } else {
-                            self.generate(MoveStorm {
-                                sectors: self.state.storm_card.as_ref().unwrap().inner.val,
-                            })?;
-                        }
-                        self.generate(AdvancePhase)?;
-                    }
-                },
-                Phase::SpiceBlow(s) => match s {
-                    SpiceBlowPhase::Reveal => {
-                        loop {
-                            self.generate(RevealSpiceBlow)?;
-                            match self.state.spice_card.as_ref().unwrap().inner {
-                                SpiceCard::ShaiHalud => (),
-                                _ => {
-                                    break;
-                                }
-                            }
-                        }
-                        self.generate(AdvancePhase)?;
-                    }
-                    SpiceBlowPhase::ShaiHalud => {
-                        if let Some(nexus_card) = self.state.nexus.as_ref() {
-                            let SpiceLocationData { location, .. } =
-                                self.data.spice_cards[&nexus_card.inner].location_data.unwrap();
-                            self.generate(RideTheWorm { location })?;
-                        }
-                        self.generate(AdvancePhase)?;
-                    }
-                    SpiceBlowPhase::PlaceSpice => {
-                        if let Some(spice_card) = self.state.spice_card.as_ref() {
-                            let SpiceLocationData {
-                                location,
-                                sector,
-                                spice,
-                            } = self.data.spice_cards[&spice_card.inner].location_data.unwrap();
-                            self.generate(PlaceSpice {
-                                location: location.with_sector(sector),
-                                spice,
-                            })?;
-                        }
-                        self.generate(AdvancePhase)?;
-                    }
-                },
-                Phase::Nexus => {
-                    if self.state.nexus.is_some() {
-                        // TODO: hold the nexus
-                    }
-                    self.generate(AdvancePhase)?;
-                }
-                Phase::Bidding(s) => match s {
-                    BiddingPhase::DealCards => {
-                        self.generate(StartBidding)?;
-                        self.generate(AdvancePhase)?;
-                    }
-                    BiddingPhase::Bidding => {
-                        self.generate(StartRound)?;
-                    }
-                },
-                _ => (),
-            },
-            StartRound | Pass { .. } => match self.state.phase {
-                Phase::Setup(s) => match s {
-                    SetupPhase::ChooseFactions => {
-                        if let Some(player_id) = self.state.active_player {
-                            let mut remaining = Faction::iter().collect::<HashSet<_>>();
-                            for faction in self.state.factions.keys() {
-                                remaining.remove(faction);
-                            }
-                            self.generate(ShowPrompt {
-                                player_id,
-                                prompt: Prompt::Faction { remaining },
-                            })?;
-                        } else {
-                            self.generate(AdvancePhase)?;
-                        }
-                    }
-                    SetupPhase::DealTraitors => {
-                        if self.state.prompts.is_empty() {
-                            self.generate(AdvancePhase)?;
-                        }
-                    }
-                    SetupPhase::PlaceForces => {
-                        if let Some(player_id) = self.state.active_player {
-                            if self.data.factions[&self.state.players[&player_id].faction]
-                                .starting_values
-                                .units
-                                == 0
-                            {
-                                self.generate(Pass { player_id })?;
-                            }
-                        } else {
-                            self.generate(AdvancePhase)?;
-                        }
-                    }
-                    _ => (),
-                },
-                Phase::Bidding(s) => match s {
-                    BiddingPhase::Bidding => {
-                        // If there is a card to bid on
-                        if let Some(bid) = self.state.bidding_cards.current() {
-                            if let Some(player_id) = self.state.active_player.clone() {
-                                if let Some(current_bid) = bid.current_bid.as_ref() {
-                                    if current_bid.player_id == player_id {
-                                        if current_bid.spice > 0 {
-                                            self.generate(WinBid {
-                                                player_id: current_bid.player_id,
-                                                card_id: bid.card.id,
-                                            })?;
-                                            self.generate(StartRound)?;
-                                        } else {
-                                            self.generate(AdvancePhase)?;
-                                        }
-                                    } else {
-                                        if self.state.players[&player_id].spice > current_bid.spice {
-                                            self.generate(ShowPrompt {
-                                                player_id,
-                                                prompt: Prompt::Bid,
-                                            })?;
-                                        } else {
-                                            self.generate(Pass { player_id })?;
-                                        }
-                                    }
-                                } else {
-                                    self.generate(MakeBid { player_id, spice: 0 })?;
-                                    self.generate(ShowPrompt {
-                                        player_id,
-                                        prompt: Prompt::Bid,
-                                    })?;
-                                }
-                            } else {
-                                self.generate(StartRound)?;
-                            }
-                        } else {
-                            self.generate(AdvancePhase)?;
-                        }
-                    }
-                    _ => (),
-                },
-                _ => (),
-            },
-            ChooseFaction { player_id, faction } => {
-                for leader in self
-                    .data
-                    .leaders
-                    .clone()
-                    .into_iter()
-                    .filter_map(|(leader, data)| (data.faction == faction).then_some(leader))
-                {
-                    let leader = self.spawn(leader);
-                    self.generate(SpawnObject {
-                        spawn_type: SpawnType::Leader { player_id, leader },
-                    })?;
-                }
-                for unit in std::iter::repeat_with(|| Troop { is_special: false })
-                    .take(20 - self.data.factions[&faction].special_forces as usize)
-                    .chain(
-                        std::iter::repeat_with(|| Troop { is_special: true })
-                            .take(self.data.factions[&faction].special_forces as usize),
-                    )
-                {
-                    let unit = self.spawn(unit);
-                    self.generate(SpawnObject {
-                        spawn_type: SpawnType::Troop { player_id, unit },
-                    })?;
+    /// Check that a game could be started with everyone in the lobby.
+    fn validate_lobby(&self) -> Result<(), LobbyError> {
+        let players = self.waiting_players.len() + self.ready_players.len();
+        if players < MIN_PLAYERS {
+            return Err(LobbyError::NotEnoughPlayers(players));
+        } else if players > MAX_PLAYERS {
+            return Err(LobbyError::TooManyPlayers(players));
+        }
+        // Players who didn't pick a faction get whichever are left, but nobody can be promised one twice
+        let mut picked = HashSet::new();
+        for player_id in self.join_order.iter() {
+            if let Some(&faction) = self.picked_factions.get(player_id) {
+                if !picked.insert(faction) {
+                    return Err(LobbyError::FactionTaken(faction));
                 }
-                self.generate(Pass { player_id })?;
             }
-            ChooseTraitor { player_id, card_id } => {
-                // Discard the cards that weren't picked
-                for card_id in self.state.players[&player_id]
-                    .traitor_cards
+        }
+        Ok(())
+    }
+
+    /// The first person to join who isn't a bot, which is whoever is running the server.
+    fn host(&self) -> Option<PlayerId> {
+        self.join_order
+            .iter()
+            .find(|player_id| !self.bots.contains_key(player_id))
+            .copied()
+    }
+
+    /// Consume an event and any follow-on events, and broadcast them to all clients.
+    fn dispatch(&mut self, event: GameEvent) -> Result<(), RenetNetworkingError> {
+        let events = self.driver.run(event);
+        self.broadcast(events)
+    }
+
+    fn broadcast(&mut self, events: Vec<GameEvent>) -> Result<(), RenetNetworkingError> {
+        let game_over = events.iter().any(|event| matches!(event, GameEvent::EndGame { .. }));
+        let new_turn = events.iter().any(|event| matches!(event, GameEvent::AdvanceTurn));
+        for event in events {
+            let recipients = self
+                .renet_server
+                .clients_id()
+                .into_iter()
+                .filter_map(|client_id| self.told(client_id, &event).map(|told| (client_id, told)))
+                .collect::<Vec<_>>();
+            #[cfg(feature = "debug")]
+            {
+                // Spectators are meant to be told everything
+                let players = recipients
                     .iter()
-                    .filter_map(|card| (card.id != card_id).then_some(card.id))
-                    .collect::<Vec<_>>()
-                {
-                    self.generate(DiscardCard {
-                        player_id,
-                        card_id,
-                        to: DeckType::Traitor,
-                    })?;
+                    .map(|(client_id, _)| PlayerId::from(*client_id))
+                    .filter(|player_id| !self.spectators.contains(player_id))
+                    .collect::<Vec<_>>();
+                for leak in audit_events(std::slice::from_ref(&event), &players, &self.driver.state.rules) {
+                    error!("Leaked a secret: {}", leak);
                 }
-                self.generate(Pass { player_id })?;
             }
-            MakeFactionPrediction { .. } => {
-                self.generate(ShowPrompt {
-                    player_id: self.state.active_player.unwrap(),
-                    prompt: Prompt::TurnPrediction,
-                })?;
+            for (client_id, told) in recipients {
+                self.send_game_event(client_id, told)?;
             }
-            MakeTurnPrediction { .. } => {
-                self.generate(AdvancePhase)?;
-            }
-            ShipForces { .. } => {
-                if matches!(self.state.phase, Phase::Setup(SetupPhase::PlaceForces)) {
-                    if let Some(player_id) = self.state.active_player {
-                        let player = &self.state.players[&player_id];
-                        let faction_data = &self.data.factions[&player.faction];
-                        if player.offworld_forces.len() == 20 - faction_data.starting_values.units as usize {
-                            self.generate(Pass { player_id })?;
-                        }
-                    }
-                } else {
-                    // TODO: shipping during ship n' move
+            self.log.push(event);
+        }
+        if new_turn && self.autosave.load(Ordering::Relaxed) {
+            self.autosave();
+        }
+        if game_over {
+            self.save_game();
+            self.record_stats();
+        }
+        Ok(())
+    }
+
+    /// Save the game in the background, so writing it out doesn't hold up the game.
+    fn autosave(&self) {
+        let save = SaveFile::new(self.driver.state.clone(), self.driver.ids.clone(), self.driver.seed());
+        thread::spawn(move || match save.autosave() {
+            Ok(path) => info!("Autosaved the game to {}", path.display()),
+            Err(e) => error!("Failed to autosave the game: {}", e),
+        });
+    }
+
+    /// Send a game event to one client, numbered so it can tell if it missed any.
+    fn send_game_event(&mut self, client_id: u64, event: GameEvent) -> Result<(), RenetNetworkingError> {
+        let seq = self.sent.entry(client_id.into()).or_default().push(event.clone());
+        self.renet_server
+            .send_message(client_id, 0, pack(&ServerEvent::Sequenced { seq, event })?);
+        Ok(())
+    }
+
+    /// An event as a client is told it, or none if they aren't told it at all.
+    fn told(&self, client_id: u64, event: &GameEvent) -> Option<GameEvent> {
+        let player_id = client_id.into();
+        if self.spectators.contains(&player_id) {
+            Some(event.clone())
+        } else {
+            event
+                .visible_to(player_id, &self.driver.state.rules)
+                .then(|| event.redacted_for(player_id))
+        }
+    }
+
+    /// Everything a client has been able to see of the game so far.
+    fn full_sync(&self, client_id: u64) -> ServerEvent {
+        ServerEvent::FullSync {
+            events: self
+                .log
+                .iter()
+                .filter_map(|event| self.told(client_id, event))
+                .collect(),
+            next_seq: self.sent.get(&client_id.into()).map_or(0, |sent| sent.next_seq),
+            spectator: self.spectators.contains(&client_id.into()),
+        }
+    }
+
+    /// Send a client the events it missed again. If it has missed more than are kept, it is sent the whole game.
+    fn catch_up(&mut self, client_id: u64, from_seq: u64) -> Result<(), RenetNetworkingError> {
+        let missed = self
+            .sent
+            .get(&client_id.into())
+            .map_or(Some(Vec::new()), |sent| sent.since(from_seq));
+        match missed {
+            Some(missed) => {
+                debug!("Sending player {} {} events again", client_id, missed.len());
+                for (seq, event) in missed {
+                    self.renet_server
+                        .send_message(client_id, 0, pack(&ServerEvent::Sequenced { seq, event })?);
                 }
             }
-            MakeBid { player_id, .. } => {
-                self.generate(Pass { player_id })?;
+            None => {
+                info!(
+                    "Player {} is too far behind to catch up from event {}, syncing the whole game",
+                    client_id, from_seq
+                );
+                let sync = self.full_sync(client_id);
+                self.renet_server.send_message(client_id, 0, pack(&sync)?);
             }
-            _ => (),
         }
         Ok(())
     }
 
-    /// Consume an event and broadcast it to all clients.
-    fn generate(&mut self, event: GameEvent) -> Result<(), RenetNetworkingError> {
-        let serialized_event = bincode::serialize(&event)?;
-        self.state.consume(&self.data, event.clone());
-        self.renet_server.broadcast_message(0, serialized_event);
-        self.game_logic(event)?;
+    /// Keep a copy of the game if the host asked for one.
+    fn save_game(&self) {
+        if let Ok(path) = std::env::var("SAVE_GAME") {
+            match SaveFile::new(self.driver.state.clone(), self.driver.ids.clone(), self.driver.seed()).write(&path) {
+                Ok(()) => info!("Saved the game to {}", path),
+                Err(e) => error!("Failed to save the game to {}: {}", path, e),
+            }
+        }
+    }
+
+    /// Add the game which just ended to the host's stats.
+    fn record_stats(&self) {
+        if let Err(e) = Stats::record(GameRecord::new(&self.driver.state, &self.names)) {
+            error!("Failed to record the game in the stats: {}", e);
+        }
+    }
+
+    /// Write out everything the server knows, including the secrets no client has, for a bug report.
+    fn dump_state(&self) {
+        match StateDump::new(&self.driver.state, &self.log).write("server") {
+            Ok(path) => info!("Dumped the server state to {}", path.display()),
+            Err(e) => error!("Failed to dump the server state: {}", e),
+        }
+        // Go back over everything that was sent, in case a leak was missed as it happened
+        let players = self.driver.state.players.keys().copied().collect::<Vec<_>>();
+        for leak in audit_events(&self.log, &players, &self.driver.state.rules) {
+            warn!("Leaked a secret: {}", leak);
+        }
+    }
+
+    /// Welcome a client who just connected. Before the game starts they join the lobby, and afterwards they watch.
+    fn connect(&mut self, client_id: u64, name: Option<String>) -> Result<(), RenetNetworkingError> {
+        self.sent.insert(client_id.into(), SentEvents::default());
+        // Anyone joining a game which has already started can only watch, and has no place in the lobby
+        let spectating = !self.driver.state.play_order.is_empty();
+        if !spectating {
+            self.waiting_players.insert(client_id.into());
+            self.join_order.push(client_id.into());
+        }
+        if let Some(name) = name {
+            self.names.insert(client_id.into(), name);
+        }
+        let event = GameEvent::PlayerJoined {
+            player_id: client_id.into(),
+        };
+        // Tell the recently joined player about the other players
+        let others = self
+            .waiting_players
+            .iter()
+            .chain(self.bots.keys())
+            .copied()
+            .collect::<Vec<_>>();
+        for player_id in others {
+            self.send_game_event(client_id, GameEvent::PlayerJoined { player_id })?;
+        }
+        // And how they will be seated
+        let seating = ServerEvent::SetSeating {
+            seating: self.rules.seating,
+        };
+        self.renet_server.send_message(client_id, 0, pack(&seating)?);
+        // And bring them up to date on a game which has already started
+        if spectating {
+            self.spectators.insert(client_id.into());
+            let sync = self.full_sync(client_id);
+            self.renet_server.send_message(client_id, 0, pack(&sync)?);
+        }
+
+        // Add the new player to the game
+        self.dispatch(event)?;
+
+        info!("Client {} connected.", client_id);
         Ok(())
     }
 
+    /// See a client off, taking them out of the game if they were playing.
+    fn disconnect(&mut self, client_id: u64) -> Result<(), RenetNetworkingError> {
+        let player_id = client_id.into();
+        self.sent.remove(&player_id);
+        self.spectators.remove(&player_id);
+        self.waiting_players.remove(&player_id);
+        self.ready_players.remove(&player_id);
+        self.picked_factions.remove(&player_id);
+        self.join_order.retain(|id| *id != player_id);
+        // Players who conceded have already left the game gracefully
+        let in_game = self.driver.state.play_order.contains(&player_id);
+        if in_game && self.driver.state.play_order.len() > MIN_PLAYERS {
+            self.leave_game(player_id)?;
+        }
+        self.dispatch(GameEvent::PlayerDisconnected { player_id })?;
+        info!("Client {} disconnected", client_id);
+
+        // Without enough players left to carry on, end the game
+        if self.driver.state.play_order.contains(&player_id) {
+            self.dispatch(GameEvent::EndGame {
+                reason: EndGameReason::PlayerLeft { player_id },
+            })?;
+        }
+
+        // NOTE: Since we don't authenticate users we can't do any reconnection attempts.
+        // We simply have no way to know if the next user is the same as the one that disconnected.
+        Ok(())
+    }
+
+    /// A player in the lobby is ready to start. Once everyone is, the game starts. Returns whether the player was
+    /// waiting to be ready.
+    fn ready(&mut self, client_id: u64) -> Result<bool, RenetNetworkingError> {
+        if !self.driver.state.play_order.is_empty() {
+            return Ok(false);
+        }
+        let player_id = match self.waiting_players.take(&client_id.into()) {
+            Some(player_id) => player_id,
+            None => return Ok(false),
+        };
+        self.ready_players.insert(player_id);
+        if self.waiting_players.is_empty() {
+            self.ready_players.clear();
+            let players = self.join_order.clone();
+            let seed = self.seed.unwrap_or_else(rand::random);
+            info!("Starting the game with seed {}", seed);
+            let events = self.driver.start_game(players, self.host(), self.rules.clone(), seed);
+            self.broadcast(events)?;
+        }
+        Ok(true)
+    }
+
+    /// Change how players will be seated, if the host asked before the game started. Returns whether it was changed.
+    fn set_seating(&mut self, client_id: u64, seating: Seating) -> bool {
+        if self.host() != Some(client_id.into()) || !self.driver.state.play_order.is_empty() {
+            return false;
+        }
+        self.rules.seating = seating;
+        true
+    }
+
+    /// Remember the faction a player picked in the lobby, to choose for them once the game starts. Returns whether
+    /// they were allowed to pick.
+    fn pick_faction(&mut self, client_id: u64, player_id: PlayerId, faction: Option<Faction>) -> bool {
+        if player_id != client_id.into() || !self.driver.state.play_order.is_empty() {
+            return false;
+        }
+        match faction {
+            Some(faction) => self.picked_factions.insert(player_id, faction),
+            None => self.picked_factions.remove(&player_id),
+        };
+        true
+    }
+
+    /// Play an event a client sent, if it is theirs to send and it is legal.
+    fn receive(&mut self, client_id: u64, event: GameEvent) -> Result<(), RenetNetworkingError> {
+        trace!("Player {} sent:\n\t{:#?}", client_id, event);
+        if event.sender().map_or(false, |player_id| player_id != client_id.into()) {
+            warn!(
+                "Player {} sent an event on behalf of another player:\n\t{:#?}",
+                client_id, event
+            );
+            return Ok(());
+        }
+        match self.driver.apply(event) {
+            Ok(events) => self.broadcast(events),
+            Err(e) => {
+                warn!("Player {} sent invalid event: {}", client_id, e);
+                Ok(())
+            }
+        }
+    }
+
     /// Process the current buffer of events.
     fn process_events(&mut self) -> Result<(), RenetNetworkingError> {
         // Receive connection events from clients
         while let Some(event) = self.renet_server.get_event() {
             match event {
-                renet::ServerEvent::ClientConnected(id, ..) => {
-                    self.waiting_players.insert(id.into());
-                    let event = GameEvent::PlayerJoined { player_id: id.into() };
-                    // Tell the recently joined player about the other players
-                    for player_id in self.waiting_players.iter() {
-                        let event = GameEvent::PlayerJoined { player_id: *player_id };
-                        self.renet_server.send_message(id, 0, bincode::serialize(&event)?);
-                    }
-
-                    // Add the new player to the game
-                    self.generate(event)?;
-
-                    info!("Client {} connected.", id);
+                renet::ServerEvent::ClientConnected(id, user_data) => {
+                    self.connect(id, name_from_user_data(&user_data))?;
                 }
                 renet::ServerEvent::ClientDisconnected(id) => {
-                    let player_id = id.into();
-                    self.waiting_players.remove(&player_id);
-                    self.ready_players.remove(&player_id);
-                    self.generate(GameEvent::PlayerDisconnected { player_id })?;
-                    info!("Client {} disconnected", id);
-
-                    // Then end the game
-                    self.generate(GameEvent::EndGame {
-                        reason: EndGameReason::PlayerLeft { player_id: id.into() },
-                    })?;
-
-                    // NOTE: Since we don't authenticate users we can't do any reconnection attempts.
-                    // We simply have no way to know if the next user is the same as the one that disconnected.
+                    self.disconnect(id)?;
                 }
             }
         }
@@ -473,56 +495,221 @@ impl Server {
         // Receive GameEvents from clients. Consume valid events.
         for client_id in self.renet_server.clients_id().into_iter() {
             while let Some(message) = self.renet_server.receive_message(client_id, 0) {
+                let message = match unpack(&message) {
+                    Ok(message) => message,
+                    Err(e) => {
+                        warn!("Player {} sent an invalid message: {}", client_id, e);
+                        continue;
+                    }
+                };
                 if let Ok(event) = bincode::deserialize::<ServerEvent>(&message) {
                     match &event {
                         ServerEvent::LoadAssets | ServerEvent::StartGame => {
-                            if self.waiting_players.len() + self.ready_players.len() < 2 {
+                            if !self.driver.state.play_order.is_empty() {
+                                warn!("Player {} tried to start a game which has already started", client_id);
+                                continue;
+                            }
+                            if let Err(reason) = self.validate_lobby() {
+                                warn!("Player {} can't start the game: {}", client_id, reason);
+                                let rejection = ServerEvent::CannotStart { reason };
+                                self.renet_server.broadcast_message(0, pack(&rejection)?);
+                                continue;
+                            }
+                        }
+                        ServerEvent::Rematch => {
+                            if self.request_rematch(client_id) {
+                                info!("Player {} started a rematch", client_id);
+                                self.renet_server.broadcast_message(0, pack(&ServerEvent::LoadAssets)?);
+                            } else {
+                                warn!(
+                                    "Player {} asked for a rematch, but isn't the host or the game isn't over",
+                                    client_id
+                                );
+                            }
+                            continue;
+                        }
+                        ServerEvent::SetSeating { seating } => {
+                            if !self.set_seating(client_id, *seating) {
+                                warn!(
+                                    "Player {} tried to change the seating, but isn't the host or the game has started",
+                                    client_id
+                                );
+                                continue;
+                            }
+                        }
+                        ServerEvent::PickFaction { player_id, faction } => {
+                            if !self.pick_faction(client_id, *player_id, *faction) {
                                 warn!("Player {} sent invalid event:\n\t{:#?}", client_id, event);
                                 continue;
                             }
                         }
+                        ServerEvent::RequestCatchup { from_seq } => {
+                            self.catch_up(client_id, *from_seq)?;
+                            continue;
+                        }
+                        ServerEvent::Sequenced { .. }
+                        | ServerEvent::FullSync { .. }
+                        | ServerEvent::CannotStart { .. } => {
+                            warn!("Player {} sent invalid event:\n\t{:#?}", client_id, event);
+                            continue;
+                        }
                     }
                     if let ServerEvent::StartGame = &event {
-                        if let Some(player_id) = self.waiting_players.take(&client_id.into()) {
-                            self.ready_players.insert(player_id);
-                            if self.waiting_players.len() == 0 {
-                                self.generate(GameEvent::AdvancePhase)?;
-                            }
-                        } else {
+                        if !self.ready(client_id)? {
                             warn!("Player {} sent invalid event:\n\t{:#?}", client_id, event);
+                            continue;
                         }
                     }
-                    let serialized_event = bincode::serialize(&event)?;
+                    let serialized_event = pack(&event)?;
                     self.renet_server.broadcast_message(0, serialized_event);
                 } else if let Ok(event) = bincode::deserialize::<GameEvent>(&message) {
-                    if self.state.validate(&self.data, &event) {
-                        trace!("Player {} sent:\n\t{:#?}", client_id, event);
-                        self.generate(event)?;
-                    } else {
-                        warn!("Player {} sent invalid event:\n\t{:#?}", client_id, event);
-                    }
+                    self.receive(client_id, event)?;
                 }
             }
         }
 
+        self.expire_responses()?;
+        self.choose_picked_factions()?;
+        self.run_bots()?;
+
         self.renet_server.send_packets()?;
         Ok(())
     }
 
-    fn spawn<T>(&mut self, t: T) -> Object<T> {
-        self.ids.spawn(t)
+    /// Start a rematch if the host asked for one once the game is over. Returns whether it was started.
+    fn request_rematch(&mut self, client_id: u64) -> bool {
+        if self.host() != Some(client_id.into()) || self.driver.state.phase != Phase::EndGame {
+            return false;
+        }
+        self.rematch();
+        true
+    }
+
+    /// Start over with everyone who is still connected, keeping their names for the stats. Seats and factions are
+    /// drawn again as the new game is set up, once everyone has loaded back in.
+    fn rematch(&mut self) {
+        self.driver.reset();
+        self.log.clear();
+        self.response_deadlines.clear();
+        self.picked_factions.clear();
+        // Anyone who was watching plays in the new game, after everyone who played in the last
+        let mut spectators = self.spectators.drain().collect::<Vec<_>>();
+        spectators.sort();
+        self.join_order.extend(spectators);
+        self.waiting_players = self
+            .join_order
+            .iter()
+            .filter(|player_id| !self.bots.contains_key(player_id))
+            .copied()
+            .collect();
+        self.ready_players = self.bots.keys().copied().collect();
+    }
+
+    /// Take a player who disconnected out of the game, so nobody is left waiting on them. Whatever they were in the
+    /// middle of deciding is settled with the least they could have done, then they leave as if they had conceded.
+    fn leave_game(&mut self, player_id: PlayerId) -> Result<(), RenetNetworkingError> {
+        let state = &self.driver.state;
+        if state
+            .response_window
+            .as_ref()
+            .map_or(false, |window| window.can_respond(&player_id))
+        {
+            self.dispatch(GameEvent::Decline { player_id })?;
+        }
+        if self.driver.state.active_player == Some(player_id) {
+            self.dispatch(GameEvent::Pass { player_id })?;
+        }
+        self.dispatch(GameEvent::Concede { player_id })
+    }
+
+    /// Choose the factions players picked in the lobby for them, as their turns to choose come up.
+    fn choose_picked_factions(&mut self) -> Result<(), RenetNetworkingError> {
+        while let (Phase::Setup(SetupPhase::ChooseFactions), Some(player_id)) =
+            (self.driver.state.phase, self.driver.state.active_player)
+        {
+            let faction = match self.picked_factions.remove(&player_id) {
+                Some(faction) => faction,
+                None => break,
+            };
+            match self.driver.apply(GameEvent::ChooseFaction { player_id, faction }) {
+                Ok(events) => self.broadcast(events)?,
+                Err(e) => {
+                    warn!("Couldn't choose {} for player {}: {}", faction, player_id, e);
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Let bots act until none of them are being waited on.
+    fn run_bots(&mut self) -> Result<(), RenetNetworkingError> {
+        // Bots should never stall the game, but don't let a confused one spin forever either
+        for _ in 0..100 {
+            let mut waiting = self
+                .bots
+                .keys()
+                .filter(|player_id| needs_action(&self.driver.state, **player_id))
+                .copied()
+                .collect::<Vec<_>>();
+            waiting.sort();
+            if let Some(player_id) = waiting.first() {
+                let event = self.bots[player_id].choose_action(&self.driver, *player_id);
+                match self.driver.apply(event) {
+                    Ok(events) => self.broadcast(events)?,
+                    Err(e) => {
+                        warn!("Bot {} chose an invalid event: {}", player_id, e);
+                        break;
+                    }
+                }
+            } else {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Decline on behalf of players who have let a response window run out.
+    fn expire_responses(&mut self) -> Result<(), RenetNetworkingError> {
+        let now = Instant::now();
+        if let Some(window) = self.driver.state.response_window.as_ref() {
+            self.response_deadlines
+                .retain(|player_id, _| window.can_respond(player_id));
+            for player_id in window.pending() {
+                self.response_deadlines
+                    .entry(*player_id)
+                    .or_insert(now + RESPONSE_TIMEOUT);
+            }
+        } else {
+            self.response_deadlines.clear();
+        }
+        let expired = self
+            .response_deadlines
+            .iter()
+            .filter_map(|(player_id, deadline)| (*deadline <= now).then_some(*player_id))
+            .collect::<Vec<_>>();
+        for player_id in expired {
+            self.response_deadlines.remove(&player_id);
+            self.dispatch(GameEvent::Decline { player_id })?;
+        }
+        Ok(())
     }
 }
 
-fn server() -> Result<(), RenetNetworkingError> {
+fn server(dump_requested: Arc<AtomicBool>, autosave: Arc<AtomicBool>) -> Result<(), RenetNetworkingError> {
     let server_addr: SocketAddr =
         format!("{}:{}", std::env::var("SERVER_HOST")?, std::env::var("SERVER_PORT")?).parse()?;
     let renet_server = renet::RenetServer::new(
         // Pass the current time to renet, so it can use it to order messages
         SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap(),
-        // Pass a server configuration specifying that we want to allow only 2 clients to connect
+        // Pass a server configuration specifying that we want to allow a full table and a few spectators to connect
         // and that we don't want to authenticate them. Everybody is welcome!
-        ServerConfig::new(2, PROTOCOL_ID, server_addr, ServerAuthentication::Unsecure),
+        ServerConfig::new(
+            MAX_PLAYERS + MAX_SPECTATORS,
+            PROTOCOL_ID,
+            server_addr,
+            ServerAuthentication::Unsecure,
+        ),
         // Pass the default connection configuration. This will create a reliable, unreliable and blocking channel.
         // We only actually need the reliable one, but we can just not use the other two.
         RenetConnectionConfig::default(),
@@ -531,17 +718,76 @@ fn server() -> Result<(), RenetNetworkingError> {
 
     info!("Dune server listening on {}", server_addr);
 
-    let game_state = GameState::default();
     let mut last_updated = Instant::now();
 
-    let mut server = Server {
-        renet_server,
-        state: game_state,
-        data: Default::default(),
-        waiting_players: Default::default(),
-        ready_players: Default::default(),
-        ids: Default::default(),
-    };
+    let seed = std::env::var("SEED").ok().and_then(|seed| seed.parse().ok());
+    let mut server = Server::new(renet_server, seed, autosave);
+
+    if let Ok(path) = std::env::var("RULES") {
+        match RuleSet::read(&path) {
+            Ok(rules) => server.rules = rules,
+            Err(e) => error!("Failed to read the rules in {}: {}", path, e),
+        }
+    }
+
+    // Ask for `autosave` to pick up from the most recent autosave
+    let load_game = std::env::var("LOAD_GAME").ok().and_then(|path| {
+        if path == "autosave" {
+            let latest = SaveFile::latest_autosave();
+            if latest.is_none() {
+                error!("There is no autosave to resume");
+            }
+            latest.map(|path| path.display().to_string())
+        } else {
+            Some(path)
+        }
+    });
+    if let Some(path) = load_game {
+        match SaveFile::read(&path) {
+            Ok(save) => {
+                info!("Resuming the game saved in {}", path);
+                server.driver.state = save.state;
+                server.driver.ids = save.ids;
+                if let Some(seed) = save.seed {
+                    server.driver.reseed(seed);
+                }
+            }
+            Err(e) => error!("Failed to load the game saved in {}: {}", path, e),
+        }
+    }
+
+    // Bots are described as `difficulty[:personality]`, separated by commas. They play the same way in games with the
+    // same seed, unless they are given one of their own.
+    let seed: u64 = std::env::var("BOT_SEED")
+        .ok()
+        .and_then(|seed| seed.parse().ok())
+        .or(server.seed)
+        .unwrap_or_else(rand::random);
+    for (i, bot) in std::env::var("BOTS")
+        .unwrap_or_default()
+        .split(',')
+        .filter(|s| !s.is_empty())
+        .enumerate()
+    {
+        let mut parts = bot.trim().split(':');
+        let difficulty = parts.next().and_then(|s| s.parse().ok()).unwrap_or(Difficulty::Greedy);
+        let personality = parts
+            .next()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(Personality::Balanced);
+        let player_id = PlayerId(u64::MAX - i as u64);
+        info!("Adding {} {} bot as player {}", difficulty, personality, player_id);
+        server.bots.insert(
+            player_id,
+            Box::new(TieredBot::new(difficulty, personality, seed.wrapping_add(i as u64))),
+        );
+        server.ready_players.insert(player_id);
+        server.join_order.push(player_id);
+        server
+            .names
+            .insert(player_id, format!("{} {} bot", difficulty, personality));
+        server.dispatch(GameEvent::PlayerJoined { player_id })?;
+    }
 
     loop {
         // Update server time
@@ -550,6 +796,414 @@ fn server() -> Result<(), RenetNetworkingError> {
         last_updated = now;
 
         server.process_events()?;
+        if dump_requested.swap(false, Ordering::Relaxed) {
+            server.dump_state();
+        }
         thread::sleep(Duration::from_millis(50));
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        components::Faction,
+        game::{
+            driver::tests::{next_action, players, PREDICTED_TURN, SEED},
+            phase::{bidding::BiddingPhase, setup::SetupPhase},
+            state::{DeckType, EventReduce, GameState, Prompt},
+        },
+    };
+
+    /// A server nobody can reach, which the tests play by hand as if messages had come from its clients.
+    fn test_server() -> Server {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let config = ServerConfig::new(
+            MAX_PLAYERS + MAX_SPECTATORS,
+            PROTOCOL_ID,
+            socket.local_addr().unwrap(),
+            ServerAuthentication::Unsecure,
+        );
+        let renet_server =
+            renet::RenetServer::new(Duration::ZERO, config, RenetConnectionConfig::default(), socket).unwrap();
+        let mut server = Server::new(renet_server, Some(SEED), Default::default());
+        server.rules.seating = Seating::JoinOrder;
+        server
+    }
+
+    /// Connect a player for each faction, and have them all ask to start.
+    fn start(server: &mut Server, factions: &[Faction]) {
+        for player_id in players(factions) {
+            server
+                .connect(player_id.0, Some(format!("Player {}", player_id)))
+                .unwrap();
+        }
+        server.validate_lobby().unwrap();
+        for player_id in players(factions) {
+            assert!(server.ready(player_id.0).unwrap());
+        }
+    }
+
+    /// Send whatever each player's client would send to play through setup.
+    fn play_setup(server: &mut Server, factions: &[Faction]) {
+        while matches!(server.driver.state.phase, Phase::Setup(_)) {
+            let event = next_action(&server.driver, factions).expect("setup stalled");
+            server.receive(event.sender().unwrap().0, event).unwrap();
+        }
+    }
+
+    #[test]
+    fn setup_deals_hands_and_spice() {
+        let factions = [
+            Faction::Atreides,
+            Faction::Harkonnen,
+            Faction::BeneGesserit,
+            Faction::Fremen,
+        ];
+        let mut server = test_server();
+        start(&mut server, &factions);
+        assert_eq!(server.driver.state.phase, Phase::Setup(SetupPhase::ChooseFactions));
+        play_setup(&mut server, &factions);
+
+        let state = &server.driver.state;
+        // Seats are kept in join order, and the first turn starts with the first seat
+        assert_eq!(state.play_order, players(&factions));
+        for (player_id, faction) in players(&factions).into_iter().zip(factions) {
+            let player = &state.players[&player_id];
+            assert_eq!(player.faction, faction);
+            assert_eq!(
+                player.spice,
+                server.driver.data.factions[&faction].starting_values.spice
+            );
+            // The Harkonnen keep every traitor and are dealt an extra treachery card
+            let (treachery, traitors) = if faction == Faction::Harkonnen { (2, 4) } else { (1, 1) };
+            assert_eq!(player.treachery_cards.len(), treachery, "{}", faction);
+            assert_eq!(player.traitor_cards.len(), traitors, "{}", faction);
+        }
+        assert_eq!(state.bg_predictions.faction, Some(Faction::Atreides));
+        assert_eq!(state.bg_predictions.turn, Some(PREDICTED_TURN));
+        assert!(server.log.iter().any(|event| matches!(
+            event,
+            GameEvent::DealCard {
+                from: DeckType::Treachery,
+                ..
+            }
+        )));
+        assert_eq!(state.phase, Phase::Bidding(BiddingPhase::Bidding));
+    }
+
+    #[test]
+    fn ignores_events_sent_for_someone_else() {
+        let factions = [Faction::Atreides, Faction::Harkonnen];
+        let mut server = test_server();
+        start(&mut server, &factions);
+        let event = GameEvent::ChooseFaction {
+            player_id: PlayerId(1),
+            faction: Faction::Atreides,
+        };
+        server.receive(2, event.clone()).unwrap();
+        assert!(server.driver.state.players.is_empty());
+        server.receive(1, event).unwrap();
+        assert_eq!(server.driver.state.factions[&Faction::Atreides], PlayerId(1));
+    }
+
+    #[test]
+    fn leaving_during_bidding_lets_the_auction_carry_on() {
+        let factions = [Faction::Atreides, Faction::Harkonnen, Faction::Fremen, Faction::Emperor];
+        let mut server = test_server();
+        start(&mut server, &factions);
+        play_setup(&mut server, &factions);
+        let (harkonnen, fremen) = (PlayerId(2), PlayerId(3));
+        server
+            .receive(
+                harkonnen.0,
+                GameEvent::MakeBid {
+                    player_id: harkonnen,
+                    spice: 3,
+                },
+            )
+            .unwrap();
+        assert_eq!(server.driver.state.active_player, Some(fremen));
+
+        // The player being waited on leaves, then so does the high bidder
+        server.disconnect(fremen.0).unwrap();
+        assert_eq!(server.driver.state.active_player, Some(PlayerId(4)));
+        server.disconnect(harkonnen.0).unwrap();
+        let state = &server.driver.state;
+        assert_eq!(state.play_order, vec![PlayerId(1), PlayerId(4)]);
+        let bid = state.bidding_cards.current().unwrap().current_bid.as_ref().unwrap();
+        assert_ne!(bid.player_id, harkonnen);
+        assert!(state.board.values().all(|location| location
+            .sectors
+            .values()
+            .all(|sector| !sector.forces.contains_key(&harkonnen) && !sector.forces.contains_key(&fremen))));
+
+        // Everyone left can play on into the next phase
+        while server.driver.state.phase != Phase::Movement {
+            let event = next_action(&server.driver, &factions).expect("bidding stalled");
+            server.receive(event.sender().unwrap().0, event).unwrap();
+        }
+    }
+
+    #[test]
+    fn start_is_rejected_without_the_right_number_of_players() {
+        let mut server = test_server();
+        server.connect(1, None).unwrap();
+        assert!(matches!(server.validate_lobby(), Err(LobbyError::NotEnoughPlayers(1))));
+        for client_id in 2..=MAX_PLAYERS as u64 {
+            server.connect(client_id, None).unwrap();
+            server.validate_lobby().unwrap();
+        }
+        server.connect(MAX_PLAYERS as u64 + 1, None).unwrap();
+        assert!(matches!(
+            server.validate_lobby(),
+            Err(LobbyError::TooManyPlayers(players)) if players == MAX_PLAYERS + 1
+        ));
+    }
+
+    #[test]
+    fn start_is_rejected_when_two_players_pick_the_same_faction() {
+        let mut server = test_server();
+        for client_id in 1..=3 {
+            server.connect(client_id, None).unwrap();
+        }
+        assert!(server.pick_faction(1, PlayerId(1), Some(Faction::Atreides)));
+        assert!(server.pick_faction(2, PlayerId(2), Some(Faction::Atreides)));
+        assert!(matches!(
+            server.validate_lobby(),
+            Err(LobbyError::FactionTaken(Faction::Atreides))
+        ));
+        // Nobody can pick for someone else
+        assert!(!server.pick_faction(1, PlayerId(2), None));
+        assert!(server.validate_lobby().is_err());
+
+        assert!(server.pick_faction(2, PlayerId(2), Some(Faction::Fremen)));
+        server.validate_lobby().unwrap();
+        assert!(server.pick_faction(2, PlayerId(2), None));
+        server.validate_lobby().unwrap();
+    }
+
+    #[test]
+    fn picked_factions_are_chosen_once_the_game_starts() {
+        let mut server = test_server();
+        for client_id in 1..=3 {
+            server.connect(client_id, None).unwrap();
+        }
+        assert!(server.pick_faction(1, PlayerId(1), Some(Faction::Fremen)));
+        assert!(server.pick_faction(3, PlayerId(3), Some(Faction::Harkonnen)));
+        for client_id in 1..=3 {
+            assert!(server.ready(client_id).unwrap());
+        }
+        // Nobody can change their mind once the game has started
+        assert!(!server.pick_faction(2, PlayerId(2), Some(Faction::Emperor)));
+
+        // The first player's pick is chosen straight away, but the third must wait for the second to choose
+        server.choose_picked_factions().unwrap();
+        let state = &server.driver.state;
+        assert_eq!(state.factions.get(&Faction::Fremen), Some(&PlayerId(1)));
+        assert_eq!(state.active_player, Some(PlayerId(2)));
+        server
+            .receive(
+                2,
+                GameEvent::ChooseFaction {
+                    player_id: PlayerId(2),
+                    faction: Faction::Atreides,
+                },
+            )
+            .unwrap();
+        server.choose_picked_factions().unwrap();
+        let state = &server.driver.state;
+        assert_eq!(state.factions.get(&Faction::Atreides), Some(&PlayerId(2)));
+        assert_eq!(state.factions.get(&Faction::Harkonnen), Some(&PlayerId(3)));
+        assert_ne!(state.phase, Phase::Setup(SetupPhase::ChooseFactions));
+    }
+
+    #[test]
+    fn only_spectators_are_synced_everyone_s_secrets() {
+        let factions = [Faction::Atreides, Faction::BeneGesserit, Faction::Fremen];
+        let mut server = test_server();
+        start(&mut server, &factions);
+        play_setup(&mut server, &factions);
+        let spectator = factions.len() as u64 + 1;
+        server.connect(spectator, None).unwrap();
+        assert!(server.spectators.contains(&PlayerId(spectator)));
+
+        // Rebuild the game from what a client was sent, as the client would
+        let synced = |client_id: u64| match server.full_sync(client_id) {
+            ServerEvent::FullSync { events, spectator, .. } => {
+                let mut state = GameState::default();
+                for event in events {
+                    state.consume(&server.driver.data, event);
+                }
+                (state, spectator)
+            }
+            _ => unreachable!(),
+        };
+        let traitors = |state: &GameState, player_id| {
+            let mut traitors = state.players[&player_id]
+                .traitor_cards
+                .iter()
+                .map(|card| card.id)
+                .collect::<Vec<_>>();
+            traitors.sort();
+            traitors
+        };
+        let fremen = PlayerId(3);
+
+        let (watched, is_spectator) = synced(spectator);
+        assert!(is_spectator);
+        assert_eq!(traitors(&watched, fremen), traitors(&server.driver.state, fremen));
+        assert_eq!(watched.bg_predictions.faction, Some(Faction::Atreides));
+        assert_eq!(watched.bg_predictions.turn, Some(PREDICTED_TURN));
+
+        let (seen, is_spectator) = synced(1);
+        assert!(!is_spectator);
+        assert_ne!(traitors(&seen, fremen), traitors(&server.driver.state, fremen));
+        assert_eq!(seen.bg_predictions.faction, None);
+        assert_eq!(seen.bg_predictions.turn, None);
+        assert!(!seen
+            .prompts
+            .values()
+            .any(|prompt| matches!(prompt, Prompt::FactionPrediction | Prompt::TurnPrediction)));
+        // The Bene Gesserit know what they predicted
+        let (predicted, _) = synced(2);
+        assert_eq!(predicted.bg_predictions.turn, Some(PREDICTED_TURN));
+    }
+
+    #[test]
+    fn a_rematch_starts_afresh_with_the_same_players() {
+        let factions = [Faction::Atreides, Faction::Harkonnen];
+        let mut server = test_server();
+        start(&mut server, &factions);
+        play_setup(&mut server, &factions);
+        let seats = server.driver.state.seats.clone();
+        let next_seq = |server: &Server| server.sent[&PlayerId(1)].next_seq;
+        let sent = next_seq(&server);
+        assert!(sent > 0);
+
+        // Only the host can ask for a rematch, and only once the game is over
+        assert!(!server.request_rematch(1));
+        server.driver.state.phase = Phase::EndGame;
+        assert!(!server.request_rematch(2));
+        assert_eq!(server.driver.state.phase, Phase::EndGame);
+        assert!(server.request_rematch(1));
+        assert_eq!(server.driver.state, GameState::default());
+        assert!(server.log.is_empty());
+        assert_eq!(server.waiting_players, players(&factions).into_iter().collect());
+        assert_eq!(server.names[&PlayerId(1)], "Player 1");
+        // Clients keep counting from where they were, so nothing from the new game looks like it was already seen
+        assert_eq!(next_seq(&server), sent);
+
+        for player_id in players(&factions) {
+            assert!(server.ready(player_id.0).unwrap());
+        }
+        assert_eq!(server.driver.state.phase, Phase::Setup(SetupPhase::ChooseFactions));
+        play_setup(&mut server, &factions);
+        assert_eq!(server.driver.state.seats, seats);
+    }
+
+    #[test]
+    fn a_client_which_missed_some_events_is_caught_up_in_order() {
+        let factions = [Faction::Atreides, Faction::Harkonnen];
+        let mut server = test_server();
+        start(&mut server, &factions);
+        play_setup(&mut server, &factions);
+        let mut sent = SentEvents::default();
+        for event in server.log.iter() {
+            sent.push(event.clone());
+        }
+
+        // A few events go missing on the way, and the client asks for them once it notices
+        let mut sequence = EventSequence::default();
+        let mut applied = Vec::new();
+        let mut requested = Vec::new();
+        for (seq, event) in sent.recent.iter().cloned() {
+            if (3..6).contains(&seq) {
+                continue;
+            }
+            let (ready, missing) = sequence.receive(seq, event);
+            applied.extend(ready);
+            requested.extend(missing);
+        }
+        assert_eq!(applied.len(), 3);
+        assert_eq!(requested, vec![3]);
+
+        // Everything since is sent again, and what was already received is ignored
+        for (seq, event) in sent.since(3).unwrap() {
+            let (ready, missing) = sequence.receive(seq, event);
+            applied.extend(ready);
+            assert!(missing.is_none());
+        }
+        assert_eq!(applied, server.log);
+    }
+
+    #[test]
+    fn a_client_too_far_behind_is_sent_the_whole_game() {
+        let mut sent = SentEvents::default();
+        for _ in 0..CATCHUP_BUFFER + 10 {
+            sent.push(GameEvent::AdvancePhase);
+        }
+        assert!(sent.since(0).is_none());
+        assert!(sent.since(9).is_none());
+        assert_eq!(sent.since(10).unwrap().len(), CATCHUP_BUFFER);
+        assert_eq!(sent.since(sent.next_seq).unwrap(), Vec::new());
+    }
+
+    #[test]
+    fn spectators_cannot_start_the_game_again() {
+        let factions = [Faction::Atreides, Faction::Harkonnen];
+        let mut server = test_server();
+        start(&mut server, &factions);
+        play_setup(&mut server, &factions);
+        let (state, logged) = (server.driver.state.clone(), server.log.len());
+
+        let spectators = [PlayerId(3), PlayerId(4)];
+        for player_id in spectators {
+            server.connect(player_id.0, None).unwrap();
+        }
+        assert!(server.waiting_players.is_empty());
+        assert_eq!(server.join_order, players(&factions));
+        assert!(server.validate_lobby().is_err());
+        for player_id in spectators {
+            assert!(!server.ready(player_id.0).unwrap());
+        }
+        // Players who already started can't start it again either
+        assert!(!server.ready(1).unwrap());
+        assert_eq!(server.driver.state.play_order, state.play_order);
+        assert_eq!(server.driver.state.phase, state.phase);
+        assert!(!server.log[logged..]
+            .iter()
+            .any(|event| matches!(event, GameEvent::SetRules { .. } | GameEvent::AdvancePhase)));
+
+        // Those who watched play in the next game, after those who played
+        server.driver.state.phase = Phase::EndGame;
+        server.rematch();
+        assert_eq!(server.join_order, (1..=4).map(PlayerId).collect::<Vec<_>>());
+        assert!(server.spectators.is_empty());
+    }
+
+    #[test]
+    fn only_the_host_chooses_the_seating() {
+        let factions = [Faction::Atreides, Faction::Harkonnen, Faction::Fremen];
+        let mut server = test_server();
+        server.rules.seating = Seating::Random;
+        // Join out of order, so seats in join order can't be mistaken for seats in id order
+        let joined = [PlayerId(3), PlayerId(1), PlayerId(2)];
+        for player_id in joined {
+            server.connect(player_id.0, None).unwrap();
+        }
+        assert!(!server.set_seating(1, Seating::JoinOrder));
+        assert_eq!(server.rules.seating, Seating::Random);
+        assert!(server.set_seating(3, Seating::JoinOrder));
+        assert_eq!(server.rules.seating, Seating::JoinOrder);
+
+        for player_id in joined {
+            assert!(server.ready(player_id.0).unwrap());
+        }
+        play_setup(&mut server, &factions);
+        assert_eq!(server.driver.state.play_order, joined);
+        // Once the game has started the seats are settled, even for the host
+        assert!(!server.set_seating(3, Seating::Random));
+        assert_eq!(server.rules.seating, Seating::JoinOrder);
+    }
+}
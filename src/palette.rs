@@ -0,0 +1,54 @@
+use bevy::prelude::Color;
+
+use crate::components::Faction;
+
+/// The color each faction is known by. The colorblind palette is the Okabe-Ito set, which stays distinguishable with
+/// the common kinds of colorblindness.
+pub fn faction_color(faction: Faction, colorblind: bool) -> Color {
+    if colorblind {
+        match faction {
+            Faction::Atreides => Color::rgb_u8(0, 158, 115),
+            Faction::Harkonnen => Color::rgb_u8(230, 159, 0),
+            Faction::Emperor => Color::rgb_u8(213, 94, 0),
+            Faction::SpacingGuild => Color::rgb_u8(86, 180, 233),
+            Faction::Fremen => Color::rgb_u8(240, 228, 66),
+            Faction::BeneGesserit => Color::rgb_u8(204, 121, 167),
+        }
+    } else {
+        match faction {
+            Faction::Atreides => Color::rgb(0.2, 0.6, 0.2),
+            Faction::Harkonnen => Color::rgb(0.45, 0.45, 0.45),
+            Faction::Emperor => Color::rgb(0.8, 0.15, 0.15),
+            Faction::SpacingGuild => Color::rgb(0.9, 0.5, 0.1),
+            Faction::Fremen => Color::rgb(0.85, 0.75, 0.35),
+            Faction::BeneGesserit => Color::rgb(0.2, 0.4, 0.8),
+        }
+    }
+}
+
+/// The color of a planned move which is allowed.
+pub fn legal_color(colorblind: bool) -> Color {
+    if colorblind {
+        Color::rgb_u8(0, 114, 178)
+    } else {
+        Color::GREEN
+    }
+}
+
+/// The color of a planned move which isn't allowed.
+pub fn illegal_color(colorblind: bool) -> Color {
+    if colorblind {
+        Color::rgb_u8(230, 159, 0)
+    } else {
+        Color::RED
+    }
+}
+
+/// The tint of sectors the storm is about to sweep through.
+pub fn storm_preview_color(colorblind: bool) -> Color {
+    if colorblind {
+        Color::rgba_u8(213, 94, 0, 140)
+    } else {
+        Color::rgba(1.0, 0.0, 0.0, 0.35)
+    }
+}
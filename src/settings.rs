@@ -0,0 +1,121 @@
+use std::fs::File;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+const SETTINGS_PATH: &str = "settings.ron";
+
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Settings::load()).add_system(save_settings);
+    }
+}
+
+/// Client preferences which are kept between sessions.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub show_legend: bool,
+    /// Show an overview of the whole board, which can be clicked to look at a territory.
+    pub show_minimap: bool,
+    /// Pass automatically when there is nothing worth deciding.
+    pub auto_pass: bool,
+    /// Draw with colors which stay distinguishable with colorblindness, and mark forces with their faction.
+    pub colorblind: bool,
+    /// Place the spice as soon as the spice blow is revealed, rather than giving everyone a moment to read it.
+    pub skip_spice_blow_delay: bool,
+    /// Show everyone's hidden information while spectating.
+    pub show_director_view: bool,
+    /// Save the game at the start of every turn when we host it, so it can be resumed after a crash.
+    pub autosave: bool,
+    /// The language to show text in, named after its file in `data/lang`. English if not chosen.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    pub key_bindings: KeyBindings,
+}
+
+/// Which key triggers each shortcut.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeyBindings {
+    /// Pass, or decline whatever we are being asked.
+    pub pass: KeyCode,
+    /// Take the obvious action, such as the lowest possible bid.
+    pub confirm: KeyCode,
+    pub cancel: KeyCode,
+    /// Pass, but only when it is our turn.
+    pub end_turn: KeyCode,
+    pub toggle_legend: KeyCode,
+    pub toggle_minimap: KeyCode,
+    pub toggle_auto_pass: KeyCode,
+    pub toggle_colorblind: KeyCode,
+    pub concede: KeyCode,
+    /// Stop waiting on whatever is being shown and carry on with the game.
+    pub skip_pause: KeyCode,
+    pub replay_back: KeyCode,
+    pub replay_forward: KeyCode,
+    pub replay_play: KeyCode,
+    /// Dial more or fewer forces into a battle plan.
+    pub dial_up: KeyCode,
+    pub dial_down: KeyCode,
+    /// Held to dial special forces instead of normal ones.
+    pub dial_special: KeyCode,
+    pub next_leader: KeyCode,
+    /// Look at the next spice blow, for the Fremen under the advanced rules.
+    pub peek_spice_blow: KeyCode,
+    pub toggle_director_view: KeyCode,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            pass: KeyCode::Space,
+            confirm: KeyCode::Return,
+            cancel: KeyCode::Escape,
+            end_turn: KeyCode::E,
+            toggle_legend: KeyCode::L,
+            toggle_minimap: KeyCode::M,
+            toggle_auto_pass: KeyCode::A,
+            toggle_colorblind: KeyCode::F6,
+            concede: KeyCode::F10,
+            skip_pause: KeyCode::Period,
+            replay_back: KeyCode::Left,
+            replay_forward: KeyCode::Right,
+            replay_play: KeyCode::P,
+            dial_up: KeyCode::Up,
+            dial_down: KeyCode::Down,
+            dial_special: KeyCode::LShift,
+            next_leader: KeyCode::Tab,
+            peek_spice_blow: KeyCode::S,
+            toggle_director_view: KeyCode::I,
+        }
+    }
+}
+
+impl Settings {
+    pub fn load() -> Self {
+        File::open(SETTINGS_PATH)
+            .ok()
+            .and_then(|file| ron::de::from_reader(file).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) {
+        match ron::ser::to_string_pretty(self, Default::default()) {
+            Ok(settings) => {
+                if let Err(e) = std::fs::write(SETTINGS_PATH, settings) {
+                    error!("Failed to save settings: {}", e);
+                }
+            }
+            Err(e) => error!("Failed to serialize settings: {}", e),
+        }
+    }
+}
+
+fn save_settings(settings: Res<Settings>) {
+    if settings.is_changed() && !settings.is_added() {
+        settings.save();
+    }
+}
@@ -56,6 +56,8 @@ pub fn effects_system(mut stack: ResMut<EffectStack>) {
             CardEffect::Revive => {}
             CardEffect::Truthtrance => {}
             CardEffect::WeatherControl => {}
+            CardEffect::Thumper => {}
+            CardEffect::MakerHook => {}
         }
     }
 }
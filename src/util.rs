@@ -54,10 +54,28 @@ where
     }
 }
 
-pub fn hand_positions(n: usize) -> Vec<Vec2> {
-    // TODO: Make this radial
+/// Fans a hand of cards along an arc at the bottom of the screen. Returns the position and rotation of each card,
+/// where the rotation rolls the card about the view axis.
+pub fn hand_positions(n: usize) -> Vec<(Vec2, Quat)> {
+    const RADIUS: f32 = 3.0;
+    // Cards spread out until the hand gets large, then they start to overlap more
+    let step = (0.5 / n as f32).min(0.12);
     (0..n)
-        .map(|i| vec2(2.0 * ((1.0 + i as f32) / (1.0 + n as f32)) - 1.0, -1.1))
+        .map(|i| {
+            let angle = (i as f32 - 0.5 * (n as f32 - 1.0)) * step;
+            (
+                vec2(RADIUS * angle.sin(), -1.1 - RADIUS * (1.0 - angle.cos())),
+                Quat::from_rotation_z(-angle),
+            )
+        })
+        .collect()
+}
+
+/// Positions along the top of the screen for another player's hand, given their seat among the other players.
+pub fn opponent_hand_positions(seat: usize, seats: usize, n: usize) -> Vec<Vec2> {
+    let x = 2.0 * ((1.0 + seat as f32) / (1.0 + seats as f32)) - 1.0;
+    (0..n)
+        .map(|i| vec2(x + 0.04 * (i as f32 - 0.5 * n as f32), 1.1))
         .collect()
 }
 
@@ -81,3 +99,26 @@ pub fn card_jitter() -> Transform {
         * Transform::from_translation(Vec3::Z * rand::random::<f32>() * 0.001)
         * Transform::from_rotation(Quat::from_rotation_y(rand::random::<f32>() * PI * 0.01))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opponents_hands_are_laid_out_apart_along_the_top() {
+        let seats = 5;
+        let hands = (0..seats)
+            .map(|seat| opponent_hand_positions(seat, seats, 4))
+            .collect::<Vec<_>>();
+        for hand in hands.iter() {
+            assert_eq!(hand.len(), 4);
+            assert!(hand.iter().all(|pos| pos.y > 1.0));
+            assert!(hand.windows(2).all(|pair| pair[0].x < pair[1].x));
+        }
+        // Each seat's hand ends before the next one starts
+        for pair in hands.windows(2) {
+            assert!(pair[0].last().unwrap().x < pair[1].first().unwrap().x);
+        }
+        assert!(opponent_hand_positions(0, seats, 0).is_empty());
+    }
+}